@@ -1,5 +1,7 @@
 use ushell_config::*;
 use ushell_dispatcher::{generate_commands_dispatcher, generate_shortcuts_dispatcher};
+use ushell_input::input::parser::StatusIndicator;
+use ushell_input::transport::{telnet, websocket};
 use ushell2::uShell;
 
 use usercode::commands as uc;
@@ -8,6 +10,7 @@ use usercode::shortcuts as us;
 generate_commands_dispatcher! {
     mod commands;
     hexstr_size = crate::MAX_HEXSTR_LEN;
+    doc_help = true;
     path = "../usercode/src/commands.cfg"
 }
 
@@ -17,22 +20,86 @@ generate_shortcuts_dispatcher! {
     path = "../usercode/src/shortcuts.cfg"
 }
 
+type Shell = uShell<
+    { commands::NUM_COMMANDS },
+    { commands::MAX_FUNCTION_NAME_LEN },
+    { INPUT_MAX_LEN },
+    { HISTORY_TOTAL_CAPACITY },
+    { HISTORY_MAX_ENTRIES },
+    commands::DispatchError,
+>;
+
+/// `demo_app` runs the shell on the terminal. Instead:
+/// - `demo_app --telnet <addr>` serves it to one telnet client at a time, e.g. `127.0.0.1:2323`
+/// - `demo_app --serial <device> [baud]` runs it on a serial port, e.g. `/dev/ttyUSB0 115200`
+/// - `demo_app --websocket <addr>` serves it to a browser at `http://<addr>`
 fn main() {
-    uShell::<
-        { commands::NUM_COMMANDS },
-        { commands::MAX_FUNCTION_NAME_LEN },
-        { INPUT_MAX_LEN },
-        { HISTORY_TOTAL_CAPACITY },
-        { HISTORY_MAX_ENTRIES },
-        commands::DispatchError,
-    >::new(
+    let args: Vec<String> = std::env::args().collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let code = match args.as_slice() {
+        [_, "--telnet", addr] => serve_telnet(addr),
+        [_, "--websocket", addr] => serve_websocket(addr),
+        [_, "--serial", device] => run_serial(device, 115200),
+        [_, "--serial", device, baud] => run_serial(device, baud.parse().expect("invalid baud")),
+        _ => shell().run(),
+    };
+    std::process::exit(code);
+}
+
+#[cfg(unix)]
+fn run_serial(device: &str, baud: u32) -> i32 {
+    use ushell_input::transport::serial::SerialPort;
+    let port = SerialPort::open(device, baud).expect("cannot open the serial port");
+    let (keys, out) = port.with_local_echo(true).split();
+    shell().with_output(out).with_reader(keys).run()
+}
+
+#[cfg(not(unix))]
+fn run_serial(_: &str, _: u32) -> i32 {
+    eprintln!("--serial is only supported on unix");
+    1
+}
+
+fn serve_telnet(addr: &str) -> ! {
+    let listener = std::net::TcpListener::bind(addr).expect("cannot listen");
+    println!("Serving the shell on telnet://{}", addr);
+    loop {
+        match telnet::accept(&listener) {
+            Ok((keys, out)) => {
+                shell().with_output(out).with_reader(keys).run();
+            }
+            Err(e) => eprintln!("telnet: {}", e),
+        }
+    }
+}
+
+fn serve_websocket(addr: &str) -> ! {
+    let listener = std::net::TcpListener::bind(addr).expect("cannot listen");
+    println!("Serving the shell on http://{}", addr);
+    loop {
+        match websocket::accept(&listener) {
+            Ok((keys, out)) => {
+                shell().with_output(out).with_reader(keys).run();
+            }
+            Err(e) => eprintln!("websocket: {}", e),
+        }
+    }
+}
+
+fn shell() -> Shell {
+    Shell::new(
         commands::get_commands,
+        commands::has_command,
         commands::get_datatypes,
         shortcuts::get_shortcuts,
         shortcuts::is_supported_shortcut,
-        commands::dispatch,
+        |line| commands::dispatch_with_cancel(line, &ushell2::CANCEL),
         shortcuts::dispatch,
         PROMPT,
     )
-    .run();
+    .with_usage(commands::usage)
+    .with_help(commands::help)
+    .with_ctrl_c_cancel(true)
+    .with_status_prompt(StatusIndicator::Glyph)
+    .with_shortcut_specs(shortcuts::get_shortcut_specs)
 }
@@ -1,13 +1,21 @@
 #![allow(non_snake_case)]
 
+use ushell_dispatcher::command_doc;
+
+/// Initializes the device.
+#[command_doc]
 pub fn init() {
     println!("init | no-args");
 }
 
+/// Reads bytes through a descriptor.
+#[command_doc]
 pub fn read(descr: i8, nbytes: u32) {
     println!("read | descriptor: {}, bytes:{}", descr, nbytes);
 }
 
+/// Writes a value to a file.
+#[command_doc]
 pub fn write(filename: &str, nbytes: u64, val: u8) {
     println!(
         "write | filename: {}, bytes:{}, value:{:X}/{:o}/{:b}",
@@ -15,6 +23,8 @@ pub fn write(filename: &str, nbytes: u64, val: u8) {
     );
 }
 
+/// Switches the LED on or off.
+#[command_doc]
 pub fn led(onoff: bool) {
     if onoff {
         println!("led | ON");
@@ -23,22 +33,32 @@ pub fn led(onoff: bool) {
     }
 }
 
+/// Prints two greeting words.
+#[command_doc]
 pub fn greeting(s1: &str, s2: &str) {
     println!("greeting | [{}] : [{}]", s1, s2);
 }
 
+/// Sends bytes to a port.
+#[command_doc]
 pub fn send(port: &str, baud: u32, data: &[u8]) {
     println!("send | port: {} baudrate: {}, data:{:?}", port, baud, data);
 }
 
+/// Echoes a string (a).
+#[command_doc]
 pub fn astring(s: &str) {
     println!("astring | {}", s);
 }
 
+/// Echoes a string (b).
+#[command_doc]
 pub fn bstring(s: &str) {
     println!("bstring | {}", s);
 }
 
+/// Echoes a string (c).
+#[command_doc]
 pub fn cstring(s: &str) {
     println!("cstring | {}", s);
 }
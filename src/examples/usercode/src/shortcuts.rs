@@ -3,8 +3,8 @@ pub fn shortcut_plus_plus(param: &str) {
     println!("Executing ++ with param: '{}'", param);
 }
 
-pub fn shortcut_plus_l(param: &str) {
-    println!("Executing +l with param: '{}'", param);
+pub fn shortcut_plus_l(level: u8, mode: &str) {
+    println!("Executing +l with level: {} mode: '{}'", level, mode);
 }
 
 pub fn shortcut_plus_m(param: &str) {
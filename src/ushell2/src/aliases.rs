@@ -0,0 +1,173 @@
+use core::fmt::{self, Display};
+use heapless::String;
+
+use crate::table::{NamedTable, TableError};
+
+/// Maximum number of runtime aliases.
+pub const MAX_ALIASES: usize = 16;
+
+/// Maximum length of an alias name.
+pub const ALIAS_NAME_LEN: usize = 16;
+
+/// Error of defining or expanding an alias.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AliasError {
+    /// The name is empty, too long, holds whitespace or starts with `#` / `!`.
+    InvalidName,
+    /// `MAX_ALIASES` aliases are already defined.
+    Full,
+    /// The command line, or the line once expanded, does not fit in `IML` bytes.
+    TooLong,
+}
+
+impl Display for AliasError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AliasError::InvalidName => write!(f, "invalid alias name"),
+            AliasError::Full => write!(f, "too many aliases"),
+            AliasError::TooLong => write!(f, "line too long"),
+        }
+    }
+}
+
+/// Fixed-capacity table of the runtime aliases (`alias NAME command line`).
+///
+/// - `IML`: Maximum length of a command line, and of a line once expanded.
+///
+pub struct Aliases<const IML: usize> {
+    entries: NamedTable<MAX_ALIASES, ALIAS_NAME_LEN, IML>,
+}
+
+impl<const IML: usize> Default for Aliases<IML> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const IML: usize> Aliases<IML> {
+    pub const fn new() -> Self {
+        Self {
+            entries: NamedTable::new(),
+        }
+    }
+
+    /// Maps `name` to `command`, replacing its previous command line.
+    pub fn set(&mut self, name: &str, command: &str) -> Result<(), AliasError> {
+        if !is_valid_name(name) {
+            return Err(AliasError::InvalidName);
+        }
+        self.entries.set(name, command).map_err(|e| match e {
+            TableError::NameTooLong => AliasError::InvalidName,
+            TableError::ValueTooLong => AliasError::TooLong,
+            TableError::Full => AliasError::Full,
+        })
+    }
+
+    /// Removes `name`; returns `false` if it was not defined.
+    pub fn unset(&mut self, name: &str) -> bool {
+        self.entries.unset(name)
+    }
+
+    /// Returns the command line of `name`, if defined.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries.get(name)
+    }
+
+    /// Returns the `(name, command)` pairs in the order they were first defined.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter()
+    }
+
+    /// Replaces the first word of `line` with its command line if it is an alias, keeping the
+    /// arguments after it; `None` if it is not an alias.
+    ///
+    /// Aliases are expanded once: an alias used in a command line is not expanded again.
+    pub fn expand(&self, line: &str) -> Option<Result<String<IML>, AliasError>> {
+        let line = line.trim_start();
+        let (name, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let command = self.get(name)?;
+        let mut expanded = String::new();
+        let result = expanded.push_str(command).and_then(|_| {
+            if args.is_empty() {
+                Ok(())
+            } else {
+                expanded.push(' ').and_then(|_| expanded.push_str(args))
+            }
+        });
+        Some(result.map(|_| expanded).map_err(|_| AliasError::TooLong))
+    }
+}
+
+/// Returns `true` if `name` can be used as an alias name.
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= ALIAS_NAME_LEN
+        && !name.starts_with(['#', '!'])
+        && !name.contains(char::is_whitespace)
+}
+
+// ==================== TESTS =======================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aliases() -> Aliases<32> {
+        let mut aliases = Aliases::new();
+        aliases.set("on", "led true").unwrap();
+        aliases.set("ll", "ls -l").unwrap();
+        aliases
+    }
+
+    #[test]
+    fn test_expand() {
+        let aliases = aliases();
+        assert_eq!(aliases.expand("on").unwrap().unwrap(), "led true");
+        assert_eq!(
+            aliases.expand("  ll /tmp  x").unwrap().unwrap(),
+            "ls -l /tmp  x"
+        );
+        assert!(aliases.expand("led false").is_none());
+        assert!(aliases.expand("only").is_none());
+        assert!(aliases.expand("").is_none());
+    }
+
+    #[test]
+    fn test_expand_once() {
+        let mut aliases = aliases();
+        aliases.set("ls", "ls --color").unwrap();
+        aliases.set("a", "b 1").unwrap();
+        aliases.set("b", "a 2").unwrap();
+        assert_eq!(aliases.expand("ls").unwrap().unwrap(), "ls --color");
+        assert_eq!(aliases.expand("a x").unwrap().unwrap(), "b 1 x");
+        assert_eq!(aliases.expand("ll").unwrap().unwrap(), "ls -l");
+    }
+
+    #[test]
+    fn test_expand_too_long() {
+        let aliases = aliases();
+        assert_eq!(
+            aliases.expand("on 0123456789012345678901234"),
+            Some(Err(AliasError::TooLong))
+        );
+    }
+
+    #[test]
+    fn test_set_replaces_and_validates() {
+        let mut aliases = aliases();
+        aliases.set("on", "led 1").unwrap();
+        assert_eq!(aliases.get("on"), Some("led 1"));
+        assert_eq!(aliases.iter().count(), 2);
+        for name in ["", "#x", "!x", "a b", "NAME_LONGER_THAN_16"] {
+            assert_eq!(
+                aliases.set(name, "x"),
+                Err(AliasError::InvalidName),
+                "{}",
+                name
+            );
+        }
+        assert!(aliases.unset("on"));
+        assert!(!aliases.unset("on"));
+        assert!(aliases.expand("on").is_none());
+    }
+}
@@ -0,0 +1,93 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// Cancellation token set by Ctrl+C while a line runs (see [`uShell::with_ctrl_c_cancel`]).
+///
+/// Pass it to the generated `dispatch_with_cancel`, which hands it to the `[cancel]` handlers:
+/// `|line| commands::dispatch_with_cancel(line, &ushell2::CANCEL)`.
+///
+/// [`uShell::with_ctrl_c_cancel`]: crate::uShell::with_ctrl_c_cancel
+pub static CANCEL: AtomicBool = AtomicBool::new(false);
+
+/// Ctrl+C sets [`CANCEL`] instead of ending the process until dropped. A second Ctrl+C while
+/// the token is still set ends the process as usual, so a handler that never polls it can
+/// still be stopped.
+pub(crate) struct CtrlCGuard {
+    #[cfg(all(unix, feature = "std"))]
+    previous: libc::sighandler_t,
+}
+
+impl CtrlCGuard {
+    /// Clears [`CANCEL`] and catches `SIGINT`.
+    #[cfg(all(unix, feature = "std"))]
+    pub(crate) fn install() -> Self {
+        CANCEL.store(false, Ordering::Relaxed);
+        // SAFETY: `on_sigint` only touches an atomic and calls async-signal-safe functions.
+        let previous = unsafe {
+            libc::signal(
+                libc::SIGINT,
+                on_sigint as extern "C" fn(libc::c_int) as libc::sighandler_t,
+            )
+        };
+        Self { previous }
+    }
+
+    /// Clears [`CANCEL`]; only unix (with `std`) delivers Ctrl+C as a signal caught here.
+    #[cfg(not(all(unix, feature = "std")))]
+    pub(crate) fn install() -> Self {
+        CANCEL.store(false, Ordering::Relaxed);
+        Self {}
+    }
+}
+
+#[cfg(all(unix, feature = "std"))]
+extern "C" fn on_sigint(_: libc::c_int) {
+    if CANCEL.load(Ordering::Relaxed) {
+        // SAFETY: `signal` and `raise` are async-signal-safe.
+        unsafe {
+            libc::signal(libc::SIGINT, libc::SIG_DFL);
+            libc::raise(libc::SIGINT);
+        }
+    }
+    CANCEL.store(true, Ordering::Relaxed);
+}
+
+impl Drop for CtrlCGuard {
+    /// Restores the previous `SIGINT` disposition.
+    fn drop(&mut self) {
+        #[cfg(all(unix, feature = "std"))]
+        // SAFETY: `previous` was returned by `signal` for the same signal.
+        unsafe {
+            libc::signal(libc::SIGINT, self.previous);
+        }
+    }
+}
+
+// ==================== TESTS =======================
+
+#[cfg(all(test, unix, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ctrl_c_sets_token_while_guarded() {
+        CANCEL.store(true, Ordering::Relaxed);
+        let guard = CtrlCGuard::install();
+        assert!(!CANCEL.load(Ordering::Relaxed));
+        // SAFETY: the guard catches `SIGINT`, and the token is clear so the process survives.
+        unsafe { libc::raise(libc::SIGINT) };
+        assert!(CANCEL.load(Ordering::Relaxed));
+        drop(guard);
+
+        // SAFETY: reads back the disposition restored by the guard without changing it.
+        let restored = unsafe {
+            let current = libc::signal(libc::SIGINT, libc::SIG_DFL);
+            libc::signal(libc::SIGINT, current);
+            current
+        };
+        assert_ne!(
+            restored,
+            on_sigint as extern "C" fn(libc::c_int) as libc::sighandler_t
+        );
+        CANCEL.store(false, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,78 @@
+//! Slots through which a shell lends a value (its state, its output) to the handlers of the line
+//! it runs: one per thread with the `std` feature, a single one without it. Targets without
+//! atomic pointer swaps (and without `std`) lend nothing.
+
+#[cfg(feature = "std")]
+pub(crate) type Slot = std::thread::LocalKey<core::cell::Cell<*mut ()>>;
+#[cfg(not(feature = "std"))]
+pub(crate) type Slot = core::sync::atomic::AtomicPtr<()>;
+
+/// Declares an empty [`Slot`].
+macro_rules! slot {
+    ($(#[$attr:meta])* $name:ident) => {
+        #[cfg(feature = "std")]
+        std::thread_local! {
+            $(#[$attr])*
+            static $name: core::cell::Cell<*mut ()> = const { core::cell::Cell::new(core::ptr::null_mut()) };
+        }
+        #[cfg(not(feature = "std"))]
+        $(#[$attr])*
+        static $name: core::sync::atomic::AtomicPtr<()> =
+            core::sync::atomic::AtomicPtr::new(core::ptr::null_mut());
+    };
+}
+pub(crate) use slot;
+
+/// Stores `value` in `slot` and returns the previous one.
+#[cfg(any(feature = "std", target_has_atomic = "ptr"))]
+fn swap(slot: &'static Slot, value: *mut ()) -> *mut () {
+    #[cfg(feature = "std")]
+    return slot.with(|cell| cell.replace(value));
+    #[cfg(not(feature = "std"))]
+    return slot.swap(value, core::sync::atomic::Ordering::AcqRel);
+}
+
+/// Puts a value back into its slot, also when the code that took it out panics.
+#[cfg(any(feature = "std", target_has_atomic = "ptr"))]
+struct Restore(&'static Slot, *mut ());
+
+#[cfg(any(feature = "std", target_has_atomic = "ptr"))]
+impl Drop for Restore {
+    fn drop(&mut self) {
+        swap(self.0, self.1);
+    }
+}
+
+/// Makes `value` reachable through [`with`] on `slot` while `f` runs.
+#[cfg(any(feature = "std", target_has_atomic = "ptr"))]
+pub(crate) fn lend<T: ?Sized, R>(slot: &'static Slot, value: &mut T, f: impl FnOnce() -> R) -> R {
+    let mut value = value;
+    let _restore = Restore(slot, swap(slot, &mut value as *mut &mut T as *mut ()));
+    f()
+}
+
+/// Runs `f`; nothing can be lent without atomic pointer swaps.
+#[cfg(not(any(feature = "std", target_has_atomic = "ptr")))]
+pub(crate) fn lend<T: ?Sized, R>(_: &'static Slot, _: &mut T, f: impl FnOnce() -> R) -> R {
+    f()
+}
+
+/// Runs `f` with the value lent to `slot`; `None` if there is none, or from a nested call.
+///
+/// # Safety
+///
+/// Only `&mut T` may be lent to `slot`.
+#[cfg(any(feature = "std", target_has_atomic = "ptr"))]
+pub(crate) unsafe fn with<T: ?Sized, R>(
+    slot: &'static Slot,
+    f: impl FnOnce(&mut T) -> R,
+) -> Option<R> {
+    let lent = swap(slot, core::ptr::null_mut());
+    if lent.is_null() {
+        return None;
+    }
+    let _restore = Restore(slot, lent);
+    // SAFETY: `lend` keeps the value borrowed until it restores the previous one, after `f`
+    // returned; the slot stays empty meanwhile, so a nested call cannot alias the value.
+    Some(f(unsafe { &mut **(lent as *mut &mut T) }))
+}
@@ -1,8 +1,312 @@
-use core::fmt::Debug;
-use heapless::String;
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
-use ushell_input::input::parser::InputParser;
-use ushell_input::terminal::RawMode;
+use core::fmt::{self, Debug, Display, Write};
+use core::sync::atomic::{AtomicI32, AtomicU32, Ordering};
+use core::time::Duration;
+use heapless::{String, Vec};
+
+use ushell_input::history::DedupPolicy;
+#[cfg(feature = "std")]
+use ushell_input::input::key_reader::Key;
+use ushell_input::input::key_reader::{KeyDecoder, KeyReader};
+use ushell_input::input::parser::{
+    DefaultOutput, DefaultReader, InputParser, MAX_COMMAND_TABLES, STATUS_CODE_LEN, ShellEvent,
+    StatusIndicator,
+};
+use ushell_input::input::renderer::Theme;
+use ushell_input::output::Output;
+#[cfg(feature = "std")]
+use ushell_input::output::StreamOutput;
+#[cfg(feature = "std")]
+use ushell_input::terminal::{self, RawMode};
+
+mod aliases;
+mod interrupt;
+mod lent;
+#[cfg(feature = "std")]
+mod redirect;
+mod sink;
+mod state;
+mod table;
+mod vars;
+pub use aliases::{ALIAS_NAME_LEN, AliasError, Aliases, MAX_ALIASES};
+pub use interrupt::CANCEL;
+#[cfg(feature = "std")]
+pub use redirect::{RedirectError, Redirection};
+#[cfg(any(feature = "std", target_has_atomic = "ptr"))]
+pub use sink::with_output;
+pub use state::ShellState;
+#[cfg(any(feature = "std", target_has_atomic = "ptr"))]
+pub use state::with_state;
+pub use vars::{MAX_VARIABLES, VAR_NAME_LEN, VarError, Variables};
+
+/// Error of a line run by the shell, from either dispatcher.
+#[derive(Debug)]
+pub enum ShellError<ERRTYPE: Debug, const IML: usize> {
+    /// The command dispatcher rejected the line (e.g. the generated `DispatchError`).
+    Command(ERRTYPE),
+    /// The shortcut dispatcher rejected the line, with its message.
+    Shortcut(String<IML>),
+    /// A mounted command provider rejected the line, with its error.
+    Provider(String<IML>),
+    /// A shell variable could not be set or expanded.
+    Variable(VarError),
+    /// An alias could not be defined or expanded.
+    Alias(AliasError),
+    /// The output of a `command > file` line could not be sent to the file.
+    #[cfg(feature = "std")]
+    Redirect(RedirectError),
+    /// `privilege` was given a level that is not a number or above the current one.
+    Privilege,
+}
+
+impl<ERRTYPE: Debug, const IML: usize> Display for ShellError<ERRTYPE, IML> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShellError::Command(e) => write!(f, "{:?}", e),
+            ShellError::Shortcut(msg) => write!(f, "{}", msg),
+            ShellError::Provider(msg) => write!(f, "{}", msg),
+            ShellError::Variable(e) => write!(f, "{}", e),
+            ShellError::Alias(e) => write!(f, "{}", e),
+            #[cfg(feature = "std")]
+            ShellError::Redirect(e) => write!(f, "{}", e),
+            ShellError::Privilege => write!(f, "privilege can only be lowered"),
+        }
+    }
+}
+
+/// A set of commands mounted into a shell next to its own command dispatcher, see
+/// [`uShell::add_provider`].
+pub trait CommandProvider<const IML: usize> {
+    /// Returns the `(name, descriptor)` pairs of the commands, listed and completed by the shell.
+    fn get_commands(&self) -> &'static [(&'static str, &'static str)];
+
+    /// Returns `true` if the command `name` (the first word of a line) belongs to this provider.
+    fn is_match(&self, name: &str) -> bool;
+
+    /// Runs a line whose command belongs to this provider.
+    fn dispatch(&self, line: &str) -> Result<(), String<IML>>;
+}
+
+/// Source of input bytes awaited by [`uShell::run_async`], e.g. the RX side of an async UART
+/// driver or a USB CDC class of an embassy application.
+pub trait AsyncByteSource {
+    /// Waits for input and copies it into `buf`; returns the number of bytes, 0 once no more
+    /// input will arrive.
+    fn read(&mut self, buf: &mut [u8]) -> impl Future<Output = usize>;
+}
+
+/// Command provider made of the functions of a generated command module (`get_commands`,
+/// `has_command`, `dispatch`), or of hand-written ones with the same signatures.
+///
+/// ```ignore
+/// static NET: Provider<net::DispatchError> =
+///     Provider::new(net::get_commands, net::has_command, net::dispatch);
+/// ```
+pub struct Provider<E: Debug> {
+    get_commands: fn() -> &'static [(&'static str, &'static str)],
+    is_match: fn(&str) -> bool,
+    dispatch: fn(&str) -> Result<(), E>,
+}
+
+impl<E: Debug> Provider<E> {
+    pub const fn new(
+        get_commands: fn() -> &'static [(&'static str, &'static str)],
+        is_match: fn(&str) -> bool,
+        dispatch: fn(&str) -> Result<(), E>,
+    ) -> Self {
+        Self {
+            get_commands,
+            is_match,
+            dispatch,
+        }
+    }
+}
+
+impl<E: Debug, const IML: usize> CommandProvider<IML> for Provider<E> {
+    fn get_commands(&self) -> &'static [(&'static str, &'static str)] {
+        (self.get_commands)()
+    }
+
+    fn is_match(&self, name: &str) -> bool {
+        (self.is_match)(name)
+    }
+
+    /// Runs the line; the error is reported with its `Debug` form, as for the main dispatcher.
+    fn dispatch(&self, line: &str) -> Result<(), String<IML>> {
+        (self.dispatch)(line).map_err(|e| {
+            let mut msg = String::new();
+            let _ = write!(msg, "{:?}", e);
+            msg
+        })
+    }
+}
+
+/// Shell-level action requested by a command, see [`request_action`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ShellAction {
+    /// Keep reading lines (what every line does unless it requests otherwise).
+    #[default]
+    Continue,
+    /// Leave the shell loop; `uShell::run` returns the exit code.
+    Exit(i32),
+    /// Start over: clear the history, the screen and greet again.
+    Reset,
+}
+
+/// Action requested by the running command: 0 `Continue`, 1 `Exit`, 2 `Reset`.
+/// Only plain loads/stores are used so targets without atomic RMW are supported.
+static REQUESTED_ACTION: AtomicU32 = AtomicU32::new(0);
+
+/// Exit code of a requested `ShellAction::Exit`.
+static REQUESTED_EXIT_CODE: AtomicI32 = AtomicI32::new(0);
+
+/// Requests a shell-level action from a command or shortcut handler.
+///
+/// The shell takes the request once the current line has run and its result was reported,
+/// e.g. a `quit <code>` command calls `request_action(ShellAction::Exit(code))`. A later
+/// request replaces an earlier one of the same line. The request is global, so it is taken
+/// by whichever shell runs the line.
+pub fn request_action(action: ShellAction) {
+    let tag = match action {
+        ShellAction::Continue => 0,
+        ShellAction::Exit(code) => {
+            REQUESTED_EXIT_CODE.store(code, Ordering::Relaxed);
+            1
+        }
+        ShellAction::Reset => 2,
+    };
+    REQUESTED_ACTION.store(tag, Ordering::Relaxed);
+}
+
+/// Returns `true` if a command requested an action not taken yet.
+#[cfg(feature = "std")]
+fn action_pending() -> bool {
+    REQUESTED_ACTION.load(Ordering::Relaxed) != 0
+}
+
+/// Returns the pending request and resets it to `Continue`.
+fn take_action() -> ShellAction {
+    let action = match REQUESTED_ACTION.load(Ordering::Relaxed) {
+        1 => ShellAction::Exit(REQUESTED_EXIT_CODE.load(Ordering::Relaxed)),
+        2 => ShellAction::Reset,
+        _ => ShellAction::Continue,
+    };
+    REQUESTED_ACTION.store(0, Ordering::Relaxed);
+    action
+}
+
+/// What the shell prints after running a line.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ReportMode {
+    /// Nothing: commands print their own output.
+    Quiet,
+    /// Only failures (`Error: ... for line '...'`); silent on success.
+    ErrorsOnly,
+    /// Failures and `Success: <line>` for every successful line.
+    #[default]
+    Verbose,
+}
+
+impl ReportMode {
+    /// Parses a mode name as accepted by `#v`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "quiet" => Some(ReportMode::Quiet),
+            "errors" => Some(ReportMode::ErrorsOnly),
+            "verbose" => Some(ReportMode::Verbose),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the mode as accepted by `#v`.
+    pub fn name(self) -> &'static str {
+        match self {
+            ReportMode::Quiet => "quiet",
+            ReportMode::ErrorsOnly => "errors",
+            ReportMode::Verbose => "verbose",
+        }
+    }
+
+    /// Returns the mode `#v` switches to without an argument.
+    pub fn next(self) -> Self {
+        match self {
+            ReportMode::Quiet => ReportMode::ErrorsOnly,
+            ReportMode::ErrorsOnly => ReportMode::Verbose,
+            ReportMode::Verbose => ReportMode::Quiet,
+        }
+    }
+}
+
+/// Hashtag commands handled by the shell itself, listed by `##`.
+#[cfg(feature = "std")]
+const SHELL_HASHTAGS: &[(&str, &str)] = &[
+    ("v", "result reporting (#v quiet|errors|verbose)"),
+    ("a", "list aliases"),
+    (
+        "w",
+        "repeat a command until a key is pressed (#w ms command)",
+    ),
+    ("t", "time each command (#t on|off)"),
+];
+
+/// Hashtag commands handled by the shell itself, listed by `##` (`#w` needs `std` to wait).
+#[cfg(not(feature = "std"))]
+const SHELL_HASHTAGS: &[(&str, &str)] = &[
+    ("v", "result reporting (#v quiet|errors|verbose)"),
+    ("a", "list aliases"),
+    ("t", "time each command (#t on|off)"),
+];
+
+/// Default clock of the command timing: microseconds of wall-clock time since its first call.
+#[cfg(feature = "std")]
+fn wall_clock_us() -> u64 {
+    static START: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+    START
+        .get_or_init(std::time::Instant::now)
+        .elapsed()
+        .as_micros() as u64
+}
+
+/// Default clock of the command timing without `std`: none, durations read 0 until
+/// [`uShell::with_clock`] installs one.
+#[cfg(not(feature = "std"))]
+fn no_clock() -> u64 {
+    0
+}
+
+/// Step of the wait between two runs of a watched command, in which a key press is checked.
+#[cfg(feature = "std")]
+const WATCH_POLL_MS: u64 = 10;
+
+/// Lines printed by the `help` builtin before it waits for a key.
+#[cfg(feature = "std")]
+const HELP_PAGE_LINES: usize = 20;
+
+/// The output of the line being run: its redirection (`command > file`), or the shell's
+/// output. A macro rather than a method, so that it only borrows these two fields of the shell.
+macro_rules! line_out {
+    ($shell:ident) => {{
+        #[cfg(feature = "std")]
+        let out: &mut dyn Output = match &mut $shell.redirect {
+            Some(file) => file,
+            None => $shell.parser.output(),
+        };
+        #[cfg(not(feature = "std"))]
+        let out: &mut dyn Output = $shell.parser.output();
+        out
+    }};
+}
+
+/// Writer of the generated `help(name, out)`, see [`uShell::with_help`].
+pub type HelpFn<ERRTYPE> = fn(&str, &mut dyn fmt::Write) -> Result<(), ERRTYPE>;
+
+/// Callback told of every dispatched line, with its result and duration, see
+/// [`uShell::with_on_command`].
+pub type CommandHook<ERRTYPE, const IML: usize> =
+    fn(&str, &Result<(), ShellError<ERRTYPE, IML>>, Duration);
 
 #[allow(non_camel_case_types)]
 pub struct uShell<
@@ -12,12 +316,34 @@ pub struct uShell<
     const HTC: usize,
     const HME: usize,
     ERRTYPE: Debug,
+    OUT: Output = DefaultOutput,
+    KR: KeyReader = DefaultReader,
 > {
-    parser: InputParser<'static, NC, FNL, IML, HTC, HME>,
-    _terminal: RawMode,
+    parser: InputParser<'static, NC, FNL, IML, HTC, HME, KR, OUT>,
+    /// Raw mode of the local terminal, while it is the key source.
+    #[cfg(feature = "std")]
+    terminal: Option<RawMode>,
+    /// `false` in pipe mode: stdin is not a terminal and no other key source was installed
+    /// (always `true` without `std`).
+    interactive: bool,
     is_shortcut: fn(&str) -> bool,
+    /// Finds the commands of `command_dispatcher`, which take precedence over the shortcuts.
+    has_command: fn(&str) -> bool,
     command_dispatcher: fn(&str) -> Result<(), ERRTYPE>,
     shortcut_dispatcher: fn(&str) -> Result<(), heapless::String<IML>>,
+    providers: Vec<&'static dyn CommandProvider<IML>, MAX_COMMAND_TABLES>,
+    state: ShellState<IML>,
+    aliases: Aliases<IML>,
+    /// Output of the line being run while it is redirected to a file (`command > file`).
+    #[cfg(feature = "std")]
+    redirect: Option<StreamOutput<std::fs::File>>,
+    timing: bool,
+    ctrl_c_cancel: bool,
+    clock: fn() -> u64,
+    on_command: Option<CommandHook<ERRTYPE, IML>>,
+    help: Option<HelpFn<ERRTYPE>>,
+    exit_code: i32,
+    started: bool,
 }
 
 impl<
@@ -29,8 +355,17 @@ impl<
     ERRTYPE: Debug,
 > uShell<NC, FNL, IML, HTC, HME, ERRTYPE>
 {
+    /// Creates a shell on the local terminal. Without the `std` feature, the shell has no keys
+    /// and no output until [`with_reader`](Self::with_reader) (or [`run_async`](Self::run_async))
+    /// and [`with_output`](Self::with_output) provide them, e.g. over a UART.
+    ///
+    /// `get_commands` lists the commands of `command_dispatcher` for completion and `##`, and
+    /// `has_command` finds them when routing a line (see [`route`]): pass the generated
+    /// `has_command`, which also finds the commands added with `register_command`.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         get_commands: fn() -> &'static [(&'static str, &'static str)],
+        has_command: fn(&str) -> bool,
         get_datatypes: fn() -> &'static str,
         get_shortcuts: fn() -> &'static str,
         is_shortcut: fn(&str) -> bool,
@@ -43,56 +378,1333 @@ impl<
             get_datatypes(),
             get_shortcuts(),
             prompt,
-        );
-
-        println!("Shell started (try ###)");
+        )
+        .with_hashtags(SHELL_HASHTAGS);
 
+        #[cfg(feature = "std")]
+        let interactive = terminal::is_interactive();
+        #[cfg(not(feature = "std"))]
+        let interactive = true;
         Self {
             parser,
-            _terminal: RawMode::new(0),
+            #[cfg(feature = "std")]
+            terminal: interactive.then(|| RawMode::new(0)),
+            interactive,
             is_shortcut,
+            has_command,
             command_dispatcher,
             shortcut_dispatcher,
+            providers: Vec::new(),
+            state: ShellState::new(),
+            aliases: Aliases::new(),
+            #[cfg(feature = "std")]
+            redirect: None,
+            timing: false,
+            ctrl_c_cancel: false,
+            #[cfg(feature = "std")]
+            clock: wall_clock_us,
+            #[cfg(not(feature = "std"))]
+            clock: no_clock,
+            on_command: None,
+            help: None,
+            exit_code: 0,
+            started: false,
         }
     }
+}
 
-    pub fn run(&mut self) {
-        let is_shortcut = self.is_shortcut;
-        let command_dispatcher = self.command_dispatcher;
-        let shortcut_dispatcher = self.shortcut_dispatcher;
+impl<
+    const NC: usize,
+    const FNL: usize,
+    const IML: usize,
+    const HTC: usize,
+    const HME: usize,
+    ERRTYPE: Debug,
+    OUT: Output,
+    KR: KeyReader,
+> uShell<NC, FNL, IML, HTC, HME, ERRTYPE, OUT, KR>
+{
+    /// Sends all output (prompt, listings, command results) to `out` instead of stdout.
+    pub fn with_output<O: Output>(self, out: O) -> uShell<NC, FNL, IML, HTC, HME, ERRTYPE, O, KR> {
+        uShell {
+            parser: self.parser.with_output(out),
+            #[cfg(feature = "std")]
+            terminal: self.terminal,
+            interactive: self.interactive,
+            is_shortcut: self.is_shortcut,
+            has_command: self.has_command,
+            command_dispatcher: self.command_dispatcher,
+            shortcut_dispatcher: self.shortcut_dispatcher,
+            providers: self.providers,
+            state: self.state,
+            aliases: self.aliases,
+            #[cfg(feature = "std")]
+            redirect: self.redirect,
+            timing: self.timing,
+            ctrl_c_cancel: self.ctrl_c_cancel,
+            clock: self.clock,
+            on_command: self.on_command,
+            help: self.help,
+            exit_code: self.exit_code,
+            started: self.started,
+        }
+    }
+
+    /// Reads the keys from `reader` instead of the local terminal, e.g. a telnet connection or a
+    /// serial port (see the `transport` feature of `ushell_input`), usually with a matching
+    /// [`with_output`](Self::with_output).
+    ///
+    /// The local terminal is left in its normal mode, and the shell runs interactively (with line
+    /// editing, greeting and prompt) even when stdin is not a terminal.
+    pub fn with_reader<R: KeyReader>(
+        self,
+        reader: R,
+    ) -> uShell<NC, FNL, IML, HTC, HME, ERRTYPE, OUT, R> {
+        uShell {
+            parser: self.parser.with_reader(reader),
+            #[cfg(feature = "std")]
+            terminal: None,
+            interactive: true,
+            is_shortcut: self.is_shortcut,
+            has_command: self.has_command,
+            command_dispatcher: self.command_dispatcher,
+            shortcut_dispatcher: self.shortcut_dispatcher,
+            providers: self.providers,
+            state: self.state,
+            aliases: self.aliases,
+            #[cfg(feature = "std")]
+            redirect: self.redirect,
+            timing: self.timing,
+            ctrl_c_cancel: self.ctrl_c_cancel,
+            clock: self.clock,
+            on_command: self.on_command,
+            help: self.help,
+            exit_code: self.exit_code,
+            started: self.started,
+        }
+    }
+
+    /// Shows readable command signatures (e.g. the generated `usage(name)`) in the `##` list.
+    pub fn with_usage(mut self, get_usage: fn(&str) -> &'static str) -> Self {
+        self.parser = self.parser.with_usage(get_usage);
+        self
+    }
 
+    /// Shows the user shortcuts with their descriptions (e.g. the generated
+    /// `get_shortcut_specs()`) in the `###` list.
+    pub fn with_shortcut_specs(
+        mut self,
+        get_specs: fn() -> &'static [(&'static str, &'static str)],
+    ) -> Self {
+        self.parser = self.parser.with_shortcut_specs(get_specs());
+        self
+    }
+
+    /// Replaces the static prompt with a provider called before each render, e.g. to show
+    /// device state or the status of the last command.
+    pub fn with_prompt_fn(mut self, prompt_fn: fn(&mut dyn fmt::Write)) -> Self {
+        self.parser = self.parser.with_prompt_fn(prompt_fn);
+        self
+    }
+
+    /// Prefixes the prompt with the result of the previous line (`StatusIndicator::Off` by
+    /// default): a `✓` / `✗` glyph and/or the error code, e.g. `✗ UnknownFunction > `.
+    pub fn with_status_prompt(mut self, indicator: StatusIndicator) -> Self {
+        self.parser = self.parser.with_status_indicator(indicator);
+        self
+    }
+
+    /// Sets the colors of the prompt, hints and result messages (`Theme::detect()` by default,
+    /// `Theme::PLAIN` for none).
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.parser = self.parser.with_theme(theme);
+        self
+    }
+
+    /// Selects how re-run commands are stored in the history (`DedupPolicy::MoveToFront` by
+    /// default).
+    pub fn with_dedup_policy(mut self, policy: DedupPolicy) -> Self {
+        self.parser = self.parser.with_dedup_policy(policy);
+        self
+    }
+
+    /// Persists the history to the file at `path` instead of `.hist`, or not at all with
+    /// `None` (only used with the `history-persistence` feature of `ushell_input`).
+    pub fn with_history_file(mut self, path: Option<&'static str>) -> Self {
+        self.parser = self.parser.with_history_file(path);
+        self
+    }
+
+    /// Selects whether `Ctrl+D` on an empty line exits the shell (enabled by default).
+    pub fn with_ctrl_d_eof(mut self, enabled: bool) -> Self {
+        self.parser = self.parser.with_ctrl_d_eof(enabled);
+        self
+    }
+
+    /// Selects what is printed after each line (`ReportMode::Verbose` by default); `#v`
+    /// changes it at run time.
+    pub fn with_report_mode(mut self, mode: ReportMode) -> Self {
+        self.state.verbosity = mode;
+        self
+    }
+
+    /// Returns the reporting mode in use.
+    pub fn report_mode(&self) -> ReportMode {
+        self.state.verbosity
+    }
+
+    /// Makes Ctrl+C while a line runs set [`CANCEL`] instead of ending the process (disabled
+    /// by default), so handlers dispatched with `dispatch_with_cancel(line, &CANCEL)` can stop
+    /// early. A second Ctrl+C ends the process as usual. Unix only.
+    pub fn with_ctrl_c_cancel(mut self, enabled: bool) -> Self {
+        self.ctrl_c_cancel = enabled;
+        self
+    }
+
+    /// Prints how long each dispatched line took, after its result (disabled by default);
+    /// `#t` changes it at run time.
+    pub fn with_timing(mut self, enabled: bool) -> Self {
+        self.timing = enabled;
+        self
+    }
+
+    /// Measures the commands with `clock` instead of the wall clock, e.g. a hardware timer or
+    /// the RTOS tick converted to microseconds. The value must not decrease; its origin does not
+    /// matter.
+    pub fn with_clock(mut self, clock: fn() -> u64) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Calls `hook` after each dispatched line with the line as typed, its result and its
+    /// duration (measured with the same clock as `#t`), e.g. to keep an audit log in flash,
+    /// RTT or syslog.
+    ///
+    /// The hook runs before the result is reported, whatever the reporting mode; hashtag
+    /// commands are not passed to it.
+    pub fn with_on_command(mut self, hook: CommandHook<ERRTYPE, IML>) -> Self {
+        self.on_command = Some(hook);
+        self
+    }
+
+    /// Adds a `help [command]` builtin printing with `help` (e.g. the generated `help`): bare
+    /// `help` lists the commands, `help <name>` shows the usage and parameters of `name`.
+    ///
+    /// Output longer than a page waits for a key between pages (`q` stops) when the shell is
+    /// interactive. A command named `help` takes precedence over the builtin.
+    pub fn with_help(mut self, help: HelpFn<ERRTYPE>) -> Self {
+        self.help = Some(help);
+        self
+    }
+
+    /// Mounts the commands of `provider` (e.g. another generated module) into the shell.
+    ///
+    /// The commands are listed and completed with the shell's own ones, and a line whose
+    /// command matches the provider is dispatched to it. Providers are asked in the order
+    /// they were added, after the shell's own commands (see [`route`]).
+    ///
+    /// Returns `false` and mounts nothing if `MAX_COMMAND_TABLES` providers are mounted, or if
+    /// the command names do not all fit in the `NC` autocomplete candidates.
+    pub fn add_provider(&mut self, provider: &'static dyn CommandProvider<IML>) -> bool {
+        if self.providers.is_full() || !self.parser.add_commands(provider.get_commands()) {
+            return false;
+        }
+        let _ = self.providers.push(provider);
+        true
+    }
+
+    /// Mounts the commands of `provider`, see [`add_provider`](Self::add_provider).
+    ///
+    /// Panics if the provider cannot be mounted.
+    pub fn with_provider(mut self, provider: &'static dyn CommandProvider<IML>) -> Self {
+        assert!(
+            self.add_provider(provider),
+            "too many command providers or commands"
+        );
+        self
+    }
+
+    /// Returns the shell variables, e.g. to set some before `run`.
+    pub fn vars(&mut self) -> &mut Variables<IML> {
+        &mut self.state.vars
+    }
+
+    /// Returns the state shared with the handlers (variables, verbosity, privilege level,
+    /// counters), e.g. to set the privilege level of the session before `run`.
+    pub fn state(&mut self) -> &mut ShellState<IML> {
+        &mut self.state
+    }
+
+    /// Returns the runtime aliases, e.g. to define some before `run`.
+    pub fn aliases(&mut self) -> &mut Aliases<IML> {
+        &mut self.aliases
+    }
+
+    /// Runs one line through the shortcut dispatcher, a matching provider or the command
+    /// dispatcher, without the prompt.
+    ///
+    /// A line starting with an alias is expanded first. The builtins (`set`, `unset`, `alias`,
+    /// `unalias`, `state`, `privilege`, `help`) are then handled, unless a command of the shell or
+    /// of a provider has the same name; any other line has its `$NAME` variables expanded before
+    /// it is dispatched.
+    ///
+    /// With the `std` feature, a line ending with `> file` (or `>> file` to append) runs with
+    /// its output sent to `file`, e.g. `dump 0x2000 4k > mem.txt`: the text of the builtins,
+    /// and what the handlers write through `with_output`. The file name may use `$NAME`
+    /// variables; the result of the line is still reported on the shell's output.
+    pub fn exec(&mut self, line: &str) -> Result<(), ShellError<ERRTYPE, IML>> {
+        let aliased = self
+            .aliases
+            .expand(line)
+            .transpose()
+            .map_err(ShellError::Alias)?;
+        let line = aliased.as_deref().unwrap_or(line);
+        #[cfg(feature = "std")]
+        if let Some(redirection) = Redirection::parse(line) {
+            return self.exec_redirected(redirection);
+        }
+        self.exec_line(line)
+    }
+
+    /// Runs the command of `redirection` with its output sent to the file.
+    #[cfg(feature = "std")]
+    fn exec_redirected(
+        &mut self,
+        redirection: Redirection<'_>,
+    ) -> Result<(), ShellError<ERRTYPE, IML>> {
+        let path = self
+            .state
+            .vars
+            .expand(redirection.path)
+            .map_err(ShellError::Variable)?;
+        let file = redirect::open(&path, redirection.append).map_err(ShellError::Redirect)?;
+        self.redirect = Some(file);
+        let result = self.exec_line(redirection.command);
+        self.redirect = None;
+        result
+    }
+
+    /// Runs an expanded line (no alias, no redirection) through the builtins or the
+    /// dispatchers, see [`exec`](Self::exec).
+    fn exec_line(&mut self, line: &str) -> Result<(), ShellError<ERRTYPE, IML>> {
+        let name = line.split_whitespace().next().unwrap_or("");
+        if !self.is_command(name)
+            && let Some(result) = self.exec_builtin(line)
+        {
+            return result;
+        }
+        let line = self.state.vars.expand(line).map_err(ShellError::Variable)?;
+        let out = line_out!(self);
+        state::lend(&mut self.state, || {
+            sink::lend(out, || {
+                exec::<IML, ERRTYPE>(
+                    &line,
+                    self.is_shortcut,
+                    self.has_command,
+                    &self.providers,
+                    self.command_dispatcher,
+                    self.shortcut_dispatcher,
+                )
+            })
+        })
+    }
+
+    /// Reads and runs lines like [`run`](Self::run), awaiting the input bytes from `source`
+    /// instead of blocking on the key reader, so the shell can share a single-core executor
+    /// (embassy, or any other) with other tasks.
+    ///
+    /// Each line is first offered, as typed, to `async_exec`, which returns `None` for lines it
+    /// does not handle: these run through the builtins and the dispatchers as in `run`. The
+    /// result of an awaited command is reported like any other. The shell runs interactively,
+    /// with line editing, greeting and prompt.
+    ///
+    /// ```ignore
+    /// #[embassy_executor::task]
+    /// async fn shell_task(mut rx: UartRx<'static, Async>) {
+    ///     let mut shell = shell().with_output(UartOutput::new());
+    ///     shell
+    ///         .run_async(&mut rx, async |line: &str| match line.split_once(' ') {
+    ///             Some(("sleep", ms)) => {
+    ///                 Timer::after_millis(ms.parse().ok()?).await;
+    ///                 Some(Ok(()))
+    ///             }
+    ///             _ => None,
+    ///         })
+    ///         .await;
+    /// }
+    /// ```
+    pub async fn run_async<S, F>(&mut self, source: &mut S, mut async_exec: F) -> i32
+    where
+        S: AsyncByteSource,
+        F: AsyncFnMut(&str) -> Option<Result<(), ERRTYPE>>,
+    {
+        self.interactive = true;
+        self.start();
+        let mut decoder = KeyDecoder::new();
+        let mut buf = [0u8; 32];
         loop {
-            let continue_running = self.parser.parse_input(move |input| {
-                exec::<IML, ERRTYPE>(input, is_shortcut, command_dispatcher, shortcut_dispatcher)
-            });
+            self.parser.show_prompt();
+            let len = source.read(&mut buf).await;
+            if len == 0 {
+                self.handle(ShellEvent::Exit);
+                return self.exit_code;
+            }
+            for &b in &buf[..len.min(buf.len())] {
+                let Some(key) = decoder.feed(b) else {
+                    continue;
+                };
+                let event = self.parser.feed_key(key);
+                if let ShellEvent::Line(line) = &event
+                    && !line.starts_with('#')
+                {
+                    let start = (self.clock)();
+                    if let Some(result) = async_exec(line).await {
+                        let result = result.map_err(ShellError::Command);
+                        self.finish_line(line, result, start);
+                        if !self.take_request() {
+                            return self.exit_code;
+                        }
+                        continue;
+                    }
+                }
+                if !self.handle(event) {
+                    return self.exit_code;
+                }
+            }
+        }
+    }
+
+    /// Reads and runs lines until the user quits or a command requests
+    /// `ShellAction::Exit`; returns the exit code (0 for `#q` and end of input).
+    ///
+    /// When stdin is not a terminal (e.g. `echo "status" | my_shell`) and no other key source
+    /// was installed with [`with_reader`](Self::with_reader), the lines are read as they come,
+    /// without raw mode, line editing, greeting or prompt.
+    pub fn run(&mut self) -> i32 {
+        #[cfg(feature = "std")]
+        if !self.interactive {
+            return self.run_piped();
+        }
+        self.start();
+        loop {
+            let event = self.parser.read_line();
+            if !self.handle(event) {
+                break;
+            }
+        }
+        self.exit_code
+    }
+
+    /// Returns `true` if `name` is a command of the shell or of a provider, which then shadows
+    /// a builtin of the same name.
+    fn is_command(&self, name: &str) -> bool {
+        (self.has_command)(name)
+            || self
+                .providers
+                .iter()
+                .any(|provider| provider.is_match(name))
+    }
+
+    /// Runs the builtins, `None` if `line` is not one of them:
+    /// - `set`: lists the variables
+    /// - `set NAME value`: sets `NAME` to the rest of the line (with variables expanded)
+    /// - `unset NAME`: removes `NAME`
+    /// - `alias`: lists the aliases, as `#a` does
+    /// - `alias NAME`: prints the command line of `NAME`
+    /// - `alias NAME command line`: maps `NAME` to the rest of the line
+    /// - `unalias NAME`: removes the alias `NAME`
+    /// - `help [command]`: prints the help, paged (only with [`with_help`](Self::with_help))
+    /// - `state`: prints the shared [`ShellState`] (verbosity, privilege level, counters)
+    /// - `privilege [N]`: prints the privilege level, or lowers it to `N`
+    fn exec_builtin(&mut self, line: &str) -> Option<Result<(), ShellError<ERRTYPE, IML>>> {
+        let line = line.trim();
+        let (command, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let args = args.trim_start();
+        match command {
+            "set" if args.is_empty() => {
+                for (name, value) in self.state.vars.iter() {
+                    let _ = writeln!(line_out!(self), "{} = {}", name, value);
+                }
+                Some(Ok(()))
+            }
+            "set" => {
+                let (name, value) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+                Some(
+                    self.state
+                        .vars
+                        .expand(value.trim())
+                        .and_then(|value| self.state.vars.set(name, &value))
+                        .map_err(ShellError::Variable),
+                )
+            }
+            "unset" => {
+                self.state.vars.unset(args);
+                Some(Ok(()))
+            }
+            "alias" if args.is_empty() => {
+                self.list_aliases();
+                Some(Ok(()))
+            }
+            "alias" => {
+                let (name, command) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+                let command = command.trim();
+                if command.is_empty() {
+                    if let Some(command) = self.aliases.get(name) {
+                        let _ = writeln!(line_out!(self), "{} = {}", name, command);
+                    }
+                    return Some(Ok(()));
+                }
+                Some(self.aliases.set(name, command).map_err(ShellError::Alias))
+            }
+            "unalias" => {
+                self.aliases.unset(args);
+                Some(Ok(()))
+            }
+            "state" => {
+                let state = &self.state;
+                let _ = write!(
+                    line_out!(self),
+                    "verbosity: {}\nprivilege: {}\ncommands:  {} ({} failed)\nvariables: {}\n",
+                    state.verbosity.name(),
+                    state.privilege,
+                    state.commands,
+                    state.failures,
+                    state.vars.iter().count()
+                );
+                Some(Ok(()))
+            }
+            "privilege" if args.is_empty() => {
+                let _ = writeln!(line_out!(self), "Privilege: {}", self.state.privilege);
+                Some(Ok(()))
+            }
+            "privilege" => Some(match args.parse::<u8>() {
+                Ok(level) if level <= self.state.privilege => {
+                    self.state.privilege = level;
+                    Ok(())
+                }
+                _ => Err(ShellError::Privilege),
+            }),
+            #[cfg(feature = "std")]
+            "help" => {
+                let help = self.help?;
+                let mut text = std::string::String::new();
+                if args.is_empty() {
+                    text.push_str("Commands (help <command> for details):\n");
+                }
+                let result = help(args, &mut text).map_err(ShellError::Command);
+                self.page(&text);
+                Some(result)
+            }
+            #[cfg(not(feature = "std"))]
+            "help" => {
+                let help = self.help?;
+                let out = line_out!(self);
+                if args.is_empty() {
+                    let _ = out.write_str("Commands (help <command> for details):\n");
+                }
+                Some(help(args, out).map_err(ShellError::Command))
+            }
+            _ => None,
+        }
+    }
 
-            if !continue_running {
-                println!("Shell exited...");
+    /// Runs each line of the piped stdin until its end, `#q` or an exit request.
+    #[cfg(feature = "std")]
+    fn run_piped(&mut self) -> i32 {
+        use std::io::BufRead;
+        for line in std::io::stdin().lock().lines().map_while(Result::ok) {
+            let event = self.parser.submit(&line);
+            if !self.handle(event) {
                 break;
             }
         }
+        self.exit_code
+    }
+
+    /// Returns the exit code requested by the command that ended the shell (0 if none did).
+    pub fn exit_code(&self) -> i32 {
+        self.exit_code
+    }
+
+    /// Handles at most one pending key without blocking, for shells driven from a superloop or
+    /// an RTOS tick instead of `run`.
+    ///
+    /// Finished lines are executed and their result printed as in `run`.
+    /// Returns `false` once the user or a command (see [`request_action`]) requested to quit.
+    pub fn poll(&mut self) -> bool {
+        self.start();
+        match self.parser.poll() {
+            Some(event) => self.handle(event),
+            None => true,
+        }
     }
+
+    /// Prints the greeting, once (not in pipe mode).
+    fn start(&mut self) {
+        if !self.started && self.interactive {
+            let hint = if self.help.is_some() {
+                "help or ###"
+            } else {
+                "###"
+            };
+            let _ = writeln!(self.parser.output(), "Shell started (try {})", hint);
+            self.started = true;
+        }
+    }
+
+    /// Prints `text`, waiting for a key after every `HELP_PAGE_LINES` lines when interactive
+    /// and not redirected; `q` or the end of the input stops it.
+    #[cfg(feature = "std")]
+    fn page(&mut self, text: &str) {
+        if self.redirect.is_some() {
+            let _ = line_out!(self).write_str(text);
+            return;
+        }
+        for (i, line) in text.lines().enumerate() {
+            if i > 0 && i % HELP_PAGE_LINES == 0 && self.interactive {
+                let out = self.parser.output();
+                let _ = write!(out, "-- more (q to stop) --");
+                out.flush();
+                let key = self.parser.wait_key();
+                self.parser.output().clear_line();
+                if matches!(key, None | Some(Key::Char('q'))) {
+                    return;
+                }
+            }
+            let _ = writeln!(self.parser.output(), "{}", line);
+        }
+    }
+
+    /// Prints the aliases, as `NAME = command line`.
+    fn list_aliases(&mut self) {
+        let out = line_out!(self);
+        if self.aliases.iter().next().is_none() {
+            let _ = writeln!(out, "No aliases");
+        }
+        for (name, command) in self.aliases.iter() {
+            let _ = writeln!(out, "{} = {}", name, command);
+        }
+    }
+
+    /// Runs a hashtag command of the shell (see `SHELL_HASHTAGS`); `input` has no `#`.
+    fn handle_hashtag(&mut self, input: &str) {
+        let mut words = input.split_whitespace();
+        let name = words.next();
+        match name {
+            Some("a") => self.list_aliases(),
+            #[cfg(feature = "std")]
+            Some("w") => {
+                let interval = words.next().and_then(|ms| ms.parse::<u64>().ok());
+                let command = words.next().map(|first| {
+                    let start = first.as_ptr() as usize - input.as_ptr() as usize;
+                    &input[start..]
+                });
+                match (interval, command) {
+                    (Some(interval), Some(command)) => self.watch(interval, command),
+                    _ => {
+                        let _ = writeln!(self.parser.output(), "Usage: #w interval_ms command");
+                    }
+                }
+            }
+            Some("t") => {
+                let timing = match words.next() {
+                    Some("on") => Some(true),
+                    Some("off") => Some(false),
+                    Some(_) => None,
+                    None => Some(!self.timing),
+                };
+                match timing {
+                    Some(timing) => {
+                        self.timing = timing;
+                        let state = if timing { "on" } else { "off" };
+                        let _ = writeln!(self.parser.output(), "Timing: {}", state);
+                    }
+                    None => {
+                        let _ = writeln!(self.parser.output(), "Usage: #t [on|off]");
+                    }
+                }
+            }
+            Some("v") => {
+                let mode = match words.next() {
+                    Some(name) => ReportMode::from_name(name),
+                    None => Some(self.state.verbosity.next()),
+                };
+                match mode {
+                    Some(mode) => {
+                        self.state.verbosity = mode;
+                        let _ = writeln!(self.parser.output(), "Reporting: {}", mode.name());
+                    }
+                    None => {
+                        let _ = writeln!(self.parser.output(), "Usage: #v [quiet|errors|verbose]");
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Runs `command` and prints its outcome every `interval_ms`, redrawing the screen each
+    /// time, until a key is pressed or the command requests an action (`#w`).
+    #[cfg(feature = "std")]
+    fn watch(&mut self, interval_ms: u64, command: &str) {
+        if !self.interactive {
+            let _ = writeln!(self.parser.output(), "#w needs a terminal");
+            return;
+        }
+        loop {
+            self.parser.clear_screen();
+            let _ = writeln!(
+                self.parser.output(),
+                "Every {}ms: {} (press a key to stop)\n",
+                interval_ms,
+                command
+            );
+            self.run_line(command);
+            let mut waited = 0;
+            loop {
+                if action_pending() || self.parser.key_pressed() {
+                    return;
+                }
+                if waited >= interval_ms {
+                    break;
+                }
+                let step = WATCH_POLL_MS.min(interval_ms - waited);
+                std::thread::sleep(std::time::Duration::from_millis(step));
+                waited += step;
+            }
+        }
+    }
+
+    /// Executes `line` and prints its outcome, and its duration if timing is on.
+    fn run_line(&mut self, line: &str) {
+        let start = (self.clock)();
+        let guard = self.ctrl_c_cancel.then(interrupt::CtrlCGuard::install);
+        let result = self.exec(line);
+        drop(guard);
+        self.finish_line(line, result, start);
+    }
+
+    /// Tells the command hook and prints the outcome of `line`, which started at `start`.
+    fn finish_line(
+        &mut self,
+        line: &str,
+        result: Result<(), ShellError<ERRTYPE, IML>>,
+        start: u64,
+    ) {
+        let duration = Duration::from_micros((self.clock)().saturating_sub(start));
+        if let Some(hook) = self.on_command {
+            hook(line, &result, duration);
+        }
+        match &result {
+            Ok(()) => self.parser.set_last_status(Ok(())),
+            Err(e) => self.parser.set_last_status(Err(&status_code(e))),
+        }
+        let theme = *self.parser.theme();
+        self.state.count(result.is_ok());
+        report(
+            self.parser.output(),
+            &theme,
+            self.state.verbosity,
+            line,
+            result,
+        );
+        if self.timing {
+            let _ = writeln!(self.parser.output(), "Time: {:?}", duration);
+        }
+    }
+
+    /// Applies the action requested by the line that just ran; returns `false` on exit.
+    fn take_request(&mut self) -> bool {
+        match take_action() {
+            ShellAction::Continue => true,
+            ShellAction::Exit(code) => {
+                self.exit_code = code;
+                self.handle(ShellEvent::Exit)
+            }
+            ShellAction::Reset => {
+                self.parser.reset();
+                self.started = false;
+                self.start();
+                true
+            }
+        }
+    }
+
+    /// Executes a finished line and prints its outcome; returns `false` on exit.
+    fn handle(&mut self, event: ShellEvent<IML>) -> bool {
+        match event {
+            ShellEvent::Line(line) => {
+                match line.strip_prefix('#') {
+                    Some(hashtag) => self.handle_hashtag(hashtag),
+                    None => self.run_line(&line),
+                }
+                self.take_request()
+            }
+            ShellEvent::Exit => {
+                if self.interactive {
+                    let _ = writeln!(self.parser.output(), "Shell exited...");
+                }
+                false
+            }
+            ShellEvent::Pending => true,
+        }
+    }
+}
+
+/// Returns the short code of an error shown by the status prompt: the variant name of the
+/// dispatcher's error (e.g. `UnknownFunction`), or of the `ShellError` otherwise.
+fn status_code<ERRTYPE: Debug, const IML: usize>(
+    error: &ShellError<ERRTYPE, IML>,
+) -> String<STATUS_CODE_LEN> {
+    let shown: &dyn Debug = match error {
+        ShellError::Command(e) => e,
+        e => e,
+    };
+    let mut code = StatusCode::default();
+    let _ = write!(code, "{:?}", shown);
+    code.code
+}
+
+/// Keeps the start of a `Debug` text up to its first `(`, ` ` or `{`, truncated to fit.
+#[derive(Default)]
+struct StatusCode {
+    code: String<STATUS_CODE_LEN>,
+    ended: bool,
 }
 
+impl Write for StatusCode {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            self.ended |= matches!(c, '(' | ' ' | '{');
+            if self.ended || self.code.push(c).is_err() {
+                return Err(fmt::Error);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Prints the outcome of running `input` to `out`, as selected by `mode`.
+fn report<ERRTYPE: Debug, const IML: usize>(
+    out: &mut dyn Output,
+    theme: &Theme,
+    mode: ReportMode,
+    input: &str,
+    result: Result<(), ShellError<ERRTYPE, IML>>,
+) {
+    let _ = match result {
+        Ok(_) if mode == ReportMode::Verbose => {
+            writeln!(out, "{}: {}", theme.paint(theme.success, "Success"), input)
+        }
+        Ok(_) => Ok(()),
+        Err(_) if mode == ReportMode::Quiet => Ok(()),
+        Err(e) => writeln!(
+            out,
+            "{}: {} for line '{}'",
+            theme.paint(theme.error, "Error"),
+            e,
+            input
+        ),
+    };
+}
+
+/// Where a line is dispatched, see [`route`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Route {
+    /// The shortcut dispatcher.
+    Shortcut,
+    /// The provider at this index.
+    Provider(usize),
+    /// The command dispatcher.
+    Command,
+}
+
+/// Picks the dispatcher of `input` by its first token, as `uShell` does; public so that other
+/// shell loops built on the generated modules route lines the same way.
+///
+/// A command found by `has_command` or by a provider wins, so a command line is never taken for
+/// a shortcut whose prefix or chord root starts it (`-reset` under the `-` prefix, `set_temp -5`
+/// with a `set_temp` chord). Any other line goes to the shortcut dispatcher if `is_shortcut`
+/// accepts it, else to the command dispatcher.
+pub fn route<const IML: usize>(
+    input: &str,
+    is_shortcut: fn(&str) -> bool,
+    has_command: fn(&str) -> bool,
+    providers: &[&'static dyn CommandProvider<IML>],
+) -> Route {
+    let name = input.split_whitespace().next().unwrap_or("");
+    if has_command(name) {
+        return Route::Command;
+    }
+    if let Some(index) = providers
+        .iter()
+        .position(|provider| provider.is_match(name))
+    {
+        return Route::Provider(index);
+    }
+    if is_shortcut(input) {
+        Route::Shortcut
+    } else {
+        Route::Command
+    }
+}
+
+/// Runs `input` through the dispatcher picked by [`route`].
 fn exec<const IML: usize, ERRTYPE: Debug>(
-    input: &String<IML>,
+    input: &str,
     is_shortcut: fn(&str) -> bool,
+    has_command: fn(&str) -> bool,
+    providers: &[&'static dyn CommandProvider<IML>],
     command_dispatcher: fn(&str) -> Result<(), ERRTYPE>,
     shortcut_dispatcher: fn(&str) -> Result<(), String<IML>>,
-) {
-    let result: Result<(), String<IML>> = if is_shortcut(input) {
-        shortcut_dispatcher(input)
-    } else {
-        command_dispatcher(input).map_err(|e| {
-            let mut err_str = String::<IML>::new();
-            use core::fmt::Write;
-            write!(&mut err_str, "{:?}", e).unwrap();
-            err_str
-        })
-    };
+) -> Result<(), ShellError<ERRTYPE, IML>> {
+    match route(input, is_shortcut, has_command, providers) {
+        Route::Shortcut => shortcut_dispatcher(input).map_err(ShellError::Shortcut),
+        Route::Provider(index) => providers[index]
+            .dispatch(input)
+            .map_err(ShellError::Provider),
+        Route::Command => command_dispatcher(input).map_err(ShellError::Command),
+    }
+}
+
+// ==================== TESTS =======================
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use ushell_input::input::mock::{MockKeys, MockOutput, MockTerminal};
+
+    type TestShell = uShell<8, 16, 64, 256, 8, (), MockOutput, MockKeys>;
+
+    thread_local! {
+        /// The last line passed to a dispatcher of a test shell.
+        static DISPATCHED: RefCell<std::string::String> = const { RefCell::new(std::string::String::new()) };
+    }
+
+    /// Returns the line dispatched since the last call, if any.
+    fn take_dispatched() -> std::string::String {
+        DISPATCHED.with(|line| core::mem::take(&mut *line.borrow_mut()))
+    }
+
+    /// Records `line`; `quit <code>` and `reset` also request the matching action, `print
+    /// text` writes `text` through [`with_output`].
+    fn dispatch_command(line: &str) -> Result<(), ()> {
+        DISPATCHED.with(|dispatched| *dispatched.borrow_mut() = line.to_string());
+        match line.split_once(' ') {
+            Some(("quit", code)) => {
+                request_action(ShellAction::Exit(code.parse().map_err(|_| ())?))
+            }
+            Some(("print", text)) => with_output(|out| writeln!(out, "{}", text))
+                .ok_or(())?
+                .map_err(|_| ())?,
+            _ if line == "reset" => request_action(ShellAction::Reset),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn dispatch_shortcut(line: &str) -> Result<(), String<64>> {
+        DISPATCHED.with(|dispatched| *dispatched.borrow_mut() = format!("shortcut {}", line));
+        Ok(())
+    }
+
+    fn no_datatypes() -> &'static str {
+        ""
+    }
+
+    /// A shell finding its commands with `has_command`, on a scripted terminal.
+    fn shell(has_command: fn(&str) -> bool) -> (TestShell, MockTerminal) {
+        let term = MockTerminal::new();
+        let shell = uShell::<8, 16, 64, 256, 8, ()>::new(
+            no_commands,
+            has_command,
+            no_datatypes,
+            no_datatypes,
+            is_shortcut,
+            dispatch_command,
+            dispatch_shortcut,
+            "> ",
+        )
+        .with_output(term.output())
+        .with_reader(term.keys());
+        (shell, term)
+    }
+
+    /// Shortcuts under the `-` prefix and the `set_temp` chord root.
+    fn is_shortcut(input: &str) -> bool {
+        input.starts_with('-') || input.split_whitespace().next() == Some("set_temp")
+    }
+
+    /// Static commands, and `-boot` as if added with `register_command`.
+    fn has_command(name: &str) -> bool {
+        matches!(name, "set_temp" | "-reset" | "-boot")
+    }
+
+    fn has_no_command(_: &str) -> bool {
+        false
+    }
+
+    fn no_commands() -> &'static [(&'static str, &'static str)] {
+        &[]
+    }
+
+    fn is_net(name: &str) -> bool {
+        matches!(name, "-net" | "set_temp" | "state")
+    }
 
-    match result {
-        Ok(_) => println!("Success: {}", input),
-        Err(e) => println!("Error: {} for line '{}'", e, input),
+    fn dispatch_ok(_: &str) -> Result<(), ()> {
+        Ok(())
+    }
+
+    static NET: Provider<()> = Provider::new(no_commands, is_net, dispatch_ok);
+
+    #[test]
+    fn test_route_prefers_commands_over_shortcuts() {
+        let routed = |input| route::<16>(input, is_shortcut, has_command, &[]);
+        assert_eq!(routed("set_temp -5"), Route::Command);
+        assert_eq!(routed("-reset"), Route::Command);
+        assert_eq!(routed("-boot 0x800"), Route::Command);
+        assert_eq!(routed("-r"), Route::Shortcut);
+        assert_eq!(routed("-reset_all"), Route::Shortcut);
+        assert_eq!(routed("unknown -5"), Route::Command);
+        assert_eq!(routed(""), Route::Command);
+    }
+
+    #[test]
+    fn test_route_providers_after_commands() {
+        let providers: [&'static dyn CommandProvider<16>; 1] = [&NET];
+        let routed = |input| route::<16>(input, is_shortcut, has_command, &providers);
+        assert_eq!(routed("set_temp -5"), Route::Command);
+        assert_eq!(routed("-net up"), Route::Provider(0));
+        assert_eq!(routed("-n"), Route::Shortcut);
+
+        let without_commands = |input| route::<16>(input, is_shortcut, has_no_command, &providers);
+        assert_eq!(without_commands("set_temp -5"), Route::Provider(0));
+        assert_eq!(without_commands("set_temp"), Route::Provider(0));
+        assert_eq!(without_commands("-reset"), Route::Shortcut);
+    }
+
+    fn has_builtin_names(name: &str) -> bool {
+        matches!(name, "set" | "privilege" | "alias")
+    }
+
+    #[test]
+    fn test_builtins_run_without_same_named_command() {
+        let (mut shell, term) = shell(has_command);
+        shell.exec("set X 1").unwrap();
+        assert_eq!(shell.vars().get("X"), Some("1"));
+        shell.exec("state").unwrap();
+        assert!(term.take_text().contains("variables: 1"));
+        assert_eq!(take_dispatched(), "");
+    }
+
+    #[test]
+    fn test_commands_shadow_builtins() {
+        let (mut shell, term) = shell(has_builtin_names);
+        shell.exec("set X 1").unwrap();
+        assert_eq!(take_dispatched(), "set X 1");
+        assert_eq!(shell.vars().get("X"), None);
+        shell.exec("privilege 3").unwrap();
+        assert_eq!(take_dispatched(), "privilege 3");
+        shell.exec("alias").unwrap();
+        assert_eq!(take_dispatched(), "alias");
+
+        // A provider's command shadows a builtin as well; the others still run
+        assert!(shell.add_provider(&NET));
+        shell.exec("state").unwrap();
+        assert_eq!(take_dispatched(), "");
+        shell.exec("unset X").unwrap();
+        assert_eq!(take_dispatched(), "");
+        assert!(!term.take_text().contains("verbosity"));
+    }
+
+    /// A file of the temporary directory, removed first.
+    fn temp_file(name: &str) -> std::string::String {
+        let path = std::env::temp_dir().join(format!("ushell2-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn test_redirect_to_file() {
+        let (mut shell, term) = shell(has_command);
+        let path = temp_file("redirect");
+        shell.vars().set("OUT", &path).unwrap();
+        shell.exec("print one > $OUT").unwrap();
+        assert_eq!(take_dispatched(), "print one");
+        shell.exec("privilege >> $OUT").unwrap();
+        shell.exec("print \"a > b\" >>$OUT").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "one\nPrivilege: 0\n\"a > b\"\n"
+        );
+        shell.exec("print two > $OUT").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "two\n");
+        assert_eq!(term.take_text(), "");
+
+        // Without redirection, the output is the shell's again
+        shell.exec("print three").unwrap();
+        assert_eq!(term.take_text(), "three\n");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_redirect_errors() {
+        let (mut shell, _term) = shell(has_command);
+        assert!(matches!(
+            shell.exec("print one >"),
+            Err(ShellError::Redirect(RedirectError::MissingFile))
+        ));
+        assert_eq!(take_dispatched(), "");
+        shell.exec("set X \"a>b\"").unwrap();
+        assert_eq!(shell.vars().get("X"), Some("\"a>b\""));
+    }
+
+    #[test]
+    fn test_aliases_expanded_once_before_builtins() {
+        let (mut shell, _term) = shell(has_command);
+        shell.exec("alias a b 1").unwrap();
+        shell.exec("alias b a 2").unwrap();
+        shell.exec("a x").unwrap();
+        assert_eq!(take_dispatched(), "b 1 x");
+
+        shell.exec("alias setx set X").unwrap();
+        shell.exec("setx $$1").unwrap();
+        assert_eq!(shell.vars().get("X"), Some("$1"));
+        assert_eq!(take_dispatched(), "");
+    }
+
+    #[test]
+    fn test_report_mode_names() {
+        for mode in [
+            ReportMode::Quiet,
+            ReportMode::ErrorsOnly,
+            ReportMode::Verbose,
+        ] {
+            assert_eq!(ReportMode::from_name(mode.name()), Some(mode));
+            assert_ne!(mode.next(), mode);
+            assert_eq!(mode.next().next().next(), mode);
+        }
+        assert_eq!(ReportMode::from_name("loud"), None);
+        assert_eq!(ReportMode::default(), ReportMode::Verbose);
+    }
+
+    #[test]
+    fn test_report_by_mode() {
+        let reported = |mode, result: Result<(), ShellError<(), 64>>| {
+            let mut out = std::string::String::new();
+            report(&mut out, &Theme::PLAIN, mode, "led 1", result);
+            out
+        };
+        assert_eq!(reported(ReportMode::Verbose, Ok(())), "Success: led 1\n");
+        assert_eq!(reported(ReportMode::ErrorsOnly, Ok(())), "");
+        assert_eq!(reported(ReportMode::Quiet, Ok(())), "");
+        let failed = || Err(ShellError::Command(()));
+        assert_eq!(
+            reported(ReportMode::Verbose, failed()),
+            "Error: () for line 'led 1'\n"
+        );
+        assert_eq!(
+            reported(ReportMode::ErrorsOnly, failed()),
+            "Error: () for line 'led 1'\n"
+        );
+        assert_eq!(reported(ReportMode::Quiet, failed()), "");
+    }
+
+    #[test]
+    fn test_report_mode_switched_by_hashtag() {
+        let (shell, term) = shell(has_command);
+        let mut shell = shell
+            .with_theme(Theme::PLAIN)
+            .with_report_mode(ReportMode::Quiet);
+        shell.handle_hashtag("v");
+        assert_eq!(shell.report_mode(), ReportMode::ErrorsOnly);
+        shell.handle_hashtag("v verbose");
+        assert_eq!(shell.report_mode(), ReportMode::Verbose);
+        shell.handle_hashtag("v loud");
+        assert_eq!(shell.report_mode(), ReportMode::Verbose);
+        assert_eq!(
+            term.take_text(),
+            "Reporting: errors\nReporting: verbose\nUsage: #v [quiet|errors|verbose]\n"
+        );
+        shell.run_line("set_temp 5");
+        assert!(term.take_text().contains("Success: set_temp 5"));
+    }
+
+    /// Serializes the tests running whole scripts: the requested action is global.
+    static ACTIONS: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    #[test]
+    fn test_exit_requested_by_command() {
+        let _actions = ACTIONS.lock().unwrap();
+        let (mut shell, term) = shell(has_command);
+        term.type_line("quit 3").type_line("never");
+        assert_eq!(shell.run(), 3);
+        assert_eq!(shell.exit_code(), 3);
+        assert_eq!(take_dispatched(), "quit 3");
+        assert!(term.pending_keys() > 0);
+        assert!(term.take_text().contains("Shell exited..."));
+    }
+
+    #[test]
+    fn test_reset_requested_by_command() {
+        let _actions = ACTIONS.lock().unwrap();
+        let (mut shell, term) = shell(has_command);
+        term.type_line("reset").type_line("set_temp 1");
+        assert_eq!(shell.run(), 0);
+        assert_eq!(take_dispatched(), "set_temp 1");
+        assert_eq!(term.take_output().matches("Shell started").count(), 2);
+    }
+
+    fn gpio_commands() -> &'static [(&'static str, &'static str)] {
+        &[("gpio_set", "B"), ("gpio_get", "")]
+    }
+
+    fn is_gpio(name: &str) -> bool {
+        name.starts_with("gpio_")
+    }
+
+    fn dispatch_gpio(line: &str) -> Result<(), &'static str> {
+        if line.ends_with("9") {
+            return Err("no such pin");
+        }
+        dispatch_command(line).map_err(|_| "")
+    }
+
+    fn many_commands() -> &'static [(&'static str, &'static str)] {
+        &[
+            ("c1", ""),
+            ("c2", ""),
+            ("c3", ""),
+            ("c4", ""),
+            ("c5", ""),
+            ("c6", ""),
+            ("c7", ""),
+            ("c8", ""),
+            ("c9", ""),
+        ]
+    }
+
+    static GPIO: Provider<&'static str> = Provider::new(gpio_commands, is_gpio, dispatch_gpio);
+    static MANY: Provider<()> = Provider::new(many_commands, is_net, dispatch_ok);
+
+    #[test]
+    fn test_provider_dispatch_and_errors() {
+        let (mut shell, _term) = shell(has_command);
+        assert!(shell.add_provider(&GPIO));
+        shell.exec("gpio_set 1").unwrap();
+        assert_eq!(take_dispatched(), "gpio_set 1");
+        match shell.exec("gpio_set 9") {
+            Err(ShellError::Provider(msg)) => assert_eq!(msg, "\"no such pin\""),
+            other => panic!("{:?}", other),
+        }
+        shell.exec("set_temp 1").unwrap();
+        assert_eq!(take_dispatched(), "set_temp 1");
+    }
+
+    #[test]
+    fn test_provider_capacity() {
+        let (mut shell, _term) = shell(has_command);
+        assert!(!shell.add_provider(&MANY));
+        for _ in 0..MAX_COMMAND_TABLES {
+            assert!(shell.add_provider(&NET));
+        }
+        assert!(!shell.add_provider(&NET));
+        assert_eq!(shell.providers.len(), MAX_COMMAND_TABLES);
+    }
+
+    /// Help of `set_temp`, or 25 numbered lines for `long`.
+    fn help(name: &str, out: &mut dyn fmt::Write) -> Result<(), ()> {
+        match name {
+            "" => writeln!(out, "set_temp <i8>").map_err(|_| ()),
+            "set_temp" => writeln!(out, "Usage: set_temp <i8>").map_err(|_| ()),
+            "long" => (1..=25)
+                .try_for_each(|i| writeln!(out, "line {}", i))
+                .map_err(|_| ()),
+            _ => Err(()),
+        }
+    }
+
+    #[test]
+    fn test_help_builtin() {
+        let (mut shell, term) = shell(has_command);
+        shell.exec("help set_temp").unwrap();
+        assert_eq!(take_dispatched(), "help set_temp");
+
+        let mut shell = shell.with_help(help);
+        shell.exec("help").unwrap();
+        shell.exec("help set_temp").unwrap();
+        assert!(matches!(
+            shell.exec("help other"),
+            Err(ShellError::Command(()))
+        ));
+        assert_eq!(take_dispatched(), "");
+        assert_eq!(
+            term.take_text(),
+            "Commands (help <command> for details):\nset_temp <i8>\nUsage: set_temp <i8>\n"
+        );
+    }
+
+    #[test]
+    fn test_help_paged() {
+        let (shell, term) = shell(has_command);
+        let mut shell = shell.with_help(help);
+        shell.exec("help long").unwrap();
+        let text = term.take_text();
+        assert!(text.contains("line 20\n"));
+        assert!(!text.contains("line 21"));
+
+        term.key(Key::Char(' '));
+        shell.exec("help long").unwrap();
+        let text = term.take_text();
+        assert!(text.contains("line 25\n"));
+        assert!(!text.contains("-- more"));
+    }
+
+    #[allow(dead_code)]
+    #[derive(Debug)]
+    enum CodeError {
+        UnknownFunction,
+        WrongArity { expected: u16 },
+        BadValue(u8),
+        AVariantNameLongerThanTheStatusCode,
+    }
+
+    #[test]
+    fn test_status_code() {
+        let code = |e| status_code::<CodeError, 16>(&ShellError::Command(e));
+        assert_eq!(code(CodeError::UnknownFunction), "UnknownFunction");
+        assert_eq!(code(CodeError::WrongArity { expected: 2 }), "WrongArity");
+        assert_eq!(code(CodeError::BadValue(3)), "BadValue");
+        assert_eq!(
+            code(CodeError::AVariantNameLongerThanTheStatusCode),
+            "AVariantNameLongerThanTh"
+        );
+        let shell_code = |e| status_code::<CodeError, 16>(&e);
+        assert_eq!(shell_code(ShellError::Privilege), "Privilege");
+        assert_eq!(shell_code(ShellError::Alias(AliasError::Full)), "Alias");
+        assert_eq!(
+            shell_code(ShellError::Shortcut(String::try_from("x y").unwrap())),
+            "Shortcut"
+        );
+    }
+
+    #[test]
+    fn test_status_prompt_after_failure() {
+        let _actions = ACTIONS.lock().unwrap();
+        let (shell, term) = shell(has_command);
+        let mut shell = shell
+            .with_theme(Theme::PLAIN)
+            .with_status_prompt(StatusIndicator::GlyphAndCode);
+        term.type_line("privilege 9").type_line("set_temp 1");
+        shell.run();
+        let text = term.take_text();
+        assert!(
+            text.contains("\u{2717} Privilege > set_temp 1\n"),
+            "{}",
+            text
+        );
+        assert!(text.contains("\u{2713} > "), "{}", text);
     }
 }
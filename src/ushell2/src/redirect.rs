@@ -0,0 +1,160 @@
+use core::fmt::{self, Display};
+use std::fs::{File, OpenOptions};
+use std::io;
+use ushell_input::output::StreamOutput;
+
+/// Error of a `command > file` redirection.
+#[derive(Debug)]
+pub enum RedirectError {
+    /// No file name after `>` / `>>`.
+    MissingFile,
+    /// The file could not be opened.
+    Io(io::Error),
+}
+
+impl Display for RedirectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RedirectError::MissingFile => write!(f, "missing file after >"),
+            RedirectError::Io(e) => write!(f, "cannot redirect: {}", e),
+        }
+    }
+}
+
+/// A line split at its redirection: `command > path` or `command >> path`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Redirection<'a> {
+    /// The command line before the `>`.
+    pub command: &'a str,
+    /// The file name after it (empty if missing).
+    pub path: &'a str,
+    /// `>>`: append to the file instead of truncating it.
+    pub append: bool,
+}
+
+impl<'a> Redirection<'a> {
+    /// Splits `line` at its first word starting with `>`; `None` if it has none.
+    ///
+    /// Words are split as the generated tokenizer does: a `"` quoted word or a `[` byte list
+    /// is one word, spaces included, so `set x "a > b"`, `set x a>b` or `take_char '>'` are
+    /// not redirected.
+    pub fn parse(line: &'a str) -> Option<Self> {
+        let bytes = line.as_bytes();
+        let is_space = |b: u8| b == b' ' || b == b'\t';
+        let mut i = 0;
+        while i < bytes.len() {
+            while i < bytes.len() && is_space(bytes[i]) {
+                i += 1;
+            }
+            match bytes.get(i) {
+                None => break,
+                Some(b'>') => return Some(Self::split(line, i)),
+                Some(&open @ (b'"' | b'[')) => {
+                    let close = if open == b'"' { b'"' } else { b']' };
+                    i += 1;
+                    while i < bytes.len() && bytes[i] != close {
+                        i += 1;
+                    }
+                }
+                Some(_) => {}
+            }
+            while i < bytes.len() && !is_space(bytes[i]) {
+                i += 1;
+            }
+        }
+        None
+    }
+
+    /// Splits `line` at the `>` at `pos`.
+    fn split(line: &'a str, pos: usize) -> Self {
+        let rest = &line[pos + 1..];
+        let (path, append) = match rest.strip_prefix('>') {
+            Some(rest) => (rest, true),
+            None => (rest, false),
+        };
+        Self {
+            command: line[..pos].trim_end(),
+            path: path.trim(),
+            append,
+        }
+    }
+}
+
+/// Opens `path` as the output of a redirected line, created if needed and truncated unless
+/// `append`. Its text is written out when it is dropped.
+pub(crate) fn open(path: &str, append: bool) -> Result<StreamOutput<File>, RedirectError> {
+    if path.is_empty() {
+        return Err(RedirectError::MissingFile);
+    }
+    let file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append)
+        .open(path)
+        .map_err(RedirectError::Io)?;
+    Ok(StreamOutput::new(file).with_crlf(false))
+}
+
+// ==================== TESTS =======================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Option<(&str, &str, bool)> {
+        Redirection::parse(line).map(|r| (r.command, r.path, r.append))
+    }
+
+    #[test]
+    fn test_parse_truncate_and_append() {
+        assert_eq!(
+            parse("dump 0 4k > mem.txt"),
+            Some(("dump 0 4k", "mem.txt", false))
+        );
+        assert_eq!(
+            parse("dump 0 4k >> mem.txt"),
+            Some(("dump 0 4k", "mem.txt", true))
+        );
+        assert_eq!(parse("dump >mem.txt"), Some(("dump", "mem.txt", false)));
+        assert_eq!(parse("dump >>mem.txt "), Some(("dump", "mem.txt", true)));
+    }
+
+    #[test]
+    fn test_parse_without_redirection() {
+        assert_eq!(parse("dump 0 4k"), None);
+        assert_eq!(parse(""), None);
+    }
+
+    #[test]
+    fn test_parse_missing_path() {
+        assert_eq!(parse("dump >"), Some(("dump", "", false)));
+        assert_eq!(parse("dump >>  "), Some(("dump", "", true)));
+    }
+
+    #[test]
+    fn test_parse_ignores_gt_inside_words() {
+        assert_eq!(parse("set x a>b"), None);
+        assert_eq!(parse("set x \"a>b\""), None);
+        assert_eq!(parse("set x \"a > b\""), None);
+        assert_eq!(parse("take_char '>'"), None);
+        assert_eq!(parse("write [1 > 2]"), None);
+    }
+
+    #[test]
+    fn test_parse_after_quoted_word() {
+        assert_eq!(
+            parse("echo \"a > b\" > out.txt"),
+            Some(("echo \"a > b\"", "out.txt", false))
+        );
+        assert_eq!(
+            parse("write [1, 2] >> out.bin"),
+            Some(("write [1, 2]", "out.bin", true))
+        );
+    }
+
+    #[test]
+    fn test_open_missing_file() {
+        assert!(matches!(open("", false), Err(RedirectError::MissingFile)));
+    }
+}
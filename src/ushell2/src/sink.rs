@@ -0,0 +1,76 @@
+use ushell_input::output::Output;
+
+use crate::lent;
+
+lent::slot! {
+    /// Output of the line being dispatched, lent by [`lend`].
+    CURRENT
+}
+
+/// Runs `f` with the output of the line being dispatched: the shell's output, or the file of
+/// its redirection (`command > file`).
+///
+/// Returns `None` outside a line dispatched by a shell, or from a nested `with_output` call.
+/// Text printed with `println!` instead goes to the standard output of the process, whatever
+/// the output of the shell and the redirection of the line. Without the `std` feature, it is
+/// only available on targets with atomic pointer swaps, as [`with_state`](crate::with_state).
+///
+/// ```ignore
+/// fn dump(addr: u32, len: u32) -> Result<(), &'static str> {
+///     ushell2::with_output(|out| {
+///         for offset in (0..len).step_by(4) {
+///             let _ = writeln!(out, "{:08x}: {:08x}", addr + offset, read_word(addr + offset));
+///         }
+///     })
+///     .ok_or("no shell")
+/// }
+/// ```
+#[cfg(any(feature = "std", target_has_atomic = "ptr"))]
+pub fn with_output<R>(f: impl FnOnce(&mut dyn Output) -> R) -> Option<R> {
+    // SAFETY: `lend` is the only one lending to `CURRENT`, a `dyn Output` whose lifetime is
+    // erased: it is only reachable while `lend` runs, so `f` cannot outlive it.
+    unsafe { lent::with(&CURRENT, |out: &mut (dyn Output + 'static)| f(out)) }
+}
+
+/// Makes `out` reachable through [`with_output`] while `f` runs.
+pub(crate) fn lend<R>(out: &mut dyn Output, f: impl FnOnce() -> R) -> R {
+    lent::lend(&CURRENT, out, f)
+}
+
+// ==================== TESTS =======================
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use std::string::String;
+
+    #[test]
+    fn test_with_output_outside_lend() {
+        assert_eq!(with_output(|out| write!(out, "lost")), None);
+    }
+
+    #[test]
+    fn test_lend_writes_to_output() {
+        let mut text = String::new();
+        let written = lend(&mut text, || {
+            let first = with_output(|out| write!(out, "a"));
+            let nested = with_output(|_| with_output(|out| write!(out, "b")));
+            let last = with_output(|out| write!(out, "c"));
+            (first, nested, last)
+        });
+        assert_eq!(written, (Some(Ok(())), Some(None), Some(Ok(()))));
+        assert_eq!(text, "ac");
+        assert_eq!(with_output(|_| ()), None);
+    }
+
+    #[test]
+    fn test_nested_lend() {
+        let mut outer = String::new();
+        let mut inner = String::new();
+        lend(&mut outer, || {
+            lend(&mut inner, || with_output(|out| out.write_str("inner")));
+            with_output(|out| out.write_str("outer"))
+        });
+        assert_eq!((outer.as_str(), inner.as_str()), ("outer", "inner"));
+    }
+}
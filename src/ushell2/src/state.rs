@@ -0,0 +1,143 @@
+use core::any::Any;
+
+use crate::ReportMode;
+use crate::lent;
+use crate::vars::Variables;
+
+/// State of a shell shared by its builtins, hashtag commands and the handlers it dispatches.
+///
+/// The shell owns it; while a line is dispatched, the handler reaches it through [`with_state`]
+/// instead of keeping cross-command state in globals of its own.
+///
+/// - `IML`: Maximum length of a variable value, as for [`Variables`].
+///
+pub struct ShellState<const IML: usize> {
+    /// Shell variables (`set NAME value`), expanded as `$NAME`.
+    pub vars: Variables<IML>,
+    /// What is printed after each line (`#v`).
+    pub verbosity: ReportMode,
+    /// Privilege level of the session, for handlers to check (0 by default). The `privilege`
+    /// builtin only lowers it; raising it is left to a command, e.g. after a password check.
+    pub privilege: u8,
+    /// Lines run so far (builtins included, hashtag commands excluded).
+    pub commands: u32,
+    /// Lines that failed.
+    pub failures: u32,
+}
+
+impl<const IML: usize> Default for ShellState<IML> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const IML: usize> ShellState<IML> {
+    /// Creates a state without variables, with verbose reporting and privilege level 0.
+    pub fn new() -> Self {
+        Self {
+            vars: Variables::new(),
+            verbosity: ReportMode::Verbose,
+            privilege: 0,
+            commands: 0,
+            failures: 0,
+        }
+    }
+
+    /// Counts a dispatched line and its outcome.
+    pub(crate) fn count(&mut self, ok: bool) {
+        self.commands = self.commands.wrapping_add(1);
+        if !ok {
+            self.failures = self.failures.wrapping_add(1);
+        }
+    }
+}
+
+lent::slot! {
+    /// State of the shell dispatching a line, lent by [`lend`] as a `dyn Any`.
+    CURRENT
+}
+
+/// Runs `f` with the state of the shell dispatching the current line.
+///
+/// Returns `None` outside a line dispatched by a shell, from a nested `with_state` call, or if
+/// `IML` is not the one of the shell. Without the `std` feature, the state of a single line is
+/// reachable at a time, and only on targets with atomic pointer swaps.
+///
+/// ```ignore
+/// fn reboot() -> Result<(), &'static str> {
+///     let level = ushell2::with_state::<INPUT_MAX_LEN, _>(|state| state.privilege);
+///     if level < Some(2) {
+///         return Err("permission denied");
+///     }
+///     ...
+/// }
+/// ```
+#[cfg(any(feature = "std", target_has_atomic = "ptr"))]
+pub fn with_state<const IML: usize, R>(f: impl FnOnce(&mut ShellState<IML>) -> R) -> Option<R> {
+    // SAFETY: `lend` is the only one lending to `CURRENT`, a `dyn Any`.
+    unsafe { lent::with(&CURRENT, |state: &mut dyn Any| state.downcast_mut().map(f)) }.flatten()
+}
+
+/// Makes `state` reachable through [`with_state`] while `f` runs.
+pub(crate) fn lend<const IML: usize, R>(state: &mut ShellState<IML>, f: impl FnOnce() -> R) -> R {
+    lent::lend::<dyn Any, _>(&CURRENT, state, f)
+}
+
+// ==================== TESTS =======================
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_state_outside_lend() {
+        assert_eq!(with_state::<64, _>(|state| state.privilege), None);
+    }
+
+    #[test]
+    fn test_lend_gives_state_back() {
+        let mut state = ShellState::<64>::new();
+        let seen = lend(&mut state, || {
+            with_state::<64, _>(|state| {
+                state.privilege = 3;
+                state.vars.set("X", "1")
+            })
+        });
+        assert_eq!(seen, Some(Ok(())));
+        assert_eq!(state.privilege, 3);
+        assert_eq!(state.vars.get("X"), Some("1"));
+        assert_eq!(with_state::<64, _>(|_| ()), None);
+    }
+
+    #[test]
+    fn test_with_state_of_another_iml() {
+        let mut state = ShellState::<64>::new();
+        lend(&mut state, || {
+            assert_eq!(with_state::<32, _>(|_| ()), None);
+            assert_eq!(with_state::<64, _>(|_| ()), Some(()));
+        });
+    }
+
+    #[test]
+    fn test_nested_with_state() {
+        let mut state = ShellState::<64>::new();
+        let nested = lend(&mut state, || {
+            with_state::<64, _>(|_| with_state::<64, _>(|state| state.privilege))
+        });
+        assert_eq!(nested, Some(None));
+    }
+
+    #[test]
+    fn test_nested_lend_restores_outer_state() {
+        let mut outer = ShellState::<64>::new();
+        outer.privilege = 1;
+        let mut inner = ShellState::<64>::new();
+        inner.privilege = 2;
+        lend(&mut outer, || {
+            let seen = lend(&mut inner, || with_state::<64, _>(|state| state.privilege));
+            assert_eq!(seen, Some(2));
+            assert_eq!(with_state::<64, _>(|state| state.privilege), Some(1));
+        });
+        assert_eq!((outer.privilege, inner.privilege), (1, 2));
+    }
+}
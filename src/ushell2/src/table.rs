@@ -0,0 +1,100 @@
+//! Fixed-capacity table of named values, the storage of the shell variables and aliases.
+
+use heapless::{String, Vec};
+
+/// Error of setting an entry of a [`NamedTable`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TableError {
+    /// The name does not fit in `K` bytes.
+    NameTooLong,
+    /// The value does not fit in `V` bytes.
+    ValueTooLong,
+    /// `N` entries are already set.
+    Full,
+}
+
+/// Table of up to `N` names of at most `K` bytes, each with a value of at most `V` bytes, kept in
+/// the order they were first set.
+pub(crate) struct NamedTable<const N: usize, const K: usize, const V: usize> {
+    entries: Vec<(String<K>, String<V>), N>,
+}
+
+impl<const N: usize, const K: usize, const V: usize> NamedTable<N, K, V> {
+    pub(crate) const fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Sets `name` to `value`, replacing its previous value.
+    pub(crate) fn set(&mut self, name: &str, value: &str) -> Result<(), TableError> {
+        let value = String::try_from(value).map_err(|_| TableError::ValueTooLong)?;
+        if let Some((_, old)) = self.entries.iter_mut().find(|(n, _)| n == name) {
+            *old = value;
+            return Ok(());
+        }
+        let name = String::try_from(name).map_err(|_| TableError::NameTooLong)?;
+        self.entries
+            .push((name, value))
+            .map_err(|_| TableError::Full)
+    }
+
+    /// Removes `name`; returns `false` if it was not set.
+    pub(crate) fn unset(&mut self, name: &str) -> bool {
+        match self.entries.iter().position(|(n, _)| n == name) {
+            Some(pos) => {
+                self.entries.remove(pos);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the value of `name`, if set.
+    pub(crate) fn get(&self, name: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the `(name, value)` pairs in the order they were first set.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+}
+
+// ==================== TESTS =======================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_get_unset() {
+        let mut table = NamedTable::<2, 4, 8>::new();
+        table.set("a", "1").unwrap();
+        table.set("b", "2").unwrap();
+        table.set("a", "3").unwrap();
+        assert_eq!(table.get("a"), Some("3"));
+        assert!(table.iter().eq([("a", "3"), ("b", "2")]));
+        assert!(table.unset("a"));
+        assert!(!table.unset("a"));
+        assert_eq!(table.get("a"), None);
+    }
+
+    #[test]
+    fn test_set_errors() {
+        let mut table = NamedTable::<1, 4, 8>::new();
+        assert_eq!(
+            table.set("a", "too long value"),
+            Err(TableError::ValueTooLong)
+        );
+        assert_eq!(table.set("name", "v"), Ok(()));
+        assert_eq!(table.set("other", "v"), Err(TableError::NameTooLong));
+        assert_eq!(table.set("b", "v"), Err(TableError::Full));
+        assert_eq!(table.set("name", "w"), Ok(()));
+    }
+}
@@ -0,0 +1,205 @@
+use core::fmt::{self, Display};
+use heapless::String;
+
+use crate::table::{NamedTable, TableError};
+
+/// Maximum number of shell variables.
+pub const MAX_VARIABLES: usize = 16;
+
+/// Maximum length of a variable name.
+pub const VAR_NAME_LEN: usize = 16;
+
+/// Error of setting or expanding a shell variable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VarError {
+    /// The name is empty, too long, or not made of letters, digits and `_` (not starting with a
+    /// digit).
+    InvalidName,
+    /// `MAX_VARIABLES` variables are already set.
+    Full,
+    /// The value, or the line once expanded, does not fit in `IML` bytes.
+    TooLong,
+    /// `$NAME` refers to a variable that is not set.
+    Undefined(String<VAR_NAME_LEN>),
+}
+
+impl Display for VarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VarError::InvalidName => write!(f, "invalid variable name"),
+            VarError::Full => write!(f, "too many variables"),
+            VarError::TooLong => write!(f, "line too long"),
+            VarError::Undefined(name) => write!(f, "undefined variable ${}", name),
+        }
+    }
+}
+
+/// Fixed-capacity store of the shell variables (`set NAME value`), expanded as `$NAME`.
+///
+/// - `IML`: Maximum length of a value, and of a line once expanded.
+///
+pub struct Variables<const IML: usize> {
+    entries: NamedTable<MAX_VARIABLES, VAR_NAME_LEN, IML>,
+}
+
+impl<const IML: usize> Default for Variables<IML> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const IML: usize> Variables<IML> {
+    pub const fn new() -> Self {
+        Self {
+            entries: NamedTable::new(),
+        }
+    }
+
+    /// Sets `name` to `value`, replacing its previous value.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), VarError> {
+        if !is_valid_name(name) {
+            return Err(VarError::InvalidName);
+        }
+        self.entries.set(name, value).map_err(|e| match e {
+            TableError::NameTooLong => VarError::InvalidName,
+            TableError::ValueTooLong => VarError::TooLong,
+            TableError::Full => VarError::Full,
+        })
+    }
+
+    /// Removes `name`; returns `false` if it was not set.
+    pub fn unset(&mut self, name: &str) -> bool {
+        self.entries.unset(name)
+    }
+
+    /// Returns the value of `name`, if set.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries.get(name)
+    }
+
+    /// Returns the `(name, value)` pairs in the order they were first set.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter()
+    }
+
+    /// Replaces each `$NAME` of `line` with the value of the variable; `$$` stands for `$`.
+    ///
+    /// A `$` not followed by a name is kept as is.
+    pub fn expand(&self, line: &str) -> Result<String<IML>, VarError> {
+        let mut expanded = String::new();
+        let mut rest = line;
+        while let Some(pos) = rest.find('$') {
+            expanded
+                .push_str(&rest[..pos])
+                .map_err(|_| VarError::TooLong)?;
+            let after = &rest[pos + 1..];
+            if let Some(after) = after.strip_prefix('$') {
+                expanded.push('$').map_err(|_| VarError::TooLong)?;
+                rest = after;
+                continue;
+            }
+            let len = name_len(after);
+            if len == 0 {
+                expanded.push('$').map_err(|_| VarError::TooLong)?;
+                rest = after;
+                continue;
+            }
+            let name = &after[..len];
+            let value = self.get(name).ok_or_else(|| {
+                let mut n = String::new();
+                let _ = n.push_str(&name[..name.len().min(VAR_NAME_LEN)]);
+                VarError::Undefined(n)
+            })?;
+            expanded.push_str(value).map_err(|_| VarError::TooLong)?;
+            rest = &after[len..];
+        }
+        expanded.push_str(rest).map_err(|_| VarError::TooLong)?;
+        Ok(expanded)
+    }
+}
+
+/// Returns the length of the name at the start of `text` (0 if there is none).
+fn name_len(text: &str) -> usize {
+    if text.starts_with(|c: char| c.is_ascii_digit()) {
+        return 0;
+    }
+    text.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(text.len())
+}
+
+/// Returns `true` if `name` can be used as a variable name.
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.len() <= VAR_NAME_LEN && name_len(name) == name.len()
+}
+
+// ==================== TESTS =======================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars() -> Variables<32> {
+        let mut vars = Variables::new();
+        vars.set("ADDR", "0x2000").unwrap();
+        vars.set("N", "4").unwrap();
+        vars
+    }
+
+    #[test]
+    fn test_expand() {
+        let vars = vars();
+        assert_eq!(vars.expand("read $ADDR $N").unwrap(), "read 0x2000 4");
+        assert_eq!(vars.expand("x$N,$ADDR.").unwrap(), "x4,0x2000.");
+        assert_eq!(vars.expand("no variables").unwrap(), "no variables");
+    }
+
+    #[test]
+    fn test_expand_escaped_and_bare_dollar() {
+        let vars = vars();
+        assert_eq!(vars.expand("cost $$N").unwrap(), "cost $N");
+        assert_eq!(vars.expand("$$$N").unwrap(), "$4");
+        assert_eq!(vars.expand("a $ b $").unwrap(), "a $ b $");
+        assert_eq!(vars.expand("$1 $-").unwrap(), "$1 $-");
+    }
+
+    #[test]
+    fn test_expand_unset() {
+        let mut vars = vars();
+        assert!(vars.unset("N"));
+        assert!(!vars.unset("N"));
+        assert_eq!(
+            vars.expand("read $ADDR $N"),
+            Err(VarError::Undefined(String::try_from("N").unwrap()))
+        );
+        assert_eq!(vars.expand("$$N").unwrap(), "$N");
+    }
+
+    #[test]
+    fn test_expand_too_long() {
+        let mut vars = vars();
+        vars.set("LONG", "0123456789012345678901234567").unwrap();
+        assert_eq!(vars.expand("xx $LONG $N"), Err(VarError::TooLong));
+    }
+
+    #[test]
+    fn test_set_replaces_and_validates() {
+        let mut vars = vars();
+        vars.set("N", "8").unwrap();
+        assert_eq!(vars.get("N"), Some("8"));
+        assert_eq!(vars.iter().count(), 2);
+        for name in ["", "1X", "A-B", "A B", "NAME_LONGER_THAN_16"] {
+            assert_eq!(vars.set(name, "v"), Err(VarError::InvalidName), "{}", name);
+        }
+        assert_eq!(vars.set("_x1", "v"), Ok(()));
+    }
+
+    #[test]
+    fn test_set_full() {
+        let mut vars = Variables::<8>::new();
+        for i in 0..MAX_VARIABLES {
+            vars.set(&format!("V{}", i), "v").unwrap();
+        }
+        assert_eq!(vars.set("MORE", "v"), Err(VarError::Full));
+        assert_eq!(vars.set("V0", "w"), Ok(()));
+    }
+}
@@ -25,8 +25,12 @@
 //! +------+-------+   +------+------+   +------+------+   +------+------+   +------+------+
 //! | Z    | usize |   | F    | f32  |   | c    | char |   | b    | bool |   | v    | void |
 //! +------+-------+   +------+------+   +------+------+   +------+------+   +------+------+
-//! | z    | isize |   | f    | f64  |   | s    | &str |   | h    | &[u8]|
-//!+------+-------+   +------+------+   +------+------+   +------+------+
+//! | z    | isize |   | f    | f64  |   | s    | &str |   | h    | &[u8]|   | a    | &[u8]|
+//!+------+-------+   +------+------+   +------+------+   +------+------+   +------+------+
+//!
+//! `h` takes a hex string (`AABB`), `a` a byte list (`[0xAA, 0xBB]`); both fill a
+//! `hexstr_size`-byte buffer. `T` takes an ISO-8601 timestamp (`2024-05-01T12:00:00Z`) as a
+//! `(u64, u32)` pair of seconds and nanoseconds since the Unix epoch (`BadTimestamp` otherwise).
 //!
 //! Examples:
 //! - "DdFsb" => arguments: u32, i32, f64, &str, bool
@@ -35,6 +39,11 @@
 //!
 //! ## Macro Input Format
 //! - DSL: `generate_commands_dispatcher!(mod m; \"dFs: path::to::f1 path::to::f2, t: path::to::f3\");`
+//! - Structured: `generate_commands_dispatcher!(mod m; \"dFs\": [path::to::f1, path::to::f2]; \"t\": [path::to::f3]);`
+//! - File: `path = \"commands.cfg\"`; may be mixed with inline DSL strings and structured entries.
+//!   Files may contain `@include \"other.cfg\"` lines, resolved relative to the including file.
+//! - Malformed groups, unknown descriptor characters and invalid paths are compile errors
+//!   (prefixed with `file:line:` for descriptor files).
 //!
 //! * Tokenization splits a command line into tokens, respecting **double quotes** for `&str`.
 //! * `dispatch(line)` parses the function name + arguments, checks **arity**, parses into a stack
@@ -44,16 +53,90 @@
 //! ## no_std
 //! - Uses `core` only; suitable for embedded/stack-only use.
 //!
-//! `DispatchError` reports: `Empty`, `UnknownFunction`, `WrongArity` and per-type parsing errors:
-//! `BadBool`, `BadChar`, `BadUnsigned`, `BadSigned`, `BadFloat`.
+//! A descriptor may end with `[cfg(<predicate>)]` (`"d[cfg(feature = \"debug\")]: crate::dbg::poke"`):
+//! the commands of its group, their wrappers and their table entries are then only compiled when
+//! the predicate holds.
+//!
+//! Positional parameters may be named with `:name` (`"D:freq F:duty s:label"`); names are used
+//! by `usage(name)` and `param_name(name, arg_index)` but do not change the handler signature.
+//!
+//! Each command also has a stable `u16` ID (`path@0x10`, or the next free one in sorted order);
+//! `dispatch_binary(id, payload)` decodes little-endian arguments from bytes (`BadPayload` on a
+//! malformed payload).
+//!
+//! With `manifest = true` the module also exports `MANIFEST_JSON`, a JSON description of every
+//! command (name, id, descriptor, usage, arity, parameters, options, flags) for host tools.
+//!
+//! With `doc_help = true` each command's help starts with the first line of its handler's doc
+//! comment, harvested by `#[command_doc]` on the handler.
+//!
+//! `fn_prefix = "shell_"` renames the public functions (`shell_dispatch`, `shell_tokenize`,
+//! `shell_get_commands`, ...) so several modules can be glob-imported side by side.
+//!
+//! With `ffi = true` it exports `#[no_mangle] SHELL_ENTRIES_C`, a `#[repr(C)]` table of
+//! `CEntry { name, arity, call }` so C code can run the commands (`call("1200 0.5")`).
+//!
+//! Each handler is checked against its descriptor at compile time; a mismatch is reported as
+//! ``handler `path` does not match command `name` (descriptor `..`)`` with the expected signature.
+//!
+//! `usage(name)` returns a readable signature such as `set_pwm <u32 freq> <f64 duty> <str label>`;
+//! `WrongArity` carries the same text. `help(name, out)` writes it with the parameters, options
+//! and flags of the command (types, ranges, defaults), or the usage of every command for `""`.
+//!
+//! `dispatch_with_cancel(line, cancel)` runs a line with an `AtomicBool` cancellation token
+//! (cleared first). Handlers of a `[cancel]` descriptor (`"v[cancel]: crate::erase"`) take it as
+//! their first parameter, poll it and return early once it is set.
+//!
+//! With `service = crate::drv::Driver` the handlers `crate::drv::Driver::<method>` are methods:
+//! `dispatch_with_service(line, &mut driver)` calls them with the driver as `self`
+//! (`dispatch_with_service_cancel(line, &mut driver, cancel)` adds the token), and other entry
+//! points fail them with `NoService`.
+//!
+//! `runtime_commands = <capacity>` adds a table searched after the static `match`:
+//! `register_command(name, arity, parser_id, handler)` adds a command parsed with the descriptor
+//! `PARAM_SPECS[parser_id]`, whose handler reads its arguments from `CallCtx`.
+//!
+//! With `defmt = true` `DispatchError` derives `defmt::Format` (the invoking crate depends on
+//! `defmt`).
+//!
+//! With `std = true` the module also has `get_function_names()`, a `Vec` of `FUNCTION_NAMES`, for
+//! crates with `std` (or `alloc` in scope).
+//!
+//! `minimal = true` omits `PARAM_SPECS`, `DESCRIPTOR_HELP`, the descriptors of `NAME_AND_SPEC`
+//! and the help text (usages are the bare names) and shares parsers between descriptors that only
+//! differ in parameter names or flags, for targets where flash is scarce. `get_commands` still
+//! lists the names, so the module works with `uShell`, providers and `merge_commands!`; it cannot
+//! be combined with `runtime_commands`, which parses with `PARAM_SPECS`.
+//!
+//! `DispatchError` reports: `Empty`, `UnknownFunction`, `Disabled`, `WrongArity` and per-type parsing errors:
+//! `BadBool`, `BadChar`, `BadUnsigned`, `BadSigned`, `BadFloat`, `BadHexStr`, `BadByteList`, `BadTimestamp`, plus `UnknownFlag`,
+//! `MissingOptionValue`, `OutOfRange` and `TooManyTokens` (more tokens than the buffer holds).
+//!
+//! `v` marks a command without positional parameters (arity 0). It cannot be mixed with value
+//! types, but may carry flags and options (`"v --verbose"`).
+//!
+//! Descriptors may end with `--name` switches (`"Ds --verbose"`); each adds a trailing `bool`
+//! parameter, set when the switch appears anywhere on the line (`UnknownFlag` otherwise).
+//!
+//! Options `--name=T` / `--name=T(default)` (`"s --count=D(1)"`) add a typed parameter after the
+//! positional ones, given as `--count=5` or `--count 5`; absent options take the default
+//! (`Default::default()` when none is declared). They do not count towards the arity.
+//!
+//! Numeric parameters may carry a range (`d{0..=100}`, `B{1..8}`, `f{0.0..1.0}`); values outside
+//! it fail with `OutOfRange { arg_index }` before the handler is called.
+//!
+//! Integer arguments accept an optional `k`/`M`/`G` suffix (`4k`, `0x10k`), scaled by the
+//! `unit_base` macro option (1024 by default, or 1000).
+//!
+//! Commands can be locked out at runtime with `set_command_enabled(name, false)`; the state is
+//! kept in a static bitmask with one bit per generated entry.
 //!
 use proc_macro::TokenStream;
 use proc_macro2::{Span, TokenStream as TokenStream2};
-use quote::{format_ident, quote};
-use syn::{Ident, LitStr, Result, Token, parse::Parse, parse_macro_input};
+use quote::{format_ident, quote, quote_spanned};
+use syn::{Ident, LitStr, Result, Token, parse::Parse, parse_macro_input, spanned::Spanned};
 
-/// A std-like alias used locally during macro input parsing.
-type StdResult<T, E> = std::result::Result<T, E>;
+use crate::docgen::doc_const_ident;
 
 /// Per-descriptor maximum counts of each primitive (used to size `CallCtx`).
 #[derive(Default, Clone, Copy)]
@@ -87,6 +170,12 @@ struct HostCounts {
 
     // hexstring AABBF3C6 => [170, 187, 243, 198]
     hexstr_c: usize,
+
+    // byte list [0xAA, 0xBB] => [170, 187]
+    bytelist_c: usize,
+
+    // timestamp 2024-05-01T12:00:00Z => (1714564800, 0)
+    timestamp_c: usize,
 }
 
 /// Component-wise maximum between two `HostCounts`.
@@ -115,16 +204,48 @@ fn host_counts_max(a: HostCounts, b: HostCounts) -> HostCounts {
         char_c: m!(char_c),
         str_c: m!(str_c),
         hexstr_c: m!(hexstr_c),
+        bytelist_c: m!(bytelist_c),
+        timestamp_c: m!(timestamp_c),
     }
 }
 
-/// Parsed macro input: `mod <ident>;` followed by either a DSL `LitStr`
-struct CommandMacroInput {
-    mod_ident: Ident,               // Module identifier for the generated dispatcher
-    body: LitStr,                   // Macro input body as string
-    hexstr_size: Option<syn::Expr>, // Optional size for hexstr buffers
+/// Parsed macro input: `mod <ident>;` followed by `;`-separated items, each one of
+/// - an option `key = value` (`hexstr_size`, `unit_base`, `path`, `manifest`, `ffi`,
+///   `fn_prefix`, `doc_help`, `service`, `runtime_commands`, `minimal`, `std`, `defmt`),
+/// - a DSL string `"desc: path path, desc: path"`,
+/// - a structured entry `"desc": [path, path]`.
+///
+/// A `path = "<file>"` option and inline items may be mixed freely.
+pub(crate) struct CommandMacroInput {
+    pub(crate) mod_ident: Ident, // Module identifier for the generated dispatcher
+    pub(crate) groups: Vec<DescGroup>, // Descriptor groups, from all DSL/file/structured sources
+    pub(crate) hexstr_size: Option<syn::Expr>, // Optional size for hexstr buffers
+    pub(crate) unit_base: u64,   // Multiplier for `k`/`M`/`G` integer suffixes (1024 or 1000)
+    pub(crate) manifest: bool,   // Emit `MANIFEST_JSON` describing every command
+    pub(crate) ffi: bool,        // Emit the `#[no_mangle]` C table `SHELL_ENTRIES_C`
+    pub(crate) fn_prefix: String, // Prepended to the public function names (`shell_dispatch`)
+    pub(crate) doc_help: bool,   // Help summaries come from `#[command_doc]` on the handlers
+    pub(crate) service: Option<syn::Path>, // Type whose methods are handlers (`Type::method`)
+    pub(crate) runtime_commands: Option<syn::Expr>, // Capacity of the `register_command` table
+    pub(crate) minimal: bool,    // Omit descriptor strings and help text, share equivalent parsers
+    pub(crate) std: bool,        // Emit `get_function_names()`, which returns a `Vec`
+    pub(crate) defmt: bool,      // `DispatchError` derives `defmt::Format`
+    pub(crate) handler_results: bool, // Handlers return `super::HandlerResult` (typed shortcuts)
 }
 
+/// One `descriptor: functions` group, from either the string DSL or the structured form.
+pub(crate) struct DescGroup {
+    pub(crate) desc: String,              // Raw descriptor, e.g. `"Dd{0..=100}"`
+    pub(crate) funcs: Vec<syn::Path>,     // Functions sharing this descriptor
+    pub(crate) ids: Vec<Option<u16>>,     // Explicit command ID per function (`path@0x10`), if any
+    pub(crate) cfg: Option<TokenStream2>, // `[cfg(<predicate>)]` gating the group's commands
+    pub(crate) cancel: bool,              // `[cancel]`: handlers take the cancellation token
+    pub(crate) span: Span,                // Span used for diagnostics about this group
+}
+
+/// Default multiplier for the `k`/`M`/`G` integer suffixes.
+pub(crate) const DEFAULT_UNIT_BASE: u64 = 1024;
+
 /// Implementation for CommandMacroInput structure
 impl Parse for CommandMacroInput {
     fn parse(input: syn::parse::ParseStream) -> Result<Self> {
@@ -133,121 +254,716 @@ impl Parse for CommandMacroInput {
         let mod_ident: Ident = input.parse()?;
         input.parse::<Token![;]>()?;
 
-        // Optionally parse hexstr_size = <expr>;
-        let hexstr_size = if input.peek(syn::Ident) && input.peek2(Token![=]) {
-            let key: Ident = input.parse()?;
-            if key == "hexstr_size" {
+        // Items: options, DSL strings and structured entries (the final `;` is optional)
+        let mut hexstr_size = None;
+        let mut unit_base = DEFAULT_UNIT_BASE;
+        let mut manifest = false;
+        let mut ffi = false;
+        let mut fn_prefix = String::new();
+        let mut doc_help = false;
+        let mut service = None;
+        let mut runtime_commands = None;
+        let mut minimal = false;
+        let mut std = false;
+        let mut defmt = false;
+        let mut groups = Vec::new();
+        let mut has_commands = false;
+        while !input.is_empty() {
+            if input.peek(syn::Ident) && input.peek2(Token![=]) {
+                let key: Ident = input.parse()?;
                 input.parse::<Token![=]>()?;
-                let expr: syn::Expr = input.parse()?;
-                input.parse::<Token![;]>()?;
-                Some(expr)
+                match key.to_string().as_str() {
+                    "hexstr_size" => hexstr_size = Some(input.parse::<syn::Expr>()?),
+                    "unit_base" => {
+                        let lit: syn::LitInt = input.parse()?;
+                        unit_base = lit.base10_parse::<u64>()?;
+                        if unit_base != 1000 && unit_base != 1024 {
+                            return Err(syn::Error::new(
+                                lit.span(),
+                                "`unit_base` must be either 1000 or 1024",
+                            ));
+                        }
+                    }
+                    "path" => {
+                        groups.extend(read_descriptor_file(&input.parse::<LitStr>()?)?);
+                        has_commands = true;
+                    }
+                    "manifest" => manifest = input.parse::<syn::LitBool>()?.value,
+                    "ffi" => ffi = input.parse::<syn::LitBool>()?.value,
+                    "fn_prefix" => {
+                        let lit: LitStr = input.parse()?;
+                        fn_prefix = lit.value();
+                        if syn::parse_str::<Ident>(&format!("{}dispatch", fn_prefix)).is_err() {
+                            return Err(syn::Error::new(
+                                lit.span(),
+                                "`fn_prefix` must start an identifier, e.g. \"shell_\"",
+                            ));
+                        }
+                    }
+                    "doc_help" => doc_help = input.parse::<syn::LitBool>()?.value,
+                    "service" => service = Some(input.parse::<syn::Path>()?),
+                    "runtime_commands" => runtime_commands = Some(input.parse::<syn::Expr>()?),
+                    "minimal" => minimal = input.parse::<syn::LitBool>()?.value,
+                    "std" => std = input.parse::<syn::LitBool>()?.value,
+                    "defmt" => defmt = input.parse::<syn::LitBool>()?.value,
+                    _ => {
+                        return Err(syn::Error::new(
+                            key.span(),
+                            "Unexpected identifier, expected 'hexstr_size', 'unit_base', 'path', 'manifest', 'ffi', 'fn_prefix', 'doc_help', 'service', 'runtime_commands', 'minimal', 'std' or 'defmt'",
+                        ));
+                    }
+                }
+            } else if input.peek(LitStr) && input.peek2(Token![:]) {
+                // Structured form: `"desc": [path, ...]`
+                let desc: LitStr = input.parse()?;
+                input.parse::<Token![:]>()?;
+                let content;
+                syn::bracketed!(content in input);
+                let funcs: syn::punctuated::Punctuated<(syn::Path, Option<u16>), Token![,]> =
+                    content.parse_terminated(parse_func_ref, Token![,])?;
+                if funcs.is_empty() {
+                    return Err(syn::Error::new(
+                        desc.span(),
+                        "Expected at least one function path for this descriptor",
+                    ));
+                }
+                let value = desc.value();
+                let (spec, cfg) =
+                    split_cfg(value.trim()).map_err(|msg| syn::Error::new(desc.span(), msg))?;
+                let (spec, cancel) = split_cancel(spec);
+                split_descriptor(spec, desc.span())?;
+                let (funcs, ids) = funcs.into_iter().unzip();
+                groups.push(DescGroup {
+                    desc: spec.to_string(),
+                    funcs,
+                    ids,
+                    cfg,
+                    cancel,
+                    span: desc.span(),
+                });
+                has_commands = true;
+            } else if input.peek(LitStr) {
+                let body: LitStr = input.parse()?;
+                groups.extend(parse_dsl_groups(
+                    &body.value(),
+                    body.span(),
+                    DslOrigin::Literal(&body),
+                )?);
+                has_commands = true;
             } else {
-                return Err(syn::Error::new(
-                    key.span(),
-                    "Unexpected identifier, expected 'hexstr_size'",
+                return Err(input.error(
+                    "Expected `key = value`, a descriptor string or a `\"desc\": [paths]` entry",
                 ));
             }
-        } else {
-            None
-        };
+            if input.is_empty() {
+                break;
+            }
+            input.parse::<Token![;]>()?;
+        }
 
-        let body: LitStr = input.parse()?;
+        if !has_commands {
+            return Err(syn::Error::new(
+                mod_ident.span(),
+                "Expected a descriptor string, `path = \"...\"` or `\"desc\": [paths]` entries",
+            ));
+        }
+        if minimal && doc_help {
+            return Err(syn::Error::new(
+                mod_ident.span(),
+                "`doc_help = true` cannot be combined with `minimal = true`, which omits help text",
+            ));
+        }
+        if minimal && runtime_commands.is_some() {
+            return Err(syn::Error::new(
+                mod_ident.span(),
+                "`runtime_commands` cannot be combined with `minimal = true`, which omits the `PARAM_SPECS` it parses with",
+            ));
+        }
         Ok(CommandMacroInput {
             mod_ident,
+            groups,
             hexstr_size,
-            body,
+            unit_base,
+            manifest,
+            ffi,
+            fn_prefix,
+            doc_help,
+            service,
+            runtime_commands,
+            minimal,
+            std,
+            defmt,
+            handler_results: false,
         })
     }
 }
 
-/// Generate a no-heap dispatcher module from a DSL mapping.
-pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
-    let CommandMacroInput {
-        mod_ident,
-        body,
-        hexstr_size,
-    } = parse_macro_input!(input as CommandMacroInput);
+/// Parse one function of a structured entry: a path with an optional `@ <id>` command ID.
+fn parse_func_ref(input: syn::parse::ParseStream) -> Result<(syn::Path, Option<u16>)> {
+    let path: syn::Path = input.parse()?;
+    if !input.peek(Token![@]) {
+        return Ok((path, None));
+    }
+    input.parse::<Token![@]>()?;
+    let id: syn::LitInt = input.parse()?;
+    Ok((path, Some(id.base10_parse::<u16>()?)))
+}
 
-    // Collect (descriptor, [paths]) pairs from either the DSL
+/// Where DSL text comes from, to point its diagnostics at the offending part.
+#[derive(Clone, Copy)]
+enum DslOrigin<'a> {
+    /// A string literal of the macro input.
+    Literal(&'a LitStr),
+    /// A descriptor file and the line the text starts at.
+    File(&'a std::path::Path, usize),
+}
 
-    let mut pairs: Vec<(String, Vec<syn::Path>)> = {
-        let s = body.value();
-        let mut acc = Vec::new();
-        for group in s.split(',') {
-            let grp = group.trim();
-            if grp.is_empty() {
-                continue;
-            }
-            let (desc, names) = match grp.split_once(':') {
-                Some((d, r)) => (d.trim(), r.trim()),
-                None => continue,
+/// Build a DSL diagnostic about `text[range]`.
+///
+/// Text from a file is prefixed with `file:line:`. Text from a literal is located by line and
+/// column, and the error spans just that part of the literal where the compiler supports it.
+fn dsl_error(
+    span: Span,
+    origin: DslOrigin,
+    text: &str,
+    range: std::ops::Range<usize>,
+    msg: String,
+) -> syn::Error {
+    let before = &text[..range.start];
+    let line = before.matches('\n').count();
+    match origin {
+        DslOrigin::File(file, first_line) => syn::Error::new(
+            span,
+            format!("{}:{}: {}", file.display(), first_line + line, msg),
+        ),
+        DslOrigin::Literal(lit) => {
+            let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+            let at = if text.contains('\n') {
+                format!("line {}, column {}", line + 1, column)
+            } else {
+                format!("column {}", column)
             };
-            if desc.is_empty() || names.is_empty() {
-                continue;
+            syn::Error::new(
+                literal_subspan(lit, range).unwrap_or(span),
+                format!("{} (at {} of the DSL string)", msg, at),
+            )
+        }
+    }
+}
+
+/// Span of the bytes `range` of the value of `lit`, if the literal has no escapes (so value
+/// and source offsets match) and the compiler can give spans inside literals.
+fn literal_subspan(lit: &LitStr, range: std::ops::Range<usize>) -> Option<Span> {
+    let token = lit.token();
+    let source = token.to_string();
+    let open = source.find('"')? + 1;
+    let value = lit.value();
+    if source.get(open..open + value.len())? != value {
+        return None;
+    }
+    token.subspan(open + range.start..open + range.end)
+}
+
+/// Byte offset of `part` in `text`, which it was sliced from.
+fn offset_in(text: &str, part: &str) -> usize {
+    part.as_ptr() as usize - text.as_ptr() as usize
+}
+
+/// Split a DSL string (`"desc: path path, desc: path"`) into descriptor groups.
+/// Malformed groups, invalid descriptors and unparsable paths are reported as errors, located
+/// at the group or path in question.
+fn parse_dsl_groups(text: &str, span: Span, origin: DslOrigin) -> Result<Vec<DescGroup>> {
+    let mut acc = Vec::new();
+    for group in split_groups(text) {
+        let grp = group.trim();
+        if grp.is_empty() {
+            continue;
+        }
+        let err_at = |part: &str, msg: String| {
+            let start = offset_in(text, part);
+            dsl_error(span, origin, text, start..start + part.len(), msg)
+        };
+        let err = |msg: String| err_at(grp, msg);
+
+        let Some((desc, names)) = split_group(grp).map(|(desc, names)| {
+            // `d: crate:cmd::foo` splits at the colon of the path: the `: ` before it is the
+            // separator, descriptors have no whitespace after their colons.
+            match desc.find(": ").or_else(|| desc.find(":\t")) {
+                Some(i) => (&grp[..i], &grp[i + 1..]),
+                None => (desc, names),
             }
-            let desc_str = desc.to_string();
-            let funcs: StdResult<Vec<_>, _> = names
-                .split_whitespace()
-                .map(syn::parse_str::<syn::Path>)
-                .collect();
-            let funcs = match funcs {
-                Ok(v) => v,
-                Err(_) => continue,
-            };
-            acc.push((desc_str, funcs));
+        }) else {
+            return Err(err(format!(
+                "Malformed command group `{}`: expected `<descriptor>: <function paths>`",
+                grp
+            )));
+        };
+        let (desc, names) = (desc.trim(), names.trim());
+        if desc.is_empty() {
+            return Err(err(format!(
+                "Missing descriptor in command group `{}`",
+                grp
+            )));
+        }
+        if names.is_empty() {
+            return Err(err(format!(
+                "No function paths for descriptor `{}` in command group `{}`",
+                desc, grp
+            )));
+        }
+        let (desc, cfg) = split_cfg(desc).map_err(|msg| err_at(desc, msg))?;
+        let (desc, cancel) = split_cancel(desc);
+        split_descriptor(desc, span).map_err(|e| err_at(desc, e.to_string()))?;
+        let (funcs, ids) = names
+            .split_whitespace()
+            .map(|name| {
+                // `path@id` assigns an explicit command ID.
+                let (name, id) = match name.split_once('@') {
+                    Some((name, id)) => {
+                        let id = syn::parse_str::<syn::LitInt>(id)
+                            .and_then(|lit| lit.base10_parse::<u16>())
+                            .map_err(|_| {
+                                err_at(
+                                    id,
+                                    format!("Invalid command id `{}` for function `{}`", id, name),
+                                )
+                            })?;
+                        (name, Some(id))
+                    }
+                    None => (name, None),
+                };
+                let path = syn::parse_str::<syn::Path>(name).map_err(|_| {
+                    let hint = if name.replace("::", "").contains(':') {
+                        " (paths use `::`)"
+                    } else {
+                        ""
+                    };
+                    err_at(
+                        name,
+                        format!(
+                            "Invalid function path `{}`{} in command group `{}`",
+                            name, hint, grp
+                        ),
+                    )
+                })?;
+                Ok((path, id))
+            })
+            .collect::<Result<(Vec<_>, Vec<_>)>>()?;
+        acc.push(DescGroup {
+            desc: desc.to_string(),
+            funcs,
+            ids,
+            cfg,
+            cancel,
+            span,
+        });
+    }
+    Ok(acc)
+}
+
+/// Split DSL text at the commas between groups; commas inside `[...]` (the predicate of a
+/// `[cfg(...)]` suffix) do not end a group.
+fn split_groups(text: &str) -> Vec<&str> {
+    let mut groups = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0usize;
+    for (i, b) in text.bytes().enumerate() {
+        match b {
+            b'[' => depth += 1,
+            b']' => depth = depth.saturating_sub(1),
+            b',' if depth == 0 => {
+                groups.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
         }
-        acc
+    }
+    groups.push(&text[start..]);
+    groups
+}
+
+/// Split the `[cfg(<predicate>)]` suffix off a descriptor (`d[cfg(feature = "debug")]`),
+/// returning the bare descriptor and the parsed predicate.
+pub(crate) fn split_cfg(desc: &str) -> core::result::Result<(&str, Option<TokenStream2>), String> {
+    let Some(start) = desc.find("[cfg(") else {
+        return Ok((desc, None));
     };
+    let pred = desc[start + 5..].strip_suffix(")]").ok_or_else(|| {
+        format!(
+            "Malformed descriptor `{}`: `[cfg(...)]` must end the descriptor",
+            desc
+        )
+    })?;
+    let pred = syn::parse_str::<syn::Meta>(pred)
+        .map_err(|_| format!("Invalid cfg predicate `{}` in descriptor `{}`", pred, desc))?;
+    Ok((desc[..start].trim_end(), Some(quote! { #pred })))
+}
+
+/// Split the `[cancel]` suffix off a descriptor (`v[cancel]`), whose handlers take the
+/// cancellation token of `dispatch_with_cancel` as their first parameter.
+pub(crate) fn split_cancel(desc: &str) -> (&str, bool) {
+    match desc.strip_suffix("[cancel]") {
+        Some(desc) => (desc.trim_end(), true),
+        None => (desc, false),
+    }
+}
+
+/// Split a group at the `:` separating descriptor and paths, skipping `::` path separators.
+/// The last single `:` is used, since parameter names (`d:freq`) put colons in the descriptor.
+pub(crate) fn split_group(grp: &str) -> Option<(&str, &str)> {
+    let bytes = grp.as_bytes();
+    let mut i = 0usize;
+    let mut sep = None;
+    while i < bytes.len() {
+        if bytes[i] == b':' {
+            if bytes.get(i + 1) == Some(&b':') {
+                i += 2;
+                continue;
+            }
+            sep = Some(i);
+        }
+        i += 1;
+    }
+    sep.map(|i| (&grp[..i], &grp[i + 1..]))
+}
+
+/// Read a descriptor file relative to the invoking crate's `CARGO_MANIFEST_DIR`.
+/// Diagnostics point at the `path` literal and name the offending file and line.
+fn read_descriptor_file(path: &LitStr) -> Result<Vec<DescGroup>> {
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    let full_path = std::path::Path::new(&manifest_dir).join(path.value());
+    load_descriptor_file(&full_path, path.span(), &mut Vec::new())
+}
+
+/// Load a descriptor file, expanding `@include "<file>"` lines recursively. Included paths are
+/// resolved relative to the including file; `stack` holds the files being expanded to catch cycles.
+fn load_descriptor_file(
+    full_path: &std::path::Path,
+    span: Span,
+    stack: &mut Vec<std::path::PathBuf>,
+) -> Result<Vec<DescGroup>> {
+    let raw_dsl = std::fs::read_to_string(full_path).map_err(|e| {
+        syn::Error::new(
+            span,
+            format!(
+                "Failed to read command descriptor file {:?}: {}",
+                full_path, e
+            ),
+        )
+    })?;
+    let canonical = full_path
+        .canonicalize()
+        .unwrap_or_else(|_| full_path.to_path_buf());
+    if stack.contains(&canonical) {
+        return Err(syn::Error::new(
+            span,
+            format!("Recursive @include of {:?}", full_path),
+        ));
+    }
+    stack.push(canonical);
+
+    // Text between `@include` lines is parsed as one segment; includes close the groups around them.
+    let mut groups = Vec::new();
+    let mut segment = String::new();
+    let mut segment_line = 1usize;
+    for (lineno, line) in raw_dsl.lines().enumerate() {
+        let Some(rest) = line.trim().strip_prefix("@include") else {
+            segment.push_str(line);
+            segment.push('\n');
+            continue;
+        };
+        groups.extend(parse_dsl_groups(
+            &segment,
+            span,
+            DslOrigin::File(full_path, segment_line),
+        )?);
+        segment.clear();
+        segment_line = lineno + 2;
+
+        let target = rest
+            .trim()
+            .strip_prefix('"')
+            .and_then(|r| r.strip_suffix('"'))
+            .filter(|r| !r.is_empty())
+            .ok_or_else(|| {
+                syn::Error::new(
+                    span,
+                    format!(
+                        "{}:{}: expected `@include \"<file>\"`",
+                        full_path.display(),
+                        lineno + 1
+                    ),
+                )
+            })?;
+        let base = full_path.parent().unwrap_or(std::path::Path::new(""));
+        groups.extend(load_descriptor_file(&base.join(target), span, stack)?);
+    }
+    groups.extend(parse_dsl_groups(
+        &segment,
+        span,
+        DslOrigin::File(full_path, segment_line),
+    )?);
+
+    stack.pop();
+    Ok(groups)
+}
+
+/// Generate a no-heap dispatcher module from a DSL mapping.
+pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as CommandMacroInput);
+    expand_dispatcher(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Expand a parsed macro input into the generated dispatcher module.
+pub(crate) fn expand_dispatcher(input: CommandMacroInput) -> Result<TokenStream2> {
+    let CommandMacroInput {
+        mod_ident,
+        groups,
+        hexstr_size,
+        unit_base,
+        manifest,
+        ffi,
+        fn_prefix,
+        doc_help,
+        service,
+        runtime_commands,
+        minimal,
+        std,
+        defmt,
+        handler_results,
+    } = input;
 
     // Deduplicate descriptors, assign indices, gather entries; stable sort by function name.
     let mut unique_desc: Vec<String> = Vec::new();
+    let mut unique_spans: Vec<Span> = Vec::new();
     let mut entries: Vec<FnEntry> = Vec::new();
-    for (desc, funcs) in pairs.drain(..) {
+    for DescGroup {
+        desc,
+        funcs,
+        ids,
+        cfg,
+        cancel,
+        span,
+    } in groups
+    {
         let idx = match unique_desc.iter().position(|x| x == &desc) {
             Some(i) => i,
             None => {
                 unique_desc.push(desc.clone());
+                unique_spans.push(span);
                 unique_desc.len() - 1
             }
         };
-        for p in funcs {
+        for (p, id) in funcs.into_iter().zip(ids) {
             let name_str = path_last_ident(&p).unwrap_or_else(|| "unknown".into());
             entries.push(FnEntry {
                 name_str,
                 path: p,
                 spec: desc.clone(),
                 spec_idx: idx,
+                id,
+                cfg: cfg.clone(),
+                cancel,
+                span,
             });
         }
     }
 
+    // Split each descriptor into its type characters and optional per-parameter ranges.
+    let mut unique_types: Vec<String> = Vec::new();
+    let mut unique_ranges: Vec<Vec<Option<TokenStream2>>> = Vec::new();
+    let mut unique_names: Vec<Vec<Option<String>>> = Vec::new();
+    let mut unique_flags: Vec<Vec<String>> = Vec::new();
+    let mut unique_options: Vec<Vec<OptionSpec>> = Vec::new();
+    for (desc, span) in unique_desc.iter().zip(&unique_spans) {
+        let parsed = split_descriptor(desc, *span)?;
+        unique_types.push(parsed.types);
+        unique_ranges.push(parsed.ranges);
+        unique_names.push(parsed.names);
+        unique_flags.push(parsed.flags);
+        unique_options.push(parsed.options);
+    }
+    let max_flags = unique_flags.iter().map(Vec::len).max().unwrap_or(0);
+    let max_options = unique_options.iter().map(Vec::len).max().unwrap_or(0);
+
+    // Parser of each descriptor. `minimal` lets descriptors that only differ in parameter names
+    // and flags (parsed by the caller) share the parser of the first one.
+    let parse_keys: Vec<String> = (0..unique_types.len())
+        .map(|sid| {
+            let ranges: Vec<String> = unique_ranges[sid]
+                .iter()
+                .map(|r| r.as_ref().map_or(String::new(), ToString::to_string))
+                .collect();
+            let options: Vec<String> = unique_options[sid]
+                .iter()
+                .map(|o| {
+                    let default = o
+                        .default
+                        .as_ref()
+                        .map_or(String::new(), ToString::to_string);
+                    format!("{}={}", o.ty, default)
+                })
+                .collect();
+            format!("{}|{:?}|{:?}", unique_types[sid], ranges, options)
+        })
+        .collect();
+    let parser_of: Vec<usize> = (0..unique_types.len())
+        .map(|sid| {
+            if minimal {
+                parse_keys
+                    .iter()
+                    .position(|k| k == &parse_keys[sid])
+                    .unwrap_or(sid)
+            } else {
+                sid
+            }
+        })
+        .collect();
+
     // Stable sort entries by function name
     entries.sort_by(|a, b| a.name_str.cmp(&b.name_str));
 
+    // Command IDs for binary dispatch: explicit ones are kept, the rest take the lowest free IDs
+    // in sorted-name order.
+    let explicit: Vec<u16> = entries.iter().filter_map(|e| e.id).collect();
+    let mut ids: Vec<u16> = Vec::with_capacity(entries.len());
+    let mut free = (0..=u16::MAX).filter(|id| !explicit.contains(id));
+    for e in &entries {
+        let id = match e.id {
+            Some(id) if explicit.iter().filter(|x| **x == id).count() > 1 => {
+                return Err(syn::Error::new(
+                    e.span,
+                    format!("Duplicate command id {} (function `{}`)", id, e.name_str),
+                ));
+            }
+            Some(id) => id,
+            None => free
+                .next()
+                .ok_or_else(|| syn::Error::new(e.span, "No free command id left"))?,
+        };
+        ids.push(id);
+    }
+
     // Get the largest name for a function
     let function_name_max_len = entries.iter().map(|e| e.name_str.len()).max().unwrap_or(0) + 1;
 
+    // Commands of a `[cfg(...)]` group carry the attribute on every item generated for them.
+    let gates: Vec<TokenStream2> = entries
+        .iter()
+        .map(|e| match &e.cfg {
+            Some(pred) => quote! { #[cfg(#pred)] },
+            None => quote! {},
+        })
+        .collect();
+
+    // Table position of each entry: gated entries before it only count when compiled in.
+    let positions: Vec<TokenStream2> = (0..entries.len())
+        .map(|pos| {
+            let before = &entries[..pos];
+            let ungated = before.iter().filter(|p| p.cfg.is_none()).count();
+            let gated: Vec<&TokenStream2> = before.iter().filter_map(|p| p.cfg.as_ref()).collect();
+            if gated.is_empty() {
+                quote! { #ungated }
+            } else {
+                quote! { { #ungated #( + (cfg!(#gated) as usize) )* } }
+            }
+        })
+        .collect();
+
     // Human-readable registry of function names for diagnostics/UI.
-    let fn_names: Vec<LitStr> = entries
+    let fn_names: Vec<TokenStream2> = entries
         .iter()
-        .map(|e| LitStr::new(&e.name_str, Span::call_site()))
+        .zip(&gates)
+        .map(|(e, gate)| {
+            let name_lit = LitStr::new(&e.name_str, Span::call_site());
+            quote! { #gate #name_lit }
+        })
         .collect();
 
-    // Generated registry function
-    let registry_fn = quote! {
-        /// Return function names in the generated table (sorted).
-        pub fn get_function_names() -> Vec<&'static str> {
-            vec![ #( #fn_names ),* ]
+    // Public functions carry the optional `fn_prefix`; private aliases keep the plain names
+    // working inside the module without exporting them.
+    let api = |name: &str| format_ident!("{}{}", fn_prefix, name);
+    let fn_get_function_names = api("get_function_names");
+    let fn_set_command_enabled = api("set_command_enabled");
+    let fn_has_command = api("has_command");
+    let fn_usage = api("usage");
+    let fn_help = api("help");
+    let fn_param_name = api("param_name");
+    let fn_command_id = api("command_id");
+    let fn_is_command_enabled = api("is_command_enabled");
+    let fn_get_commands = api("get_commands");
+    let fn_get_datatypes = api("get_datatypes");
+    let fn_parse_hexstr = api("parse_hexstr");
+    let fn_parse_bytelist = api("parse_bytelist");
+    let fn_parse_timestamp = api("parse_timestamp");
+    let fn_tokenize = api("tokenize");
+    let fn_dispatch_binary = api("dispatch_binary");
+    let fn_dispatch = api("dispatch");
+    let fn_dispatch_with_buf = api("dispatch_with_buf");
+    let fn_dispatch_with_cancel = api("dispatch_with_cancel");
+    let fn_dispatch_tokens = api("dispatch_tokens");
+    let fn_ffi_error_code = api("ffi_error_code");
+    let api_aliases = (!fn_prefix.is_empty()).then(|| {
+        let service_fns: &[&str] = if service.is_some() {
+            &["dispatch_with_service", "dispatch_with_service_cancel"]
+        } else {
+            &[]
+        };
+        let plain: Vec<Ident> = [
+            "set_command_enabled",
+            "has_command",
+            "usage",
+            "help",
+            "param_name",
+            "command_id",
+            "is_command_enabled",
+            "get_commands",
+            "get_datatypes",
+            "parse_hexstr",
+            "parse_bytelist",
+            "parse_timestamp",
+            "tokenize",
+            "dispatch_binary",
+            "dispatch",
+            "dispatch_with_buf",
+            "dispatch_with_cancel",
+            "dispatch_tokens",
+        ]
+        .iter()
+        .chain(service_fns)
+        .map(|name| format_ident!("{}", name))
+        .collect();
+        let prefixed = plain.iter().map(|name| api(&name.to_string()));
+        quote! { use self::{ #( #prefixed as #plain ),* }; }
+    });
+
+    // Generated registry of the function names; the `Vec` variant needs `alloc`, so it is only
+    // generated with `std = true`.
+    let vec_fn = std.then(|| {
+        quote! {
+            /// Return function names in the generated table (sorted), as a `Vec`.
+            pub fn #fn_get_function_names() -> Vec<&'static str> {
+                FUNCTION_NAMES.to_vec()
+            }
         }
+    });
+    let registry_fn = quote! {
+        /// Function names in the generated table (sorted). No allocations.
+        pub static FUNCTION_NAMES: [&'static str; NUM_COMMANDS] = [ #( #fn_names ),* ];
+
+        #vec_fn
     };
 
     // Compute per-spec counts for each primitive type and the overall max arity.
     let mut max_counts = HostCounts::default();
     let mut max_arity: usize = 0;
 
-    for desc in &unique_desc {
+    for (desc, options) in unique_types.iter().zip(&unique_options) {
+        // Option values are stored in the typed slots right after the positional ones.
         let mut c = HostCounts::default();
-        for ch in desc.chars() {
+        for ch in desc.chars().chain(options.iter().map(|o| o.ty)) {
             match ch {
                 // unsigned (lowercase)
                 'B' => c.u8_c += 1,   // u8
@@ -272,39 +988,19 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
                 'F' => c.f64_c += 1, // f64
 
                 // bool, char, string, hexstring
-                't' => c.bool_c += 1,   // bool
-                'c' => c.char_c += 1,   // char
-                's' => c.str_c += 1,    // &str
-                'h' => c.hexstr_c += 1, // hex &str
+                't' => c.bool_c += 1,      // bool
+                'c' => c.char_c += 1,      // char
+                's' => c.str_c += 1,       // &str
+                'h' => c.hexstr_c += 1,    // hex &str
+                'a' => c.bytelist_c += 1,  // byte list &[u8]
+                'T' => c.timestamp_c += 1, // timestamp (secs, nanos)
 
-                // void
-                'v' => {}
                 _ => {}
             }
         }
 
-        let arity = if desc == "v" {
-            0
-        } else {
-            c.u8_c
-                + c.u16_c
-                + c.u32_c
-                + c.u64_c
-                + c.u128_c
-                + c.i8_c
-                + c.i16_c
-                + c.i32_c
-                + c.i64_c
-                + c.i128_c
-                + c.usize_c
-                + c.isize_c
-                + c.f32_c
-                + c.f64_c
-                + c.bool_c
-                + c.char_c
-                + c.str_c
-                + c.hexstr_c
-        };
+        // Options are not positional, so only the descriptor's own types count towards arity.
+        let arity = desc.chars().count();
 
         if arity > max_arity {
             max_arity = arity;
@@ -338,11 +1034,16 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
     let max_char = max_counts.char_c;
     let max_str = max_counts.str_c;
     let max_hexstr = max_counts.hexstr_c;
+    let max_bytelist = max_counts.bytelist_c;
+    let max_timestamp = max_counts.timestamp_c;
     let max_arity_num = max_arity;
 
     // Generate per-descriptor parsers that fill `CallCtx` from `&[&str]`.
     let mut parsers: Vec<TokenStream2> = Vec::new();
-    for (sid, spec) in unique_desc.iter().enumerate() {
+    for (sid, spec) in unique_types.iter().enumerate() {
+        if parser_of[sid] != sid {
+            continue;
+        }
         let fn_ident = format_ident!("__parse_spec_{}", sid);
         let header = quote! {
             // `k` indexes into the argument tokens slice; individual idx_* track per-type positions.
@@ -352,82 +1053,71 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
             let mut idx_B=0usize; let mut idx_W=0usize; let mut idx_D=0usize; let mut idx_Q=0usize; let mut idx_X=0usize;
             let mut idx_z=0usize; let mut idx_Z=0usize;
             let mut idx_f=0usize; let mut idx_F=0usize;
-            let mut idx_t=0usize; let mut idx_c=0usize; let mut idx_s=0usize; let mut idx_h=0usize;
+            let mut idx_t=0usize; let mut idx_c=0usize; let mut idx_s=0usize; let mut idx_h=0usize; let mut idx_a=0usize;
+            let mut idx_T=0usize;
         };
 
         let mut stmts: Vec<TokenStream2> = Vec::new();
-        for ch in spec.chars() {
-            let stmt = match ch {
-                // unsigned
-                'B' => {
-                    quote! { ctx.u8s   [idx_b] = parse_u8   (args[k]).ok_or(DispatchError::BadUnsigned)?; idx_b+=1; k+=1; }
-                }
-                'W' => {
-                    quote! { ctx.u16s  [idx_w] = parse_u16  (args[k]).ok_or(DispatchError::BadUnsigned)?; idx_w+=1; k+=1; }
-                }
-                'D' => {
-                    quote! { ctx.u32s  [idx_d] = parse_u32  (args[k]).ok_or(DispatchError::BadUnsigned)?; idx_d+=1; k+=1; }
-                }
-                'Q' => {
-                    quote! { ctx.u64s  [idx_q] = parse_u64  (args[k]).ok_or(DispatchError::BadUnsigned)?; idx_q+=1; k+=1; }
-                }
-                'X' => {
-                    quote! { ctx.u128s [idx_x] = parse_u128 (args[k]).ok_or(DispatchError::BadUnsigned)?; idx_x+=1; k+=1; }
-                }
-                // signed
-                'b' => {
-                    quote! { ctx.i8s   [idx_B] = parse_i8   (args[k]).ok_or(DispatchError::BadSigned  )?; idx_B+=1; k+=1; }
-                }
-                'w' => {
-                    quote! { ctx.i16s  [idx_W] = parse_i16  (args[k]).ok_or(DispatchError::BadSigned  )?; idx_W+=1; k+=1; }
-                }
-                'd' => {
-                    quote! { ctx.i32s  [idx_D] = parse_i32  (args[k]).ok_or(DispatchError::BadSigned  )?; idx_D+=1; k+=1; }
-                }
-                'q' => {
-                    quote! { ctx.i64s  [idx_Q] = parse_i64  (args[k]).ok_or(DispatchError::BadSigned  )?; idx_Q+=1; k+=1; }
-                }
-                'x' => {
-                    quote! { ctx.i128s [idx_X] = parse_i128 (args[k]).ok_or(DispatchError::BadSigned  )?; idx_X+=1; k+=1; }
-                }
-                // sized
-                'Z' => {
-                    quote! { ctx.usizes[idx_z] = parse_usize(args[k]).ok_or(DispatchError::BadUnsigned)?; idx_z+=1; k+=1; }
-                }
-                'z' => {
-                    quote! { ctx.isizes[idx_Z] = parse_isize(args[k]).ok_or(DispatchError::BadSigned  )?; idx_Z+=1; k+=1; }
-                }
-                // floats
-                'f' => {
-                    quote! { ctx.f32s  [idx_f] = parse_f::<f32  >(args[k]).ok_or(DispatchError::BadFloat)?; idx_f+=1; k+=1; }
-                }
-                'F' => {
-                    quote! { ctx.f64s  [idx_F] = parse_f::<f64  >(args[k]).ok_or(DispatchError::BadFloat)?; idx_F+=1; k+=1; }
-                }
-                //  bool, char, string, hexstring
-                't' => {
-                    quote! { ctx.bools [idx_t] = parse_bool(args[k]).ok_or(DispatchError::BadBool)?; idx_t+=1; k+=1; }
-                }
-                'c' => {
-                    quote! { ctx.chars [idx_c] = parse_char(args[k]).ok_or(DispatchError::BadChar)?; idx_c+=1; k+=1; }
-                }
-                's' => quote! { ctx.strs  [idx_s] = args[k]; idx_s+=1; k+=1; },
-                'h' => {
-                    quote! { ctx.hexstrs[idx_h]= parse_hexstr(args[k]).ok_or(DispatchError::BadHexStr)?; idx_h+=1; k+=1; }
+        let mut decode_stmts: Vec<TokenStream2> = Vec::new();
+        for (ch, range) in spec.chars().zip(&unique_ranges[sid]) {
+            // (CallCtx slot, per-type index, value type, parse expression) for this parameter.
+            let Some((slot, idx, ty, value)) = param_slot(ch, &quote! { args[k] }) else {
+                continue;
+            };
+            // Optional post-parse bounds check; `k` is the zero-based argument position.
+            let check = range.as_ref().map(|r| {
+                quote! {
+                    if !core::ops::RangeBounds::<#ty>::contains(&(#r), &ctx.#slot[#idx]) {
+                        return Err(DispatchError::OutOfRange { arg_index: k as u16 });
+                    }
                 }
-                _ => quote! {},
+            });
+            stmts.push(quote! { ctx.#slot[#idx] = #value; #check #idx += 1; k += 1; });
+            let decoded = binary_value(ch);
+            decode_stmts.push(quote! { ctx.#slot[#idx] = #decoded; #check #idx += 1; k += 1; });
+        }
+        // Options read their value (if given) from `opts[j]`, otherwise take the default.
+        for (j, opt) in unique_options[sid].iter().enumerate() {
+            let Some((slot, idx, _, value)) = param_slot(opt.ty, &quote! { v }) else {
+                continue;
             };
-            stmts.push(stmt);
+            let default = opt
+                .default
+                .clone()
+                .unwrap_or_else(|| quote! { Default::default() });
+            stmts.push(quote! {
+                ctx.#slot[#idx] = match opts[#j] {
+                    Some(v) => #value,
+                    None => #default,
+                };
+                #idx += 1;
+            });
+            // Binary payloads carry no options, so they always take the default.
+            decode_stmts.push(quote! { ctx.#slot[#idx] = #default; #idx += 1; });
         }
+        let decode_ident = format_ident!("__decode_spec_{}", sid);
         parsers.push(quote! {
 
             /// Parse arguments for this descriptor into `CallCtx`.
             #[inline(always)]
-            fn #fn_ident<'a>(ctx: &mut CallCtx<'a>, args: &[&'a str]) -> Result<(), DispatchError> {
+            fn #fn_ident<'a>(ctx: &mut CallCtx<'a>, args: &[&'a str], opts: &[Option<&'a str>]) -> Result<(), DispatchError> {
                 #header
                 #(#stmts)*
                 Ok(())
             }
+
+            /// Decode a little-endian binary payload for this descriptor into `CallCtx`.
+            #[inline(always)]
+            fn #decode_ident<'a>(ctx: &mut CallCtx<'a>, payload: &'a [u8]) -> Result<(), DispatchError> {
+                #header
+                // `p` is the read position in `payload`.
+                let mut p = 0usize;
+                #(#decode_stmts)*
+                if p != payload.len() {
+                    return Err(DispatchError::BadPayload);
+                }
+                Ok(())
+            }
         });
     }
 
@@ -435,30 +1125,44 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
     let mut wrappers: Vec<TokenStream2> = Vec::new();
     let mut entry_inits: Vec<TokenStream2> = Vec::new();
     let mut match_arms: Vec<TokenStream2> = Vec::new();
+    let mut id_arms: Vec<TokenStream2> = Vec::new();
+    let mut manifest_cmds: Vec<String> = Vec::new();
+    let mut ffi_entries: Vec<TokenStream2> = Vec::new();
+    let mut max_handler_args = 0usize;
 
-    // Pairs of (function name, descriptor) for diagnostics / UI
+    // Pairs of (function name, descriptor) for diagnostics / UI; `minimal` keeps the names only.
     let name_spec_pairs: Vec<TokenStream2> = entries
         .iter()
-        .map(|e| {
+        .zip(&gates)
+        .map(|(e, gate)| {
             let name_lit = LitStr::new(&e.name_str, Span::call_site());
-            let spec_lit = LitStr::new(&e.spec, Span::call_site());
-            quote! { (#name_lit, #spec_lit) }
+            let spec_lit = LitStr::new(if minimal { "" } else { &e.spec }, Span::call_site());
+            quote! { #gate (#name_lit, #spec_lit) }
         })
         .collect();
 
     for (pos, e) in entries.iter().enumerate() {
+        let gate = &gates[pos];
+        let position = &positions[pos];
         let name_lit = LitStr::new(&e.name_str, Span::call_site());
-        let spec_str = &e.spec;
-        //let arity_u8 = (spec_str.chars().count()) as u8;
-        let arity_u8 = if spec_str == "v" {
-            0
-        } else {
-            spec_str.chars().count() as u8
-        };
+        let spec_str = &unique_types[e.spec_idx];
+        // `v` descriptors have no types, so they get arity 0 like any other empty list.
+        let arity = u16::try_from(spec_str.chars().count()).map_err(|_| {
+            syn::Error::new(
+                unique_spans[e.spec_idx],
+                format!(
+                    "Descriptor of `{}` has more than {} parameters",
+                    e.name_str,
+                    u16::MAX
+                ),
+            )
+        })?;
         let wrapper_ident = format_ident!("__call_{}", sanitize_ident(&e.name_str));
         let path = &e.path;
         let spec_idx_u16 = e.spec_idx as u16;
-        let parser_ident = format_ident!("__parse_spec_{}", e.spec_idx);
+        let parser_ident = format_ident!("__parse_spec_{}", parser_of[e.spec_idx]);
+        let decoder_ident = format_ident!("__decode_spec_{}", parser_of[e.spec_idx]);
+        let id = ids[pos];
 
         // Build type list and extraction expressions according to the descriptor order.
         let mut arg_types: Vec<TokenStream2> = Vec::new();
@@ -481,8 +1185,11 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
         let mut idx_c = 0usize;
         let mut idx_s = 0usize;
         let mut idx_h = 0usize;
+        let mut idx_a = 0usize;
+        let mut idx_T = 0usize;
 
-        for ch in spec_str.chars() {
+        let options = &unique_options[e.spec_idx];
+        for ch in spec_str.chars().chain(options.iter().map(|o| o.ty)) {
             match ch {
                 // unsigned
                 'B' => {
@@ -583,56 +1290,619 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
                     arg_exprs.push(quote! { &ctx.hexstrs[#idx_h] });
                     idx_h += 1;
                 }
+                'a' => {
+                    arg_types.push(quote! { &[u8] });
+                    arg_exprs.push(quote! { &ctx.bytelists[#idx_a] });
+                    idx_a += 1;
+                }
+                'T' => {
+                    arg_types.push(quote! { (u64, u32) });
+                    arg_exprs.push(quote! { ctx.timestamps[#idx_T] });
+                    idx_T += 1;
+                }
                 _ => {}
             }
         }
 
-        // Compile-time signature check: ensures `path` has the expected arity/types.
+        // `--flag` switches follow the positional parameters and options as `bool`s.
+        let flags = &unique_flags[e.spec_idx];
+        for fi in 0..flags.len() {
+            arg_types.push(quote! { bool  });
+            arg_exprs.push(quote! { ctx.flags  [#fi] });
+        }
+        let flag_lits: Vec<LitStr> = flags
+            .iter()
+            .map(|f| LitStr::new(f, Span::call_site()))
+            .collect();
+        let option_lits: Vec<LitStr> = options
+            .iter()
+            .map(|o| LitStr::new(&o.name, Span::call_site()))
+            .collect();
+        let param_lits: Vec<LitStr> = unique_names[e.spec_idx]
+            .iter()
+            .map(|n| LitStr::new(n.as_deref().unwrap_or(""), Span::call_site()))
+            .collect();
+        let usage_lit = LitStr::new(
+            &usage_text(
+                &e.name_str,
+                spec_str,
+                &unique_names[e.spec_idx],
+                options,
+                flags,
+            ),
+            Span::call_site(),
+        );
+        let details_lit = LitStr::new(
+            &help_details(
+                spec_str,
+                &unique_names[e.spec_idx],
+                &unique_ranges[e.spec_idx],
+                options,
+                flags,
+            ),
+            Span::call_site(),
+        );
+
+        // `[cancel]` handlers take the token of the dispatch first (a fresh, never set one outside
+        // `dispatch_with_cancel`).
+        if e.cancel {
+            arg_types.insert(0, quote! { &core::sync::atomic::AtomicBool });
+            arg_exprs.insert(
+                0,
+                quote! { ctx.cancel.unwrap_or(&core::sync::atomic::AtomicBool::new(false)) },
+            );
+        }
+
+        // Methods of the service take it first, as `&mut self` (or `&self`).
+        let method = service.as_ref().filter(|svc| is_method_of(path, svc));
+        let receiver_types = match method {
+            Some(svc) => vec![quote! { &mut #svc, }, quote! { &#svc, }],
+            None => vec![quote! {}],
+        };
+        if let Some(svc) = method {
+            arg_types.insert(0, quote! { &mut #svc });
+        }
+
+        // Compile-time signature check: `sig_of` infers the handler's parameter tuple and a
+        // per-command trait, implemented only for the expected tuple, reports a mismatch with a
+        // message naming the command, the handler and the descriptor.
+        max_handler_args = max_handler_args.max(arg_types.len());
+        let param_types = &arg_types[method.is_some() as usize..];
         let sig_check = {
-            let fn_type = quote! { fn(#(#arg_types),*) -> _ };
+            let trait_ident = format_ident!("__Sig_{}", sanitize_ident(&e.name_str));
+            let expected = arg_types
+                .iter()
+                .map(|t| {
+                    t.to_string()
+                        .replace("& ", "&")
+                        .replace(" ]", "]")
+                        .replace(" :: ", "::")
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            // `{`/`}` are placeholders in diagnostic attributes, so ranges must be escaped.
+            let escape = |s: &str| s.replace('{', "{{").replace('}', "}}");
+            let handler = quote!(#path).to_string().replace(' ', "");
+            let message = LitStr::new(
+                &escape(&format!(
+                    "handler `{}` does not match command `{}` (descriptor `{}`)",
+                    handler, e.name_str, e.spec
+                )),
+                Span::call_site(),
+            );
+            let note = LitStr::new(
+                &escape(&format!(
+                    "descriptor `{}` expects `fn({})`; fix the handler or the descriptor",
+                    e.spec, expected
+                )),
+                Span::call_site(),
+            );
+            let sig_impls = receiver_types.iter().map(|receiver| {
+                quote! { #gate impl #trait_ident<( #receiver #(#param_types,)* )> for () {} }
+            });
+            // Point the error at the handler path where it has a real span (structured entries).
+            let check = quote_spanned! { path.span()=>
+                <() as #trait_ident<_>>::check(sig_of(&#path))
+            };
             quote! {
+                #gate
+                #[diagnostic::on_unimplemented(
+                    message = #message,
+                    label = "this handler takes `{T}`",
+                    note = #note
+                )]
+                trait #trait_ident<T> {
+                    fn check(_: Sig<T>) {}
+                }
+                #( #sig_impls )*
+                #gate
+                impl #trait_ident<NoSig> for () {}
+                #gate
                 const _: fn() = || {
-                    let _check: #fn_type = #path;
-                    let _ = _check;
+                    #check;
                 };
             }
         };
 
+        // Methods run on the service lent by `dispatch_with_service`, and fail without one.
+        let (take_service, receiver) = if method.is_some() {
+            (
+                quote! {
+                    let Some(service) = ctx.service.as_deref_mut() else {
+                        return Err(DispatchError::NoService);
+                    };
+                },
+                quote! { service, },
+            )
+        } else {
+            (quote! {}, quote! {})
+        };
+
+        // Typed shortcuts report handler failures; commands ignore what the handler returns.
+        let call = if handler_results {
+            quote! {
+                #take_service
+                super::HandlerResult::into_result(#path( #receiver #(#arg_exprs),* )).map_err(DispatchError::Failed)
+            }
+        } else {
+            quote! {
+                #take_service
+                let _ = #path( #receiver #(#arg_exprs),* );
+                Ok(())
+            }
+        };
+
         wrappers.push(quote! {
             #sig_check
 
             /// Wrapper that extracts arguments from `CallCtx` and calls the target function.
+            #gate
             #[inline(always)]
             fn #wrapper_ident<'__ctx>(ctx: &mut CallCtx<'__ctx>, _av: ArgsView<'__ctx>) -> Result<(), DispatchError> {
-                let _ = #path( #(#arg_exprs),* );
-                Ok(())
+                #call
             }
         });
 
+        // With `doc_help`, `#[command_doc]` left the summary next to the handler.
+        let summary = if doc_help {
+            let mut doc_path = path.clone();
+            if let Some(last) = doc_path.segments.last_mut() {
+                last.ident = doc_const_ident(&last.ident.to_string());
+            }
+            quote! { #doc_path }
+        } else {
+            quote! { "" }
+        };
+
+        // `minimal` keeps no help text: the usage is the bare name, the rest is empty.
+        let (usage, details, params) = if minimal {
+            (quote! { #name_lit }, quote! { "" }, quote! { &[] })
+        } else {
+            (
+                quote! { #usage_lit },
+                quote! { #details_lit },
+                quote! { &[ #( #param_lits ),* ] },
+            )
+        };
+
         entry_inits.push(quote! {
+            #gate
             Entry {
                 name: #name_lit,
-                arity: #arity_u8,
+                arity: #arity,
                 parser: #parser_ident,
+                decoder: #decoder_ident,
                 caller: #wrapper_ident,
+                id: #id,
                 spec_idx: #spec_idx_u16,
+                flags: &[ #( #flag_lits ),* ],
+                options: &[ #( #option_lits ),* ],
+                usage: #usage,
+                details: #details,
+                params: #params,
+                summary: #summary,
             }
         });
 
-        match_arms.push(quote! { #name_lit => Some(&ENTRIES[#pos]), });
+        if manifest {
+            manifest_cmds.push(manifest_command(
+                e,
+                id,
+                &usage_lit.value(),
+                spec_str,
+                &unique_names[e.spec_idx],
+                options,
+                flags,
+            ));
+        }
+
+        if ffi {
+            let mut c_name = e.name_str.clone().into_bytes();
+            c_name.push(0);
+            let c_name = syn::LitByteStr::new(&c_name, Span::call_site());
+            ffi_entries.push(quote! {
+                #gate
+                CEntry {
+                    name: #c_name.as_ptr().cast(),
+                    arity: #arity,
+                    call: ffi_call::<#position>,
+                }
+            });
+        }
+
+        match_arms.push(quote! { #gate #name_lit => Some(#position), });
+        id_arms.push(quote! { #gate #id => Some(#position), });
     }
 
-    let max_hexstr_len_expr = if let Some(expr) = &hexstr_size {
-        quote! { #expr }
+    // Descriptor strings for diagnostics and UIs (input hints, `merge_commands!`); `minimal`
+    // leaves them out, keeping the command names for shells and merged modules.
+    let (descriptor_tables, datatypes) = if minimal {
+        (quote! {}, quote! { "" })
     } else {
-        // Emit a compile error at macro expansion time
-        return syn::Error::new(
-            Span::call_site(),
-            "You must provide `hexstr_size = ...;` in the macro input.",
-        )
-        .to_compile_error()
-        .into();
-    };
+        (
+            quote! {
+                /// All unique parameter descriptors encountered (for diagnostics/UIs).
+                pub static PARAM_SPECS: [&'static str; #param_specs_len] = [ #( #param_specs ),* ];
+
+                /// Descriptor character to Rust type mapping (for help/diagnostics).
+                pub static DESCRIPTOR_HELP: &str = "B:u8   | W:u16  | D:u32 | Q:u64 | X:u128 | Z:usize | F:f64\nb:i8   | w:i16  | d:i32 | q:i64 | x:i128 | z:isize | f:f32\nv:void | c:char | s:str | t:bool | h:hexstr | a:bytes\nT:timestamp\n";
+            },
+            quote! { DESCRIPTOR_HELP },
+        )
+    };
+    let command_specs = quote! {
+        /// Static pairs of (function name, parameter descriptor).
+        pub static NAME_AND_SPEC: &[(&'static str, &'static str)] = &[
+            #( #name_spec_pairs ),*
+        ];
+
+        /// Return (function name, descriptor) pairs. No allocations.
+        #[inline(always)]
+        pub fn #fn_get_commands() -> &'static [(&'static str, &'static str)] {
+            NAME_AND_SPEC
+        }
+
+        /// Return descriptor help string (character to type mapping).
+        #[inline(always)]
+        pub fn #fn_get_datatypes() -> &'static str {
+            #datatypes
+        }
+    };
+
+    let max_hexstr_len_expr = if let Some(expr) = &hexstr_size {
+        quote! { #expr }
+    } else {
+        // Emit a compile error at macro expansion time
+        return Err(syn::Error::new(
+            Span::call_site(),
+            "You must provide `hexstr_size = ...;` in the macro input.",
+        ));
+    };
+
+    // A failing handler's report may not be `Copy`, so only typed shortcuts carry it. It is
+    // only known to be `Debug`, so defmt prints it through `Debug2Format`.
+    let defmt_derive = defmt.then(|| quote! { #[derive(defmt::Format)] });
+    let (error_derive, failed_variant) = if handler_results {
+        let debug2 = defmt.then(|| quote! { #[defmt(Debug2Format)] });
+        (
+            quote! { #[derive(Debug, Clone, PartialEq, Eq)] #defmt_derive },
+            quote! {
+                /// The handler ran and returned an error, as reported by the shortcut module.
+                Failed(#debug2 super::HandlerError),
+            },
+        )
+    } else {
+        (
+            quote! { #[derive(Debug, Clone, Copy, PartialEq, Eq)] #defmt_derive },
+            quote! {},
+        )
+    };
+
+    // With a `service`, methods reach it through `CallCtx`, lent by `dispatch_with_service`.
+    let (no_service_variant, service_field, service_init) = match &service {
+        Some(svc) => (
+            quote! {
+                /// A method of the service was called without one, e.g. through `dispatch`
+                /// instead of `dispatch_with_service`.
+                NoService,
+            },
+            quote! {
+                /// Service the methods of the module run on (`None` outside `dispatch_with_service`).
+                pub service: Option<&'a mut #svc>,
+            },
+            quote! { service: None, },
+        ),
+        None => (quote! {}, quote! {}, quote! {}),
+    };
+
+    // Context-taking entry points: a cancellation token, and with a service, the service and an
+    // optional token.
+    let fn_dispatch_with_service = api("dispatch_with_service");
+    let fn_dispatch_with_service_cancel = api("dispatch_with_service_cancel");
+    let service_fns = service.as_ref().map(|svc| {
+        quote! {
+            /// Like `dispatch`, running the methods of the service on `service`.
+            pub fn #fn_dispatch_with_service(line: &str, service: &mut #svc) -> Result<(), DispatchError> {
+                let mut toks: [&str; 2 + MAX_ARITY + MAX_FLAGS + 2 * MAX_OPTIONS] = [""; 2 + MAX_ARITY + MAX_FLAGS + 2 * MAX_OPTIONS];
+                let len = tokenize(line, &mut toks)?;
+                let mut ctx = CallCtx::new();
+                ctx.service = Some(service);
+                run_tokens(&mut toks, len, ctx)
+            }
+
+            /// Like `dispatch_with_service`, with a cancellation token for the `[cancel]` handlers,
+            /// see `dispatch_with_cancel`.
+            pub fn #fn_dispatch_with_service_cancel(line: &str, service: &mut #svc, cancel: &core::sync::atomic::AtomicBool) -> Result<(), DispatchError> {
+                cancel.store(false, core::sync::atomic::Ordering::Relaxed);
+                let mut toks: [&str; 2 + MAX_ARITY + MAX_FLAGS + 2 * MAX_OPTIONS] = [""; 2 + MAX_ARITY + MAX_FLAGS + 2 * MAX_OPTIONS];
+                let len = tokenize(line, &mut toks)?;
+                let mut ctx = CallCtx::new();
+                ctx.service = Some(service);
+                ctx.cancel = Some(cancel);
+                run_tokens(&mut toks, len, ctx)
+            }
+        }
+    });
+    let ctx_fns = quote! {
+        /// Like `dispatch`, handing `cancel` to the `[cancel]` handlers (and `CallCtx::cancel`).
+        /// The token is cleared first; setting it from elsewhere (a Ctrl+C handler, an
+        /// interrupt, another thread) asks a long command to return early.
+        pub fn #fn_dispatch_with_cancel(line: &str, cancel: &core::sync::atomic::AtomicBool) -> Result<(), DispatchError> {
+            cancel.store(false, core::sync::atomic::Ordering::Relaxed);
+            let mut toks: [&str; 2 + MAX_ARITY + MAX_FLAGS + 2 * MAX_OPTIONS] = [""; 2 + MAX_ARITY + MAX_FLAGS + 2 * MAX_OPTIONS];
+            let len = tokenize(line, &mut toks)?;
+            let mut ctx = CallCtx::new();
+            ctx.cancel = Some(cancel);
+            run_tokens(&mut toks, len, ctx)
+        }
+
+        #service_fns
+    };
+
+    // Optional table of commands added at runtime, searched when the static match fails.
+    let fn_register_command = api("register_command");
+    let runtime_variants = runtime_commands.is_some().then(|| {
+        quote! {
+            /// `register_command` found the runtime table full.
+            TableFull,
+
+            /// `register_command` was given a name already in use.
+            AlreadyDefined,
+
+            /// `register_command` was given a `parser_id` outside `PARAM_SPECS`.
+            UnknownParser,
+        }
+    });
+    let (runtime_find, runtime_unknown, runtime_listed) = if runtime_commands.is_some() {
+        (
+            quote! { .or_else(|| find_runtime(name)) },
+            quote! { find_runtime(name).ok_or(DispatchError::UnknownFunction) },
+            quote! { .chain(runtime_entries()) },
+        )
+    } else {
+        (
+            quote! {},
+            quote! { Err(DispatchError::UnknownFunction) },
+            quote! {},
+        )
+    };
+    let runtime_table = runtime_commands.as_ref().map(|capacity| {
+        quote! {
+            /// Capacity of the runtime command table.
+            pub const RUNTIME_COMMANDS: usize = #capacity;
+
+            /// Handler of a runtime command: reads its arguments from `CallCtx`, filled by the
+            /// parser it was registered with, or the raw tokens from `ArgsView`.
+            pub type RuntimeHandler = for<'ctx> fn(&mut CallCtx<'ctx>, ArgsView<'ctx>) -> Result<(), DispatchError>;
+
+            /// Append-only table of the commands added by `register_command`. Slots below `len`
+            /// are written once, before `len` is raised past them, and never change afterwards.
+            struct RuntimeTable {
+                slots: [core::cell::UnsafeCell<Option<Entry>>; RUNTIME_COMMANDS],
+                len: core::sync::atomic::AtomicUsize,
+                writing: core::sync::atomic::AtomicBool,
+            }
+
+            // SAFETY: published slots are only read; writers are serialized by `writing`.
+            unsafe impl Sync for RuntimeTable {}
+
+            static RUNTIME: RuntimeTable = RuntimeTable {
+                slots: [const { core::cell::UnsafeCell::new(None) }; RUNTIME_COMMANDS],
+                len: core::sync::atomic::AtomicUsize::new(0),
+                writing: core::sync::atomic::AtomicBool::new(false),
+            };
+
+            /// Commands added by `register_command`, in registration order.
+            fn runtime_entries() -> impl Iterator<Item = &'static Entry> {
+                let len = RUNTIME.len.load(core::sync::atomic::Ordering::Acquire);
+                // SAFETY: slots below `len` are published and never written again.
+                RUNTIME.slots[..len].iter().filter_map(|slot| unsafe { (*slot.get()).as_ref() })
+            }
+
+            /// Lookup of a command added by `register_command`.
+            fn find_runtime(name: &str) -> Option<&'static Entry> {
+                runtime_entries().find(|ent| ent.name == name)
+            }
+
+            /// Adds the command `name` after the static table, within `RUNTIME_COMMANDS`.
+            ///
+            /// Its arguments are parsed as for the static commands with the descriptor
+            /// `PARAM_SPECS[parser_id]` (flags and options included), whose arity `arity` must
+            /// match, then `handler` runs with them in `CallCtx`. Runtime commands cannot be
+            /// disabled, have no binary ID and use their name as usage.
+            ///
+            /// Only generated for targets with atomic read-modify-write, used to serialize
+            /// registrations: one interrupting another on the same core spins forever.
+            #[cfg(target_has_atomic = "8")]
+            pub fn #fn_register_command(name: &'static str, arity: u16, parser_id: u16, handler: RuntimeHandler) -> Result<(), DispatchError> {
+                use core::sync::atomic::Ordering;
+                let spec = ENTRIES.iter().find(|ent| ent.spec_idx == parser_id).ok_or(DispatchError::UnknownParser)?;
+                if arity != spec.arity {
+                    return Err(DispatchError::WrongArity { expected: spec.arity, usage: spec.usage });
+                }
+                while RUNTIME.writing.swap(true, Ordering::Acquire) {
+                    core::hint::spin_loop();
+                }
+                let len = RUNTIME.len.load(Ordering::Relaxed);
+                let result = if find_entry(name).is_some() {
+                    Err(DispatchError::AlreadyDefined)
+                } else if len == RUNTIME_COMMANDS {
+                    Err(DispatchError::TableFull)
+                } else {
+                    let entry = Entry {
+                        name,
+                        arity,
+                        parser: spec.parser,
+                        decoder: spec.decoder,
+                        caller: handler,
+                        id: u16::MAX,
+                        spec_idx: parser_id,
+                        flags: spec.flags,
+                        options: spec.options,
+                        usage: name,
+                        details: spec.details,
+                        params: spec.params,
+                        summary: "",
+                    };
+                    // SAFETY: no reader sees the slot before `len` is raised past it, and
+                    // `writing` keeps other writers out.
+                    unsafe { *RUNTIME.slots[len].get() = Some(entry) };
+                    RUNTIME.len.store(len + 1, Ordering::Release);
+                    Ok(())
+                };
+                RUNTIME.writing.store(false, Ordering::Release);
+                result
+            }
+        }
+    });
+
+    // Optional machine-readable description of the table for host-side tools.
+    let manifest_const = manifest.then(|| {
+        let json = LitStr::new(
+            &format!(
+                "{{\"module\":{},\"commands\":[{}]}}",
+                json_string(&mod_ident.to_string()),
+                manifest_cmds.join(",")
+            ),
+            Span::call_site(),
+        );
+        quote! {
+            /// JSON manifest of all commands (name, id, descriptor, usage, arity, parameters,
+            /// options and flags), enabled with `manifest = true`.
+            pub const MANIFEST_JSON: &str = #json;
+        }
+    });
+
+    // C view of the table for non-Rust callers; the symbols are unmangled, so one module per
+    // binary may enable it.
+    let ffi_table = ffi.then(|| {
+        // Optional variants follow `TooManyTokens`, numbered in declaration order.
+        let optional_codes = [
+            (handler_results, quote! { DispatchError::Failed(_) }),
+            (service.is_some(), quote! { DispatchError::NoService }),
+            (runtime_commands.is_some(), quote! { DispatchError::TableFull }),
+            (runtime_commands.is_some(), quote! { DispatchError::AlreadyDefined }),
+            (runtime_commands.is_some(), quote! { DispatchError::UnknownParser }),
+        ]
+        .into_iter()
+        .filter(|(present, _)| *present)
+        .zip(18i32..)
+        .map(|((_, pattern), code)| quote! { #pattern => #code, });
+        let alias = (!fn_prefix.is_empty())
+            .then(|| quote! { use self::#fn_ffi_error_code as ffi_error_code; });
+        quote! {
+            #alias
+
+            /// C view of a command in `SHELL_ENTRIES_C` (enabled with `ffi = true`).
+            #[repr(C)]
+            pub struct CEntry {
+                /// NUL-terminated command name.
+                pub name: *const core::ffi::c_char,
+
+                /// Required positional arity.
+                pub arity: u16,
+
+                /// Runs the command with its arguments, a NUL-terminated line such as
+                /// `"1200 0.5 --verbose"` (null for none). Returns 0 on success or the negated
+                /// `ffi_error_code` of the failure.
+                pub call: unsafe extern "C" fn(args: *const core::ffi::c_char) -> i32,
+            }
+
+            // SAFETY: the table only points to static strings and functions.
+            unsafe impl Sync for CEntry {}
+
+            /// The commands for C callers, in `ENTRIES` order.
+            #[unsafe(no_mangle)]
+            pub static SHELL_ENTRIES_C: [CEntry; NUM_COMMANDS] = [ #( #ffi_entries ),* ];
+
+            /// Number of entries in `SHELL_ENTRIES_C`.
+            #[unsafe(no_mangle)]
+            pub static SHELL_ENTRIES_C_LEN: usize = NUM_COMMANDS;
+
+            /// Positive code of `e` for C callers, in `DispatchError` declaration order
+            /// (`Empty` = 1, `UnknownFunction` = 2, ..., `TooManyTokens` = 17, then the optional
+            /// variants present from 18 on).
+            pub fn #fn_ffi_error_code(e: &DispatchError) -> i32 {
+                match e {
+                    DispatchError::Empty => 1,
+                    DispatchError::UnknownFunction => 2,
+                    DispatchError::Disabled => 3,
+                    DispatchError::WrongArity { .. } => 4,
+                    DispatchError::BadBool => 5,
+                    DispatchError::BadChar => 6,
+                    DispatchError::BadUnsigned => 7,
+                    DispatchError::BadSigned => 8,
+                    DispatchError::BadFloat => 9,
+                    DispatchError::BadHexStr => 10,
+                    DispatchError::BadByteList => 11,
+                    DispatchError::BadTimestamp => 12,
+                    DispatchError::UnknownFlag => 13,
+                    DispatchError::MissingOptionValue => 14,
+                    DispatchError::BadPayload => 15,
+                    DispatchError::OutOfRange { .. } => 16,
+                    DispatchError::TooManyTokens => 17,
+                    #( #optional_codes )*
+                }
+            }
+
+            /// Uniform C entry point of `ENTRIES[POS]`: tokenizes `args` after the command name
+            /// and dispatches them as a line would be.
+            unsafe extern "C" fn ffi_call<const POS: usize>(args: *const core::ffi::c_char) -> i32 {
+                let args = if args.is_null() {
+                    ""
+                } else {
+                    // SAFETY: the caller passes a NUL-terminated string that outlives the call.
+                    match unsafe { core::ffi::CStr::from_ptr(args) }.to_str() {
+                        Ok(args) => args,
+                        Err(_) => return -ffi_error_code(&DispatchError::BadPayload),
+                    }
+                };
+                let mut toks: [&str; 2 + MAX_ARITY + MAX_FLAGS + 2 * MAX_OPTIONS] = [""; 2 + MAX_ARITY + MAX_FLAGS + 2 * MAX_OPTIONS];
+                toks[0] = ENTRIES[POS].name;
+                let len = match tokenize(args, &mut toks[1..]) {
+                    Ok(n) => 1 + n,
+                    Err(DispatchError::Empty) => 1,
+                    Err(e) => return -ffi_error_code(&e),
+                };
+                match dispatch_tokens(&mut toks, len) {
+                    Ok(()) => 0,
+                    Err(e) => -ffi_error_code(&e),
+                }
+            }
+        }
+    });
+
+    // `SigOf` impls for every handler arity in use, so `sig_of` can infer parameter tuples.
+    let sig_of_impls: Vec<TokenStream2> = (0..=max_handler_args)
+        .map(|n| {
+            let args: Vec<Ident> = (0..n).map(|i| format_ident!("A{}", i)).collect();
+            quote! {
+                impl<F: Fn( #(#args),* ) -> R, R, #(#args),*> SigOf<(R, #(#args,)* )> for F {
+                    type Args = ( #(#args,)* );
+                }
+            }
+        })
+        .collect();
 
     let out = quote! {
         #[allow(dead_code)]
@@ -642,20 +1912,90 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
             //! Generated by `generate_commands_dispatcher!`. See the macro docs for usage and the descriptor table.
             extern crate core;
 
-            // Macro and parse functions for integer parsing with base detection
+            #api_aliases
+
+            /// Parameter tuple of a handler, carried as a type for the signature checks.
+            #[doc(hidden)]
+            pub struct Sig<T>(core::marker::PhantomData<T>);
+
+            /// Placeholder tuple keeping the signature checks from inferring the expected type.
+            #[doc(hidden)]
+            pub enum NoSig {}
+
+            /// Maps a handler function to its parameter tuple; `M` disambiguates the arity.
+            #[doc(hidden)]
+            pub trait SigOf<M> {
+                type Args;
+            }
+
+            #( #sig_of_impls )*
+
+            /// Parameter tuple of `f` (type-level only, used by the signature checks).
+            #[doc(hidden)]
+            pub fn sig_of<F: SigOf<M>, M>(_: &F) -> Sig<F::Args> {
+                Sig(core::marker::PhantomData)
+            }
+
+            /// Multiplier applied per `k`/`M`/`G` suffix on integer arguments (set via `unit_base`).
+            pub const UNIT_BASE: u64 = #unit_base;
+
+            #manifest_const
+
+            #ffi_table
+
+            /// Split an optional `k`/`K`, `M`/`m` or `G`/`g` suffix off an integer token,
+            /// returning the remaining digits and the multiplier to apply.
+            #[inline(always)]
+            fn split_unit_suffix(s: &str) -> (&str, u64) {
+                // The suffixes are ASCII, so `s.len() - 1` is a char boundary in their arms only.
+                match s.as_bytes().last() {
+                    Some(b'k' | b'K') => (&s[..s.len() - 1], UNIT_BASE),
+                    Some(b'm' | b'M') => (&s[..s.len() - 1], UNIT_BASE * UNIT_BASE),
+                    Some(b'g' | b'G') => (&s[..s.len() - 1], UNIT_BASE * UNIT_BASE * UNIT_BASE),
+                    _ => (s, 1),
+                }
+            }
+
+            // Macro and parse functions for integer parsing with an optional `+`/`-` sign, base
+            // detection, `_` digit separators (`1_000_000`, `0xFF_FF`) and optional unit suffix
+            // (`4k`, `2M`, `0x10k`).
             macro_rules! parse_int {
                 ($name:ident, $ty:ty) => {
                     fn $name(s: &str) -> Option<$ty> {
-                        let s = s.trim();
-                        if let Some(stripped) = s.strip_prefix("0x") {
-                            <$ty>::from_str_radix(stripped, 16).ok()
+                        let (s, scale) = split_unit_suffix(s.trim());
+                        let (negative, s) = match s.as_bytes().first() {
+                            Some(b'-') => (true, &s[1..]),
+                            Some(b'+') => (false, &s[1..]),
+                            _ => (false, s),
+                        };
+                        if negative && <$ty>::MIN == 0 {
+                            return None;
+                        }
+                        let (radix, digits) = if let Some(stripped) = s.strip_prefix("0x") {
+                            (16, stripped)
                         } else if let Some(stripped) = s.strip_prefix("0o") {
-                            <$ty>::from_str_radix(stripped, 8).ok()
+                            (8, stripped)
                         } else if let Some(stripped) = s.strip_prefix("0b") {
-                            <$ty>::from_str_radix(stripped, 2).ok()
+                            (2, stripped)
+                        } else if s.starts_with('_') {
+                            return None;
                         } else {
-                            s.parse::<$ty>().ok()
+                            (10, s)
+                        };
+                        // Accumulate towards the sign, so the most negative value fits too.
+                        let base = <$ty>::try_from(radix).ok()?;
+                        let mut value: $ty = 0;
+                        let mut any = false;
+                        for c in digits.chars().filter(|c| *c != '_') {
+                            let digit = <$ty>::try_from(c.to_digit(radix)?).ok()?;
+                            value = value.checked_mul(base)?;
+                            value = if negative { value.checked_sub(digit)? } else { value.checked_add(digit)? };
+                            any = true;
                         }
+                        if !any {
+                            return None;
+                        }
+                        value.checked_mul(<$ty>::try_from(scale).ok()?)
                     }
                 };
             }
@@ -675,11 +2015,7 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
             parse_int!(parse_usize, usize);
             parse_int!(parse_isize, isize);
 
-            /// All unique parameter descriptors encountered (for diagnostics/UIs).
-            pub static PARAM_SPECS: [&'static str; #param_specs_len] = [ #( #param_specs ),* ];
-
-            /// Descriptor character to Rust type mapping (for help/diagnostics).
-            pub static DESCRIPTOR_HELP: &str = "B:u8   | W:u16  | D:u32 | Q:u64 | X:u128 | Z:usize | F:f64\nb:i8   | w:i16  | d:i32 | q:i64 | x:i128 | z:isize | f:f32\nv:void | c:char | s:str | t:bool | h:hexstr\n";
+            #descriptor_tables
 
             /// Maximum counts per primitive across all descriptors. These sizes define the
             pub const MAX_U8:    usize = #max_u8;
@@ -703,12 +2039,20 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
             pub const MAX_BOOL:  usize = #max_bool;
             pub const MAX_CHAR:  usize = #max_char;
             pub const MAX_HEXSTR:usize = #max_hexstr;
+            pub const MAX_BYTELIST:usize = #max_bytelist;
+            pub const MAX_TIMESTAMP:usize = #max_timestamp;
             pub const MAX_STR:   usize = #max_str;
             pub const MAX_HEXSTR_LEN: usize = #max_hexstr_len_expr;
 
             /// Maximum arity across all functions; token buffers use `1 + MAX_ARITY`.
             pub const MAX_ARITY: usize = #max_arity_num;
 
+            /// Maximum number of `--flag` switches accepted by a single function.
+            pub const MAX_FLAGS: usize = #max_flags;
+
+            /// Maximum number of `--key=value` options accepted by a single function.
+            pub const MAX_OPTIONS: usize = #max_options;
+
             /// Maximum number of commands
             pub const NUM_COMMANDS: usize = ENTRIES.len();
 
@@ -722,16 +2066,43 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
                 pub name: &'static str,
 
                 /// Required positional arity.
-                pub arity: u8,
+                pub arity: u16,
 
-                /// Descriptor-specific parser filling `CallCtx` from `&[&str]`.
-                pub parser: for<'ctx> fn(&mut CallCtx<'ctx>, &[&'ctx str]) -> Result<(), DispatchError>,
+                /// Descriptor-specific parser filling `CallCtx` from the positional tokens and
+                /// the option values (`None` = absent, use the default).
+                pub parser: for<'ctx> fn(&mut CallCtx<'ctx>, &[&'ctx str], &[Option<&'ctx str>]) -> Result<(), DispatchError>,
+
+                /// Descriptor-specific decoder filling `CallCtx` from a binary payload.
+                pub decoder: for<'ctx> fn(&mut CallCtx<'ctx>, &'ctx [u8]) -> Result<(), DispatchError>,
 
                 /// Wrapper invoking the target function.
                 pub caller: for<'ctx> fn(&mut CallCtx<'ctx>, ArgsView<'ctx>) -> Result<(), DispatchError>,
 
+                /// Stable command ID used by `dispatch_binary` (explicit `path@id` or derived).
+                pub id: u16,
+
                 /// Index into `PARAM_SPECS` (for diagnostics).
                 pub spec_idx: u16,
+
+                /// Accepted `--flag` switches (without dashes), in `CallCtx::flags` order.
+                pub flags: &'static [&'static str],
+
+                /// Accepted `--key=value` options (without dashes), in descriptor order.
+                pub options: &'static [&'static str],
+
+                /// Readable signature, e.g. `set_pwm <u32> <f64> [--verbose]` (see `usage`).
+                pub usage: &'static str,
+
+                /// Parameters, options and flags of the command, one indented line each, as
+                /// printed by `help(name)` after the usage.
+                pub details: &'static str,
+
+                /// Positional parameter names from the descriptor (`d:freq`), `""` where unnamed.
+                pub params: &'static [&'static str],
+
+                /// One-line description of the command, from the doc comment of its handler
+                /// (`doc_help = true`), `""` otherwise.
+                pub summary: &'static str,
             }
 
             /// A lightweight view over the raw tokens for advanced callers.
@@ -741,7 +2112,7 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
             }
 
             /// Errors Generateted by tokenization, arity check, or per-type parsing.
-            #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+            #error_derive
             pub enum DispatchError {
 
                 /// Input line contains no tokens.
@@ -750,8 +2121,11 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
                 /// No function with the given name exists in the table.
                 UnknownFunction,
 
-                /// Function exists, but arity mismatched.
-                WrongArity { expected: u8 },
+                /// Function exists, but was disabled at runtime via `set_command_enabled`.
+                Disabled,
+
+                /// Function exists, but arity mismatched; `usage` is the expected signature.
+                WrongArity { expected: u16, usage: &'static str },
 
                 /// Failed to parse a `bool`.
                 BadBool,
@@ -770,6 +2144,35 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
 
                 /// Failed to parse a hexlified string.
                 BadHexStr,
+
+                /// Failed to parse a byte list (`[0x01, 2, 0xFF]`).
+                BadByteList,
+
+                /// Failed to parse a timestamp (`2024-05-01T12:00:00Z`).
+                BadTimestamp,
+
+                /// A `--flag` token that the function does not declare.
+                UnknownFlag,
+
+                /// A `--key` option given last on the line, without a value.
+                MissingOptionValue,
+
+                /// Binary payload is truncated, has trailing bytes or holds invalid UTF-8.
+                BadPayload,
+
+                /// Argument parsed, but is outside the range declared in its descriptor (`d{0..=100}`).
+                /// `arg_index` is the zero-based position among the arguments.
+                OutOfRange { arg_index: u16 },
+
+                /// The line has more tokens than the token buffer holds; the extra ones would
+                /// otherwise be dropped silently.
+                TooManyTokens,
+
+                #failed_variant
+
+                #no_service_variant
+
+                #runtime_variants
             }
 
             /// Stack-only argument storage sized by the `MAX_*` constants.
@@ -796,6 +2199,12 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
                 pub chars:  [char;  MAX_CHAR],
                 pub strs:   [&'a str; MAX_STR],
                 pub hexstrs: [heapless::Vec<u8, MAX_HEXSTR_LEN>; MAX_HEXSTR],
+                pub bytelists: [heapless::Vec<u8, MAX_HEXSTR_LEN>; MAX_BYTELIST],
+                pub timestamps: [(u64, u32); MAX_TIMESTAMP],
+                pub flags:  [bool;  MAX_FLAGS],
+                /// Cancellation token of `dispatch_with_cancel` (`None` outside of it).
+                pub cancel: Option<&'a core::sync::atomic::AtomicBool>,
+                #service_field
             }
 
             impl<'a> CallCtx<'a> {
@@ -825,6 +2234,11 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
                         chars:  ['\0'; MAX_CHAR],
                         strs:   ["";   MAX_STR],
                         hexstrs: core::array::from_fn(|_| heapless::Vec::new()),
+                        bytelists: core::array::from_fn(|_| heapless::Vec::new()),
+                        timestamps: [(0, 0); MAX_TIMESTAMP],
+                        flags:  [false; MAX_FLAGS],
+                        cancel: None,
+                        #service_init
                     }
                 }
             }
@@ -843,48 +2257,271 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
                 #( #entry_inits ),*
             ];
 
-            /// Fast string-table lookup (match on string literal).
+            /// Fast string-table lookup (match on string literal), yielding the index into `ENTRIES`.
             #[inline(always)]
-            fn find_entry(name: &str) -> Option<&'static Entry> {
+            fn find_index(name: &str) -> Option<usize> {
                 match name {
                     #( #match_arms )*
                     _ => None,
                 }
             }
 
-            /// Static pairs of (function name, parameter descriptor).
-            pub static NAME_AND_SPEC: &[(&'static str, &'static str)] = &[
-                #( #name_spec_pairs ),*
-            ];
+            /// Lookup of the table entry for a function name.
+            #[inline(always)]
+            fn find_entry(name: &str) -> Option<&'static Entry> {
+                find_index(name).map(|pos| &ENTRIES[pos]) #runtime_find
+            }
+
+            /// Entry of the command `name` if it is registered and enabled.
+            #[inline(always)]
+            fn find_enabled(name: &str) -> Result<&'static Entry, DispatchError> {
+                let Some(pos) = find_index(name) else {
+                    return #runtime_unknown;
+                };
+                if !is_enabled_at(pos) {
+                    return Err(DispatchError::Disabled);
+                }
+                Ok(&ENTRIES[pos])
+            }
+
+            #runtime_table
+
+            /// Number of 32-bit words in the runtime enable mask (one bit per entry).
+            const ENABLE_MASK_WORDS: usize = NUM_COMMANDS.div_ceil(32);
+
+            /// Runtime enable mask, bit `n` mirrors `ENTRIES[n]`. All commands start enabled.
+            static ENABLE_MASK: [core::sync::atomic::AtomicU32; ENABLE_MASK_WORDS] =
+                [const { core::sync::atomic::AtomicU32::new(u32::MAX) }; ENABLE_MASK_WORDS];
+
+            #[inline(always)]
+            fn is_enabled_at(pos: usize) -> bool {
+                ENABLE_MASK[pos / 32].load(core::sync::atomic::Ordering::Relaxed) & (1u32 << (pos % 32)) != 0
+            }
+
+            /// Enable or disable a command at runtime; disabled commands fail with `DispatchError::Disabled`.
+            ///
+            /// The bit is flipped with an atomic read-modify-write, so toggles from several
+            /// threads or from an interrupt are not lost. Targets without atomic read-modify-write
+            /// fall back to a load and a store: toggle from one context there.
+            pub fn #fn_set_command_enabled(name: &str, enabled: bool) -> Result<(), DispatchError> {
+                use core::sync::atomic::Ordering;
+                let pos = find_index(name).ok_or(DispatchError::UnknownFunction)?;
+                let word = &ENABLE_MASK[pos / 32];
+                let bit = 1u32 << (pos % 32);
+                #[cfg(target_has_atomic = "32")]
+                if enabled {
+                    word.fetch_or(bit, Ordering::Relaxed);
+                } else {
+                    word.fetch_and(!bit, Ordering::Relaxed);
+                }
+                #[cfg(not(target_has_atomic = "32"))]
+                {
+                    let cur = word.load(Ordering::Relaxed);
+                    word.store(if enabled { cur | bit } else { cur & !bit }, Ordering::Relaxed);
+                }
+                Ok(())
+            }
+
+            /// Returns `true` if `name` is registered in this module (enabled or not).
+            #[inline(always)]
+            pub fn #fn_has_command(name: &str) -> bool {
+                find_entry(name).is_some()
+            }
+
+            /// Readable signature of `name`, e.g. `set_pwm <u32> <f64> <str>`; empty if unknown.
+            pub fn #fn_usage(name: &str) -> &'static str {
+                find_entry(name).map_or("", |ent| ent.usage)
+            }
+
+            /// Usage of the automatic `help` command, see `help`.
+            pub const HELP_USAGE: &str = "help [command]";
+
+            /// Writes the help of `name` to `out`: its usage and summary, then its parameters,
+            /// options and flags with their types, ranges and defaults. An empty `name` writes the
+            /// usage of every command instead, one per line, followed by its summary if any.
+            ///
+            /// Shells run it for a `help [command]` line, e.g. `uShell::with_help(help)`.
+            pub fn #fn_help(name: &str, out: &mut dyn core::fmt::Write) -> Result<(), DispatchError> {
+                if name.is_empty() {
+                    for ent in ENTRIES.iter() #runtime_listed {
+                        let _ = out.write_str(ent.usage);
+                        if !ent.summary.is_empty() {
+                            let _ = out.write_str(" - ");
+                            let _ = out.write_str(ent.summary);
+                        }
+                        let _ = out.write_str("\n");
+                    }
+                    return Ok(());
+                }
+                let ent = find_entry(name).ok_or(DispatchError::UnknownFunction)?;
+                let _ = out.write_str("Usage: ");
+                let _ = out.write_str(ent.usage);
+                let _ = out.write_str("\n");
+                if !ent.summary.is_empty() {
+                    let _ = out.write_str(ent.summary);
+                    let _ = out.write_str("\n");
+                }
+                let _ = out.write_str(ent.details);
+                Ok(())
+            }
+
+            /// Name of positional parameter `arg_index` of `name` (as in `OutOfRange { arg_index }`),
+            /// or `None` if the command is unknown or the parameter is unnamed.
+            pub fn #fn_param_name(name: &str, arg_index: usize) -> Option<&'static str> {
+                let ent = find_entry(name)?;
+                ent.params.get(arg_index).copied().filter(|p| !p.is_empty())
+            }
+
+            /// Command ID of `name` for `dispatch_binary`, if registered.
+            pub fn #fn_command_id(name: &str) -> Option<u16> {
+                find_index(name).map(|pos| ENTRIES[pos].id)
+            }
 
-            /// Return (function name, descriptor) pairs. No allocations.
+            /// Lookup of the table position for a command ID.
             #[inline(always)]
-            pub fn get_commands() -> &'static [(&'static str, &'static str)] {
-                NAME_AND_SPEC
+            fn find_id(id: u16) -> Option<usize> {
+                match id {
+                    #( #id_arms )*
+                    _ => None,
+                }
+            }
+
+            /// Returns `true` if `name` is registered and currently enabled.
+            pub fn #fn_is_command_enabled(name: &str) -> bool {
+                find_enabled(name).is_ok()
             }
 
-            /// Return descriptor help string (character to type mapping).
+            #command_specs
+
+            /// Parse a hexlified string (even digit count, non-empty, valid hex) such as `AABBCC`.
+            /// An optional `0x` prefix is skipped, and one `_`, `:` or space separator is accepted
+            /// between bytes (`0xAA_BB`, `AA:BB:CC`, `"AA BB CC"`).
             #[inline(always)]
-            pub fn get_datatypes() -> &'static str {
-                DESCRIPTOR_HELP
+            pub fn #fn_parse_hexstr(s: &str) -> Option<heapless::Vec<u8, MAX_HEXSTR_LEN>> {
+                let s = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")).unwrap_or(s);
+                let mut out = heapless::Vec::new();
+                // High nibble of the byte being read, if its first digit was seen.
+                let mut high: Option<u8> = None;
+                // Whether the last character was a separator.
+                let mut separated = false;
+                for b in s.bytes() {
+                    if matches!(b, b'_' | b':' | b' ') {
+                        if high.is_some() || out.is_empty() || separated {
+                            return None;
+                        }
+                        separated = true;
+                        continue;
+                    }
+                    separated = false;
+                    let digit = (b as char).to_digit(16)? as u8;
+                    match high.take() {
+                        Some(h) => out.push((h << 4) | digit).ok()?,
+                        None => high = Some(digit),
+                    }
+                }
+                if high.is_some() || out.is_empty() || separated {
+                    return None;
+                }
+                Some(out)
             }
 
-            /// Parse a hexlified string (even-length, non-empty, valid hex).
+            /// Parse a byte list literal such as `[0x01, 2, 0b11]` (whitespace tolerant, trailing
+            /// comma allowed). Elements use the integer syntax of `u8` arguments.
             #[inline(always)]
-            pub fn parse_hexstr(s: &str) -> Option<heapless::Vec<u8, MAX_HEXSTR_LEN>> {
-                if s.len() % 2 != 0 || s.is_empty() || (s.len() / 2) > MAX_HEXSTR_LEN {
+            pub fn #fn_parse_bytelist(s: &str) -> Option<heapless::Vec<u8, MAX_HEXSTR_LEN>> {
+                let inner = s.trim().strip_prefix('[')?.strip_suffix(']')?.trim();
+                let mut out = heapless::Vec::new();
+                if inner.is_empty() {
+                    return Some(out);
+                }
+                let inner = inner.strip_suffix(',').unwrap_or(inner);
+                for item in inner.split(',') {
+                    out.push(parse_u8(item)?).ok()?;
+                }
+                Some(out)
+            }
+
+            /// Parse an ISO-8601 timestamp into `(seconds, nanoseconds)` since the Unix epoch.
+            /// Accepts `YYYY-MM-DD` or `YYYY-MM-DDTHH:MM[:SS[.fraction]]`, optionally followed by
+            /// `Z` or a `+HH:MM` / `-HH:MM` offset (UTC when absent). Instants before 1970 are rejected.
+            pub fn #fn_parse_timestamp(s: &str) -> Option<(u64, u32)> {
+                // Fixed-width decimal field.
+                fn num(b: &[u8]) -> Option<i64> {
+                    if b.is_empty() || !b.iter().all(u8::is_ascii_digit) {
+                        return None;
+                    }
+                    Some(b.iter().fold(0i64, |n, d| n * 10 + (d - b'0') as i64))
+                }
+
+                let b = s.trim().as_bytes();
+                if b.len() < 10 || b[4] != b'-' || b[7] != b'-' {
+                    return None;
+                }
+                let (year, month, day) = (num(&b[0..4])?, num(&b[5..7])?, num(&b[8..10])?);
+                let leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+                let month_days = [31, if leap { 29 } else { 28 }, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+                if !(1..=12).contains(&month) || day < 1 || day > month_days[month as usize - 1] {
                     return None;
                 }
-                (0..s.len())
-                    .step_by(2)
-                    .map(|i| u8::from_str_radix(&s[i..i+2], 16).ok())
-                    .collect()
+
+                let (mut hour, mut minute, mut second, mut nanos) = (0i64, 0i64, 0i64, 0u32);
+                let mut rest = &b[10..];
+                if let [b'T' | b't', time @ ..] = rest {
+                    if time.len() < 5 || time[2] != b':' {
+                        return None;
+                    }
+                    hour = num(&time[0..2])?;
+                    minute = num(&time[3..5])?;
+                    rest = &time[5..];
+                    if let [b':', sec @ ..] = rest {
+                        second = num(sec.get(0..2)?)?;
+                        rest = &sec[2..];
+                        if let [b'.' | b',', frac @ ..] = rest {
+                            let digits = frac.iter().take_while(|c| c.is_ascii_digit()).count();
+                            if digits == 0 || digits > 9 {
+                                return None;
+                            }
+                            nanos = num(&frac[..digits])? as u32 * 10u32.pow(9 - digits as u32);
+                            rest = &frac[digits..];
+                        }
+                    }
+                    if hour > 23 || minute > 59 || second > 59 {
+                        return None;
+                    }
+                }
+
+                let offset = match rest {
+                    [] | [b'Z' | b'z'] => 0,
+                    [sign @ (b'+' | b'-'), oh1, oh2, b':', om1, om2] => {
+                        let (oh, om) = (num(&[*oh1, *oh2])?, num(&[*om1, *om2])?);
+                        if oh > 23 || om > 59 {
+                            return None;
+                        }
+                        let off = oh * 3600 + om * 60;
+                        if *sign == b'-' { -off } else { off }
+                    }
+                    _ => return None,
+                };
+
+                // Days since 1970-01-01 (Howard Hinnant's days_from_civil).
+                let y = if month <= 2 { year - 1 } else { year };
+                let era = y.div_euclid(400);
+                let yoe = y - era * 400;
+                let mp = (month + 9) % 12;
+                let doy = (153 * mp + 2) / 5 + day - 1;
+                let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+                let days = era * 146_097 + doe - 719_468;
+
+                let secs = days * 86_400 + hour * 3600 + minute * 60 + second - offset;
+                Some((u64::try_from(secs).ok()?, nanos))
             }
 
             // Quotes-aware tokenizer (no heap). Caller provides the buffer.
             /// Splits by ASCII space or tab. A pair of `"` quotes groups a token (quotes
-            /// Returns `Empty` if no tokens were produced.
-            pub fn tokenize<'a>(line: &'a str, out: &mut [&'a str]) -> Result<usize, DispatchError> {
+            /// A `[` starts a byte-list token that runs to the closing `]`, spaces included.
+            /// Returns `Empty` if no tokens were produced, `TooManyTokens` if they do not all
+            /// fit in `out`.
+            pub fn #fn_tokenize<'a>(line: &'a str, out: &mut [&'a str]) -> Result<usize, DispatchError> {
                 let bytes = line.as_bytes();
                 let mut i = 0usize;
                 let mut n = 0usize;
@@ -893,21 +2530,32 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
                     // Skip leading spaces
                     while i < bytes.len() && is_space(bytes[i]) { i += 1; }
                     if i >= bytes.len() { break; }
+                    if n == out.len() { return Err(DispatchError::TooManyTokens); }
 
                     if bytes[i] == b'"' {
                         // Quoted token
                         let start = i + 1;
                         i = start;
                         while i < bytes.len() && bytes[i] != b'"' { i += 1; }
-                        if n < out.len() { out[n] = &line[start..i]; n += 1; }
+                        out[n] = &line[start..i];
+                        n += 1;
                         if i < bytes.len() { i += 1; }
                         // Consume trailing non-space until next whitespace to match original behavior.
                         while i < bytes.len() && !is_space(bytes[i]) { i += 1; }
+                    } else if bytes[i] == b'[' {
+                        // Byte list token: keep everything up to the closing `]`, spaces included
+                        let start = i;
+                        while i < bytes.len() && bytes[i] != b']' { i += 1; }
+                        if i < bytes.len() { i += 1; }
+                        while i < bytes.len() && !is_space(bytes[i]) { i += 1; }
+                        out[n] = &line[start..i];
+                        n += 1;
                     } else {
                         // Unquoted token
                         let start = i;
                         while i < bytes.len() && !is_space(bytes[i]) { i += 1; }
-                        if n < out.len() { out[n] = &line[start..i]; n += 1; }
+                        out[n] = &line[start..i];
+                        n += 1;
                     }
                 }
 
@@ -937,31 +2585,147 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
                 if it.next().is_none() { Some(c) } else { None }
             }
 
+            /// `char` argument: one character, optionally in single quotes (`'a'`), or one of
+            /// the escapes `\n`, `\r`, `\t`, `\0`, `\\`, `\'` and `\xNN` (ASCII, up to `\x7F`).
+            /// A lone `\` is the backslash itself.
+            #[inline(always)]
+            fn parse_char_ext(s: &str) -> Option<char> {
+                let s = match s.strip_prefix('\'').and_then(|r| r.strip_suffix('\'')) {
+                    Some(inner) if !inner.is_empty() => inner,
+                    _ => s,
+                };
+                let Some(escape) = s.strip_prefix('\\') else {
+                    return parse_char(s);
+                };
+                match escape {
+                    "" | "\\" => Some('\\'),
+                    "n" => Some('\n'),
+                    "r" => Some('\r'),
+                    "t" => Some('\t'),
+                    "0" => Some('\0'),
+                    "'" => Some('\''),
+                    _ => {
+                        let hex = escape.strip_prefix('x')?;
+                        if hex.len() != 2 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                            return None;
+                        }
+                        let code = u8::from_str_radix(hex, 16).ok().filter(u8::is_ascii)?;
+                        Some(char::from(code))
+                    }
+                }
+            }
+
             #[inline(always)]
             fn parse_f<T>(s: &str) -> Option<T> where T: core::str::FromStr { s.parse::<T>().ok() }
 
+            /// Next `N` payload bytes as an array (for `from_le_bytes`).
+            #[inline(always)]
+            fn take_array<const N: usize>(payload: &[u8], p: &mut usize) -> Result<[u8; N], DispatchError> {
+                let bytes = payload.get(*p..*p + N).ok_or(DispatchError::BadPayload)?;
+                *p += N;
+                let mut out = [0u8; N];
+                out.copy_from_slice(bytes);
+                Ok(out)
+            }
+
+            /// Next length-prefixed (one length byte) run of payload bytes.
+            #[inline(always)]
+            fn take_prefixed<'a>(payload: &'a [u8], p: &mut usize) -> Result<&'a [u8], DispatchError> {
+                let [len] = take_array::<1>(payload, p)?;
+                let bytes = payload.get(*p..*p + len as usize).ok_or(DispatchError::BadPayload)?;
+                *p += len as usize;
+                Ok(bytes)
+            }
+
+            /// Binary entry point: run the command with ID `id`, decoding its positional arguments
+            /// from `payload` (little-endian, see the descriptor docs). Options take their
+            /// defaults and flags are `false`.
+            pub fn #fn_dispatch_binary(id: u16, payload: &[u8]) -> Result<(), DispatchError> {
+                let pos = find_id(id).ok_or(DispatchError::UnknownFunction)?;
+                if !is_enabled_at(pos) {
+                    return Err(DispatchError::Disabled);
+                }
+                let ent = &ENTRIES[pos];
+                let mut ctx = CallCtx::new();
+                (ent.decoder)(&mut ctx, payload)?;
+                (ent.caller)(&mut ctx, ArgsView { tokens: &[], len: 0 })
+            }
+
             #[inline(always)]
-            pub fn dispatch(line: &str) -> Result<(), DispatchError> {
+            pub fn #fn_dispatch(line: &str) -> Result<(), DispatchError> {
                 // + 2 in order to detect if more args than expected are provided..
-                let mut toks: [&str; 2 + MAX_ARITY] = [""; 2 + MAX_ARITY];
+                let mut toks: [&str; 2 + MAX_ARITY + MAX_FLAGS + 2 * MAX_OPTIONS] = [""; 2 + MAX_ARITY + MAX_FLAGS + 2 * MAX_OPTIONS];
                 dispatch_with_buf(line, &mut toks)
             }
 
             /// Embedded-friendly entry point: caller supplies the token buffer.
             #[inline(always)]
-            pub fn dispatch_with_buf<'a>(line: &'a str, toks: &mut [&'a str]) -> Result<(), DispatchError> {
+            pub fn #fn_dispatch_with_buf<'a>(line: &'a str, toks: &mut [&'a str]) -> Result<(), DispatchError> {
                 let len = tokenize(line, toks)?;
-                let name = toks[0];
-                let got_arity = (len - 1) as u16;
-                let ent = find_entry(name).ok_or(DispatchError::UnknownFunction)?;
-                if got_arity != ent.arity as u16 {
-                    return Err(DispatchError::WrongArity { expected: ent.arity });
+                dispatch_tokens(toks, len)
+            }
+
+            #ctx_fns
+
+            /// Dispatch already tokenized input: `toks[0]` is the command name, followed by
+            /// `len - 1` argument tokens. Used by typed shortcuts.
+            #[doc(hidden)]
+            pub fn #fn_dispatch_tokens<'a>(toks: &mut [&'a str], len: usize) -> Result<(), DispatchError> {
+                run_tokens(toks, len, CallCtx::new())
+            }
+
+            /// `dispatch_tokens` filling and running `ctx`.
+            fn run_tokens<'a: 'c, 'c>(toks: &'c mut [&'a str], len: usize, mut ctx: CallCtx<'c>) -> Result<(), DispatchError> {
+                let ent = find_enabled(toks[0])?;
+                let mut opts: [Option<&'a str>; MAX_OPTIONS] = [None; MAX_OPTIONS];
+
+                // Pull `--flag` switches and `--key=value` / `--key value` options out before
+                // positional parsing; `--` ends flag parsing.
+                let len = if ent.flags.is_empty() && ent.options.is_empty() {
+                    len
+                } else {
+                    let mut npos = 1usize;
+                    let mut i = 1usize;
+                    let mut flags_done = false;
+                    while i < len {
+                        let tok = toks[i];
+                        i += 1;
+                        if !flags_done && let Some(flag) = tok.strip_prefix("--") {
+                            if flag.is_empty() {
+                                flags_done = true;
+                                continue;
+                            }
+                            let (key, value) = match flag.split_once('=') {
+                                Some((key, value)) => (key, Some(value)),
+                                None => (flag, None),
+                            };
+                            if value.is_none() && let Some(fi) = ent.flags.iter().position(|f| *f == key) {
+                                ctx.flags[fi] = true;
+                                continue;
+                            }
+                            let oi = ent.options.iter().position(|o| *o == key).ok_or(DispatchError::UnknownFlag)?;
+                            opts[oi] = match value {
+                                Some(v) => Some(v),
+                                None if i < len => {
+                                    i += 1;
+                                    Some(toks[i - 1])
+                                }
+                                None => return Err(DispatchError::MissingOptionValue),
+                            };
+                            continue;
+                        }
+                        toks[npos] = tok;
+                        npos += 1;
+                    }
+                    npos
+                };
+                if len - 1 != ent.arity as usize {
+                    return Err(DispatchError::WrongArity { expected: ent.arity, usage: ent.usage });
                 }
 
                 // Fill CallCtx from raw &str tokens (no heap).
-                let mut ctx = CallCtx::new();
                 let args_tokens: &[&str] = &toks[1..len];
-                (ent.parser)(&mut ctx, args_tokens)?;
+                (ent.parser)(&mut ctx, args_tokens, &opts)?;
 
                 // Provide a view for advanced use (currently unused by wrappers).
                 let args = ArgsView { tokens: args_tokens, len: len - 1 };
@@ -970,82 +2734,668 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
         }
     };
 
-    out.into()
+    Ok(out)
 }
 
-/// Internal representation of one function to register (pre-codegen).
-struct FnEntry {
-    name_str: String,
-    path: syn::Path,
-    spec: String,
-    spec_idx: usize,
+/// CallCtx slot, per-type index variable, Rust type and parse expression for one parameter of
+/// type `ch`, reading the raw token from `src`. Returns `None` for `v` and unknown characters.
+fn param_slot(
+    ch: char,
+    src: &TokenStream2,
+) -> Option<(TokenStream2, TokenStream2, TokenStream2, TokenStream2)> {
+    match ch {
+        // unsigned
+        'B' => Some((
+            quote! { u8s },
+            quote! { idx_b },
+            quote! { u8 },
+            quote! { parse_u8(#src).ok_or(DispatchError::BadUnsigned)? },
+        )),
+        'W' => Some((
+            quote! { u16s },
+            quote! { idx_w },
+            quote! { u16 },
+            quote! { parse_u16(#src).ok_or(DispatchError::BadUnsigned)? },
+        )),
+        'D' => Some((
+            quote! { u32s },
+            quote! { idx_d },
+            quote! { u32 },
+            quote! { parse_u32(#src).ok_or(DispatchError::BadUnsigned)? },
+        )),
+        'Q' => Some((
+            quote! { u64s },
+            quote! { idx_q },
+            quote! { u64 },
+            quote! { parse_u64(#src).ok_or(DispatchError::BadUnsigned)? },
+        )),
+        'X' => Some((
+            quote! { u128s },
+            quote! { idx_x },
+            quote! { u128 },
+            quote! { parse_u128(#src).ok_or(DispatchError::BadUnsigned)? },
+        )),
+        // signed
+        'b' => Some((
+            quote! { i8s },
+            quote! { idx_B },
+            quote! { i8 },
+            quote! { parse_i8(#src).ok_or(DispatchError::BadSigned)? },
+        )),
+        'w' => Some((
+            quote! { i16s },
+            quote! { idx_W },
+            quote! { i16 },
+            quote! { parse_i16(#src).ok_or(DispatchError::BadSigned)? },
+        )),
+        'd' => Some((
+            quote! { i32s },
+            quote! { idx_D },
+            quote! { i32 },
+            quote! { parse_i32(#src).ok_or(DispatchError::BadSigned)? },
+        )),
+        'q' => Some((
+            quote! { i64s },
+            quote! { idx_Q },
+            quote! { i64 },
+            quote! { parse_i64(#src).ok_or(DispatchError::BadSigned)? },
+        )),
+        'x' => Some((
+            quote! { i128s },
+            quote! { idx_X },
+            quote! { i128 },
+            quote! { parse_i128(#src).ok_or(DispatchError::BadSigned)? },
+        )),
+        // sized
+        'Z' => Some((
+            quote! { usizes },
+            quote! { idx_z },
+            quote! { usize },
+            quote! { parse_usize(#src).ok_or(DispatchError::BadUnsigned)? },
+        )),
+        'z' => Some((
+            quote! { isizes },
+            quote! { idx_Z },
+            quote! { isize },
+            quote! { parse_isize(#src).ok_or(DispatchError::BadSigned)? },
+        )),
+        // floats
+        'f' => Some((
+            quote! { f32s },
+            quote! { idx_f },
+            quote! { f32 },
+            quote! { parse_f::<f32>(#src).ok_or(DispatchError::BadFloat)? },
+        )),
+        'F' => Some((
+            quote! { f64s },
+            quote! { idx_F },
+            quote! { f64 },
+            quote! { parse_f::<f64>(#src).ok_or(DispatchError::BadFloat)? },
+        )),
+        //  bool, char, string, hexstring
+        't' => Some((
+            quote! { bools },
+            quote! { idx_t },
+            quote! { bool },
+            quote! { parse_bool(#src).ok_or(DispatchError::BadBool)? },
+        )),
+        'c' => Some((
+            quote! { chars },
+            quote! { idx_c },
+            quote! { char },
+            quote! { parse_char_ext(#src).ok_or(DispatchError::BadChar)? },
+        )),
+        's' => Some((
+            quote! { strs },
+            quote! { idx_s },
+            quote! { &str },
+            quote! { #src },
+        )),
+        'h' => Some((
+            quote! { hexstrs },
+            quote! { idx_h },
+            quote! { &[u8] },
+            quote! { parse_hexstr(#src).ok_or(DispatchError::BadHexStr)? },
+        )),
+        'a' => Some((
+            quote! { bytelists },
+            quote! { idx_a },
+            quote! { &[u8] },
+            quote! { parse_bytelist(#src).ok_or(DispatchError::BadByteList)? },
+        )),
+        'T' => Some((
+            quote! { timestamps },
+            quote! { idx_T },
+            quote! { (u64, u32) },
+            quote! { parse_timestamp(#src).ok_or(DispatchError::BadTimestamp)? },
+        )),
+        _ => None,
+    }
 }
 
-/// Last path segment (function ident) as a `String`.
-fn path_last_ident(p: &syn::Path) -> Option<String> {
-    p.segments.last().map(|s| s.ident.to_string())
+/// Expression decoding one parameter of type `ch` from `payload` at position `p`. Integers and
+/// floats are little-endian (`Z`/`z` as 64-bit), `t` is one byte (0/1), `c` a `u32` scalar,
+/// `s`/`h`/`a` a length byte followed by the bytes, and `T` a `u64` seconds / `u32` nanos pair.
+fn binary_value(ch: char) -> TokenStream2 {
+    match ch {
+        'B' | 'W' | 'D' | 'Q' | 'X' | 'b' | 'w' | 'd' | 'q' | 'x' | 'f' | 'F' => {
+            let ty = format_ident!("{}", type_name(ch));
+            quote! { #ty::from_le_bytes(take_array(payload, &mut p)?) }
+        }
+        'Z' => quote! {
+            usize::try_from(u64::from_le_bytes(take_array(payload, &mut p)?))
+                .map_err(|_| DispatchError::BadUnsigned)?
+        },
+        'z' => quote! {
+            isize::try_from(i64::from_le_bytes(take_array(payload, &mut p)?))
+                .map_err(|_| DispatchError::BadSigned)?
+        },
+        't' => quote! {
+            match take_array::<1>(payload, &mut p)? {
+                [0] => false,
+                [1] => true,
+                _ => return Err(DispatchError::BadBool),
+            }
+        },
+        'c' => quote! {
+            char::from_u32(u32::from_le_bytes(take_array(payload, &mut p)?))
+                .ok_or(DispatchError::BadChar)?
+        },
+        's' => quote! {
+            core::str::from_utf8(take_prefixed(payload, &mut p)?)
+                .map_err(|_| DispatchError::BadPayload)?
+        },
+        'h' => quote! {
+            heapless::Vec::from_slice(take_prefixed(payload, &mut p)?)
+                .map_err(|_| DispatchError::BadHexStr)?
+        },
+        'a' => quote! {
+            heapless::Vec::from_slice(take_prefixed(payload, &mut p)?)
+                .map_err(|_| DispatchError::BadByteList)?
+        },
+        'T' => quote! {
+            {
+                let secs = u64::from_le_bytes(take_array(payload, &mut p)?);
+                let nanos = u32::from_le_bytes(take_array(payload, &mut p)?);
+                if nanos >= 1_000_000_000 {
+                    return Err(DispatchError::BadTimestamp);
+                }
+                (secs, nanos)
+            }
+        },
+        _ => quote! {},
+    }
 }
 
-/// Make a valid identifier for wrapper functions (replace non-ASCII-alnum with `_`).
-fn sanitize_ident(s: &str) -> String {
-    s.chars()
-        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
-        .collect()
+/// Quote and escape `s` as a JSON string.
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
 }
 
-pub fn generate_commands_dispatcher_from_file(input: TokenStream) -> TokenStream {
-    use syn::{Expr, parse::ParseStream};
-
-    struct FileMacroInput {
-        _mod_token: Token![mod],   // Token for `mod` keyword
-        mod_name: Ident,           // Name of the module to generate
-        _semi1: Token![;],         // Semicolon after module declaration
-        _hexstr_size_token: Ident, // Identifier for hexstr_size
-        _eq_token: Token![=],      // Equals token for hexstr_size assignment
-        hexstr_size: Expr,         // Expression for hexstr_size value
-        _semi2: Token![;],         // Semicolon after hexstr_size assignment
-        _path_token: Ident,        // Identifier for path
-        _eq_token2: Token![=],     // Equals token for path assignment
-        path: LitStr,              // Literal string for file path
-    }
-
-    impl Parse for FileMacroInput {
-        fn parse(input: ParseStream) -> syn::Result<Self> {
-            Ok(FileMacroInput {
-                _mod_token: input.parse()?,
-                mod_name: input.parse()?,
-                _semi1: input.parse()?,
-                _hexstr_size_token: input.parse()?,
-                _eq_token: input.parse()?,
-                hexstr_size: input.parse()?,
-                _semi2: input.parse()?,
-                _path_token: input.parse()?,
-                _eq_token2: input.parse()?,
-                path: input.parse()?,
-            })
+/// One command object of the JSON manifest; `arity` counts the positional parameters.
+fn manifest_command(
+    e: &FnEntry,
+    id: u16,
+    usage: &str,
+    types: &str,
+    names: &[Option<String>],
+    options: &[OptionSpec],
+    flags: &[String],
+) -> String {
+    let params: Vec<String> = types
+        .chars()
+        .zip(names)
+        .map(|(ch, name)| {
+            let name = name.as_deref().map_or("null".to_string(), json_string);
+            format!(
+                "{{\"type\":{},\"name\":{}}}",
+                json_string(type_name(ch)),
+                name
+            )
+        })
+        .collect();
+    let options: Vec<String> = options
+        .iter()
+        .map(|o| {
+            format!(
+                "{{\"name\":{},\"type\":{}}}",
+                json_string(&o.name),
+                json_string(type_name(o.ty))
+            )
+        })
+        .collect();
+    let flags: Vec<String> = flags.iter().map(|f| json_string(f)).collect();
+    let arity = params.len();
+    // Gated commands name their predicate; the manifest lists them whether compiled in or not.
+    let cfg = e.cfg.as_ref().map_or(String::new(), |pred| {
+        format!(",\"cfg\":{}", json_string(&pred.to_string()))
+    });
+    format!(
+        "{{\"name\":{},\"id\":{},\"descriptor\":{},\"usage\":{},\"arity\":{},\"params\":[{}],\"options\":[{}],\"flags\":[{}]{}}}",
+        json_string(&e.name_str),
+        id,
+        json_string(&e.spec),
+        json_string(usage),
+        arity,
+        params.join(","),
+        options.join(","),
+        flags.join(","),
+        cfg
+    )
+}
+
+/// Readable name of a descriptor type character, as shown in usage strings.
+fn type_name(ch: char) -> &'static str {
+    match ch {
+        'B' => "u8",
+        'W' => "u16",
+        'D' => "u32",
+        'Q' => "u64",
+        'X' => "u128",
+        'b' => "i8",
+        'w' => "i16",
+        'd' => "i32",
+        'q' => "i64",
+        'x' => "i128",
+        'Z' => "usize",
+        'z' => "isize",
+        'f' => "f32",
+        'F' => "f64",
+        't' => "bool",
+        'c' => "char",
+        's' => "str",
+        'h' => "hexstr",
+        'a' => "bytes",
+        'T' => "timestamp",
+        _ => "?",
+    }
+}
+
+/// Usage string for a command: its name, `<type>` (or `<type name>`) per positional parameter,
+/// then `[--name=<type>]` per option and `[--name]` per flag.
+fn usage_text(
+    name: &str,
+    types: &str,
+    names: &[Option<String>],
+    options: &[OptionSpec],
+    flags: &[String],
+) -> String {
+    let mut out = name.to_string();
+    for (ch, param) in types.chars().zip(names) {
+        match param {
+            Some(param) => out.push_str(&format!(" <{} {}>", type_name(ch), param)),
+            None => out.push_str(&format!(" <{}>", type_name(ch))),
         }
     }
+    for opt in options {
+        out.push_str(&format!(" [--{}=<{}>]", opt.name, type_name(opt.ty)));
+    }
+    for flag in flags {
+        out.push_str(&format!(" [--{}]", flag));
+    }
+    out
+}
 
-    let FileMacroInput {
-        mod_name,
-        hexstr_size,
-        path,
-        ..
-    } = parse_macro_input!(input as FileMacroInput);
+/// Descriptor expansion printed by `help(name)` after the usage: one line per positional
+/// parameter (name or position, type, range), option (type, default) and flag.
+fn help_details(
+    types: &str,
+    names: &[Option<String>],
+    ranges: &[Option<TokenStream2>],
+    options: &[OptionSpec],
+    flags: &[String],
+) -> String {
+    let mut rows: Vec<(String, String)> = Vec::new();
+    for (i, ((ch, name), range)) in types.chars().zip(names).zip(ranges).enumerate() {
+        let label = name.clone().unwrap_or_else(|| format!("#{}", i + 1));
+        let text = match range {
+            Some(range) => format!("{}, {}", type_name(ch), range.to_string().replace(' ', "")),
+            None => type_name(ch).to_string(),
+        };
+        rows.push((label, text));
+    }
+    for opt in options {
+        let text = match &opt.default {
+            Some(default) => format!("{}, default {}", type_name(opt.ty), default),
+            None => format!("{}, optional", type_name(opt.ty)),
+        };
+        rows.push((format!("--{}", opt.name), text));
+    }
+    for flag in flags {
+        rows.push((format!("--{}", flag), "flag".to_string()));
+    }
+    let width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+    rows.iter()
+        .map(|(label, text)| format!("  {:<width$}  {}\n", label, text, width = width))
+        .collect()
+}
 
-    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
-    let full_path = std::path::Path::new(&manifest_dir).join(path.value());
+/// Internal representation of one function to register (pre-codegen).
+struct FnEntry {
+    name_str: String,
+    path: syn::Path,
+    spec: String,
+    spec_idx: usize,
+    id: Option<u16>, // Explicit command ID; `None` takes the next free ID in sorted order
+    cfg: Option<TokenStream2>, // Predicate of a `[cfg(...)]` descriptor suffix
+    cancel: bool,    // Takes the cancellation token first (`[cancel]` suffix)
+    span: Span,
+}
+
+/// Every valid descriptor type character (see the descriptor table).
+const DESCRIPTOR_CHARS: &str = "BWDQXbwdqxZzfFtcshaTv";
+
+/// A descriptor split into its parts (see `split_descriptor`).
+#[derive(Default)]
+struct ParsedDescriptor {
+    types: String, // Positional type characters, e.g. `"Dds"` (`""` for `v`)
+    ranges: Vec<Option<TokenStream2>>, // Optional range per positional parameter
+    names: Vec<Option<String>>, // Optional `:name` per positional parameter
+    flags: Vec<String>, // `--name` switches (without dashes), in declaration order
+    options: Vec<OptionSpec>, // `--name=T(default)` options, in declaration order
+}
+
+/// One `--name=T` / `--name=T(default)` option of a descriptor.
+struct OptionSpec {
+    name: String,                  // Option name without dashes
+    ty: char,                      // Descriptor type character of the value
+    default: Option<TokenStream2>, // Value used when the option is absent (`Default` otherwise)
+}
+
+/// Split a descriptor such as `"D:addr d{0..=100}:pct s --verbose --count=D(1)"` into its type
+/// characters (`"Dds"`), one optional range and name per parameter, the `--flag` switches and
+/// the `--key=T` options. Ranges are only accepted on integer and float parameters; whitespace
+/// only separates items.
+fn split_descriptor(desc: &str, span: Span) -> Result<ParsedDescriptor> {
+    let mut types = String::new();
+    let mut ranges: Vec<Option<TokenStream2>> = Vec::new();
+    let mut flags: Vec<String> = Vec::new();
+    let mut options: Vec<OptionSpec> = Vec::new();
+    let mut names: Vec<Option<String>> = Vec::new();
+    // A `:name` may only follow a parameter type (or its range), not a flag or option.
+    let mut after_param = false;
+    let mut void = false;
+    let mut rest = desc;
+    while let Some(ch) = rest.chars().next() {
+        if ch.is_whitespace() {
+            rest = &rest[ch.len_utf8()..];
+            continue;
+        }
+        if let Some(tail) = rest.strip_prefix(':') {
+            let end = tail
+                .find(|c: char| c.is_whitespace() || c == '{')
+                .unwrap_or(tail.len());
+            let name = &tail[..end];
+            rest = &tail[end..];
+            let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !valid {
+                return Err(syn::Error::new(
+                    span,
+                    format!(
+                        "Invalid parameter name `:{}` in descriptor `{}`",
+                        name, desc
+                    ),
+                ));
+            }
+            if names.iter().flatten().any(|n| n == name) {
+                return Err(syn::Error::new(
+                    span,
+                    format!(
+                        "Duplicate parameter name `{}` in descriptor `{}`",
+                        name, desc
+                    ),
+                ));
+            }
+            match names.last_mut() {
+                Some(slot @ None) if after_param => {
+                    *slot = Some(name.to_string());
+                }
+                _ => {
+                    return Err(syn::Error::new(
+                        span,
+                        format!(
+                            "Parameter name `:{}` in descriptor `{}` must follow a parameter type",
+                            name, desc
+                        ),
+                    ));
+                }
+            }
+            continue;
+        }
+        if let Some(tail) = rest.strip_prefix("--") {
+            after_param = false;
+            let end = tail
+                .find(|c: char| c.is_whitespace() || c == '=')
+                .unwrap_or(tail.len());
+            let name = &tail[..end];
+            rest = &tail[end..];
+            let valid = name.starts_with(|c: char| c.is_ascii_alphabetic())
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+            if !valid {
+                return Err(syn::Error::new(
+                    span,
+                    format!("Invalid flag `--{}` in descriptor `{}`", name, desc),
+                ));
+            }
+            if flags.iter().any(|f| f == name) || options.iter().any(|o| o.name == name) {
+                return Err(syn::Error::new(
+                    span,
+                    format!("Duplicate flag `--{}` in descriptor `{}`", name, desc),
+                ));
+            }
+            let Some(value) = rest.strip_prefix('=') else {
+                flags.push(name.to_string());
+                continue;
+            };
+            let (option, tail) = split_option(name, value).ok_or_else(|| {
+                syn::Error::new(
+                    span,
+                    format!(
+                        "Invalid option `--{}{}` in descriptor `{}`",
+                        name, rest, desc
+                    ),
+                )
+            })?;
+            options.push(option);
+            rest = tail;
+            continue;
+        }
+        rest = &rest[ch.len_utf8()..];
+        if ch != '{' {
+            if !DESCRIPTOR_CHARS.contains(ch) {
+                return Err(syn::Error::new(
+                    span,
+                    format!("Unknown type `{}` in descriptor `{}`", ch, desc),
+                ));
+            }
+            // `v` declares "no positional parameters"; it adds no type of its own.
+            if ch == 'v' {
+                void = true;
+                after_param = false;
+                continue;
+            }
+            types.push(ch);
+            ranges.push(None);
+            names.push(None);
+            after_param = true;
+            continue;
+        }
+        let Some(end) = rest.find('}') else {
+            return Err(syn::Error::new(
+                span,
+                format!("Unclosed range in descriptor `{}`", desc),
+            ));
+        };
+        let (inner, tail) = (&rest[..end], &rest[end + 1..]);
+        rest = tail;
+        // `s{path}` marks a file name, completed from the filesystem by the line editor; the
+        // handler still receives a `&str`.
+        if inner == "path" && types.ends_with('s') && after_param {
+            continue;
+        }
+        let numeric = types
+            .chars()
+            .last()
+            .is_some_and(|t| "BWDQXbwdqxZzfF".contains(t));
+        match ranges.last_mut() {
+            Some(slot @ None) if numeric => {
+                let range = parse_range(inner).ok_or_else(|| {
+                    syn::Error::new(
+                        span,
+                        format!("Invalid range `{{{}}}` in descriptor `{}`", inner, desc),
+                    )
+                })?;
+                *slot = Some(range);
+            }
+            _ => {
+                return Err(syn::Error::new(
+                    span,
+                    format!(
+                        "Range `{{{}}}` in descriptor `{}` must follow a numeric parameter",
+                        inner, desc
+                    ),
+                ));
+            }
+        }
+    }
+    // Flags and options are not positional, so only value types conflict with `v`.
+    if void && !types.is_empty() {
+        return Err(syn::Error::new(
+            span,
+            format!(
+                "`v` (void) cannot be combined with other types in descriptor `{}`",
+                desc
+            ),
+        ));
+    }
+    Ok(ParsedDescriptor {
+        types,
+        ranges,
+        names,
+        flags,
+        options,
+    })
+}
 
-    let raw_dsl = std::fs::read_to_string(&full_path)
-        .unwrap_or_else(|_| panic!("Failed to read command descriptor file: {:?}", full_path));
+/// Parse the `T` / `T(default)` part of an option (the text after `--name=`), returning the
+/// option and the remaining descriptor. The default is a Rust expression; parentheses and
+/// quoted strings inside it may contain whitespace.
+fn split_option<'d>(name: &str, value: &'d str) -> Option<(OptionSpec, &'d str)> {
+    let ty = value.chars().next()?;
+    if ty == 'v' || !DESCRIPTOR_CHARS.contains(ty) {
+        return None;
+    }
+    let mut rest = &value[ty.len_utf8()..];
+    let mut default = None;
+    if let Some(inner) = rest.strip_prefix('(') {
+        let (mut depth, mut quoted, mut escaped) = (1usize, false, false);
+        let mut close = None;
+        for (i, c) in inner.char_indices() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' if quoted => escaped = true,
+                '"' => quoted = !quoted,
+                '(' if !quoted => depth += 1,
+                ')' if !quoted => {
+                    depth -= 1;
+                    if depth == 0 {
+                        close = Some(i);
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        let close = close?;
+        // Hex strings and byte lists borrow from the input line, so they have no default.
+        if "ha".contains(ty) {
+            return None;
+        }
+        let expr = syn::parse_str::<syn::Expr>(&inner[..close]).ok()?;
+        default = Some(quote! { #expr });
+        rest = &inner[close + 1..];
+    }
+    if rest.starts_with(|c: char| !c.is_whitespace()) {
+        return None;
+    }
+    Some((
+        OptionSpec {
+            name: name.to_string(),
+            ty,
+            default,
+        },
+        rest,
+    ))
+}
 
-    let macro_input = quote! {
-        mod #mod_name;
-        hexstr_size = #hexstr_size;
-        #raw_dsl
+/// Parse the inside of a descriptor range (`0..=100`, `1..8`, `..10`, `-5..`) into a range
+/// expression. Bounds are Rust literals, so their type is checked against the parameter type.
+fn parse_range(inner: &str) -> Option<TokenStream2> {
+    let (lo, hi, inclusive) = match inner.split_once("..=") {
+        Some((lo, hi)) => (lo.trim(), hi.trim(), true),
+        None => {
+            let (lo, hi) = inner.split_once("..")?;
+            (lo.trim(), hi.trim(), false)
+        }
+    };
+    let bound = |b: &str| -> Option<Option<syn::Expr>> {
+        if b.is_empty() {
+            Some(None)
+        } else {
+            syn::parse_str::<syn::Expr>(b).ok().map(Some)
+        }
     };
+    match (bound(lo)?, bound(hi)?, inclusive) {
+        (None, None, _) | (_, None, true) => None,
+        (lo, hi, false) => Some(quote! { #lo .. #hi }),
+        (lo, hi, true) => Some(quote! { #lo ..= #hi }),
+    }
+}
+
+/// Last path segment (function ident) as a `String`.
+fn path_last_ident(p: &syn::Path) -> Option<String> {
+    p.segments.last().map(|s| s.ident.to_string())
+}
+
+/// Whether `p` names a method of the type `service` (`crate::drv::Driver::reset` for
+/// `crate::drv::Driver`), rather than a free function.
+fn is_method_of(p: &syn::Path, service: &syn::Path) -> bool {
+    p.leading_colon.is_some() == service.leading_colon.is_some()
+        && p.segments.len() == service.segments.len() + 1
+        && p.segments
+            .iter()
+            .zip(&service.segments)
+            .all(|(a, b)| a.ident == b.ident)
+}
+
+/// Make a valid identifier for wrapper functions (replace non-ASCII-alnum with `_`).
+fn sanitize_ident(s: &str) -> String {
+    s.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
 
-    generate_dispatcher_from_dsl(macro_input.into())
+/// Entry point of `generate_commands_dispatcher!`; the descriptors come from `path = "<file>"`
+/// (resolved relative to `CARGO_MANIFEST_DIR`) or from an inline DSL literal.
+pub fn generate_commands_dispatcher_from_file(input: TokenStream) -> TokenStream {
+    generate_dispatcher_from_dsl(input)
 }
 
 // ========================= TESTS ==========================================
@@ -1070,38 +3420,13 @@ mod tests {
             // Now manually extract and process just like generate_dispatcher_from_dsl does
             let CommandMacroInput {
                 mod_ident,
-                body,
+                groups,
                 hexstr_size,
+                ..
             } = parsed;
 
-            let pairs: Vec<(String, Vec<syn::Path>)> = {
-                let s = body.value();
-                let mut acc = Vec::new();
-                for group in s.split(',') {
-                    let grp = group.trim();
-                    if grp.is_empty() {
-                        continue;
-                    }
-                    let (desc, names) = match grp.split_once(':') {
-                        Some((d, r)) => (d.trim(), r.trim()),
-                        None => continue,
-                    };
-                    if desc.is_empty() || names.is_empty() {
-                        continue;
-                    }
-                    let desc_str = desc.to_string();
-                    let funcs: StdResult<Vec<_>, _> = names
-                        .split_whitespace()
-                        .map(syn::parse_str::<syn::Path>)
-                        .collect();
-                    let funcs = match funcs {
-                        Ok(v) => v,
-                        Err(_) => continue,
-                    };
-                    acc.push((desc_str, funcs));
-                }
-                acc
-            };
+            let pairs: Vec<(String, Vec<syn::Path>)> =
+                groups.into_iter().map(|g| (g.desc, g.funcs)).collect();
 
             // Check we got at least some entries for non-empty, non-whitespace descriptors
             if !descriptor.trim().is_empty() && pairs.is_empty() {
@@ -1123,6 +3448,19 @@ mod tests {
         output.to_string()
     }
 
+    // Helper running the real expansion and returning the generated module as a string
+    fn expand_to_string(descriptor: &str) -> String {
+        let input = quote! {
+            mod test_dispatcher;
+            hexstr_size = 64;
+            #descriptor
+        };
+        let parsed = syn::parse2::<CommandMacroInput>(input).expect("Failed to parse input");
+        expand_dispatcher(parsed)
+            .expect("Expansion failed")
+            .to_string()
+    }
+
     // ============================================================================
     // Basic Parsing Tests
     // ============================================================================
@@ -1182,6 +3520,20 @@ mod tests {
         assert!(code.contains("test_dispatcher"));
     }
 
+    #[test]
+    fn test_parse_hexstr_prefix_and_separators() {
+        let code = expand_to_string("h: test::hexstr");
+        assert!(code.contains(
+            "s . strip_prefix (\"0x\") . or_else (|| s . strip_prefix (\"0X\")) . unwrap_or (s)"
+        ));
+        assert!(code.contains("matches ! (b , b'_' | b':' | b' ')"));
+        assert!(
+            code.contains(
+                "if high . is_some () || out . is_empty () || separated { return None ; }"
+            )
+        );
+    }
+
     #[test]
     fn test_custom_module_name() {
         let input = quote! {
@@ -1242,6 +3594,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::field_reassign_with_default)]
     fn test_host_counts_all_types() {
         let mut counts = HostCounts::default();
         counts.u8_c = 1;
@@ -1262,6 +3615,8 @@ mod tests {
         counts.char_c = 1;
         counts.str_c = 1;
         counts.hexstr_c = 1;
+        counts.bytelist_c = 1;
+        counts.timestamp_c = 1;
 
         // Verify all fields can be set
         assert_eq!(counts.u8_c, 1);
@@ -1375,6 +3730,10 @@ mod tests {
             path: path.clone(),
             spec: "DD".to_string(),
             spec_idx: 0,
+            id: None,
+            cfg: None,
+            cancel: false,
+            span: Span::call_site(),
         };
 
         assert_eq!(entry.name_str, "add");
@@ -1383,6 +3742,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::useless_vec)]
     fn test_fn_entry_sorting() {
         let mut entries = vec![
             FnEntry {
@@ -1390,18 +3750,30 @@ mod tests {
                 path: syn::parse_str("test::zebra").unwrap(),
                 spec: "v".to_string(),
                 spec_idx: 0,
+                id: None,
+                cfg: None,
+                cancel: false,
+                span: Span::call_site(),
             },
             FnEntry {
                 name_str: "apple".to_string(),
                 path: syn::parse_str("test::apple").unwrap(),
                 spec: "v".to_string(),
                 spec_idx: 0,
+                id: None,
+                cfg: None,
+                cancel: false,
+                span: Span::call_site(),
             },
             FnEntry {
                 name_str: "middle".to_string(),
                 path: syn::parse_str("test::middle").unwrap(),
                 spec: "v".to_string(),
                 spec_idx: 0,
+                id: None,
+                cfg: None,
+                cancel: false,
+                span: Span::call_site(),
             },
         ];
 
@@ -1475,9 +3847,39 @@ mod tests {
 
     #[test]
     fn test_void_arity() {
-        let desc = "v";
-        let arity = if desc == "v" { 0 } else { desc.chars().count() };
-        assert_eq!(arity, 0);
+        // `v` is an empty parameter list, so no special case is needed for its arity
+        let parsed = split_descriptor("v", Span::call_site()).unwrap();
+        assert_eq!(parsed.types, "");
+        assert_eq!(parsed.types.chars().count(), 0);
+    }
+
+    #[test]
+    fn test_void_with_flags_and_options() {
+        let parsed = split_descriptor("v --verbose --count=B(1)", Span::call_site()).unwrap();
+        assert_eq!(parsed.types, "");
+        assert_eq!(parsed.flags, ["verbose"]);
+        assert_eq!(parsed.options.len(), 1);
+
+        let output = expand_to_string("v --verbose: test::status");
+        assert!(output.contains("arity : 0u16"));
+        assert!(output.contains("impl __Sig_status < (bool ,) > for ()"));
+        assert!(output.contains("usage : \"status [--verbose]\""));
+    }
+
+    #[test]
+    fn test_arity_above_255() {
+        let output = expand_to_string(&format!("{}: test::fill", "B".repeat(300)));
+        assert!(output.contains("arity : 300u16"));
+        assert!(output.contains("pub const MAX_ARITY : usize = 300usize"));
+        assert!(output.contains("WrongArity { expected : u16 , usage : & 'static str }"));
+    }
+
+    #[test]
+    fn test_void_rejects_value_annotations() {
+        assert!(split_descriptor("v:x", Span::call_site()).is_err());
+        assert!(split_descriptor("v{0..1}", Span::call_site()).is_err());
+        assert!(split_descriptor("Dv", Span::call_site()).is_err());
+        assert!(split_descriptor("v --on D", Span::call_site()).is_err());
     }
 
     // ============================================================================
@@ -1497,7 +3899,8 @@ mod tests {
 
         let cmd = parsed.unwrap();
         assert_eq!(cmd.mod_ident.to_string(), "test_dispatcher");
-        assert_eq!(cmd.body.value(), "DD: test::add");
+        assert_eq!(cmd.groups.len(), 1);
+        assert_eq!(cmd.groups[0].desc, "DD");
     }
 
     #[test]
@@ -1525,6 +3928,98 @@ mod tests {
         assert!(parsed.unwrap().hexstr_size.is_some());
     }
 
+    #[test]
+    fn test_parse_structured_form() {
+        let input = quote! {
+            mod test_dispatcher;
+            hexstr_size = 64;
+            "DD": [test::add, test::sub];
+            "v": [test::reset,];
+        };
+
+        let cmd = syn::parse2::<CommandMacroInput>(input).unwrap();
+        assert_eq!(cmd.groups.len(), 2);
+        assert_eq!(cmd.groups[0].desc, "DD");
+        assert_eq!(cmd.groups[0].funcs.len(), 2);
+        assert_eq!(cmd.groups[1].desc, "v");
+        assert_eq!(cmd.groups[1].funcs.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_mixed_dsl_and_structured() {
+        let input = quote! {
+            mod test_dispatcher;
+            hexstr_size = 64;
+            "DD: test::add, s: test::greet";
+            "v": [test::reset]
+        };
+
+        let cmd = syn::parse2::<CommandMacroInput>(input).unwrap();
+        let descs: Vec<&str> = cmd.groups.iter().map(|g| g.desc.as_str()).collect();
+        assert_eq!(descs, ["DD", "s", "v"]);
+    }
+
+    #[test]
+    fn test_parse_structured_empty_list_rejected() {
+        let input = quote! {
+            mod test_dispatcher;
+            "DD": [];
+        };
+        let err = syn::parse2::<CommandMacroInput>(input).err().unwrap();
+        assert!(err.to_string().contains("at least one function path"));
+    }
+
+    #[test]
+    fn test_parse_structured_missing_separator_rejected() {
+        let input = quote! {
+            mod test_dispatcher;
+            "DD": [test::add]
+            "v": [test::reset]
+        };
+        assert!(syn::parse2::<CommandMacroInput>(input).is_err());
+    }
+
+    #[test]
+    fn test_parse_without_commands_rejected() {
+        let input = quote! {
+            mod test_dispatcher;
+            hexstr_size = 64;
+        };
+        assert!(syn::parse2::<CommandMacroInput>(input).is_err());
+    }
+
+    #[test]
+    fn test_structured_form_generates_entries() {
+        let input = quote! {
+            mod test_dispatcher;
+            hexstr_size = 64;
+            "DD": [test::add];
+            "DD: test::sub"
+        };
+        let parsed = syn::parse2::<CommandMacroInput>(input).unwrap();
+        let output = expand_dispatcher(parsed).unwrap().to_string();
+        assert!(output.contains("name : \"add\""));
+        assert!(output.contains("name : \"sub\""));
+        // Same descriptor from both forms shares one parser
+        assert!(output.contains("__parse_spec_0"));
+        assert!(!output.contains("__parse_spec_1"));
+    }
+
+    #[test]
+    fn test_commands_ignore_handler_results() {
+        let input = quote! {
+            mod test_dispatcher;
+            hexstr_size = 64;
+            "DD": [test::add];
+        };
+        let parsed = syn::parse2::<CommandMacroInput>(input).unwrap();
+        assert!(!parsed.handler_results);
+        let output = expand_dispatcher(parsed).unwrap().to_string();
+        assert!(output.contains("let _ = test :: add ("));
+        assert!(output.contains("# [derive (Debug , Clone , Copy , PartialEq , Eq)]"));
+        assert!(!output.contains("Failed (heapless"));
+    }
+
     #[test]
     fn test_parse_complex_descriptor() {
         let input = quote! {
@@ -1657,11 +4152,1322 @@ mod tests {
         assert_eq!(unique.len(), 3);
     }
 
+    // ============================================================================
+    // Runtime Enable/Disable Tests
+    // ============================================================================
+
+    #[test]
+    fn test_enable_mask_generated() {
+        let code = expand_to_string("DD: test::add test::sub, v: test::reset");
+        assert!(code.contains("ENABLE_MASK"));
+        assert!(code.contains("fn set_command_enabled"));
+        assert!(code.contains("fn is_command_enabled"));
+        // Toggles are read-modify-writes, so concurrent ones are not lost.
+        assert!(code.contains("word . fetch_or (bit , Ordering :: Relaxed)"));
+        assert!(code.contains("word . fetch_and (! bit , Ordering :: Relaxed)"));
+    }
+
+    #[test]
+    fn test_disabled_error_variant_generated() {
+        let code = expand_to_string("v: test::reset");
+        assert!(code.contains("Disabled"));
+        assert!(code.contains("return Err (DispatchError :: Disabled)"));
+    }
+
+    #[test]
+    fn test_lookup_yields_entry_index() {
+        let code = expand_to_string("v: test::zebra test::apple");
+        // Entries are sorted, so `apple` is at index 0 and `zebra` at index 1.
+        assert!(code.contains("\"apple\" => Some (0usize)"));
+        assert!(code.contains("\"zebra\" => Some (1usize)"));
+    }
+
+    #[test]
+    fn test_missing_hexstr_size_is_error() {
+        let input = quote! {
+            mod test_dispatcher;
+            "v: test::reset"
+        };
+        let parsed = syn::parse2::<CommandMacroInput>(input).unwrap();
+        assert!(expand_dispatcher(parsed).is_err());
+    }
+
+    // ============================================================================
+    // Include Directive Tests
+    // ============================================================================
+
+    /// Create a fresh scratch directory for descriptor files.
+    fn scratch_dir(name: &str) -> std::path::PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("ushell_dispatcher_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("subsys")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_include_is_expanded_relative_to_file() {
+        let dir = scratch_dir("include");
+        std::fs::write(
+            dir.join("commands.cfg"),
+            "v : crate::init,\n@include \"subsys/motor.cfg\"\ns : crate::echo,\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("subsys/motor.cfg"),
+            "D : crate::motor_speed\n@include \"brake.cfg\"\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("subsys/brake.cfg"), "t : crate::brake").unwrap();
+
+        let groups = load_descriptor_file(
+            &dir.join("commands.cfg"),
+            Span::call_site(),
+            &mut Vec::new(),
+        )
+        .unwrap();
+        let descs: Vec<&str> = groups.iter().map(|g| g.desc.as_str()).collect();
+        assert_eq!(descs, ["v", "D", "t", "s"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_include_cycle_rejected() {
+        let dir = scratch_dir("cycle");
+        std::fs::write(dir.join("a.cfg"), "@include \"b.cfg\"\n").unwrap();
+        std::fs::write(dir.join("b.cfg"), "@include \"a.cfg\"\n").unwrap();
+
+        let err = load_descriptor_file(&dir.join("a.cfg"), Span::call_site(), &mut Vec::new())
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("Recursive @include"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_include_missing_file_rejected() {
+        let dir = scratch_dir("missing");
+        std::fs::write(dir.join("a.cfg"), "@include \"nope.cfg\"\n").unwrap();
+
+        let err = load_descriptor_file(&dir.join("a.cfg"), Span::call_site(), &mut Vec::new())
+            .err()
+            .unwrap();
+        assert!(
+            err.to_string()
+                .contains("Failed to read command descriptor file")
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_include_malformed_reports_line() {
+        let dir = scratch_dir("malformed");
+        std::fs::write(dir.join("a.cfg"), "v : crate::init,\n@include motor.cfg\n").unwrap();
+
+        let err = load_descriptor_file(&dir.join("a.cfg"), Span::call_site(), &mut Vec::new())
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("a.cfg:2"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_same_file_included_twice_is_not_a_cycle() {
+        let dir = scratch_dir("twice");
+        std::fs::write(
+            dir.join("a.cfg"),
+            "@include \"subsys/common.cfg\"\n@include \"subsys/common.cfg\"\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("subsys/common.cfg"), "v : crate::noop").unwrap();
+
+        let groups =
+            load_descriptor_file(&dir.join("a.cfg"), Span::call_site(), &mut Vec::new()).unwrap();
+        assert_eq!(groups.len(), 2);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    // ============================================================================
+    // Unit Suffix Tests
+    // ============================================================================
+
+    #[test]
+    fn test_unit_base_defaults_to_1024() {
+        let input = quote! {
+            mod test_dispatcher;
+            hexstr_size = 64;
+            "D: test::func"
+        };
+        let parsed = syn::parse2::<CommandMacroInput>(input).unwrap();
+        assert_eq!(parsed.unit_base, 1024);
+
+        let output = expand_dispatcher(parsed).unwrap().to_string();
+        assert!(output.contains("pub const UNIT_BASE : u64 = 1024u64"));
+        assert!(output.contains("fn split_unit_suffix"));
+    }
+
+    #[test]
+    fn test_unit_base_1000_accepted() {
+        let input = quote! {
+            mod test_dispatcher;
+            hexstr_size = 64;
+            unit_base = 1000;
+            "D: test::func"
+        };
+        let parsed = syn::parse2::<CommandMacroInput>(input).unwrap();
+        assert_eq!(parsed.unit_base, 1000);
+
+        let output = expand_dispatcher(parsed).unwrap().to_string();
+        assert!(output.contains("pub const UNIT_BASE : u64 = 1000u64"));
+    }
+
+    #[test]
+    fn test_unit_base_invalid_value_rejected() {
+        let input = quote! {
+            mod test_dispatcher;
+            unit_base = 512;
+            "D: test::func"
+        };
+        let err = syn::parse2::<CommandMacroInput>(input).err().unwrap();
+        assert!(err.to_string().contains("1000 or 1024"));
+    }
+
+    #[test]
+    fn test_unknown_option_rejected() {
+        let input = quote! {
+            mod test_dispatcher;
+            buffer_size = 64;
+            "D: test::func"
+        };
+        let err = syn::parse2::<CommandMacroInput>(input).err().unwrap();
+        assert!(err.to_string().contains("Unexpected identifier"));
+    }
+
+    #[test]
+    fn test_parse_int_applies_scale() {
+        let output = expand_to_string("D: test::func");
+        assert!(output.contains("split_unit_suffix (s . trim ())"));
+        assert!(output.contains("checked_mul"));
+    }
+
+    #[test]
+    fn test_char_arguments_take_escapes() {
+        let output = expand_to_string("c: test::put");
+        assert!(output.contains(
+            "ctx . chars [idx_c] = parse_char_ext (args [k]) . ok_or (DispatchError :: BadChar) ? ;"
+        ));
+        assert!(output.contains("\"n\" => Some ('\\n') ,"));
+        assert!(
+            output.contains("u8 :: from_str_radix (hex , 16) . ok () . filter (u8 :: is_ascii) ?")
+        );
+    }
+
+    #[test]
+    fn test_parse_int_sign_and_separators() {
+        let output = expand_to_string("D: test::func");
+        assert!(output.contains("Some (b'+') => (false , & s [1 ..]) ,"));
+        assert!(output.contains(":: MIN == 0 { return None ; }"));
+        assert!(output.contains("for c in digits . chars () . filter (| c | * c != '_')"));
+    }
+
+    // ============================================================================
+    // Range Constraint Tests
+    // ============================================================================
+
+    #[test]
+    fn test_split_descriptor_without_ranges() {
+        let ParsedDescriptor { types, ranges, .. } =
+            split_descriptor("DdFs", Span::call_site()).unwrap();
+        assert_eq!(types, "DdFs");
+        assert_eq!(ranges.len(), 4);
+        assert!(ranges.iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn test_split_descriptor_with_ranges() {
+        let ParsedDescriptor { types, ranges, .. } =
+            split_descriptor("Dd{0..=100}sB{1..8}", Span::call_site()).unwrap();
+        assert_eq!(types, "DdsB");
+        assert!(ranges[0].is_none());
+        assert_eq!(ranges[1].as_ref().unwrap().to_string(), "0 ..= 100");
+        assert!(ranges[2].is_none());
+        assert_eq!(ranges[3].as_ref().unwrap().to_string(), "1 .. 8");
+    }
+
+    #[test]
+    fn test_parse_range_forms() {
+        assert_eq!(parse_range("..10").unwrap().to_string(), ".. 10");
+        assert_eq!(parse_range("..=10").unwrap().to_string(), "..= 10");
+        assert_eq!(parse_range("-5..").unwrap().to_string(), "- 5 ..");
+        assert_eq!(parse_range("0.5..1.5").unwrap().to_string(), "0.5 .. 1.5");
+        assert!(parse_range("..").is_none());
+        assert!(parse_range("5..=").is_none());
+        assert!(parse_range("5").is_none());
+    }
+
+    #[test]
+    fn test_range_on_non_numeric_rejected() {
+        assert!(split_descriptor("s{0..5}", Span::call_site()).is_err());
+        assert!(split_descriptor("{0..5}", Span::call_site()).is_err());
+        assert!(split_descriptor("d{0..5}{1..2}", Span::call_site()).is_err());
+    }
+
+    #[test]
+    fn test_range_unclosed_rejected() {
+        let err = split_descriptor("d{0..5", Span::call_site()).err().unwrap();
+        assert!(err.to_string().contains("Unclosed range"));
+    }
+
+    #[test]
+    fn test_range_check_generated() {
+        let output = expand_to_string("d{0..=100}: test::func");
+        assert!(output.contains("OutOfRange { arg_index : u16 }"));
+        assert!(output.contains("RangeBounds :: < i32 > :: contains (& (0 ..= 100)"));
+        // Arity and signature ignore the range annotation
+        assert!(output.contains("arity : 1u16"));
+        assert!(output.contains("< (i32 ,) > for ()"));
+        // The descriptor is still reported verbatim
+        assert!(output.contains("\"d{0..=100}\""));
+    }
+
+    #[test]
+    fn test_invalid_range_is_error() {
+        let input = quote! {
+            mod test_dispatcher;
+            hexstr_size = 64;
+            "d{abc}: test::func"
+        };
+        let err = syn::parse2::<CommandMacroInput>(input).err().unwrap();
+        assert!(err.to_string().contains("Invalid range"));
+    }
+
+    // ============================================================================
+    // Usage Tests
+    // ============================================================================
+
+    /// Usage text for `name` with descriptor `desc`.
+    fn usage_of(name: &str, desc: &str) -> String {
+        let p = split_descriptor(desc, Span::call_site()).unwrap();
+        usage_text(name, &p.types, &p.names, &p.options, &p.flags)
+    }
+
+    #[test]
+    fn test_usage_text_positional() {
+        assert_eq!(usage_of("set_pwm", "dFs"), "set_pwm <i32> <f64> <str>");
+        assert_eq!(usage_of("reset", "v"), "reset");
+        assert_eq!(usage_of("send", "ha"), "send <hexstr> <bytes>");
+    }
+
+    #[test]
+    fn test_usage_text_options_and_flags() {
+        assert_eq!(
+            usage_of("ping", "s --count=D(1) --quiet"),
+            "ping <str> [--count=<u32>] [--quiet]"
+        );
+    }
+
+    #[test]
+    fn test_usage_generated() {
+        let output = expand_to_string("Dt: test::set");
+        assert!(output.contains("usage : \"set <u32> <bool>\""));
+        assert!(output.contains("pub fn usage (name : & str) -> & 'static str"));
+        assert!(output.contains("WrongArity { expected : u16 , usage : & 'static str }"));
+        assert!(output.contains("usage : ent . usage"));
+    }
+
+    fn details_of(desc: &str) -> String {
+        let p = split_descriptor(desc, Span::call_site()).unwrap();
+        help_details(&p.types, &p.names, &p.ranges, &p.options, &p.flags)
+    }
+
+    #[test]
+    fn test_help_details_expand_descriptor() {
+        assert_eq!(
+            details_of("D:freq{1..=1000} F s --count=D(1) --tag=s --quiet"),
+            "  freq     u32, 1..=1000\n  #2       f64\n  #3       str\n  --count  u32, default 1\n  --tag    str, optional\n  --quiet  flag\n"
+        );
+        assert_eq!(details_of("v"), "");
+    }
+
+    #[test]
+    fn test_help_generated() {
+        let output = expand_to_string("D:addr --verbose: test::peek");
+        assert!(output.contains("pub const HELP_USAGE : & str = \"help [command]\""));
+        assert!(output.contains(
+            "pub fn help (name : & str , out : & mut dyn core :: fmt :: Write) -> Result < () , DispatchError >"
+        ));
+        assert!(output.contains("details : \"  addr       u32\\n  --verbose  flag\\n\""));
+    }
+
+    #[test]
+    fn test_dispatch_with_cancel_generated() {
+        let output = expand_to_string("D: test::scan");
+        assert!(output.contains(
+            "pub fn dispatch_with_cancel (line : & str , cancel : & core :: sync :: atomic :: AtomicBool)"
+        ));
+        assert!(output.contains("ctx . cancel = Some (cancel) ;"));
+        assert!(
+            output.contains("pub cancel : Option < & 'a core :: sync :: atomic :: AtomicBool >")
+        );
+        // No process-wide token
+        assert!(!output.contains("static CANCEL"));
+        assert!(!output.contains("fn cancelled"));
+        assert!(!output.contains("NoService"));
+    }
+
+    #[test]
+    fn test_cancel_handlers_take_the_token() {
+        let output = expand_to_string(
+            "D[cancel]: test::scan, v[cancel][cfg(feature = \"debug\")]: test::stop, D: test::plain",
+        );
+        assert!(output.contains(
+            "let _ = test :: scan (ctx . cancel . unwrap_or (& core :: sync :: atomic :: AtomicBool :: new (false)) , ctx . u32s [0usize]) ;"
+        ));
+        assert!(output.contains("let _ = test :: plain (ctx . u32s [0usize]) ;"));
+        assert!(output.contains("# [cfg (feature = \"debug\")]"));
+        // `[cancel]` does not change the descriptor: one parser for `scan` and `plain`
+        assert!(!output.contains("cancel]\""));
+        assert!(output.contains(
+            "impl __Sig_scan < (& core :: sync :: atomic :: AtomicBool , u32 ,) > for ()"
+        ));
+    }
+
+    #[test]
+    fn test_service_methods() {
+        let input = quote! {
+            mod test_dispatcher;
+            hexstr_size = 64;
+            service = crate::drv::Driver;
+            "D: crate::drv::Driver::set_freq, v: crate::drv::Driver::reset test::free"
+        };
+        let parsed = syn::parse2::<CommandMacroInput>(input).unwrap();
+        let output = expand_dispatcher(parsed).unwrap().to_string();
+        assert!(output.contains(
+            "pub fn dispatch_with_service (line : & str , service : & mut crate :: drv :: Driver)"
+        ));
+        assert!(output.contains("pub fn dispatch_with_service_cancel (line : & str , service : & mut crate :: drv :: Driver , cancel :"));
+        // The token-only form stays, running methods without a service
+        assert!(output.contains("pub fn dispatch_with_cancel (line : & str , cancel :"));
+        assert!(output.contains("pub service : Option < & 'a mut crate :: drv :: Driver >"));
+        assert!(output.contains("NoService ,"));
+        // Methods take the service first, free functions do not
+        assert!(output.contains(
+            "let _ = crate :: drv :: Driver :: set_freq (service , ctx . u32s [0usize]) ;"
+        ));
+        assert!(output.contains("let _ = test :: free () ;"));
+        assert!(
+            output
+                .contains("impl __Sig_set_freq < (& mut crate :: drv :: Driver , u32 ,) > for ()")
+        );
+        assert!(output.contains("impl __Sig_reset < (& crate :: drv :: Driver ,) > for ()"));
+        assert!(output.contains("impl __Sig_free < () > for ()"));
+        assert!(output.contains("expects `fn(&mut crate::drv::Driver, u32)`"));
+    }
+
+    #[test]
+    fn test_runtime_commands() {
+        let input = quote! {
+            mod test_dispatcher;
+            hexstr_size = 64;
+            runtime_commands = 4;
+            ffi = true;
+            "D: test::set, v: test::go"
+        };
+        let parsed = syn::parse2::<CommandMacroInput>(input).unwrap();
+        assert!(parsed.runtime_commands.is_some());
+        let output = expand_dispatcher(parsed).unwrap().to_string();
+        assert!(output.contains("pub const RUNTIME_COMMANDS : usize = 4 ;"));
+        assert!(output.contains("# [cfg (target_has_atomic = \"8\")] pub fn register_command (name : & 'static str , arity : u16 , parser_id : u16 , handler : RuntimeHandler)"));
+        assert!(output.contains("TableFull ,"));
+        assert!(output.contains("AlreadyDefined ,"));
+        assert!(output.contains("UnknownParser ,"));
+        assert!(output.contains(
+            "DispatchError :: TableFull => 18i32 , DispatchError :: AlreadyDefined => 19i32 , DispatchError :: UnknownParser => 20i32 ,"
+        ));
+        // The static match falls back to the runtime table
+        assert!(output.contains(
+            "find_index (name) . map (| pos | & ENTRIES [pos]) . or_else (|| find_runtime (name))"
+        ));
+        assert!(
+            output.contains(
+                "return find_runtime (name) . ok_or (DispatchError :: UnknownFunction) ;"
+            )
+        );
+        assert!(output.contains("for ent in ENTRIES . iter () . chain (runtime_entries ())"));
+
+        let output = expand_to_string("D: test::set");
+        assert!(!output.contains("RUNTIME_COMMANDS"));
+        assert!(!output.contains("find_runtime"));
+        assert!(output.contains("return Err (DispatchError :: UnknownFunction) ;"));
+    }
+
+    #[test]
+    fn test_cfg_gated_entries() {
+        let input = quote! {
+            mod test_dispatcher;
+            hexstr_size = 64;
+            ffi = true;
+            manifest = true;
+            "v: test::a test::d, D[cfg(feature = \"debug\")]: test::b";
+            "v[cfg(all(unix, not(test)))]": [test::c]
+        };
+        let parsed = syn::parse2::<CommandMacroInput>(input).unwrap();
+        assert_eq!(parsed.groups[1].desc, "D");
+        let output = expand_dispatcher(parsed).unwrap().to_string();
+        // Wrapper, signature check and entry are gated
+        assert!(output.contains("# [cfg (feature = \"debug\")] # [inline (always)] fn __call_b"));
+        assert!(output.contains(
+            "# [cfg (feature = \"debug\")] # [diagnostic :: on_unimplemented (message = \"handler `test::b`"
+        ));
+        assert!(output.contains("# [cfg (feature = \"debug\")] Entry { name : \"b\""));
+        assert!(output.contains("# [cfg (all (unix , not (test)))] Entry { name : \"c\""));
+        assert!(output.contains("[\"a\" , # [cfg (feature = \"debug\")] \"b\" ,"));
+        // Positions after a gated entry count it only when it is compiled in
+        assert!(output.contains("\"a\" => Some (0usize) ,"));
+        assert!(output.contains("# [cfg (feature = \"debug\")] \"b\" => Some (1usize) ,"));
+        assert!(output.contains(
+            "\"d\" => Some ({ 1usize + (cfg ! (feature = \"debug\") as usize) + (cfg ! (all (unix , not (test))) as usize) }) ,"
+        ));
+        assert!(output.contains(
+            "call : ffi_call :: < { 1usize + (cfg ! (feature = \"debug\") as usize) + (cfg ! (all (unix , not (test))) as usize) } > ,"
+        ));
+        assert!(output.contains("\\\"cfg\\\":\\\"feature = \\\\\\\"debug\\\\\\\"\\\""));
+    }
+
+    #[test]
+    fn test_is_method_of() {
+        let service: syn::Path = syn::parse_quote!(crate::drv::Driver);
+        assert!(is_method_of(
+            &syn::parse_quote!(crate::drv::Driver::reset),
+            &service
+        ));
+        assert!(!is_method_of(
+            &syn::parse_quote!(crate::drv::reset),
+            &service
+        ));
+        assert!(!is_method_of(
+            &syn::parse_quote!(crate::drv::Driver::inner::reset),
+            &service
+        ));
+        assert!(!is_method_of(
+            &syn::parse_quote!(::crate::drv::Driver::reset),
+            &service
+        ));
+    }
+
+    // ============================================================================
+    // Parameter Name Tests
+    // ============================================================================
+
+    #[test]
+    fn test_split_descriptor_names() {
+        let parsed = split_descriptor("d:freq F:duty s", Span::call_site()).unwrap();
+        assert_eq!(parsed.types, "dFs");
+        assert_eq!(
+            parsed.names,
+            [Some("freq".to_string()), Some("duty".to_string()), None]
+        );
+    }
+
+    #[test]
+    fn test_names_with_ranges_in_either_order() {
+        let parsed = split_descriptor("d{0..=100}:pct B:ch{1..8}", Span::call_site()).unwrap();
+        assert_eq!(parsed.types, "dB");
+        assert!(parsed.ranges.iter().all(Option::is_some));
+        assert_eq!(parsed.names[0].as_deref(), Some("pct"));
+        assert_eq!(parsed.names[1].as_deref(), Some("ch"));
+    }
+
+    #[test]
+    fn test_invalid_names_rejected() {
+        assert!(split_descriptor("d:", Span::call_site()).is_err());
+        assert!(split_descriptor("d:9x", Span::call_site()).is_err());
+        assert!(split_descriptor(":x", Span::call_site()).is_err());
+        assert!(split_descriptor("v:x", Span::call_site()).is_err());
+        assert!(split_descriptor("d:a:b", Span::call_site()).is_err());
+        assert!(split_descriptor("d --on :x", Span::call_site()).is_err());
+        let err = split_descriptor("d:x D:x", Span::call_site())
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("Duplicate parameter name `x`"));
+    }
+
+    #[test]
+    fn test_usage_text_with_names() {
+        assert_eq!(
+            usage_of("set_pwm", "D:freq F:duty s:label"),
+            "set_pwm <u32 freq> <f64 duty> <str label>"
+        );
+        assert_eq!(usage_of("poke", "D:addr B"), "poke <u32 addr> <u8>");
+    }
+
+    #[test]
+    fn test_names_in_dsl_group() {
+        let output = expand_to_string("D:freq F:duty: test::set_pwm");
+        assert!(output.contains("params : & [\"freq\" , \"duty\"]"));
+        assert!(output.contains("< (u32 , f64 ,) > for ()"));
+        assert!(output.contains("\"D:freq F:duty\""));
+        assert!(output.contains("pub fn param_name (name : & str , arg_index : usize)"));
+    }
+
+    #[test]
+    fn test_split_group_uses_last_colon() {
+        assert_eq!(
+            split_group("d:freq s: a::b, c"),
+            Some(("d:freq s", " a::b, c"))
+        );
+        assert_eq!(split_group("d: x::y::z"), Some(("d", " x::y::z")));
+        assert_eq!(split_group("d x::y"), None);
+    }
+
+    // ============================================================================
+    // Binary Dispatch Tests
+    // ============================================================================
+
+    #[test]
+    fn test_command_ids_derived_from_sorted_order() {
+        let output = expand_to_string("v: test::zeta test::alpha, D: test::mid");
+        assert!(output.contains("name : \"alpha\" , arity : 0u16 , parser : __parse_spec_0 , decoder : __decode_spec_0 , caller : __call_alpha , id : 0u16"));
+        assert!(output.contains("caller : __call_mid , id : 1u16"));
+        assert!(output.contains("caller : __call_zeta , id : 2u16"));
+        assert!(output.contains("pub fn dispatch_binary (id : u16 , payload : & [u8])"));
+    }
+
+    #[test]
+    fn test_explicit_command_ids() {
+        // Explicit IDs are kept; the others skip them
+        let output = expand_to_string("v: test::a@0 test::b, D: test::c@0x2");
+        assert!(output.contains("caller : __call_a , id : 0u16"));
+        assert!(output.contains("caller : __call_b , id : 1u16"));
+        assert!(output.contains("caller : __call_c , id : 2u16"));
+        assert!(output.contains("2u16 => Some (2usize)"));
+    }
+
+    #[test]
+    fn test_explicit_command_ids_structured() {
+        let input = quote! {
+            mod test_dispatcher;
+            hexstr_size = 64;
+            "D": [test::a @ 7, test::b]
+        };
+        let parsed = syn::parse2::<CommandMacroInput>(input).unwrap();
+        assert_eq!(parsed.groups[0].ids, [Some(7), None]);
+    }
+
+    #[test]
+    fn test_duplicate_command_id_is_error() {
+        let input = quote! {
+            mod test_dispatcher;
+            hexstr_size = 64;
+            "v: test::a@3, D: test::b@3"
+        };
+        let parsed = syn::parse2::<CommandMacroInput>(input).unwrap();
+        let err = expand_dispatcher(parsed).err().unwrap();
+        assert!(err.to_string().contains("Duplicate command id 3"));
+    }
+
+    #[test]
+    fn test_invalid_command_id_is_error() {
+        for dsl in ["v: test::a@", "v: test::a@x", "v: test::a@70000"] {
+            let input = quote! {
+                mod test_dispatcher;
+                hexstr_size = 64;
+                #dsl
+            };
+            let err = syn::parse2::<CommandMacroInput>(input).err().unwrap();
+            assert!(err.to_string().contains("Invalid command id"), "{}", dsl);
+        }
+    }
+
+    #[test]
+    fn test_binary_decoder_generated() {
+        let output = expand_to_string("Dtsd{0..=9}: test::f");
+        assert!(output.contains(
+            "fn __decode_spec_0 < 'a > (ctx : & mut CallCtx < 'a > , payload : & 'a [u8])"
+        ));
+        assert!(output.contains("u32 :: from_le_bytes (take_array (payload , & mut p) ?)"));
+        assert!(output.contains("core :: str :: from_utf8 (take_prefixed (payload , & mut p) ?)"));
+        assert!(output.contains("BadPayload"));
+        // Range checks apply to decoded values too
+        assert_eq!(
+            output
+                .matches("OutOfRange { arg_index : k as u16 }")
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_binary_value_sized_types_are_64_bit() {
+        assert!(
+            binary_value('Z')
+                .to_string()
+                .contains("u64 :: from_le_bytes")
+        );
+        assert!(
+            binary_value('z')
+                .to_string()
+                .contains("i64 :: from_le_bytes")
+        );
+        assert!(
+            binary_value('F')
+                .to_string()
+                .contains("f64 :: from_le_bytes")
+        );
+    }
+
+    // ============================================================================
+    // Manifest Tests
+    // ============================================================================
+
+    #[test]
+    fn test_json_string_escaping() {
+        assert_eq!(json_string("plain"), "\"plain\"");
+        assert_eq!(json_string("s(\"a\")"), "\"s(\\\"a\\\")\"");
+        assert_eq!(json_string("a\\b\nc"), "\"a\\\\b\\nc\"");
+        assert_eq!(json_string("\u{1}"), "\"\\u0001\"");
+    }
+
+    #[test]
+    fn test_manifest_is_opt_in() {
+        let output = expand_to_string("D: test::f");
+        assert!(!output.contains("MANIFEST_JSON"));
+    }
+
+    #[test]
+    fn test_manifest_generated() {
+        let input = quote! {
+            mod test_dispatcher;
+            hexstr_size = 64;
+            manifest = true;
+            "D:addr s --count=B --force: test::write@3, v: test::reset"
+        };
+        let parsed = syn::parse2::<CommandMacroInput>(input).unwrap();
+        assert!(parsed.manifest);
+        let output = expand_dispatcher(parsed).unwrap().to_string();
+        assert!(output.contains("pub const MANIFEST_JSON : & str ="));
+        let json = r#"{\"name\":\"write\",\"id\":3,\"descriptor\":\"D:addr s --count=B --force\",\"usage\":\"write <u32 addr> <str> [--count=<u8>] [--force]\",\"arity\":2,\"params\":[{\"type\":\"u32\",\"name\":\"addr\"},{\"type\":\"str\",\"name\":null}],\"options\":[{\"name\":\"count\",\"type\":\"u8\"}],\"flags\":[\"force\"]}"#;
+        assert!(output.contains(json), "{}", output);
+        assert!(output.contains(
+            r#"{\"module\":\"test_dispatcher\",\"commands\":[{\"name\":\"reset\",\"id\":0,"#
+        ));
+        assert!(output.contains(r#"\"arity\":0,\"params\":[],\"options\":[],\"flags\":[]}"#));
+    }
+
+    #[test]
+    fn test_ffi_is_opt_in() {
+        let output = expand_to_string("D: test::f");
+        assert!(!output.contains("SHELL_ENTRIES_C"));
+    }
+
+    #[test]
+    fn test_ffi_table_generated() {
+        let input = quote! {
+            mod test_dispatcher;
+            hexstr_size = 64;
+            ffi = true;
+            "Ds: test::write, v: test::reset"
+        };
+        let parsed = syn::parse2::<CommandMacroInput>(input).unwrap();
+        assert!(parsed.ffi);
+        let output = expand_dispatcher(parsed).unwrap().to_string();
+        assert!(output.contains("# [repr (C)] pub struct CEntry"));
+        assert!(output.contains(
+            "pub static SHELL_ENTRIES_C : [CEntry ; NUM_COMMANDS] = [CEntry { name : b\"reset\\0\" . as_ptr () . cast () , arity : 0u16 , call : ffi_call :: < 0usize > , } , CEntry { name : b\"write\\0\" . as_ptr () . cast () , arity : 2u16 , call : ffi_call :: < 1usize > , }]"
+        ), "{}", output);
+        assert!(output.contains("pub static SHELL_ENTRIES_C_LEN : usize = NUM_COMMANDS"));
+    }
+
+    #[test]
+    fn test_function_names_without_alloc() {
+        let output = expand_to_string("v: test::b, v: test::a");
+        assert!(output.contains(
+            "pub static FUNCTION_NAMES : [& 'static str ; NUM_COMMANDS] = [\"a\" , \"b\"]"
+        ));
+        assert!(!output.contains("get_function_names"));
+        assert!(!output.contains("cfg (feature"));
+    }
+
+    #[test]
+    fn test_std_function_names() {
+        let input = quote! {
+            mod test_dispatcher;
+            hexstr_size = 64;
+            std = true;
+            "v: test::a"
+        };
+        let parsed = syn::parse2::<CommandMacroInput>(input).unwrap();
+        assert!(parsed.std);
+        let output = expand_dispatcher(parsed).unwrap().to_string();
+        assert!(output.contains("pub fn get_function_names () -> Vec < & 'static str >"));
+    }
+
+    #[test]
+    fn test_defmt_is_opt_in() {
+        let output = expand_to_string("v: test::a");
+        assert!(!output.contains("defmt"));
+
+        let input = quote! {
+            mod test_dispatcher;
+            hexstr_size = 64;
+            defmt = true;
+            "v: test::a"
+        };
+        let output = expand_dispatcher(syn::parse2(input).unwrap())
+            .unwrap()
+            .to_string();
+        assert!(output.contains(
+            "# [derive (Debug , Clone , Copy , PartialEq , Eq)] # [derive (defmt :: Format)] pub enum DispatchError"
+        ));
+    }
+
+    #[test]
+    fn test_doc_help() {
+        let input = quote! {
+            mod test_dispatcher;
+            hexstr_size = 64;
+            doc_help = true;
+            "D: crate::pwm::set_freq, v: reset"
+        };
+        let parsed = syn::parse2::<CommandMacroInput>(input).unwrap();
+        assert!(parsed.doc_help);
+        let output = expand_dispatcher(parsed).unwrap().to_string();
+        assert!(output.contains("summary : __ushell_doc_reset ,"));
+        assert!(output.contains("summary : crate :: pwm :: __ushell_doc_set_freq"));
+        assert!(expand_to_string("v: test::reset").contains("summary : \"\""));
+    }
+
+    #[test]
+    fn test_minimal() {
+        let input = quote! {
+            mod test_dispatcher;
+            hexstr_size = 64;
+            minimal = true;
+            "D:freq --fast: test::set, D --fast: test::put, D{0..=9}: test::lim, v: test::go"
+        };
+        let parsed = syn::parse2::<CommandMacroInput>(input).unwrap();
+        assert!(parsed.minimal);
+        let output = expand_dispatcher(parsed).unwrap().to_string();
+        for omitted in ["PARAM_SPECS :", "DESCRIPTOR_HELP"] {
+            assert!(!output.contains(omitted), "{}", omitted);
+        }
+        // The names stay listed, so shells and `merge_commands!` can use the module.
+        assert!(output.contains("(\"put\" , \"\") , (\"set\" , \"\")"));
+        assert!(output.contains("pub fn get_commands () -> & 'static [(& 'static str , & 'static str)] { NAME_AND_SPEC }"));
+        assert!(output.contains("pub fn get_datatypes () -> & 'static str { \"\" }"));
+        assert!(output.contains("usage : \"set\" , details : \"\" , params : & [] ,"));
+        // `set` and `put` only differ in parameter names, `lim` has a range
+        assert!(output.contains("name : \"put\" , arity : 1u16 , parser : __parse_spec_0 ,"));
+        assert!(output.contains("name : \"lim\" , arity : 1u16 , parser : __parse_spec_2 ,"));
+        assert!(!output.contains("fn __parse_spec_1"));
+
+        let output = expand_to_string("D:freq --fast: test::set, D --fast: test::put");
+        assert!(output.contains("fn __parse_spec_1"));
+        assert!(output.contains("pub static NAME_AND_SPEC"));
+
+        let input = quote! {
+            mod test_dispatcher;
+            hexstr_size = 64;
+            minimal = true;
+            doc_help = true;
+            "v: test::go"
+        };
+        let err = syn::parse2::<CommandMacroInput>(input).err().unwrap();
+        assert!(
+            err.to_string()
+                .contains("cannot be combined with `minimal = true`")
+        );
+
+        let input = quote! {
+            mod test_dispatcher;
+            hexstr_size = 64;
+            runtime_commands = 4;
+            minimal = true;
+            "v: test::go"
+        };
+        let err = syn::parse2::<CommandMacroInput>(input).err().unwrap();
+        assert!(
+            err.to_string()
+                .contains("`runtime_commands` cannot be combined with `minimal = true`")
+        );
+    }
+
+    #[test]
+    fn test_fn_prefix() {
+        let input = quote! {
+            mod test_dispatcher;
+            hexstr_size = 64;
+            fn_prefix = "shell_";
+            "Ds: test::write"
+        };
+        let parsed = syn::parse2::<CommandMacroInput>(input).unwrap();
+        assert_eq!(parsed.fn_prefix, "shell_");
+        let output = expand_dispatcher(parsed).unwrap().to_string();
+        assert!(output.contains("pub fn shell_dispatch (line : & str)"));
+        assert!(output.contains("pub fn shell_tokenize < 'a >"));
+        assert!(output.contains("pub fn shell_get_commands ()"));
+        assert!(!output.contains("pub fn dispatch ("));
+        // The plain names stay usable inside the module, without being exported
+        assert!(output.contains(
+            "use self :: { shell_set_command_enabled as set_command_enabled , shell_has_command as has_command ,"
+        ));
+        assert!(output.contains(
+            "shell_dispatch_with_cancel as dispatch_with_cancel , shell_dispatch_tokens as dispatch_tokens }"
+        ));
+        assert!(!expand_to_string("Ds: test::write").contains("use self :: {"));
+    }
+
+    #[test]
+    fn test_fn_prefix_must_start_an_identifier() {
+        let input = quote! {
+            mod test_dispatcher;
+            hexstr_size = 64;
+            fn_prefix = "1x";
+            "v: test::f"
+        };
+        let err = syn::parse2::<CommandMacroInput>(input).err().unwrap();
+        assert!(
+            err.to_string()
+                .contains("`fn_prefix` must start an identifier")
+        );
+    }
+
+    #[test]
+    fn test_manifest_requires_bool() {
+        let input = quote! {
+            mod test_dispatcher;
+            hexstr_size = 64;
+            manifest = 1;
+            "v: test::f"
+        };
+        assert!(syn::parse2::<CommandMacroInput>(input).is_err());
+    }
+
+    // ============================================================================
+    // Signature Check Tests
+    // ============================================================================
+
+    #[test]
+    fn test_signature_check_names_command_and_descriptor() {
+        let output = expand_to_string("Ds: test::write");
+        assert!(output.contains(
+            "message = \"handler `test::write` does not match command `write` (descriptor `Ds`)\""
+        ));
+        assert!(output.contains(
+            "note = \"descriptor `Ds` expects `fn(u32, &str)`; fix the handler or the descriptor\""
+        ));
+        assert!(output.contains("impl __Sig_write < (u32 , & str ,) > for ()"));
+        assert!(output.contains("impl __Sig_write < NoSig > for ()"));
+        assert!(output.contains("< () as __Sig_write < _ >> :: check (sig_of (& test :: write))"));
+    }
+
+    #[test]
+    fn test_signature_check_escapes_braces() {
+        let output = expand_to_string("d{0..=9}: test::f");
+        assert!(output.contains("(descriptor `d{{0..=9}}`)"));
+    }
+
+    #[test]
+    fn test_sig_of_impls_cover_all_arities() {
+        // Two positional parameters, one option, one flag: handler takes four arguments
+        let output = expand_to_string("v: test::a, DD --n=B --f: test::b");
+        assert!(output.contains("SigOf < (R ,) > for F"));
+        assert!(output.contains("SigOf < (R , A0 , A1 , A2 , A3 ,) > for F"));
+        assert!(!output.contains("A4"));
+    }
+
+    // ============================================================================
+    // Flag Tests
+    // ============================================================================
+
+    #[test]
+    fn test_split_descriptor_flags() {
+        let parsed = split_descriptor("Ds --verbose --dry-run", Span::call_site()).unwrap();
+        assert_eq!(parsed.types, "Ds");
+        assert_eq!(parsed.ranges.len(), 2);
+        assert_eq!(parsed.flags, ["verbose", "dry-run"]);
+    }
+
+    #[test]
+    fn test_split_descriptor_flags_only() {
+        let parsed = split_descriptor("--force", Span::call_site()).unwrap();
+        assert_eq!(parsed.types, "");
+        assert_eq!(parsed.flags, ["force"]);
+    }
+
+    #[test]
+    fn test_split_descriptor_range_with_spaces() {
+        let parsed = split_descriptor("d{0 ..= 10} --x", Span::call_site()).unwrap();
+        assert_eq!(parsed.types, "d");
+        assert!(parsed.ranges[0].is_some());
+    }
+
+    #[test]
+    fn test_invalid_flags_rejected() {
+        assert!(split_descriptor("D --", Span::call_site()).is_err());
+        assert!(split_descriptor("D --9lives", Span::call_site()).is_err());
+        assert!(split_descriptor("D --a=y", Span::call_site()).is_err());
+        let err = split_descriptor("D --x --x", Span::call_site())
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("Duplicate flag `--x`"));
+    }
+
+    #[test]
+    fn test_flags_generated() {
+        let output = expand_to_string("Ds --verbose --dry-run: test::run");
+        assert!(output.contains("pub const MAX_FLAGS : usize = 2usize"));
+        assert!(output.contains("flags : & [\"verbose\" , \"dry-run\"]"));
+        assert!(output.contains("< (u32 , & str , bool , bool ,) > for ()"));
+        assert!(output.contains("ctx . flags [0usize] , ctx . flags [1usize]"));
+        assert!(output.contains("UnknownFlag"));
+        // Flags are optional, so they do not count towards the arity
+        assert!(output.contains("arity : 2u16"));
+    }
+
+    #[test]
+    fn test_no_flags_generated_as_empty() {
+        let output = expand_to_string("D: test::f");
+        assert!(output.contains("pub const MAX_FLAGS : usize = 0usize"));
+        assert!(output.contains("flags : & []"));
+    }
+
+    // ============================================================================
+    // Option Tests
+    // ============================================================================
+
+    #[test]
+    fn test_split_descriptor_options() {
+        let parsed =
+            split_descriptor("s --count=D(1) --label=s(\"a b\") --on", Span::call_site()).unwrap();
+        assert_eq!(parsed.types, "s");
+        assert_eq!(parsed.flags, ["on"]);
+        assert_eq!(parsed.options.len(), 2);
+        assert_eq!(parsed.options[0].name, "count");
+        assert_eq!(parsed.options[0].ty, 'D');
+        assert!(parsed.options[0].default.is_some());
+        assert_eq!(parsed.options[1].ty, 's');
+        assert_eq!(
+            parsed.options[1].default.as_ref().unwrap().to_string(),
+            "\"a b\""
+        );
+    }
+
+    #[test]
+    fn test_option_without_default() {
+        let parsed = split_descriptor("--scale=f", Span::call_site()).unwrap();
+        assert_eq!(parsed.types, "");
+        assert_eq!(parsed.options[0].ty, 'f');
+        assert!(parsed.options[0].default.is_none());
+    }
+
+    #[test]
+    fn test_invalid_options_rejected() {
+        assert!(split_descriptor("D --n=", Span::call_site()).is_err());
+        assert!(split_descriptor("D --n=v", Span::call_site()).is_err());
+        assert!(split_descriptor("D --n=D(1", Span::call_site()).is_err());
+        assert!(split_descriptor("D --n=D(1)x", Span::call_site()).is_err());
+        assert!(split_descriptor("D --n=h(\"00\")", Span::call_site()).is_err());
+        let err = split_descriptor("D --n --n=D", Span::call_site())
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("Duplicate flag `--n`"));
+    }
+
+    #[test]
+    fn test_options_generated() {
+        let output = expand_to_string("s --count=D(1) --dry: test::run");
+        assert!(output.contains("pub const MAX_OPTIONS : usize = 1usize"));
+        assert!(output.contains("options : & [\"count\"]"));
+        // Positional parameters, then options, then flags
+        assert!(output.contains("< (& str , u32 , bool ,) > for ()"));
+        assert!(output.contains("None => 1"));
+        assert!(output.contains("MissingOptionValue"));
+        // Options stay out of the positional arity but do size the typed slots
+        assert!(output.contains("arity : 1u16"));
+        assert!(output.contains("pub const MAX_U32 : usize = 1usize"));
+    }
+
+    #[test]
+    fn test_option_default_falls_back_to_default_trait() {
+        let output = expand_to_string("--level=B: test::f");
+        assert!(output.contains("None => Default :: default ()"));
+        assert!(output.contains("arity : 0u16"));
+    }
+
+    // ============================================================================
+    // Byte List Tests
+    // ============================================================================
+
+    #[test]
+    fn test_bytelist_descriptor_generated() {
+        let output = expand_to_string("Ba: test::regw");
+        assert!(output.contains("pub const MAX_BYTELIST : usize = 1usize"));
+        assert!(
+            output.contains(
+                "pub bytelists : [heapless :: Vec < u8 , MAX_HEXSTR_LEN > ; MAX_BYTELIST]"
+            )
+        );
+        assert!(
+            output.contains("parse_bytelist (args [k]) . ok_or (DispatchError :: BadByteList)")
+        );
+        assert!(output.contains("< (u8 , & [u8] ,) > for ()"));
+        assert!(output.contains("arity : 2u16"));
+    }
+
+    #[test]
+    fn test_bytelist_counts_once_per_param() {
+        let output = expand_to_string("aa: test::pair, a: test::one");
+        assert!(output.contains("pub const MAX_BYTELIST : usize = 2usize"));
+    }
+
+    #[test]
+    fn test_tokenizer_groups_brackets() {
+        let output = expand_to_string("a: test::one");
+        assert!(output.contains("bytes [i] == b'['"));
+        assert!(output.contains("BadByteList"));
+    }
+
+    #[test]
+    fn test_tokenizer_reports_overflow() {
+        let output = expand_to_string("D: test::one");
+        assert!(output.contains("TooManyTokens ,"));
+        assert!(
+            output
+                .contains("if n == out . len () { return Err (DispatchError :: TooManyTokens) ; }")
+        );
+        // Tokens are no longer dropped past the end of the buffer
+        assert!(!output.contains("if n < out . len ()"));
+    }
+
+    #[test]
+    fn test_bytelist_in_descriptor_help() {
+        let output = expand_to_string("a: test::one");
+        assert!(output.contains("a:bytes"));
+    }
+
+    // ============================================================================
+    // Timestamp Tests
+    // ============================================================================
+
+    #[test]
+    fn test_timestamp_descriptor_generated() {
+        let output = expand_to_string("DT: test::set_alarm");
+        assert!(output.contains("pub const MAX_TIMESTAMP : usize = 1usize"));
+        assert!(output.contains("pub timestamps : [(u64 , u32) ; MAX_TIMESTAMP]"));
+        assert!(
+            output.contains("parse_timestamp (args [k]) . ok_or (DispatchError :: BadTimestamp)")
+        );
+        assert!(output.contains("< (u32 , (u64 , u32) ,) > for ()"));
+        assert!(output.contains("arity : 2u16"));
+    }
+
+    #[test]
+    fn test_timestamp_counts_once_per_param() {
+        let output = expand_to_string("TT: test::window, T: test::set_time");
+        assert!(output.contains("pub const MAX_TIMESTAMP : usize = 2usize"));
+    }
+
+    #[test]
+    fn test_timestamp_parser_and_error_generated() {
+        let output = expand_to_string("T: test::set_time");
+        assert!(output.contains("pub fn parse_timestamp (s : & str) -> Option < (u64 , u32) >"));
+        assert!(output.contains("BadTimestamp ,"));
+        assert!(output.contains("T:timestamp"));
+    }
+
+    #[test]
+    fn test_timestamp_usage_and_binary() {
+        let output = expand_to_string("T:at: test::set_time");
+        assert!(output.contains("\"set_time <timestamp at>\""));
+        assert!(output.contains("nanos >= 1_000_000_000"));
+    }
+
+    #[test]
+    fn test_timestamp_option() {
+        let parsed = split_descriptor("v --at=T((0, 0))", Span::call_site()).unwrap();
+        assert_eq!(parsed.options.len(), 1);
+        assert_eq!(parsed.options[0].ty, 'T');
+        assert!(parsed.options[0].default.is_some());
+    }
+
+    #[test]
+    fn test_path_marker() {
+        let parsed = split_descriptor("s{path}:file D", Span::call_site()).unwrap();
+        assert_eq!(parsed.types, "sD");
+        assert!(parsed.ranges[0].is_none());
+        assert_eq!(parsed.names[0].as_deref(), Some("file"));
+    }
+
+    #[test]
+    fn test_path_marker_rejects_other_types() {
+        let err = split_descriptor("D{path}", Span::call_site())
+            .err()
+            .expect("expected a range error")
+            .to_string();
+        assert!(err.contains("Invalid range"));
+    }
+
+    #[test]
+    fn test_timestamp_rejects_range() {
+        let err = split_descriptor("T{0..10}", Span::call_site())
+            .err()
+            .expect("expected a range error")
+            .to_string();
+        assert!(err.contains("must follow a numeric parameter"));
+    }
+
+    // ============================================================================
+    // DSL Diagnostics Tests
+    // ============================================================================
+
+    fn lit(text: &str) -> LitStr {
+        LitStr::new(text, Span::call_site())
+    }
+
+    fn dsl_err(dsl: &str) -> String {
+        parse_dsl_groups(dsl, Span::call_site(), DslOrigin::Literal(&lit(dsl)))
+            .err()
+            .expect("expected a DSL error")
+            .to_string()
+    }
+
+    #[test]
+    fn test_dsl_group_without_colon_rejected() {
+        let err = dsl_err("DD: test::add, test::sub");
+        assert!(err.contains("Malformed command group `test::sub`"));
+    }
+
+    #[test]
+    fn test_dsl_missing_descriptor_rejected() {
+        assert!(dsl_err(": test::add").contains("Missing descriptor"));
+    }
+
+    #[test]
+    fn test_dsl_missing_paths_rejected() {
+        assert!(dsl_err("DD: , v: test::reset").contains("No function paths for descriptor `DD`"));
+    }
+
+    #[test]
+    fn test_dsl_invalid_path_rejected() {
+        let err = dsl_err("DD: test::add test::3sub");
+        assert!(err.contains("Invalid function path `test::3sub`"));
+    }
+
+    #[test]
+    fn test_dsl_single_colon_in_path_rejected() {
+        let err = dsl_err("D: crate:cmd::foo");
+        assert!(
+            err.contains("Invalid function path `crate:cmd::foo` (paths use `::`)"),
+            "{}",
+            err
+        );
+        assert!(err.ends_with("(at column 4 of the DSL string)"), "{}", err);
+    }
+
+    #[test]
+    fn test_dsl_error_reports_line_and_column() {
+        let err = dsl_err("v: test::init,\nDD: test::add test::3sub");
+        assert!(err.contains("in command group `DD: test::add test::3sub`"));
+        assert!(
+            err.ends_with("(at line 2, column 15 of the DSL string)"),
+            "{}",
+            err
+        );
+        let err = dsl_err("v: test::init, DY: test::add");
+        assert!(err.ends_with("(at column 16 of the DSL string)"), "{}", err);
+    }
+
+    #[test]
+    fn test_dsl_cfg_suffix() {
+        let dsl = "d:x[cfg(any(a, b))]: test::poke, v: test::reset";
+        let groups =
+            parse_dsl_groups(dsl, Span::call_site(), DslOrigin::Literal(&lit(dsl))).unwrap();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].desc, "d:x");
+        assert_eq!(
+            groups[0].cfg.as_ref().map(ToString::to_string).as_deref(),
+            Some("any (a , b)")
+        );
+        assert!(groups[1].cfg.is_none());
+
+        let err = dsl_err("v[cfg(debug)] x: test::poke");
+        assert!(
+            err.contains("`[cfg(...)]` must end the descriptor"),
+            "{}",
+            err
+        );
+        let err = dsl_err("v[cfg(= 1)]: test::poke");
+        assert!(err.contains("Invalid cfg predicate `= 1`"), "{}", err);
+    }
+
+    #[test]
+    fn test_dsl_unknown_type_rejected() {
+        assert!(dsl_err("DY: test::add").contains("Unknown type `Y` in descriptor `DY`"));
+    }
+
+    #[test]
+    fn test_dsl_void_combined_rejected() {
+        assert!(dsl_err("vD: test::add").contains("`v` (void) cannot be combined"));
+    }
+
+    #[test]
+    fn test_dsl_trailing_and_empty_groups_allowed() {
+        let groups = parse_dsl_groups(
+            "DD: test::add,, v: test::reset,",
+            Span::call_site(),
+            DslOrigin::Literal(&lit("DD: test::add,, v: test::reset,")),
+        )
+        .ok()
+        .unwrap();
+        assert_eq!(groups.len(), 2);
+    }
+
+    #[test]
+    fn test_dsl_error_reports_file_and_line() {
+        let file = std::path::Path::new("cmds.cfg");
+        let dsl = "v : test::init,\nDD : test::add,\nDX? : test::bad,\n";
+        let err = parse_dsl_groups(dsl, Span::call_site(), DslOrigin::File(file, 10))
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.starts_with("cmds.cfg:12: "), "{}", err);
+    }
+
+    #[test]
+    fn test_file_error_line_after_include() {
+        let dir = scratch_dir("lines");
+        std::fs::write(dir.join("sub.cfg"), "v : crate::x").unwrap();
+        std::fs::write(
+            dir.join("a.cfg"),
+            "v : crate::init,\n@include \"sub.cfg\"\nD : crate::ok,\nD crate::broken,\n",
+        )
+        .unwrap();
+
+        let err = load_descriptor_file(&dir.join("a.cfg"), Span::call_site(), &mut Vec::new())
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("a.cfg:4: Malformed command group"), "{}", err);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_structured_unknown_type_rejected() {
+        let input = quote! {
+            mod test_dispatcher;
+            "DY": [test::add];
+        };
+        let err = syn::parse2::<CommandMacroInput>(input).err().unwrap();
+        assert!(err.to_string().contains("Unknown type `Y`"));
+    }
+
     // ============================================================================
     // Maximum Length Tests
     // ============================================================================
 
     #[test]
+    #[allow(clippy::useless_vec)]
     fn test_max_function_name_length() {
         let names = vec!["a", "abc", "very_long_name", "x"];
         let max_len = names.iter().map(|n| n.len()).max().unwrap_or(0) + 1;
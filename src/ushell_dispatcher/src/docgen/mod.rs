@@ -0,0 +1,189 @@
+//! # Handler doc-comment harvesting attribute
+//!
+//! `#[command_doc]` on a command handler keeps the first line of its `///` doc comment as the
+//! help text of the command, so the description lives next to the implementation instead of
+//! being repeated in the descriptor file.
+//!
+//! ## Usage
+//!
+//! ```rust
+//! /// Sets the PWM frequency and duty cycle.
+//! ///
+//! /// Longer notes stay in the Rust docs only.
+//! #[command_doc]
+//! pub fn set_pwm(freq: u32, duty: f64) { ... }
+//! ```
+//!
+//! The attribute leaves the function unchanged and adds a hidden `__ushell_doc_<name>` constant
+//! next to it, with the same visibility. A command module generated with `doc_help = true` reads
+//! it through the handler path (`crate::pwm::set_pwm` => `crate::pwm::__ushell_doc_set_pwm`), so
+//! every handler of such a module must carry the attribute.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    Attribute, Ident, LitStr, Result, Token, Visibility,
+    parse::{ParseStream, Parser},
+};
+
+/// Name of the constant holding the help text of the handler `name`.
+pub(crate) fn doc_const_ident(name: &str) -> Ident {
+    format_ident!("__ushell_doc_{}", name)
+}
+
+/// The parts of a function item the handler attributes need; the body is not parsed.
+pub(crate) struct HandlerFn {
+    pub(crate) attrs: Vec<Attribute>, // Outer attributes, doc comments included
+    pub(crate) vis: Visibility,       // Visibility, given to the constant as well
+    pub(crate) ident: Ident,          // Function name
+    pub(crate) inputs: TokenStream2,  // Parameter list, without the parentheses
+}
+
+impl HandlerFn {
+    /// Parse `item` as a function, for the attribute `#[<attr>]` (named in errors).
+    pub(crate) fn parse_item(item: TokenStream2, attr: &str) -> Result<Self> {
+        let expects = |input: ParseStream| input.error(format!("`#[{}]` expects a function", attr));
+        let parser = |input: ParseStream| {
+            let attrs = input.call(Attribute::parse_outer)?;
+            let vis: Visibility = input.parse()?;
+            // Qualifiers (`const`, `unsafe`, `extern "C"`, ...) up to `fn`
+            while !input.peek(Token![fn]) {
+                if input.is_empty() {
+                    return Err(expects(input));
+                }
+                input.parse::<proc_macro2::TokenTree>()?;
+            }
+            input.parse::<Token![fn]>()?;
+            let ident: Ident = input.parse()?;
+            // Generic parameters, if any, up to the parameter list
+            let inputs = loop {
+                match input.parse::<proc_macro2::TokenTree>() {
+                    Ok(proc_macro2::TokenTree::Group(group))
+                        if group.delimiter() == proc_macro2::Delimiter::Parenthesis =>
+                    {
+                        break group.stream();
+                    }
+                    Ok(_) => {}
+                    Err(_) => return Err(expects(input)),
+                }
+            };
+            input.parse::<TokenStream2>()?;
+            Ok(HandlerFn {
+                attrs,
+                vis,
+                ident,
+                inputs,
+            })
+        };
+        parser.parse2(item)
+    }
+}
+
+/// Entry point of `#[command_doc]`.
+pub fn generate_command_doc(attr: TokenStream, item: TokenStream) -> TokenStream {
+    expand_command_doc(attr.into(), item.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Emit the function followed by the constant holding the first line of its doc comment.
+fn expand_command_doc(attr: TokenStream2, item: TokenStream2) -> Result<TokenStream2> {
+    if !attr.is_empty() {
+        return Err(syn::Error::new_spanned(
+            attr,
+            "`#[command_doc]` takes no arguments",
+        ));
+    }
+    let handler = HandlerFn::parse_item(item.clone(), "command_doc")?;
+    let summary = LitStr::new(&first_doc_line(&handler.attrs), handler.ident.span());
+    let vis = &handler.vis;
+    let ident = doc_const_ident(&handler.ident.to_string());
+    Ok(quote! {
+        #item
+
+        /// Help text of the command handled by the function above, see `#[command_doc]`.
+        #[doc(hidden)]
+        #[allow(non_upper_case_globals)]
+        #vis const #ident: &str = #summary;
+    })
+}
+
+/// First non-empty line of the `///` (or `#[doc = "..."]`) comment in `attrs`, trimmed; empty
+/// if there is no doc comment.
+fn first_doc_line(attrs: &[Attribute]) -> String {
+    attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            syn::Meta::NameValue(nv) => match &nv.value {
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(s),
+                    ..
+                }) => Some(s.value()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .flat_map(|doc| {
+            doc.lines()
+                .map(|line| line.trim().to_string())
+                .collect::<Vec<_>>()
+        })
+        .find(|line| !line.is_empty())
+        .unwrap_or_default()
+}
+
+// ==================== TESTS =======================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(attr: TokenStream2, item: TokenStream2) -> Result<String> {
+        expand_command_doc(attr, item).map(|out| out.to_string())
+    }
+
+    #[test]
+    fn test_first_doc_line_kept() {
+        let out = expand(
+            quote! {},
+            quote! {
+                ///
+                ///   Sets the PWM frequency.
+                /// Second line.
+                pub fn set_pwm(freq: u32) {}
+            },
+        )
+        .unwrap();
+        assert!(out.contains("pub fn set_pwm (freq : u32) { }"));
+        assert!(
+            out.contains("pub const __ushell_doc_set_pwm : & str = \"Sets the PWM frequency.\"")
+        );
+    }
+
+    #[test]
+    fn test_visibility_follows_function() {
+        let out = expand(quote! {}, quote! { pub(crate) fn reset() {} }).unwrap();
+        assert!(out.contains("pub (crate) const __ushell_doc_reset : & str = \"\""));
+    }
+
+    #[test]
+    fn test_qualifiers_and_non_functions() {
+        let out = expand(
+            quote! {},
+            quote! { /// Erases.
+            pub unsafe extern "C" fn erase() {} },
+        )
+        .unwrap();
+        assert!(out.contains("pub const __ushell_doc_erase : & str = \"Erases.\""));
+        let err = expand(quote! {}, quote! { pub struct Reset; }).unwrap_err();
+        assert!(err.to_string().contains("expects a function"));
+    }
+
+    #[test]
+    fn test_arguments_rejected() {
+        let err = expand(quote! { short }, quote! { fn reset() {} }).unwrap_err();
+        assert!(err.to_string().contains("takes no arguments"));
+    }
+}
@@ -1,11 +1,17 @@
 extern crate proc_macro;
 
 mod commandsgen;
+mod docgen;
+mod mergegen;
 mod shortcutsgen;
+mod siggen;
 
 use commandsgen::generate_commands_dispatcher_from_file;
+use docgen::generate_command_doc;
+use mergegen::generate_merged_dispatcher;
 use proc_macro::TokenStream;
 use shortcutsgen::generate_shortcuts_dispatcher_from_file;
+use siggen::{generate_command_sig, generate_registered_commands};
 
 #[proc_macro]
 pub fn generate_commands_dispatcher(input: TokenStream) -> TokenStream {
@@ -16,3 +22,23 @@ pub fn generate_commands_dispatcher(input: TokenStream) -> TokenStream {
 pub fn generate_shortcuts_dispatcher(input: TokenStream) -> TokenStream {
     generate_shortcuts_dispatcher_from_file(input)
 }
+
+#[proc_macro]
+pub fn merge_commands(input: TokenStream) -> TokenStream {
+    generate_merged_dispatcher(input)
+}
+
+#[proc_macro_attribute]
+pub fn command_doc(attr: TokenStream, item: TokenStream) -> TokenStream {
+    generate_command_doc(attr, item)
+}
+
+#[proc_macro]
+pub fn register_commands(input: TokenStream) -> TokenStream {
+    generate_registered_commands(input)
+}
+
+#[proc_macro_attribute]
+pub fn command_sig(attr: TokenStream, item: TokenStream) -> TokenStream {
+    generate_command_sig(attr, item)
+}
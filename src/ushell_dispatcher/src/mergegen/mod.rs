@@ -0,0 +1,545 @@
+//! # Command module merge macro
+//!
+//! Combines several modules generated by `generate_commands_dispatcher!` into a single
+//! dispatcher module, so large projects can keep one `.cfg` file per subsystem while still
+//! exposing one command table to the shell (dispatch, autocomplete, help).
+//!
+//! ## Macro Input Format
+//!
+//! ```rust
+//! merge_commands!(mod all; [commands_a, commands_b]);
+//! ```
+//!
+//! - `mod <name>`: Name of the generated module.
+//! - `std = true;` (optional, before the list): also generate `get_function_names()`, which
+//!   returns a `Vec`.
+//! - `defmt = true;` (optional, before the list): `DispatchError` derives `defmt::Format`; the
+//!   merged modules must be generated with `defmt = true` too.
+//! - `[<path>, ...]`: Paths of previously generated command modules, in lookup order.
+//!
+//! ## Generated API
+//! - `dispatch(line)` / `dispatch_with_buf(line, toks)` / `dispatch_with_cancel(line, cancel)`
+//!   routed to the owning module.
+//! - `get_commands()`, `get_datatypes()`, `FUNCTION_NAMES`, `has_command(name)`, `usage(name)`,
+//!   `help(name, out)`, `param_name(name, arg_index)`.
+//! - `set_command_enabled(name, enabled)` / `is_command_enabled(name)`.
+//! - `NUM_COMMANDS` (sum), `MAX_*`, `MAX_ARITY`, `MAX_FUNCTION_NAME_LEN` (maximum over modules).
+//! - `DispatchError` with `Empty`, `UnknownFunction` and one variant per merged module wrapping
+//!   that module's own `DispatchError`.
+//!
+//! Duplicate command names across modules are rejected at compile time.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    Ident, Result, Token, bracketed,
+    parse::{Parse, ParseStream},
+    parse_macro_input,
+    punctuated::Punctuated,
+};
+
+/// Per-type capacity constants re-exported as the maximum over all merged modules.
+const MAX_CONSTS: &[&str] = &[
+    "MAX_U8",
+    "MAX_U16",
+    "MAX_U32",
+    "MAX_U64",
+    "MAX_U128",
+    "MAX_I8",
+    "MAX_I16",
+    "MAX_I32",
+    "MAX_I64",
+    "MAX_I128",
+    "MAX_USIZE",
+    "MAX_ISIZE",
+    "MAX_F32",
+    "MAX_F64",
+    "MAX_BOOL",
+    "MAX_CHAR",
+    "MAX_HEXSTR",
+    "MAX_BYTELIST",
+    "MAX_TIMESTAMP",
+    "MAX_STR",
+    "MAX_HEXSTR_LEN",
+    "MAX_ARITY",
+    "MAX_FLAGS",
+    "MAX_OPTIONS",
+    "MAX_FUNCTION_NAME_LEN",
+];
+
+/// Parsed macro input: `mod <ident>; [std = <bool>;] [defmt = <bool>;] [<path>, ...]`
+struct MergeMacroInput {
+    mod_ident: Ident,        // Module identifier for the merged dispatcher
+    std: bool,               // Emit `get_function_names()`, which returns a `Vec`
+    defmt: bool,             // `DispatchError` derives `defmt::Format`
+    modules: Vec<syn::Path>, // Generated command modules to merge
+}
+
+impl Parse for MergeMacroInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        input.parse::<Token![mod]>()?;
+        let mod_ident: Ident = input.parse()?;
+        input.parse::<Token![;]>()?;
+
+        // Options, up to the module list
+        let mut std = false;
+        let mut defmt = false;
+        while input.peek(Ident) && input.peek2(Token![=]) {
+            let key: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            match key.to_string().as_str() {
+                "std" => std = input.parse::<syn::LitBool>()?.value,
+                "defmt" => defmt = input.parse::<syn::LitBool>()?.value,
+                _ => {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        "Expected `std = ...` or `defmt = ...`",
+                    ));
+                }
+            }
+            input.parse::<Token![;]>()?;
+        }
+
+        let content;
+        bracketed!(content in input);
+        let modules: Punctuated<syn::Path, Token![,]> =
+            content.parse_terminated(syn::Path::parse_mod_style, Token![,])?;
+        if input.peek(Token![;]) {
+            input.parse::<Token![;]>()?;
+        }
+
+        if modules.is_empty() {
+            return Err(syn::Error::new(
+                mod_ident.span(),
+                "merge_commands! requires at least one command module",
+            ));
+        }
+        Ok(MergeMacroInput {
+            mod_ident,
+            std,
+            defmt,
+            modules: modules.into_iter().collect(),
+        })
+    }
+}
+
+/// Entry point of `merge_commands!`.
+pub fn generate_merged_dispatcher(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as MergeMacroInput);
+    expand_merge(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Expand the merged module; every item refers to the public API of the merged modules.
+fn expand_merge(input: MergeMacroInput) -> Result<TokenStream2> {
+    let MergeMacroInput {
+        mod_ident,
+        std,
+        defmt,
+        modules,
+    } = input;
+
+    // Paths are used from inside the generated module, so resolve them from the parent.
+    let paths: Vec<TokenStream2> = modules
+        .iter()
+        .map(|p| {
+            if p.leading_colon.is_some()
+                || p.segments
+                    .first()
+                    .is_some_and(|s| s.ident == "crate" || s.ident == "super" || s.ident == "self")
+            {
+                quote! { #p }
+            } else {
+                quote! { super::#p }
+            }
+        })
+        .collect();
+
+    // One error variant per merged module, named after the module's last path segment.
+    let mut variants: Vec<Ident> = Vec::new();
+    for p in &modules {
+        let ident = p
+            .segments
+            .last()
+            .map(|s| s.ident.clone())
+            .ok_or_else(|| syn::Error::new_spanned(p, "Expected a module path"))?;
+        if variants.contains(&ident) {
+            return Err(syn::Error::new_spanned(p, "Module listed more than once"));
+        }
+        variants.push(ident);
+    }
+
+    let first = &paths[0];
+
+    let max_consts: Vec<TokenStream2> = MAX_CONSTS
+        .iter()
+        .map(|name| {
+            let c = format_ident!("{}", name);
+            let mut expr = quote! { 0usize };
+            for p in &paths {
+                expr = quote! { const_max(#expr, #p::#c) };
+            }
+            quote! { pub const #c: usize = #expr; }
+        })
+        .collect();
+
+    let copy_tables: Vec<TokenStream2> = paths
+        .iter()
+        .map(|p| {
+            quote! {
+                let mut i = 0usize;
+                while i < #p::NAME_AND_SPEC.len() {
+                    out[n] = #p::NAME_AND_SPEC[i];
+                    n += 1;
+                    i += 1;
+                }
+            }
+        })
+        .collect();
+
+    // The `Vec` variant of `FUNCTION_NAMES` needs `alloc`, so it is only generated on request.
+    let vec_fn = std.then(|| {
+        quote! {
+            /// Return function names of all merged modules, as a `Vec`.
+            pub fn get_function_names() -> Vec<&'static str> {
+                FUNCTION_NAMES.to_vec()
+            }
+        }
+    });
+
+    let defmt_derive = defmt.then(|| quote! { #[derive(defmt::Format)] });
+
+    Ok(quote! {
+        #[allow(dead_code)]
+        #[allow(non_snake_case, non_camel_case_types, unused_imports)]
+        pub mod #mod_ident {
+
+            //! Generated by `merge_commands!`. Routes each command to the module that defines it.
+            extern crate core;
+
+            #[inline(always)]
+            const fn const_max(a: usize, b: usize) -> usize {
+                if a > b { a } else { b }
+            }
+
+            /// Total number of commands over all merged modules.
+            pub const NUM_COMMANDS: usize = 0 #( + #paths::NUM_COMMANDS )*;
+
+            /// Capacities recomputed as the maximum over all merged modules.
+            #( #max_consts )*
+
+            /// Errors from the merged dispatcher; module errors are wrapped as-is.
+            #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+            #defmt_derive
+            pub enum DispatchError {
+
+                /// Input line contains no tokens.
+                Empty,
+
+                /// No merged module defines the given function.
+                UnknownFunction,
+
+                #(
+                    /// Error reported by the module that owns the command.
+                    #variants(#paths::DispatchError),
+                )*
+            }
+
+            /// Static pairs of (function name, parameter descriptor), in module order.
+            pub static NAME_AND_SPEC: [(&'static str, &'static str); NUM_COMMANDS] = {
+                let mut out = [("", ""); NUM_COMMANDS];
+                let mut n = 0usize;
+                #( #copy_tables )*
+                out
+            };
+
+            #[inline(always)]
+            const fn str_eq(a: &str, b: &str) -> bool {
+                let (a, b) = (a.as_bytes(), b.as_bytes());
+                if a.len() != b.len() {
+                    return false;
+                }
+                let mut i = 0usize;
+                while i < a.len() {
+                    if a[i] != b[i] {
+                        return false;
+                    }
+                    i += 1;
+                }
+                true
+            }
+
+            // Two modules registering the same name would make routing ambiguous.
+            const _: () = {
+                let mut i = 0usize;
+                while i < NUM_COMMANDS {
+                    let mut j = i + 1;
+                    while j < NUM_COMMANDS {
+                        assert!(
+                            !str_eq(NAME_AND_SPEC[i].0, NAME_AND_SPEC[j].0),
+                            "merge_commands!: the same command name is defined in more than one module"
+                        );
+                        j += 1;
+                    }
+                    i += 1;
+                }
+            };
+
+            /// Return (function name, descriptor) pairs. No allocations.
+            #[inline(always)]
+            pub fn get_commands() -> &'static [(&'static str, &'static str)] {
+                &NAME_AND_SPEC
+            }
+
+            /// Return descriptor help string (character to type mapping).
+            #[inline(always)]
+            pub fn get_datatypes() -> &'static str {
+                #first::get_datatypes()
+            }
+
+            /// Function names of all merged modules, in module order. No allocations.
+            pub static FUNCTION_NAMES: [&'static str; NUM_COMMANDS] = {
+                let mut out = [""; NUM_COMMANDS];
+                let mut i = 0usize;
+                while i < NUM_COMMANDS {
+                    out[i] = NAME_AND_SPEC[i].0;
+                    i += 1;
+                }
+                out
+            };
+
+            #vec_fn
+
+            /// Returns `true` if any merged module registers `name`.
+            #[inline(always)]
+            pub fn has_command(name: &str) -> bool {
+                #( #paths::has_command(name) )||*
+            }
+
+            /// Readable signature of `name` from the module that owns it; empty if unknown.
+            pub fn usage(name: &str) -> &'static str {
+                #(
+                    if #paths::has_command(name) {
+                        return #paths::usage(name);
+                    }
+                )*
+                ""
+            }
+
+            /// Help of `name` from the module that owns it; an empty `name` writes the usage of
+            /// every command of every module.
+            pub fn help(name: &str, out: &mut dyn core::fmt::Write) -> Result<(), DispatchError> {
+                if name.is_empty() {
+                    #( let _ = #paths::help(name, out); )*
+                    return Ok(());
+                }
+                #(
+                    if #paths::has_command(name) {
+                        return #paths::help(name, out).map_err(DispatchError::#variants);
+                    }
+                )*
+                Err(DispatchError::UnknownFunction)
+            }
+
+            /// Parameter name from the module that owns `name`; `None` if unknown or unnamed.
+            pub fn param_name(name: &str, arg_index: usize) -> Option<&'static str> {
+                #(
+                    if #paths::has_command(name) {
+                        return #paths::param_name(name, arg_index);
+                    }
+                )*
+                None
+            }
+
+            /// Enable or disable a command at runtime in the module that owns it.
+            pub fn set_command_enabled(name: &str, enabled: bool) -> Result<(), DispatchError> {
+                #(
+                    if #paths::has_command(name) {
+                        return #paths::set_command_enabled(name, enabled).map_err(DispatchError::#variants);
+                    }
+                )*
+                Err(DispatchError::UnknownFunction)
+            }
+
+            /// Returns `true` if `name` is registered and currently enabled.
+            pub fn is_command_enabled(name: &str) -> bool {
+                #( #paths::is_command_enabled(name) )||*
+            }
+
+            #[inline(always)]
+            pub fn dispatch(line: &str) -> Result<(), DispatchError> {
+                let mut toks: [&str; 2 + MAX_ARITY + MAX_FLAGS + 2 * MAX_OPTIONS] = [""; 2 + MAX_ARITY + MAX_FLAGS + 2 * MAX_OPTIONS];
+                dispatch_with_buf(line, &mut toks)
+            }
+
+            /// First token of `line`; the rest does not fit in the one-token buffer and is left
+            /// to the module dispatching it.
+            fn command_name(line: &str) -> Result<&str, DispatchError> {
+                let mut name: [&str; 1] = [""];
+                match #first::tokenize(line, &mut name) {
+                    Ok(_) | Err(#first::DispatchError::TooManyTokens) => Ok(name[0]),
+                    Err(_) => Err(DispatchError::Empty),
+                }
+            }
+
+            /// Embedded-friendly entry point: caller supplies the token buffer.
+            pub fn dispatch_with_buf<'a>(line: &'a str, toks: &mut [&'a str]) -> Result<(), DispatchError> {
+                let name = command_name(line)?;
+                #(
+                    if #paths::has_command(name) {
+                        return #paths::dispatch_with_buf(line, toks).map_err(DispatchError::#variants);
+                    }
+                )*
+                Err(DispatchError::UnknownFunction)
+            }
+
+            /// Like `dispatch`, with a cancellation token for the `[cancel]` handlers of the module
+            /// that owns the command.
+            pub fn dispatch_with_cancel(line: &str, cancel: &core::sync::atomic::AtomicBool) -> Result<(), DispatchError> {
+                let name = command_name(line)?;
+                #(
+                    if #paths::has_command(name) {
+                        return #paths::dispatch_with_cancel(line, cancel).map_err(DispatchError::#variants);
+                    }
+                )*
+                Err(DispatchError::UnknownFunction)
+            }
+        }
+    })
+}
+
+// ========================= TESTS ==========================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand_to_string(input: TokenStream2) -> String {
+        let parsed = syn::parse2::<MergeMacroInput>(input).expect("Failed to parse input");
+        expand_merge(parsed).expect("Expansion failed").to_string()
+    }
+
+    #[test]
+    fn test_parse_module_list() {
+        let parsed = syn::parse2::<MergeMacroInput>(quote! {
+            mod all; [commands_a, crate::sub::commands_b,]
+        })
+        .unwrap();
+        assert_eq!(parsed.mod_ident, "all");
+        assert_eq!(parsed.modules.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_empty_module_list_rejected() {
+        let err = syn::parse2::<MergeMacroInput>(quote! { mod all; [] })
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("at least one"));
+    }
+
+    #[test]
+    fn test_parse_missing_brackets_rejected() {
+        assert!(syn::parse2::<MergeMacroInput>(quote! { mod all; commands_a }).is_err());
+    }
+
+    #[test]
+    fn test_duplicate_module_rejected() {
+        let parsed =
+            syn::parse2::<MergeMacroInput>(quote! { mod all; [commands_a, commands_a] }).unwrap();
+        assert!(expand_merge(parsed).is_err());
+    }
+
+    #[test]
+    fn test_relative_paths_resolved_from_parent() {
+        let out = expand_to_string(quote! { mod all; [commands_a, crate::commands_b] });
+        assert!(out.contains("super :: commands_a :: NUM_COMMANDS"));
+        assert!(out.contains("crate :: commands_b :: NUM_COMMANDS"));
+        assert!(!out.contains("super :: crate"));
+    }
+
+    #[test]
+    fn test_num_commands_is_sum() {
+        let out = expand_to_string(quote! { mod all; [a, b] });
+        assert!(out.contains(
+            "pub const NUM_COMMANDS : usize = 0 + super :: a :: NUM_COMMANDS + super :: b :: NUM_COMMANDS"
+        ));
+    }
+
+    #[test]
+    fn test_function_names_without_alloc() {
+        let out = expand_to_string(quote! { mod all; [a, b] });
+        assert!(out.contains("pub static FUNCTION_NAMES : [& 'static str ; NUM_COMMANDS]"));
+        assert!(out.contains("out [i] = NAME_AND_SPEC [i] . 0"));
+        assert!(!out.contains("get_function_names"));
+    }
+
+    #[test]
+    fn test_std_function_names() {
+        let out = expand_to_string(quote! { mod all; std = true; [a, b] });
+        assert!(out.contains("pub fn get_function_names () -> Vec < & 'static str >"));
+        assert!(syn::parse2::<MergeMacroInput>(quote! { mod all; alloc = true; [a] }).is_err());
+    }
+
+    #[test]
+    fn test_defmt_option() {
+        assert!(!expand_to_string(quote! { mod all; [a] }).contains("defmt"));
+        let out = expand_to_string(quote! { mod all; std = true; defmt = true; [a] });
+        assert!(out.contains("# [derive (defmt :: Format)] pub enum DispatchError"));
+    }
+
+    #[test]
+    fn test_max_consts_recomputed() {
+        let out = expand_to_string(quote! { mod all; [a, b] });
+        for name in MAX_CONSTS {
+            assert!(
+                out.contains(&format!("pub const {} : usize", name)),
+                "{}",
+                name
+            );
+        }
+        assert!(out.contains(
+            "const_max (const_max (0usize , super :: a :: MAX_ARITY) , super :: b :: MAX_ARITY)"
+        ));
+    }
+
+    #[test]
+    fn test_error_variant_per_module() {
+        let out = expand_to_string(quote! { mod all; [a, crate::b] });
+        assert!(out.contains("a (super :: a :: DispatchError)"));
+        assert!(out.contains("b (crate :: b :: DispatchError)"));
+        assert!(out.contains("UnknownFunction"));
+    }
+
+    #[test]
+    fn test_usage_routes_to_owner() {
+        let out = expand_to_string(quote! { mod all; [a, b] });
+        assert!(out.contains("pub fn usage (name : & str) -> & 'static str"));
+        assert!(out.contains("return super :: b :: usage (name)"));
+        assert!(out.contains("return super :: a :: param_name (name , arg_index)"));
+    }
+
+    #[test]
+    fn test_help_routes_to_owner() {
+        let out = expand_to_string(quote! { mod all; [a, b] });
+        assert!(out.contains(
+            "let _ = super :: a :: help (name , out) ; let _ = super :: b :: help (name , out) ;"
+        ));
+        assert!(
+            out.contains("return super :: b :: help (name , out) . map_err (DispatchError :: b)")
+        );
+    }
+
+    #[test]
+    fn test_dispatch_routes_by_has_command() {
+        let out = expand_to_string(quote! { mod all; [a, b] });
+        assert!(out.contains("if super :: a :: has_command (name)"));
+        // The command name is read from a one-token buffer, so the rest of the line overflows it
+        assert!(out.contains(
+            "Ok (_) | Err (super :: a :: DispatchError :: TooManyTokens) => Ok (name [0]) ,"
+        ));
+        assert!(out.contains("super :: b :: dispatch_with_buf (line , toks)"));
+        assert!(out.contains("return super :: a :: dispatch_with_cancel (line , cancel)"));
+    }
+}
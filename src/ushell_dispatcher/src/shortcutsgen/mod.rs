@@ -22,14 +22,45 @@
 //! - `mod <module_name>`: Name of the generated module.
 //! - `shortcut_size`: Maximum size of the shortcut string buffer (used in error reporting).
 //! - `path`: Path to the file containing shortcut mappings.
+//! - `runtime_shortcuts` (optional, after `shortcut_size`): capacity of a table filled at runtime
+//!   with `register_shortcut(key, fn(&str))`; it is consulted after the static shortcuts.
+//!
+//! Handlers return `()` or `Result<(), E: Debug>`; errors are reported by `dispatch` as
+//! `<key>: <error>`.
+//!
+//! Static shortcuts can be rebound without recompiling with `remap_shortcut(old_key, new_key)`.
+//!
+//! Small projects can give the mappings inline instead of `path`, in the same format:
+//! `"+ : { l : crate::us::level },"`.
+//!
+//! ## Typed Shortcuts
+//! An entry `key: descriptor: path` declares the handler's parameters with the command
+//! descriptor syntax (`l : B:level s:mode : crate::us::level`). The text after the shortcut is
+//! then parsed like command arguments and a parse error is reported as `<key>: <DispatchError>`.
+//! Entries without a descriptor receive the raw parameter `&str`.
+//!
+//! ## Chords
+//! A quoted group header declares multi-token chords (`"g" : { s : crate::git::status },` is
+//! `g s`). They are resolved token by token through a compile-time prefix tree; the text after
+//! the last token is the parameter.
 //!
 //! ## Generated API
 //! - `dispatch(input: &str) -> Result<(), heapless::String<N>>`
 //! - `is_supported_shortcut(input: &str) -> bool`
 //! - `get_shortcuts() -> &'static str`
+//! - `get_shortcut_specs() -> &'static [(&'static str, &'static str)]` - `(shortcut, description)`
+//!   pairs; a description is a quoted string after the handler (`?: crate::help "show quick help"`)
+//!
+//! With `structured_errors = true;` every error is a `ShortcutError` value instead of a
+//! formatted `heapless::String<N>`. With `defmt = true;` the error types derive `defmt::Format`
+//! (the invoking crate depends on `defmt`).
 
+use crate::commandsgen::{
+    CommandMacroInput, DEFAULT_UNIT_BASE, DescGroup, expand_dispatcher, split_group,
+};
 use proc_macro::TokenStream;
-use quote::quote;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{ToTokens, quote};
 use syn::{
     Expr, Ident, LitStr, Token,
     parse::{Parse, ParseStream},
@@ -37,99 +68,597 @@ use syn::{
 };
 
 /// Struct to parse macro input in the format:
-/// `mod <name>; shortcut_size = <expr>; path = "<file_path>"`
+/// `mod <name>; shortcut_size = <expr>; path = "<file_path>"` or, with the mappings inline,
+/// `mod <name>; shortcut_size = <expr>; "<mappings>"`. The options `runtime_shortcuts = <expr>;`,
+/// `structured_errors = <bool>;` and `defmt = <bool>;` may follow `shortcut_size`. The final `;`
+/// is optional.
 struct ShortcutMacroInput {
-    _mod_token: Token![mod],     // Token for the `mod` keyword
-    mod_name: Ident,             // Identifier for the module name
-    _semi1: Token![;],           // Semicolon after module declaration
-    _shortcut_size_token: Ident, // Identifier for `shortcut_size` keyword
-    _eq_token: Token![=],        // Equals sign for shortcut_size assignment
-    shortcut_size: Expr,         // Expression representing the shortcut size
-    _semi2: Token![;],           // Semicolon after shortcut_size declaration
-    _path_token: Ident,          // Identifier for `path` keyword
-    _eq_token2: Token![=],       // Equals sign for path assignment
-    path: LitStr,                // Literal string representing the file path
+    mod_name: Ident,                 // Identifier for the module name
+    shortcut_size: Expr,             // Expression representing the shortcut size
+    runtime_shortcuts: Option<Expr>, // Capacity of the runtime-registered table, if enabled
+    structured_errors: bool,         // Return `ShortcutError` instead of `heapless::String`
+    defmt: bool,                     // The error types derive `defmt::Format`
+    source: ShortcutSource,          // Where the shortcut mappings come from
+}
+
+/// Origin of the shortcut mappings.
+enum ShortcutSource {
+    File(LitStr),   // `path = "<file_path>"`, relative to `CARGO_MANIFEST_DIR`
+    Inline(LitStr), // The mappings themselves, in the file format
 }
 
 impl Parse for ShortcutMacroInput {
     fn parse(input: ParseStream) -> syn::Result<Self> {
+        input.parse::<Token![mod]>()?;
+        let mod_name: Ident = input.parse()?;
+        input.parse::<Token![;]>()?;
+
+        let key: Ident = input.parse()?;
+        if key != "shortcut_size" {
+            return Err(syn::Error::new(
+                key.span(),
+                "Expected `shortcut_size = ...`",
+            ));
+        }
+        input.parse::<Token![=]>()?;
+        let shortcut_size: Expr = input.parse()?;
+        input.parse::<Token![;]>()?;
+
+        // Options, up to the mappings (`path = ...` or the inline string)
+        let mut runtime_shortcuts = None;
+        let mut structured_errors = false;
+        let mut defmt = false;
+        while input.peek(Ident) && input.peek2(Token![=]) {
+            let key = input.fork().parse::<Ident>()?;
+            if key == "runtime_shortcuts" {
+                input.parse::<Ident>()?;
+                input.parse::<Token![=]>()?;
+                runtime_shortcuts = Some(input.parse::<Expr>()?);
+            } else if key == "structured_errors" {
+                input.parse::<Ident>()?;
+                input.parse::<Token![=]>()?;
+                structured_errors = input.parse::<syn::LitBool>()?.value;
+            } else if key == "defmt" {
+                input.parse::<Ident>()?;
+                input.parse::<Token![=]>()?;
+                defmt = input.parse::<syn::LitBool>()?.value;
+            } else {
+                break;
+            }
+            input.parse::<Token![;]>()?;
+        }
+
+        let source = if input.peek(LitStr) {
+            ShortcutSource::Inline(input.parse()?)
+        } else {
+            let key: Ident = input.parse()?;
+            if key != "path" {
+                return Err(syn::Error::new(
+                    key.span(),
+                    "Expected `path = \"...\"`, `runtime_shortcuts = ...`, `structured_errors = ...`, `defmt = ...` or an inline shortcut string",
+                ));
+            }
+            input.parse::<Token![=]>()?;
+            ShortcutSource::File(input.parse()?)
+        };
+        if input.peek(Token![;]) {
+            input.parse::<Token![;]>()?;
+        }
+        if !input.is_empty() {
+            return Err(input.error("Unexpected input after the shortcut mappings"));
+        }
+
         Ok(ShortcutMacroInput {
-            _mod_token: input.parse()?,
-            mod_name: input.parse()?,
-            _semi1: input.parse()?,
-            _shortcut_size_token: input.parse()?,
-            _eq_token: input.parse()?,
-            shortcut_size: input.parse()?,
-            _semi2: input.parse()?,
-            _path_token: input.parse()?,
-            _eq_token2: input.parse()?,
-            path: input.parse()?,
+            mod_name,
+            shortcut_size,
+            runtime_shortcuts,
+            structured_errors,
+            defmt,
+            source,
         })
     }
 }
 
+/// One `prefix + key` shortcut (or chord) parsed from the mapping file.
+struct ShortcutEntry {
+    key: String,     // Full shortcut, prefix followed by key (`+l`), or chord tokens (`g s`)
+    chord: bool,     // Whether `key` is a space-separated chord rather than two characters
+    path: syn::Path, // Handler function
+    desc: Option<String>, // Parameter descriptor of a typed handler; `None` passes the raw `&str`
+    help: String,    // Description shown in the shortcut list (`""` if none)
+}
+
+/// Node of the chord prefix tree; the root's children are the first chord tokens.
+#[derive(Default)]
+struct ChordNode {
+    entry: Option<usize>, // Index of the chord ending here
+    children: std::collections::BTreeMap<String, ChordNode>, // Next tokens
+}
+
+/// Split the entries of a prefix group at commas outside `"..."` descriptions and outside
+/// brackets, so descriptor ranges and defaults (`d{0..=9}`, `X([1, 2])`) stay in one entry.
+fn split_entries(rest: &str) -> Vec<&str> {
+    let mut out = vec![];
+    let mut quoted = false;
+    let mut depth = 0usize;
+    let mut start = 0usize;
+    for (i, c) in rest.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '(' | '[' | '{' if !quoted => depth += 1,
+            ')' | ']' | '}' if !quoted => depth = depth.saturating_sub(1),
+            ',' if !quoted && depth == 0 => {
+                out.push(&rest[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    out.push(&rest[start..]);
+    out
+}
+
+/// Split a trailing `"description"` off an entry's handler part.
+fn split_help(func: &str) -> (&str, String) {
+    let func = func.trim();
+    if let Some(body) = func.strip_suffix('"')
+        && let Some(open) = body.find('"')
+    {
+        return (&body[..open], body[open + 1..].to_string());
+    }
+    (func, String::new())
+}
+
+/// Blank out `//` comments outside `"..."`, keeping the line breaks so offsets keep their line.
+fn strip_comments(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    for line in raw.split_inclusive('\n') {
+        let mut quoted = false;
+        let mut cut = None;
+        for (i, c) in line.char_indices() {
+            match c {
+                '"' => quoted = !quoted,
+                '/' if !quoted && line[i..].starts_with("//") => {
+                    cut = Some(i);
+                    break;
+                }
+                _ => {}
+            }
+        }
+        match cut {
+            Some(i) => {
+                out.push_str(&line[..i]);
+                if line.ends_with('\n') {
+                    out.push('\n');
+                }
+            }
+            None => out.push_str(line),
+        }
+    }
+    out
+}
+
+/// 1-based line of byte `offset` in `text`.
+fn line_of(text: &str, offset: usize) -> usize {
+    text[..offset].matches('\n').count() + 1
+}
+
+/// Byte offset of the `}` closing the group whose body starts at `text[0]`, skipping nested
+/// brackets and quoted descriptions.
+fn group_end(text: &str) -> Option<usize> {
+    let mut quoted = false;
+    let mut depth = 0usize;
+    for (i, c) in text.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '(' | '[' | '{' if !quoted => depth += 1,
+            ')' | ']' if !quoted => depth = depth.saturating_sub(1),
+            '}' if !quoted => {
+                if depth == 0 {
+                    return Some(i);
+                }
+                depth -= 1;
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Parse the mapping file into the set of prefixes and the shortcut entries, in file order.
+/// The file is a list of groups `prefix : { entry, ... }`, separated by optional commas; groups
+/// and entries may span lines, `//` starts a comment and trailing commas are allowed.
+/// An entry is `key: path` or, for typed handlers, `key: descriptor: path`, optionally followed
+/// by a quoted description (`?: crate::help "show quick help"`). A quoted group header
+/// (`"g" : { s : path },`) declares chords: the header and key are space-separated tokens.
+/// Syntax errors are reported as `line <n>: <problem>`.
+fn parse_shortcuts(
+    raw: &str,
+) -> Result<(std::collections::BTreeSet<String>, Vec<ShortcutEntry>), String> {
+    let text = strip_comments(raw);
+    let mut prefixes = std::collections::BTreeSet::new();
+    let mut entries = vec![];
+    let mut pos = 0usize;
+
+    loop {
+        // Skip blanks and the optional comma between groups.
+        let rest = text[pos..].trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        pos = text.len() - rest.len();
+        if pos == text.len() {
+            break;
+        }
+        let line = line_of(&text, pos);
+
+        // Group header: a prefix character or a quoted chord root, then `:`.
+        let from = &text[pos..];
+        let header_len = match from.strip_prefix('"') {
+            Some(quoted) => quoted.find('"').map(|i| i + 2),
+            None => from.find(':'),
+        }
+        .ok_or_else(|| format!("line {}: expected `<prefix> : {{ ... }}`", line))?;
+        let header = from[..header_len].trim();
+        let Some(after) = from[header_len..].trim_start().strip_prefix(':') else {
+            return Err(format!("line {}: expected `:` after `{}`", line, header));
+        };
+        let Some(body) = after.trim_start().strip_prefix('{') else {
+            return Err(format!("line {}: expected `{{` after `{} :`", line, header));
+        };
+        let body_start = text.len() - body.len();
+        let body_len = group_end(body)
+            .ok_or_else(|| format!("line {}: group `{}` is not closed with `}}`", line, header))?;
+        pos = body_start + body_len + 1;
+
+        let chord = header.len() >= 2 && header.starts_with('"') && header.ends_with('"');
+        let prefix = if chord {
+            header[1..header.len() - 1]
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+        } else {
+            prefixes.insert(header.to_string());
+            header.to_string()
+        };
+
+        for entry in split_entries(&body[..body_len]) {
+            let entry = entry.trim();
+            if entry.is_empty() {
+                continue;
+            }
+            let line = line_of(&text, entry.as_ptr() as usize - text.as_ptr() as usize);
+            // The key ends at the first `:` that is not part of a `::` path separator.
+            let colon = entry.char_indices().find(|&(i, c)| {
+                c == ':' && !entry[i + 1..].starts_with(':') && !entry[..i].ends_with(':')
+            });
+            let Some((key, func)) = colon.map(|(i, _)| (&entry[..i], &entry[i + 1..])) else {
+                return Err(format!(
+                    "line {}: expected `key : handler`, found `{}`",
+                    line, entry
+                ));
+            };
+            let key = key.trim();
+            let (func, help) = split_help(func);
+            let (desc, func) = match split_group(func) {
+                Some((desc, func)) => (Some(desc.trim().to_string()), func.trim()),
+                None => (None, func.trim()),
+            };
+            let path = syn::parse_str::<syn::Path>(func)
+                .map_err(|_| format!("line {}: invalid function path `{}`", line, func))?;
+            let key = if chord {
+                let steps = key.split_whitespace().collect::<Vec<_>>().join(" ");
+                format!("{} {}", prefix, steps)
+            } else {
+                format!("{}{}", prefix, key)
+            };
+            entries.push(ShortcutEntry {
+                key,
+                chord,
+                path,
+                desc,
+                help,
+            });
+        }
+    }
+    Ok((prefixes, entries))
+}
+
 pub fn generate_shortcuts_dispatcher_from_file(input: TokenStream) -> TokenStream {
     let ShortcutMacroInput {
         mod_name,
         shortcut_size,
-        path,
-        ..
+        runtime_shortcuts,
+        structured_errors,
+        defmt,
+        source,
     } = parse_macro_input!(input as ShortcutMacroInput);
 
-    // Resolve path relative to the crate invoking the macro
-    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
-    let full_path = std::path::Path::new(&manifest_dir).join(path.value());
-
-    let raw = std::fs::read_to_string(&full_path)
-        .unwrap_or_else(|_| panic!("Failed to read shortcut file: {:?}", full_path));
-
-    let mut match_arms = vec![];
-    let mut prefixes = std::collections::HashSet::new();
-    let mut shortcut_keys = vec![];
-    let mut buffer = String::new();
-
-    for line in raw.lines() {
-        let line = line.trim();
-        if line.is_empty() {
-            continue;
-        }
-        buffer.push_str(line);
-        if line.ends_with("},") {
-            if let Some((prefix, rest)) = buffer.split_once(':') {
-                let prefix = prefix.trim();
-                prefixes.insert(prefix.to_string());
-
-                for entry in rest.split(',') {
-                    let entry = entry.trim().trim_matches('{').trim_matches('}').trim();
-                    if entry.is_empty() {
-                        continue;
-                    }
-                    if let Some((key, func)) = entry.split_once(':') {
-                        let key = key.trim();
-                        let func = func.trim();
-                        if let Ok(path) = syn::parse_str::<syn::Path>(func) {
-                            let full_key = format!("{}{}", prefix, key);
-                            shortcut_keys.push(full_key.clone());
-                            match_arms.push(quote! {
-                                #full_key => {
-                                    #path(param);
-                                    Ok(())
-                                },
-                            });
-                        } else {
-                            panic!("Invalid function path: {}", func);
-                        }
-                    }
+    let (raw, span) = match source {
+        ShortcutSource::File(path) => {
+            // Resolve path relative to the crate invoking the macro
+            let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+            let full_path = std::path::Path::new(&manifest_dir).join(path.value());
+
+            let raw = std::fs::read_to_string(&full_path)
+                .unwrap_or_else(|_| panic!("Failed to read shortcut file: {:?}", full_path));
+            (raw, path.span())
+        }
+        ShortcutSource::Inline(body) => (body.value(), body.span()),
+    };
+
+    expand_shortcuts(
+        &mod_name,
+        &shortcut_size,
+        runtime_shortcuts.as_ref(),
+        structured_errors,
+        defmt,
+        &raw,
+        span,
+    )
+    .unwrap_or_else(|e| e.to_compile_error())
+    .into()
+}
+
+/// Block calling the handler of `e` with `param` (raw or parsed per its descriptor); argument
+/// and handler errors are reported against `key`.
+fn handler_body(e: &ShortcutEntry) -> TokenStream2 {
+    let path = &e.path;
+    if e.desc.is_none() {
+        return quote! {
+            HandlerResult::into_result(#path(param)).map_err(|e| handler_error(key, e))
+        };
+    }
+    let name = path
+        .segments
+        .last()
+        .map(|s| s.ident.to_string())
+        .unwrap_or_default();
+    quote! {
+        {
+            let mut toks: [&str; 2 + __args::MAX_ARITY + __args::MAX_FLAGS + 2 * __args::MAX_OPTIONS] =
+                [""; 2 + __args::MAX_ARITY + __args::MAX_FLAGS + 2 * __args::MAX_OPTIONS];
+            toks[0] = #name;
+            let len = if param.is_empty() {
+                1
+            } else {
+                1 + __args::tokenize(param, &mut toks[1..]).map_err(|e| arg_error(key, e))?
+            };
+            __args::dispatch_tokens(&mut toks, len).map_err(|e| arg_error(key, e))
+        }
+    }
+}
+
+/// Dispatch code for a chord node reached via `path`, with the text after it in `rest`. A node
+/// that ends a chord calls its shortcut with the remaining text unless the next token descends.
+fn chord_code(node: &ChordNode, path: &str, entries: &[ShortcutEntry]) -> TokenStream2 {
+    let on_end = match node.entry {
+        Some(i) => {
+            let key = &entries[i].key;
+            quote! { call_static(#key, rest.trim(), trimmed) }
+        }
+        None => quote! {
+            match next_token(rest).0 {
+                "" => fallback(trimmed, format_args!("{}", #path)),
+                tok => fallback(trimmed, format_args!("{} {}", #path, tok)),
+            }
+        },
+    };
+    if node.children.is_empty() {
+        return on_end;
+    }
+    let arms = node.children.iter().map(|(tok, child)| {
+        let code = chord_code(child, &format!("{} {}", path, tok), entries);
+        quote! { #tok => { let rest = after; #code } }
+    });
+    quote! {
+        {
+            let (tok, after) = next_token(rest);
+            match tok {
+                #( #arms )*
+                _ => #on_end,
+            }
+        }
+    }
+}
+
+/// Generate the shortcut module from the mapping file contents. Typed handlers are routed
+/// through a nested commands dispatcher (`__args`), so they share its parsing and errors.
+fn expand_shortcuts(
+    mod_name: &Ident,
+    shortcut_size: &Expr,
+    runtime_shortcuts: Option<&Expr>,
+    structured_errors: bool,
+    defmt: bool,
+    raw: &str,
+    span: Span,
+) -> syn::Result<TokenStream2> {
+    let (prefixes, entries) = parse_shortcuts(raw)
+        .map_err(|e| syn::Error::new(span, format!("Shortcut mappings, {}", e)))?;
+
+    // Errors are formatted messages, or `ShortcutError` values with `structured_errors`.
+    let err_ty = if structured_errors {
+        quote! { ShortcutError }
+    } else {
+        quote! { heapless::String<{ #shortcut_size }> }
+    };
+    let error = |variant: TokenStream2, message: TokenStream2| {
+        if structured_errors {
+            quote! { ShortcutError::#variant }
+        } else {
+            quote! {
+                {
+                    let mut msg = heapless::String::<{ #shortcut_size }>::new();
+                    use core::fmt::Write;
+                    let _ = write!(msg, #message);
+                    msg
                 }
             }
-            buffer.clear();
+        }
+    };
+
+    // Shortcuts are matched per character, so prefixes and keys may be any Unicode character.
+    let mut prefix_chars: Vec<char> = vec![];
+    for p in &prefixes {
+        let mut chars = p.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => prefix_chars.push(c),
+            _ => {
+                return Err(syn::Error::new(
+                    span,
+                    format!("Shortcut prefix `{}` must be a single character", p),
+                ));
+            }
         }
     }
+    if let Some(e) = entries
+        .iter()
+        .find(|e| !e.chord && e.key.chars().count() != 2)
+    {
+        return Err(syn::Error::new(
+            span,
+            format!(
+                "Shortcut `{}` must be a prefix character followed by a key character",
+                e.key
+            ),
+        ));
+    }
 
-    let supported_checks = prefixes.iter().map(|p| {
-        quote! { c == #p }
+    if let Some(e) = entries
+        .iter()
+        .find(|e| e.chord && e.key.split(' ').filter(|t| !t.is_empty()).count() < 2)
+    {
+        return Err(syn::Error::new(
+            span,
+            format!("Chord `{}` needs at least two tokens", e.key),
+        ));
+    }
+    for (i, e) in entries.iter().enumerate() {
+        if entries[..i].iter().any(|d| d.key == e.key) {
+            return Err(syn::Error::new(
+                span,
+                format!("Duplicate shortcut `{}`", e.key),
+            ));
+        }
+    }
+
+    // Typed handlers, one command per function; a function keeps a single descriptor.
+    let mut typed: Vec<(String, String, syn::Path)> = vec![];
+    for e in &entries {
+        let Some(desc) = &e.desc else { continue };
+        let func = e.path.to_token_stream().to_string().replace(' ', "");
+        match typed.iter().find(|(_, f, _)| *f == func) {
+            Some((d, _, _)) if d != desc => {
+                return Err(syn::Error::new(
+                    span,
+                    format!(
+                        "Shortcut `{}`: handler `{}` is already used with descriptor `{}`",
+                        e.key, func, d
+                    ),
+                ));
+            }
+            Some(_) => {}
+            None => typed.push((desc.clone(), func, e.path.clone())),
+        }
+    }
+
+    // Handler call per entry, run by `call` with `key` and `param` in scope.
+    let call_arms = entries.iter().map(|e| {
+        let (key, body) = (&e.key, handler_body(e));
+        quote! { #key => #body, }
+    });
+    let match_arms = entries.iter().filter(|e| !e.chord).map(|e| {
+        let full_key = &e.key;
+        quote! { #full_key => call_static(#full_key, param, trimmed), }
+    });
+
+    // Chords are resolved token by token through a prefix tree.
+    let mut chords = ChordNode::default();
+    for (i, e) in entries.iter().enumerate().filter(|(_, e)| e.chord) {
+        let mut node = &mut chords;
+        for tok in e.key.split(' ') {
+            node = node.children.entry(tok.to_string()).or_default();
+        }
+        node.entry = Some(i);
+    }
+    let chord_roots: Vec<&str> = chords.children.keys().map(String::as_str).collect();
+    let chord_dispatch = if chords.children.is_empty() {
+        quote! {}
+    } else {
+        let arms = chords.children.iter().map(|(tok, node)| {
+            let code = chord_code(node, tok, &entries);
+            quote! { #tok => return #code, }
+        });
+        quote! {
+            {
+                let (tok, rest) = next_token(trimmed);
+                match tok {
+                    #( #arms )*
+                    _ => {}
+                }
+            }
+        }
+    };
+
+    let has_typed = !typed.is_empty();
+    let args_mod = if typed.is_empty() {
+        quote! {}
+    } else {
+        let groups = typed
+            .into_iter()
+            .map(|(desc, _, path)| DescGroup {
+                desc,
+                funcs: vec![path],
+                ids: vec![None],
+                cfg: None,
+                cancel: false,
+                span,
+            })
+            .collect();
+        let args = expand_dispatcher(CommandMacroInput {
+            mod_ident: Ident::new("__args", span),
+            groups,
+            hexstr_size: Some(shortcut_size.clone()),
+            unit_base: DEFAULT_UNIT_BASE,
+            manifest: false,
+            ffi: false,
+            fn_prefix: String::new(),
+            doc_help: false,
+            service: None,
+            runtime_commands: None,
+            minimal: false,
+            std: false,
+            defmt,
+            handler_results: true,
+        })?;
+        let args_error = error(
+            quote! { Args { key, error: e } },
+            quote! { "{}: {:?}", key, e },
+        );
+        quote! {
+            /// Argument parsing for typed shortcuts.
+            #[doc(hidden)]
+            #args
+
+            /// Report an argument error of a typed shortcut as `<key>: <DispatchError>`.
+            fn arg_error(key: &'static str, e: __args::DispatchError) -> #err_ty {
+                if let __args::DispatchError::Failed(e) = e {
+                    return handler_error(key, e);
+                }
+                #args_error
+            }
+        }
+    };
+
+    let spec_items = entries.iter().map(|e| {
+        let (key, help) = (&e.key, &e.help);
+        quote! { (#key, #help) }
     });
+    let specs_fn = quote! {
+        /// `(shortcut, description)` pairs in file order; the description is empty if not given.
+        pub fn get_shortcut_specs() -> &'static [(&'static str, &'static str)] {
+            &[ #( #spec_items ),* ]
+        }
+    };
 
+    let shortcut_keys: Vec<&str> = entries.iter().map(|e| e.key.as_str()).collect();
     let shortcut_list = shortcut_keys.join(" | ");
     let list_fn = quote! {
         pub fn get_shortcuts() -> &'static str {
@@ -137,56 +666,364 @@ pub fn generate_shortcuts_dispatcher_from_file(input: TokenStream) -> TokenStrea
         }
     };
 
+    // Optional runtime table, append-only like the `register_command` table of the commands
+    // dispatcher: slots below `len` are published, writers are serialized by `writing`.
+    let (runtime_mod, runtime_fallback, runtime_supported) = match runtime_shortcuts {
+        None => (quote! {}, quote! {}, quote! {}),
+        Some(capacity) => {
+            let invalid_key = error(quote! { InvalidKey }, quote! { "Invalid shortcut key" });
+            let defined = error(
+                quote! { AlreadyDefined },
+                quote! { "Shortcut already defined: {}", key },
+            );
+            let table_full = error(quote! { TableFull }, quote! { "Shortcut table full" });
+            (
+                quote! {
+                    /// Capacity of the runtime shortcut table.
+                    pub const RUNTIME_SHORTCUTS: usize = #capacity;
+
+                    /// Append-only table of the shortcuts bound by `register_shortcut`. Slots
+                    /// below `len` are written once, before `len` is raised past them, and never
+                    /// change afterwards.
+                    struct RuntimeTable {
+                        slots: [core::cell::UnsafeCell<Option<(&'static str, fn(&str))>>; RUNTIME_SHORTCUTS],
+                        len: core::sync::atomic::AtomicUsize,
+                        writing: core::sync::atomic::AtomicBool,
+                    }
+
+                    // SAFETY: published slots are only read; writers are serialized by `writing`.
+                    unsafe impl Sync for RuntimeTable {}
+
+                    static RUNTIME: RuntimeTable = RuntimeTable {
+                        slots: [const { core::cell::UnsafeCell::new(None) }; RUNTIME_SHORTCUTS],
+                        len: core::sync::atomic::AtomicUsize::new(0),
+                        writing: core::sync::atomic::AtomicBool::new(false),
+                    };
+
+                    /// Registered `(key, handler)` pairs, in registration order.
+                    fn runtime_entries() -> impl Iterator<Item = (&'static str, fn(&str))> {
+                        let len = RUNTIME.len.load(core::sync::atomic::Ordering::Acquire);
+                        // SAFETY: slots below `len` are published and never written again.
+                        RUNTIME.slots[..len].iter().filter_map(|slot| unsafe { *slot.get() })
+                    }
+
+                    /// The runtime shortcut whose key starts `input`, with the parameter after it.
+                    fn find_runtime(input: &str) -> Option<(fn(&str), &str)> {
+                        runtime_entries().find_map(|(key, handler)| {
+                            input.strip_prefix(key).map(|param| (handler, param.trim()))
+                        })
+                    }
+
+                    /// Bind `key` to `handler` at runtime. Static shortcuts are matched first, so
+                    /// keys of the mapping file, repeated keys and a full table are rejected.
+                    ///
+                    /// Only generated for targets with atomic read-modify-write, used to serialize
+                    /// registrations: one interrupting another on the same core spins forever.
+                    #[cfg(target_has_atomic = "8")]
+                    pub fn register_shortcut(key: &'static str, handler: fn(&str)) -> Result<(), #err_ty> {
+                        use core::sync::atomic::Ordering;
+                        let key = key.trim();
+                        if key.is_empty() {
+                            return Err(#invalid_key);
+                        }
+                        while RUNTIME.writing.swap(true, Ordering::Acquire) {
+                            core::hint::spin_loop();
+                        }
+                        let len = RUNTIME.len.load(Ordering::Relaxed);
+                        let result = if STATIC_KEYS.contains(&key) || runtime_entries().any(|(k, _)| k == key) {
+                            Err(#defined)
+                        } else if len == RUNTIME_SHORTCUTS {
+                            Err(#table_full)
+                        } else {
+                            // SAFETY: no reader sees the slot before `len` is raised past it, and
+                            // `writing` keeps other writers out.
+                            unsafe { *RUNTIME.slots[len].get() = Some((key, handler)) };
+                            RUNTIME.len.store(len + 1, Ordering::Release);
+                            Ok(())
+                        };
+                        RUNTIME.writing.store(false, Ordering::Release);
+                        result
+                    }
+                },
+                quote! {
+                    if let Some((handler, param)) = find_runtime(input) {
+                        handler(param);
+                        return Ok(());
+                    }
+                },
+                quote! { || find_runtime(trimmed).is_some() },
+            )
+        }
+    };
+
+    // Remap table: one slot per static key holding its replacement key. Slots are rewritten in
+    // place, so readers take the spin lock of `remap_shortcut` as well.
+    let static_keys = entries.iter().map(|e| e.key.as_str());
+    let remap_unknown = error(
+        quote! { Unknown },
+        quote! { "Unknown shortcut: {}", old_key },
+    );
+    let remap_invalid = error(quote! { InvalidKey }, quote! { "Invalid shortcut key" });
+    let remap_defined = error(
+        quote! { AlreadyDefined },
+        quote! { "Shortcut already defined: {}", new_key },
+    );
+    let remap_mod = quote! {
+        /// Keys of the mapping file, in file order; these cannot be registered at runtime.
+        const STATIC_KEYS: &[&str] = &[ #( #static_keys ),* ];
+
+        /// Replacement key of each static key (`None` = not remapped), read and written with
+        /// `locked` held.
+        struct RemapTable {
+            keys: core::cell::UnsafeCell<[Option<&'static str>; STATIC_KEYS.len()]>,
+            locked: core::sync::atomic::AtomicBool,
+        }
+
+        // SAFETY: the keys are only accessed with `locked` held.
+        unsafe impl Sync for RemapTable {}
+
+        static REMAPS: RemapTable = RemapTable {
+            keys: core::cell::UnsafeCell::new([None; STATIC_KEYS.len()]),
+            locked: core::sync::atomic::AtomicBool::new(false),
+        };
+
+        /// The key currently bound to `STATIC_KEYS[index]`, if it was remapped.
+        fn remapped_key(index: usize) -> Option<&'static str> {
+            #[cfg(target_has_atomic = "8")]
+            while REMAPS.locked.swap(true, core::sync::atomic::Ordering::Acquire) {
+                core::hint::spin_loop();
+            }
+            // SAFETY: the lock keeps `remap_shortcut` out; without atomic read-modify-write
+            // there is no `remap_shortcut`, so the keys never change.
+            let key = unsafe { (*REMAPS.keys.get())[index] };
+            #[cfg(target_has_atomic = "8")]
+            REMAPS.locked.store(false, core::sync::atomic::Ordering::Release);
+            key
+        }
+
+        /// The static key whose replacement starts `input`, with the text after it.
+        fn translate(input: &str) -> Option<(&'static str, &str)> {
+            (0..STATIC_KEYS.len()).find_map(|i| {
+                remapped_key(i).and_then(|new| input.strip_prefix(new)).map(|rest| (STATIC_KEYS[i], rest))
+            })
+        }
+
+        /// Whether the static `key` was remapped, so it no longer answers to its own name.
+        fn is_remapped(key: &str) -> bool {
+            STATIC_KEYS.iter().position(|k| *k == key).is_some_and(|i| remapped_key(i).is_some())
+        }
+
+        /// Whether every static key under the root of `input` (prefix character or first chord
+        /// token `tok`) has been remapped away.
+        fn root_remapped(input: &str, tok: &str) -> bool {
+            STATIC_KEYS
+                .iter()
+                .enumerate()
+                .filter(|(_, key)| match key.split_once(' ') {
+                    Some((root, _)) => root == tok,
+                    None => key.chars().next().is_some_and(|c| input.starts_with(c)),
+                })
+                .all(|(i, _)| remapped_key(i).is_some())
+        }
+
+        /// Bind the static shortcut `old_key` to `new_key` instead, e.g. to free a prefix that
+        /// collides with command arguments. Remapping a key to itself restores it.
+        ///
+        /// Only generated for targets with atomic read-modify-write, used for the lock of the
+        /// remap table: remapping interrupted by another remapping or a dispatch on the same
+        /// core spins forever.
+        #[cfg(target_has_atomic = "8")]
+        pub fn remap_shortcut(old_key: &str, new_key: &'static str) -> Result<(), #err_ty> {
+            use core::sync::atomic::Ordering;
+            let old_key = old_key.trim();
+            let new_key = new_key.trim();
+            let Some(index) = STATIC_KEYS.iter().position(|k| *k == old_key) else {
+                return Err(#remap_unknown);
+            };
+            if new_key.is_empty() {
+                return Err(#remap_invalid);
+            }
+            while REMAPS.locked.swap(true, Ordering::Acquire) {
+                core::hint::spin_loop();
+            }
+            // SAFETY: the lock keeps readers and other writers out.
+            let keys = unsafe { &mut *REMAPS.keys.get() };
+            // Taken by another shortcut, under its own name or as a replacement.
+            let taken = STATIC_KEYS
+                .iter()
+                .zip(keys.iter())
+                .enumerate()
+                .any(|(i, (k, new))| i != index && (*new == Some(new_key) || (*k == new_key && new.is_none())));
+            let result = if taken {
+                Err(#remap_defined)
+            } else {
+                keys[index] = (new_key != old_key).then_some(new_key);
+                Ok(())
+            };
+            REMAPS.locked.store(false, Ordering::Release);
+            result
+        }
+    };
+
+    // The unknown key is only formatted into messages.
+    let key_arg = if structured_errors {
+        quote! { _key }
+    } else {
+        quote! { key }
+    };
+    let unknown = error(quote! { Unknown }, quote! { "Unknown shortcut: {}", key });
     let support_fn = quote! {
         pub fn is_supported_shortcut(input: &str) -> bool {
+            const PREFIXES: &[char] = &[ #( #prefix_chars ),* ];
+            const CHORDS: &[&str] = &[ #( #chord_roots ),* ];
             let trimmed = input.trim();
-            if trimmed.is_empty() {
-                return false;
+            let tok = next_token(trimmed).0;
+            translate(trimmed).is_some()
+                || ((trimmed.chars().next().is_some_and(|c| PREFIXES.contains(&c))
+                    || CHORDS.contains(&tok))
+                    && !root_remapped(trimmed, tok))
+                #runtime_supported
+        }
+
+        /// Split off the first whitespace-separated token; returns `(token, rest)`.
+        fn next_token(s: &str) -> (&str, &str) {
+            let s = s.trim_start();
+            let end = s.find(char::is_whitespace).unwrap_or(s.len());
+            (&s[..end], &s[end..])
+        }
+
+        /// Run the shortcut `key` of the mapping file with `param`.
+        fn call(key: &'static str, param: &str, input: &str) -> Result<(), #err_ty> {
+            match key {
+                #( #call_arms )*
+                _ => fallback(input, format_args!("{}", key)),
             }
-            let c = &trimmed[0..1];
-            #( #supported_checks )||*
+        }
+
+        /// Run the static shortcut `key` reached under its own name, unless it was remapped.
+        fn call_static(key: &'static str, param: &str, input: &str) -> Result<(), #err_ty> {
+            if is_remapped(key) {
+                return fallback(input, format_args!("{}", key));
+            }
+            call(key, param, input)
+        }
+
+        /// Input that no static shortcut matches: try the runtime table, else `Unknown shortcut`.
+        fn fallback(input: &str, #key_arg: core::fmt::Arguments) -> Result<(), #err_ty> {
+            #runtime_fallback
+            Err(#unknown)
+        }
+    };
+
+    // What a failing handler leaves: its `Debug` text, or nothing for `ShortcutError::Failed`.
+    let (handler_error_ty, describe_error, handler_error_arg) = if structured_errors {
+        (quote! { () }, quote! { |_| () }, quote! { _e })
+    } else {
+        let describe = error(quote! {}, quote! { "{:?}", e });
+        (
+            quote! { heapless::String<{ #shortcut_size }> },
+            quote! { |e| #describe },
+            quote! { e },
+        )
+    };
+    let failed = error(quote! { Failed { key } }, quote! { "{}: {}", key, e });
+    let error_enum = structured_errors.then(|| {
+        let defmt_derive = defmt.then(|| quote! { #[derive(defmt::Format)] });
+        let args_variant = has_typed.then(|| {
+            quote! {
+                /// The arguments of the typed shortcut `key` did not parse.
+                Args { key: &'static str, error: __args::DispatchError },
+            }
+        });
+        quote! {
+            /// Errors of the generated shortcut functions.
+            #[derive(Debug, Clone, PartialEq, Eq)]
+            #defmt_derive
+            pub enum ShortcutError {
+                /// No shortcut matches the input (or the given key).
+                Unknown,
+                /// The new key is empty.
+                InvalidKey,
+                /// The new key is already bound.
+                AlreadyDefined,
+                /// The runtime shortcut table is full.
+                TableFull,
+                /// The handler of `key` returned an error.
+                Failed { key: &'static str },
+                #args_variant
+            }
+        }
+    });
+    let result_fn = quote! {
+        #error_enum
+
+        /// What a failed handler reports.
+        type HandlerError = #handler_error_ty;
+
+        /// Return types accepted from shortcut handlers: `()`, or `Result<(), E>` whose error is
+        /// reported with `Debug`.
+        pub trait HandlerResult {
+            fn into_result(self) -> Result<(), HandlerError>;
+        }
+
+        impl HandlerResult for () {
+            fn into_result(self) -> Result<(), HandlerError> {
+                Ok(())
+            }
+        }
+
+        impl<E: core::fmt::Debug> HandlerResult for Result<(), E> {
+            fn into_result(self) -> Result<(), HandlerError> {
+                self.map_err(#describe_error)
+            }
+        }
+
+        /// Report a failed handler as `<key>: <error>`.
+        fn handler_error(key: &'static str, #handler_error_arg: HandlerError) -> #err_ty {
+            #failed
         }
     };
 
     let dispatch_fn = quote! {
-        pub fn dispatch(input: &str) -> Result<(), heapless::String<{ #shortcut_size }>> {
+        pub fn dispatch(input: &str) -> Result<(), #err_ty> {
             let trimmed = input.trim();
-            let (key, param) = if trimmed.len() >= 2 {
-                let key = &trimmed[..2];
-                let param = trimmed[2..].trim();
-                (key, param)
-            } else {
-                (trimmed, "")
-            };
+            if let Some((key, rest)) = translate(trimmed) {
+                return call(key, rest.trim(), trimmed);
+            }
+            #chord_dispatch
+            // The key is the first two characters (prefix and key), whatever their byte length.
+            let split = trimmed.char_indices().nth(2).map_or(trimmed.len(), |(i, _)| i);
+            let (key, param) = (&trimmed[..split], trimmed[split..].trim());
             match key {
                 #( #match_arms )*
-                _ => {
-                    let mut msg = heapless::String::<{#shortcut_size}>::new();
-                    use core::fmt::Write;
-                    let _ = write!(msg, "Unknown shortcut: {}", key);
-                    Err(msg)
-                },
+                _ => fallback(trimmed, format_args!("{}", key)),
             }
         }
     };
 
-    let expanded = quote! {
-        #[cfg_attr(not(test), no_std)]
-        use core::fmt::Write;
+    Ok(quote! {
         pub mod #mod_name {
+            #args_mod
+            #remap_mod
+            #runtime_mod
+            #result_fn
             #dispatch_fn
             #support_fn
             #list_fn
+            #specs_fn
         }
-    };
-
-    TokenStream::from(expanded)
+    })
 }
 
 // ================= TESTS ==========================
 
 #[cfg(test)]
 mod tests {
+    use super::{
+        ShortcutMacroInput, ShortcutSource, expand_shortcuts, parse_shortcuts, split_entries,
+        split_help,
+    };
+    use proc_macro2::Span;
     use std::collections::HashMap;
     use std::sync::Mutex;
 
@@ -297,13 +1134,11 @@ mod tests {
 
         pub fn dispatch(input: &str) -> Result<(), heapless::String<64>> {
             let trimmed = input.trim();
-            let (key, param) = if trimmed.len() >= 2 {
-                let key = &trimmed[..2];
-                let param = trimmed[2..].trim();
-                (key, param)
-            } else {
-                (trimmed, "")
-            };
+            let split = trimmed
+                .char_indices()
+                .nth(2)
+                .map_or(trimmed.len(), |(i, _)| i);
+            let (key, param) = (&trimmed[..split], trimmed[split..].trim());
 
             match key {
                 "!+" => {
@@ -376,12 +1211,12 @@ mod tests {
         }
 
         pub fn is_supported_shortcut(input: &str) -> bool {
-            let trimmed = input.trim();
-            if trimmed.is_empty() {
-                return false;
-            }
-            let c = &trimmed[0..1];
-            c == "!" || c == "+" || c == "-" || c == "#" || c == "?"
+            const PREFIXES: &[char] = &['!', '+', '-', '#', '?'];
+            input
+                .trim()
+                .chars()
+                .next()
+                .is_some_and(|c| PREFIXES.contains(&c))
         }
 
         pub fn get_shortcuts() -> &'static str {
@@ -732,4 +1567,585 @@ mod tests {
         // Test 1 character (invalid)
         assert!(shortcuts::dispatch("!").is_err());
     }
+
+    // ============================================================================
+    // Typed Shortcut Tests
+    // ============================================================================
+
+    const TYPED_CFG: &str =
+        "+ : { + : crate::us::raw,\n      l : B:level s:mode : crate::us::level,\n    },\n";
+
+    fn expand(raw: &str) -> Result<String, String> {
+        let mod_name = syn::Ident::new("sc", Span::call_site());
+        let size: syn::Expr = syn::parse_str("64").unwrap();
+        expand_shortcuts(&mod_name, &size, None, false, false, raw, Span::call_site())
+            .map(|t| t.to_string())
+            .map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn test_parse_untyped_entry() {
+        let (prefixes, entries) = parse_shortcuts("! : { + : crate::a::b },\n").unwrap();
+        assert!(prefixes.contains("!"));
+        assert_eq!(prefixes.len(), 1);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].key, "!+");
+        assert!(entries[0].desc.is_none());
+    }
+
+    #[test]
+    fn test_parse_typed_entry() {
+        let (_, entries) = parse_shortcuts(TYPED_CFG).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].desc.is_none());
+        assert_eq!(entries[1].key, "+l");
+        assert_eq!(entries[1].desc.as_deref(), Some("B:level s:mode"));
+        assert_eq!(
+            quote::ToTokens::to_token_stream(&entries[1].path).to_string(),
+            "crate :: us :: level"
+        );
+    }
+
+    #[test]
+    fn test_untyped_only_has_no_args_module() {
+        let output = expand("! : { + : crate::a::b },\n").unwrap();
+        assert!(!output.contains("__args"));
+        assert!(output.contains("crate :: a :: b (param)"));
+    }
+
+    #[test]
+    fn test_typed_shortcut_generated() {
+        let output = expand(TYPED_CFG).unwrap();
+        assert!(output.contains("pub mod __args"));
+        assert!(output.contains("toks [0] = \"level\""));
+        assert!(output.contains("__args :: dispatch_tokens (& mut toks , len)"));
+        assert!(output.contains("< (u8 , & str ,) > for ()"));
+        assert!(output.contains("crate :: us :: raw (param)"));
+    }
+
+    #[test]
+    fn test_typed_shortcut_error_prefix() {
+        let output = expand(TYPED_CFG).unwrap();
+        assert!(
+            output.contains("fn arg_error (key : & 'static str , e : __args :: DispatchError)")
+        );
+        assert!(output.contains("\"{}: {:?}\""));
+    }
+
+    #[test]
+    fn test_handler_results_propagated() {
+        let output = expand(TYPED_CFG).unwrap();
+        assert!(output.contains("pub trait HandlerResult"));
+        assert!(
+            output
+                .contains("impl < E : core :: fmt :: Debug > HandlerResult for Result < () , E >")
+        );
+        assert!(output.contains(
+            "HandlerResult :: into_result (crate :: us :: raw (param)) . map_err (| e | handler_error (key , e))"
+        ));
+        // Typed handlers report failures through the argument dispatcher.
+        assert!(output.contains("super :: HandlerResult :: into_result"));
+        assert!(output.contains("Failed (super :: HandlerError)"));
+        assert!(output.contains("if let __args :: DispatchError :: Failed (e) = e"));
+    }
+
+    #[test]
+    fn test_typed_shortcut_shared_handler() {
+        let output =
+            expand("+ : { l : B : crate::us::level },\n- : { l : B : crate::us::level },\n")
+                .unwrap();
+        assert_eq!(output.matches("toks [0] = \"level\"").count(), 2);
+        assert!(output.contains("FUNCTION_NAMES : [& 'static str ; NUM_COMMANDS] = [\"level\"]"));
+    }
+
+    #[test]
+    fn test_typed_shortcut_conflicting_descriptors() {
+        let err = expand("+ : { l : B : crate::us::level },\n- : { l : D : crate::us::level },\n")
+            .unwrap_err();
+        assert!(err.contains("already used with descriptor `B`"));
+    }
+
+    #[test]
+    fn test_typed_shortcut_invalid_descriptor() {
+        let err = expand("+ : { l : Y : crate::us::level },\n").unwrap_err();
+        assert!(err.contains("Y"));
+    }
+
+    // ============================================================================
+    // Shortcut Description Tests
+    // ============================================================================
+
+    #[test]
+    fn test_split_help() {
+        assert_eq!(
+            split_help(" crate::help \"show help\" "),
+            ("crate::help ", "show help".to_string())
+        );
+        assert_eq!(split_help("crate::help"), ("crate::help", String::new()));
+    }
+
+    #[test]
+    fn test_split_entries_respects_quotes() {
+        let parts = split_entries(" + : a::b \"x, y\", - : a::c ");
+        assert_eq!(parts, vec![" + : a::b \"x, y\"", " - : a::c "]);
+    }
+
+    #[test]
+    fn test_parse_description() {
+        let (_, entries) = parse_shortcuts(
+            "? : { ? : crate::help \"show: quick, help\",\n      l : B : crate::lv \"level\",\n      x : crate::x },\n",
+        )
+        .unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].key, "??");
+        assert_eq!(entries[0].help, "show: quick, help");
+        assert_eq!(entries[1].desc.as_deref(), Some("B"));
+        assert_eq!(entries[1].help, "level");
+        assert_eq!(entries[2].help, "");
+    }
+
+    #[test]
+    fn test_shortcut_specs_generated() {
+        let output = expand("? : { ? : crate::help \"show help\", x : crate::x },\n").unwrap();
+        assert!(output.contains(
+            "pub fn get_shortcut_specs () -> & 'static [(& 'static str , & 'static str)]"
+        ));
+        assert!(output.contains("(\"??\" , \"show help\") , (\"?x\" , \"\")"));
+        assert!(output.contains("\"?? | ?x\""));
+    }
+
+    // ============================================================================
+    // Macro Input Tests
+    // ============================================================================
+
+    fn parse_input(tokens: proc_macro2::TokenStream) -> syn::Result<ShortcutMacroInput> {
+        syn::parse2::<ShortcutMacroInput>(tokens)
+    }
+
+    #[test]
+    fn test_input_file_form() {
+        let input = parse_input(quote::quote! {
+            mod sc;
+            shortcut_size = 64;
+            path = "shortcuts.cfg"
+        })
+        .unwrap();
+        assert_eq!(input.mod_name, "sc");
+        assert!(matches!(input.source, ShortcutSource::File(p) if p.value() == "shortcuts.cfg"));
+    }
+
+    #[test]
+    fn test_input_inline_form() {
+        let input = parse_input(quote::quote! {
+            mod sc;
+            shortcut_size = crate::INPUT_MAX_LEN;
+            "+ : { l : crate::us::level },";
+        })
+        .unwrap();
+        let ShortcutSource::Inline(body) = input.source else {
+            panic!("expected inline mappings");
+        };
+        let (_, entries) = parse_shortcuts(&body.value()).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].key, "+l");
+    }
+
+    #[test]
+    fn test_input_trailing_semicolon_optional() {
+        assert!(parse_input(quote::quote! { mod sc; shortcut_size = 8; path = "a.cfg"; }).is_ok());
+        assert!(
+            parse_input(quote::quote! { mod sc; shortcut_size = 8; "! : { x : a::b }," }).is_ok()
+        );
+    }
+
+    #[test]
+    fn test_input_errors() {
+        let err = parse_input(quote::quote! { mod sc; size = 8; path = "a.cfg" })
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("shortcut_size"));
+
+        let err = parse_input(quote::quote! { mod sc; shortcut_size = 8; file = "a.cfg" })
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("inline shortcut string"));
+
+        let err = parse_input(quote::quote! { mod sc; shortcut_size = 8; "a"; "b" })
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("Unexpected input"));
+    }
+
+    #[test]
+    fn test_generated_module_has_no_outer_imports() {
+        let output = expand("! : { x : crate::a::b },\n").unwrap();
+        assert!(output.starts_with("pub mod sc"));
+    }
+
+    // ============================================================================
+    // Unicode Prefix Tests
+    // ============================================================================
+
+    #[test]
+    fn test_multibyte_input_does_not_panic() {
+        assert!(!shortcuts::is_supported_shortcut("ü"));
+        assert!(!shortcuts::is_supported_shortcut("  你好"));
+        assert!(shortcuts::dispatch("ü").is_err());
+        assert!(shortcuts::dispatch("你好 x").unwrap_err().contains("你好"));
+        assert!(shortcuts::dispatch("!é").unwrap_err().contains("!é"));
+    }
+
+    #[test]
+    fn test_multibyte_param_after_key() {
+        clear_log();
+        shortcuts::dispatch("!+ü").unwrap();
+        assert_eq!(get_calls("bang_plus"), vec!["ü"]);
+    }
+
+    #[test]
+    fn test_non_ascii_prefix_generated() {
+        let output = expand("§ : { é : crate::a::accent, + : crate::a::plus },\n").unwrap();
+        assert!(output.contains("const PREFIXES : & [char] = & ['§'] ;"));
+        assert!(output.contains("\"§é\" =>"));
+        assert!(output.contains("\"§+\" =>"));
+        assert!(output.contains("char_indices () . nth (2)"));
+    }
+
+    #[test]
+    fn test_prefixes_sorted_and_deduplicated() {
+        let output = expand(
+            "- : { a : crate::a::a },\n+ : { b : crate::a::b },\n- : { c : crate::a::c },\n",
+        )
+        .unwrap();
+        assert!(output.contains("const PREFIXES : & [char] = & ['+' , '-'] ;"));
+    }
+
+    #[test]
+    fn test_multi_character_prefix_rejected() {
+        let err = expand("ab : { c : crate::a::c },\n").unwrap_err();
+        assert!(err.contains("Shortcut prefix `ab` must be a single character"));
+    }
+
+    #[test]
+    fn test_multi_character_key_rejected() {
+        let err = expand("+ : { cd : crate::a::c },\n").unwrap_err();
+        assert!(
+            err.contains("Shortcut `+cd` must be a prefix character followed by a key character")
+        );
+    }
+
+    // ============================================================================
+    // Chord Tests
+    // ============================================================================
+
+    const CHORD_CFG: &str = "\"g\" : { s : crate::git::status \"status\",\n        c : B : crate::git::commit,\n        r s : crate::git::remote_show },\n";
+
+    #[test]
+    fn test_parse_chords() {
+        let (prefixes, entries) = parse_shortcuts(CHORD_CFG).unwrap();
+        assert!(prefixes.is_empty());
+        let keys: Vec<&str> = entries.iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(keys, vec!["g s", "g c", "g r s"]);
+        assert!(entries.iter().all(|e| e.chord));
+        assert_eq!(entries[0].help, "status");
+        assert_eq!(entries[1].desc.as_deref(), Some("B"));
+    }
+
+    #[test]
+    fn test_parse_chord_word_tokens() {
+        let (_, entries) = parse_shortcuts("\"net  if\" : { up : crate::n::up },\n").unwrap();
+        assert_eq!(entries[0].key, "net if up");
+    }
+
+    #[test]
+    fn test_chord_tree_generated() {
+        let output = expand(CHORD_CFG).unwrap();
+        assert!(output.contains("const CHORDS : & [& str] = & [\"g\"] ;"));
+        assert!(output.contains("\"g\" => return"));
+        assert!(output.contains("\"s\" =>"));
+        assert!(output.contains("\"r\" =>"));
+        assert!(output.contains("crate :: git :: status (param)"));
+        assert!(output.contains("call_static (\"g c\" , rest . trim () , trimmed)"));
+        assert!(output.contains("\"g s | g c | g r s\""));
+    }
+
+    #[test]
+    fn test_chords_mixed_with_prefixes() {
+        let output = expand(&format!("+ : {{ l : crate::a::l }},\n{}", CHORD_CFG)).unwrap();
+        assert!(output.contains("const PREFIXES : & [char] = & ['+'] ;"));
+        assert!(output.contains("\"+l\" =>"));
+        assert!(output.contains("\"+l | g s | g c | g r s\""));
+    }
+
+    #[test]
+    fn test_no_chords_no_chord_dispatch() {
+        let output = expand("+ : { l : crate::a::l },\n").unwrap();
+        assert!(output.contains("const CHORDS : & [& str] = & [] ;"));
+        assert!(!output.contains("=> return"));
+    }
+
+    #[test]
+    fn test_single_token_chord_rejected() {
+        let err = expand("\"g\" : { : crate::a::l },\n").unwrap_err();
+        assert!(err.contains("needs at least two tokens"));
+    }
+
+    #[test]
+    fn test_duplicate_shortcut_rejected() {
+        let err = expand("\"g\" : { s : crate::a::a, s : crate::a::b },\n").unwrap_err();
+        assert!(err.contains("Duplicate shortcut `g s`"));
+        let err = expand("+ : { l : crate::a::a },\n+ : { l : crate::a::b },\n").unwrap_err();
+        assert!(err.contains("Duplicate shortcut `+l`"));
+    }
+
+    // ============================================================================
+    // Runtime Shortcut Tests
+    // ============================================================================
+
+    fn expand_runtime(raw: &str) -> String {
+        let mod_name = syn::Ident::new("sc", Span::call_site());
+        let size: syn::Expr = syn::parse_str("64").unwrap();
+        let capacity: syn::Expr = syn::parse_str("4").unwrap();
+        expand_shortcuts(
+            &mod_name,
+            &size,
+            Some(&capacity),
+            false,
+            false,
+            raw,
+            Span::call_site(),
+        )
+        .unwrap()
+        .to_string()
+    }
+
+    #[test]
+    fn test_runtime_option_parsed() {
+        let input = parse_input(
+            quote::quote! { mod sc; shortcut_size = 8; runtime_shortcuts = 4; path = "a.cfg" },
+        )
+        .unwrap();
+        assert!(input.runtime_shortcuts.is_some());
+
+        let input = parse_input(quote::quote! { mod sc; shortcut_size = 8; "a" }).unwrap();
+        assert!(input.runtime_shortcuts.is_none());
+    }
+
+    #[test]
+    fn test_runtime_table_generated() {
+        let output = expand_runtime("+ : { l : crate::a::l },\n");
+        assert!(output.contains("pub const RUNTIME_SHORTCUTS : usize = 4 ;"));
+        assert!(output.contains("const STATIC_KEYS : & [& str] = & [\"+l\"] ;"));
+        assert!(output.contains("# [cfg (target_has_atomic = \"8\")] pub fn register_shortcut"));
+        assert!(output.contains("while RUNTIME . writing . swap (true , Ordering :: Acquire)"));
+        assert!(output.contains("|| find_runtime (trimmed) . is_some ()"));
+    }
+
+    #[test]
+    fn test_runtime_table_absent_by_default() {
+        let output = expand("+ : { l : crate::a::l },\n").unwrap();
+        assert!(!output.contains("register_shortcut"));
+        assert!(!output.contains("find_runtime"));
+    }
+
+    #[test]
+    fn test_unknown_shortcuts_fall_back() {
+        let output = expand_runtime(&format!("+ : {{ l : crate::a::l }},\n{}", CHORD_CFG));
+        assert!(output.contains("_ => fallback (trimmed , format_args ! (\"{}\" , key))"));
+        assert!(
+            output.contains("tok => fallback (trimmed , format_args ! (\"{} {}\" , \"g\" , tok))")
+        );
+    }
+
+    // ============================================================================
+    // Remap Tests
+    // ============================================================================
+
+    #[test]
+    fn test_remap_table_generated() {
+        let output = expand(&format!("+ : {{ l : crate::a::l }},\n{}", CHORD_CFG)).unwrap();
+        assert!(output.contains(
+            "const STATIC_KEYS : & [& str] = & [\"+l\" , \"g s\" , \"g c\" , \"g r s\"] ;"
+        ));
+        assert!(output.contains("# [cfg (target_has_atomic = \"8\")] pub fn remap_shortcut"));
+        assert!(output.contains("while REMAPS . locked . swap (true , Ordering :: Acquire)"));
+        assert!(output.contains("static REMAPS : RemapTable"));
+    }
+
+    #[test]
+    fn test_remapped_keys_translated_first() {
+        let output = expand("+ : { l : crate::a::l },\n").unwrap();
+        let translate = output
+            .find("if let Some ((key , rest)) = translate (trimmed)")
+            .unwrap();
+        let split = output.find("let split").unwrap();
+        assert!(translate < split);
+        assert!(output.contains("\"+l\" => call_static (\"+l\" , param , trimmed)"));
+        assert!(output.contains("if is_remapped (key)"));
+    }
+
+    #[test]
+    fn test_remapped_roots_not_supported() {
+        let output = expand("+ : { l : crate::a::l },\n").unwrap();
+        assert!(output.contains("translate (trimmed) . is_some ()"));
+        assert!(output.contains("! root_remapped (trimmed , tok)"));
+    }
+
+    // ============================================================================
+    // Mapping Parser Tests
+    // ============================================================================
+
+    #[test]
+    fn test_parse_comments_and_blank_lines() {
+        let (prefixes, entries) = parse_shortcuts(
+            "// shortcuts\n\n+ : { l : crate::a::l, // level\n      m : crate::a::m },\n\n// end\n",
+        )
+        .unwrap();
+        assert_eq!(prefixes.len(), 1);
+        let keys: Vec<&str> = entries.iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(keys, vec!["+l", "+m"]);
+    }
+
+    #[test]
+    fn test_parse_comment_markers_in_descriptions_kept() {
+        let (_, entries) = parse_shortcuts("+ : { l : crate::a::l \"see http://x\" },").unwrap();
+        assert_eq!(entries[0].help, "see http://x");
+    }
+
+    #[test]
+    fn test_parse_trailing_commas_and_free_layout() {
+        let (_, entries) = parse_shortcuts(
+            "+ :\n{\n  l : crate::a::l,\n  m : crate::a::m,\n}\n- : { l : crate::a::n }",
+        )
+        .unwrap();
+        let keys: Vec<&str> = entries.iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(keys, vec!["+l", "+m", "-l"]);
+    }
+
+    #[test]
+    fn test_parse_bracketed_descriptor() {
+        let (_, entries) =
+            parse_shortcuts("+ : { l : d{0..=9} --n=X([1, 2]) : crate::a::l, m : crate::a::m },")
+                .unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].desc.as_deref(), Some("d{0..=9} --n=X([1, 2])"));
+    }
+
+    #[test]
+    fn test_parse_errors_report_lines() {
+        let err = parse_shortcuts("+ : { l : crate::a::l },\n- { l : crate::a::l },\n")
+            .err()
+            .unwrap();
+        assert!(err.starts_with("line 2:"), "{}", err);
+
+        let err = parse_shortcuts("+ : { l : crate::a::l },\n- : l : crate::a::l\n")
+            .err()
+            .unwrap();
+        assert_eq!(err, "line 2: expected `{` after `- :`");
+
+        let err = parse_shortcuts("\n+ : { l : crate::a::l,\n m : crate::a::m\n")
+            .err()
+            .unwrap();
+        assert_eq!(err, "line 2: group `+` is not closed with `}`");
+
+        let err = parse_shortcuts("+ : { l : crate::a::l,\n\n  m crate::a::m },")
+            .err()
+            .unwrap();
+        assert_eq!(
+            err,
+            "line 3: expected `key : handler`, found `m crate::a::m`"
+        );
+
+        let err = parse_shortcuts("+ : {\n l : not a path },").err().unwrap();
+        assert_eq!(err, "line 2: invalid function path `not a path`");
+    }
+
+    #[test]
+    fn test_parse_error_is_compile_error() {
+        let err = expand("+ : { l crate::a::l },").unwrap_err();
+        assert!(err.contains("Shortcut mappings, line 1: expected `key : handler`"));
+    }
+
+    // ============================================================================
+    // Structured Error Tests
+    // ============================================================================
+
+    fn expand_structured(raw: &str) -> String {
+        let mod_name = syn::Ident::new("sc", Span::call_site());
+        let size: syn::Expr = syn::parse_str("64").unwrap();
+        let capacity: syn::Expr = syn::parse_str("2").unwrap();
+        expand_shortcuts(
+            &mod_name,
+            &size,
+            Some(&capacity),
+            true,
+            false,
+            raw,
+            Span::call_site(),
+        )
+        .unwrap()
+        .to_string()
+    }
+
+    #[test]
+    fn test_structured_errors_option_parsed() {
+        let input = parse_input(
+            quote::quote! { mod sc; shortcut_size = 8; structured_errors = true; runtime_shortcuts = 2; "a" },
+        )
+        .unwrap();
+        assert!(input.structured_errors);
+        assert!(input.runtime_shortcuts.is_some());
+
+        let input = parse_input(quote::quote! { mod sc; shortcut_size = 8; "a" }).unwrap();
+        assert!(!input.structured_errors);
+    }
+
+    #[test]
+    fn test_structured_errors_avoid_heapless() {
+        // Untyped shortcuts only: the argument parser would use `heapless::Vec`.
+        let output = expand_structured("+ : { l : crate::a::l },\n\"g\" : { s : crate::g::s },\n");
+        assert!(output.contains("pub enum ShortcutError"));
+        assert!(
+            output.contains("pub fn dispatch (input : & str) -> Result < () , ShortcutError >")
+        );
+        assert!(output.contains("Err (ShortcutError :: Unknown)"));
+        assert!(output.contains("Err (ShortcutError :: TableFull)"));
+        assert!(output.contains("type HandlerError = () ;"));
+        assert!(!output.contains("heapless"));
+        assert!(!output.contains("write !"));
+        assert!(!output.contains("Args {"));
+    }
+
+    #[test]
+    fn test_defmt_option() {
+        let input =
+            parse_input(quote::quote! { mod sc; shortcut_size = 8; defmt = true; "a" }).unwrap();
+        assert!(input.defmt);
+
+        let mod_name = syn::Ident::new("sc", Span::call_site());
+        let size: syn::Expr = syn::parse_str("64").unwrap();
+        let output = expand_shortcuts(
+            &mod_name,
+            &size,
+            None,
+            true,
+            true,
+            TYPED_CFG,
+            Span::call_site(),
+        )
+        .unwrap()
+        .to_string();
+        assert!(output.contains("# [derive (defmt :: Format)] pub enum ShortcutError"));
+        // The report is only `Debug`, so defmt formats it with `Debug2Format`.
+        assert!(output.contains("Failed (# [defmt (Debug2Format)] super :: HandlerError)"));
+        assert!(!expand(TYPED_CFG).unwrap().contains("defmt"));
+    }
+
+    #[test]
+    fn test_structured_errors_typed_args() {
+        let output = expand_structured(TYPED_CFG);
+        assert!(output.contains("Args { key : & 'static str , error : __args :: DispatchError }"));
+        assert!(output.contains("ShortcutError :: Args { key , error : e }"));
+        assert!(output.contains("ShortcutError :: Failed { key }"));
+    }
 }
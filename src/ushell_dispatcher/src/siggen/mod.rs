@@ -0,0 +1,488 @@
+//! # Signature-driven command registration
+//!
+//! `#[command_sig]` on a command handler records the descriptor matching its parameter list,
+//! and `register_commands!` builds a command module from handler paths alone, so descriptors
+//! cannot drift from the signatures they describe.
+//!
+//! ## Usage
+//!
+//! ```rust
+//! // src/pwm.rs
+//! #[command_sig]
+//! pub fn set_pwm(freq: u32, duty: f64) { ... } // => "D:freq F:duty"
+//!
+//! // src/main.rs
+//! register_commands!(mod commands; [crate::pwm::set_pwm, crate::pwm::stop]);
+//! ```
+//!
+//! The attribute leaves the function unchanged and adds a hidden `__ushell_sig_<name>` macro
+//! next to it, visible in the crate. `register_commands!` calls the macro of each handler in
+//! turn (`crate::pwm::set_pwm` => `crate::pwm::__ushell_sig_set_pwm!`); each one hands its
+//! descriptor back to `register_commands!`, which expands like `generate_commands_dispatcher!`
+//! once every handler answered. Handlers must therefore live in the crate invoking the macro.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    Attribute, Ident, LitStr, Result, Token, Type,
+    ext::IdentExt,
+    parse::{Parse, ParseStream, Parser},
+    punctuated::Punctuated,
+    spanned::Spanned,
+};
+
+use crate::commandsgen::{CommandMacroInput, expand_dispatcher};
+use crate::docgen::HandlerFn;
+
+/// Name of the macro recording the descriptor of the handler `name`.
+fn sig_macro_ident(name: &str) -> Ident {
+    format_ident!("__ushell_sig_{}", name)
+}
+
+// ==================== #[command_sig] =======================
+
+/// Entry point of `#[command_sig]`.
+pub fn generate_command_sig(attr: TokenStream, item: TokenStream) -> TokenStream {
+    expand_command_sig(attr.into(), item.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Emit the function followed by the macro handing its descriptor to `register_commands!`.
+fn expand_command_sig(attr: TokenStream2, item: TokenStream2) -> Result<TokenStream2> {
+    if !attr.is_empty() {
+        return Err(syn::Error::new_spanned(
+            attr,
+            "`#[command_sig]` takes no arguments",
+        ));
+    }
+    let handler = HandlerFn::parse_item(item.clone(), "command_sig")?;
+    let desc = LitStr::new(&signature_descriptor(handler.inputs)?, handler.ident.span());
+    let mac = sig_macro_ident(&handler.ident.unraw().to_string());
+    Ok(quote! {
+        #item
+
+        /// Descriptor of the function above for `register_commands!`, see `#[command_sig]`.
+        #[doc(hidden)]
+        macro_rules! #mac {
+            ($($state:tt)*) => {
+                ::ushell_dispatcher::register_commands! { @sig #desc $($state)* }
+            };
+        }
+        #[doc(hidden)]
+        #[allow(unused_imports)]
+        pub(crate) use #mac;
+    })
+}
+
+/// One parameter of a handler.
+struct SigParam {
+    name: Option<Ident>, // Parameter name, `None` for `_` and `_name`
+    ty: Type,            // Parameter type
+}
+
+impl Parse for SigParam {
+    fn parse(input: ParseStream) -> Result<Self> {
+        input.call(Attribute::parse_outer)?;
+        // `self`, `&self`, `&'a mut self`, `mut self`
+        let receiver = input.fork();
+        if receiver.peek(Token![&]) {
+            receiver.parse::<Token![&]>()?;
+            if receiver.peek(syn::Lifetime) {
+                receiver.parse::<syn::Lifetime>()?;
+            }
+        }
+        if receiver.peek(Token![mut]) {
+            receiver.parse::<Token![mut]>()?;
+        }
+        if receiver.peek(Token![self]) {
+            return Err(receiver.error("methods cannot be registered as commands"));
+        }
+        if input.peek(Token![mut]) {
+            input.parse::<Token![mut]>()?;
+        }
+        let name = if input.peek(Token![_]) {
+            input.parse::<Token![_]>()?;
+            None
+        } else {
+            let ident: Ident = input
+                .parse()
+                .map_err(|err| syn::Error::new(err.span(), "expected a `name: Type` parameter"))?;
+            Some(ident).filter(|ident| !ident.unraw().to_string().starts_with('_'))
+        };
+        input.parse::<Token![:]>()?;
+        let ty: Type = input.parse()?;
+        Ok(SigParam { name, ty })
+    }
+}
+
+/// Descriptor of a handler taking the parameters `inputs`: one type character per parameter,
+/// named after it (`freq: u32, _raw: bool` => `"D:freq t"`), or `"v"` for none.
+fn signature_descriptor(inputs: TokenStream2) -> Result<String> {
+    let params = Punctuated::<SigParam, Token![,]>::parse_terminated.parse2(inputs)?;
+    if params.is_empty() {
+        return Ok("v".to_string());
+    }
+    let mut parts = Vec::new();
+    for param in params {
+        let ty = &param.ty;
+        let Some(ch) = type_char(ty) else {
+            return Err(syn::Error::new(
+                ty.span(),
+                format!(
+                    "`{}` has no descriptor character; use `generate_commands_dispatcher!` with an explicit descriptor for this handler",
+                    quote!(#ty).to_string().replace(' ', "")
+                ),
+            ));
+        };
+        parts.push(match param.name {
+            Some(name) => format!("{}:{}", ch, name.unraw()),
+            None => ch.to_string(),
+        });
+    }
+    Ok(parts.join(" "))
+}
+
+/// Descriptor character of a parameter type, if it has one.
+fn type_char(ty: &Type) -> Option<char> {
+    match ty {
+        Type::Group(group) => type_char(&group.elem),
+        Type::Paren(paren) => type_char(&paren.elem),
+        Type::Path(_) => {
+            let ch = match primitive(ty)? {
+                "u8" => 'B',
+                "u16" => 'W',
+                "u32" => 'D',
+                "u64" => 'Q',
+                "u128" => 'X',
+                "i8" => 'b',
+                "i16" => 'w',
+                "i32" => 'd',
+                "i64" => 'q',
+                "i128" => 'x',
+                "usize" => 'Z',
+                "isize" => 'z',
+                "f32" => 'f',
+                "f64" => 'F',
+                "bool" => 't',
+                "char" => 'c',
+                _ => return None,
+            };
+            Some(ch)
+        }
+        Type::Reference(reference) if reference.mutability.is_none() => match &*reference.elem {
+            Type::Slice(slice) if primitive(&slice.elem) == Some("u8") => Some('h'),
+            elem if primitive(elem) == Some("str") => Some('s'),
+            _ => None,
+        },
+        Type::Tuple(tuple) => {
+            let elems: Vec<_> = tuple.elems.iter().map(primitive).collect();
+            (elems == [Some("u64"), Some("u32")]).then_some('T')
+        }
+        _ => None,
+    }
+}
+
+/// Name of a primitive type written without a path (`u32`, `str`), if `ty` is one.
+fn primitive(ty: &Type) -> Option<&'static str> {
+    const PRIMITIVES: &[&str] = &[
+        "u8", "u16", "u32", "u64", "u128", "i8", "i16", "i32", "i64", "i128", "usize", "isize",
+        "f32", "f64", "bool", "char", "str",
+    ];
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    if path.qself.is_some() {
+        return None;
+    }
+    let ident = path.path.get_ident()?;
+    PRIMITIVES.iter().copied().find(|p| ident == p)
+}
+
+// ==================== register_commands! =======================
+
+/// Input of `register_commands!`.
+enum RegisterInput {
+    /// As written by the user: `mod <ident>; [options;] [path, path, ...]`.
+    Start {
+        head: TokenStream2,    // `mod <ident>;` and the options, as given
+        paths: Vec<syn::Path>, // Handlers to register
+    },
+    /// Handed back by the macro of `current`: `@sig "<desc>" {head} [rest] {done} current`.
+    Step {
+        desc: LitStr,         // Descriptor of `current`
+        head: TokenStream2,   // `mod <ident>;` and the options, as given
+        rest: Vec<syn::Path>, // Handlers not asked yet
+        done: TokenStream2,   // `"desc": [path];` entries collected so far
+        current: syn::Path,   // Handler whose descriptor this is
+    },
+}
+
+impl Parse for RegisterInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        if input.peek(Token![@]) {
+            input.parse::<Token![@]>()?;
+            let tag: Ident = input.parse()?;
+            if tag != "sig" {
+                return Err(syn::Error::new(tag.span(), "expected `@sig`"));
+            }
+            let desc: LitStr = input.parse()?;
+            let head;
+            syn::braced!(head in input);
+            let rest;
+            syn::bracketed!(rest in input);
+            let done;
+            syn::braced!(done in input);
+            return Ok(RegisterInput::Step {
+                desc,
+                head: head.parse()?,
+                rest: parse_paths(&rest)?,
+                done: done.parse()?,
+                current: input.parse()?,
+            });
+        }
+
+        // `mod <ident>;` and `key = value;` options up to the `[...]` following a `;`
+        let mut head = TokenStream2::new();
+        let mut after_semi = false;
+        while !input.is_empty() {
+            if after_semi && input.peek(syn::token::Bracket) {
+                let list;
+                syn::bracketed!(list in input);
+                let paths = parse_paths(&list)?;
+                if paths.is_empty() {
+                    return Err(list.error("expected at least one handler path"));
+                }
+                if input.peek(Token![;]) {
+                    input.parse::<Token![;]>()?;
+                }
+                if !input.is_empty() {
+                    return Err(input.error("unexpected tokens after the handler list"));
+                }
+                return Ok(RegisterInput::Start { head, paths });
+            }
+            let token: proc_macro2::TokenTree = input.parse()?;
+            after_semi = matches!(&token, proc_macro2::TokenTree::Punct(p) if p.as_char() == ';');
+            head.extend([token]);
+        }
+        Err(input.error("expected `mod <ident>;` followed by a `[handler, ...]` list"))
+    }
+}
+
+/// Parse a comma-separated list of handler paths.
+fn parse_paths(input: ParseStream) -> Result<Vec<syn::Path>> {
+    let paths = Punctuated::<syn::Path, Token![,]>::parse_terminated(input)?;
+    Ok(paths.into_iter().collect())
+}
+
+/// Entry point of `register_commands!`.
+pub fn generate_registered_commands(input: TokenStream) -> TokenStream {
+    expand_register_commands(input.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Record the descriptor handed back, if any, then ask the next handler or expand the module.
+fn expand_register_commands(input: TokenStream2) -> Result<TokenStream2> {
+    match syn::parse2::<RegisterInput>(input)? {
+        RegisterInput::Start { head, paths } => next_step(head, paths, TokenStream2::new()),
+        RegisterInput::Step {
+            desc,
+            head,
+            rest,
+            mut done,
+            current,
+        } => {
+            // Point descriptor errors at the handler rather than at its attribute.
+            let desc = LitStr::new(&desc.value(), current.span());
+            done.extend(quote! { #desc: [#current]; });
+            next_step(head, rest, done)
+        }
+    }
+}
+
+/// Invoke the `#[command_sig]` macro of the first of `paths`, or expand the module once every
+/// handler has been asked.
+fn next_step(
+    head: TokenStream2,
+    paths: Vec<syn::Path>,
+    done: TokenStream2,
+) -> Result<TokenStream2> {
+    let mut paths = paths.into_iter();
+    let Some(next) = paths.next() else {
+        return expand_registered(head, done);
+    };
+    let mut mac = next.clone();
+    if let Some(last) = mac.segments.last_mut() {
+        last.ident = Ident::new(
+            &sig_macro_ident(&last.ident.unraw().to_string()).to_string(),
+            last.ident.span(),
+        );
+        last.arguments = syn::PathArguments::None;
+    }
+    let rest = paths;
+    Ok(quote! {
+        #mac! { { #head } [#(#rest),*] { #done } #next }
+    })
+}
+
+/// Expand the module from the options and the collected `"desc": [path];` entries.
+fn expand_registered(head: TokenStream2, done: TokenStream2) -> Result<TokenStream2> {
+    let mut input: CommandMacroInput = syn::parse2(quote! { #head #done })?;
+    // Byte-slice handlers need a buffer size; without them the option is just noise.
+    let takes_bytes = input
+        .groups
+        .iter()
+        .any(|g| g.desc.split_whitespace().any(|p| p.starts_with('h')));
+    if input.hexstr_size.is_none() && !takes_bytes {
+        input.hexstr_size = Some(syn::parse_quote!(0));
+    }
+    expand_dispatcher(input)
+}
+
+// ==================== TESTS =======================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn descriptor(inputs: TokenStream2) -> Result<String> {
+        signature_descriptor(inputs)
+    }
+
+    #[test]
+    fn test_descriptor_from_signature() {
+        assert_eq!(descriptor(quote! {}).unwrap(), "v");
+        assert_eq!(
+            descriptor(quote! { freq: u32, duty: f64, label: &str }).unwrap(),
+            "D:freq F:duty s:label"
+        );
+        assert_eq!(
+            descriptor(quote! { a: u8, b: i16, c: u64, d: i128, e: usize, f: isize, g: f32 })
+                .unwrap(),
+            "B:a w:b Q:c x:d Z:e z:f f:g"
+        );
+        assert_eq!(
+            descriptor(quote! { on: bool, key: char, data: &[u8], at: (u64, u32), }).unwrap(),
+            "t:on c:key h:data T:at"
+        );
+    }
+
+    #[test]
+    fn test_unnamed_and_raw_parameters() {
+        assert_eq!(
+            descriptor(quote! { _: u8, _unused: bool, mut r#type: &'static str }).unwrap(),
+            "B t s:type"
+        );
+    }
+
+    #[test]
+    fn test_unsupported_parameters() {
+        let err = descriptor(quote! { v: Vec<u8> }).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("`Vec<u8>` has no descriptor character")
+        );
+        let err = descriptor(quote! { buf: &mut [u8] }).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("`&mut[u8]` has no descriptor character")
+        );
+        let err = descriptor(quote! { &self, x: u8 }).unwrap_err();
+        assert!(err.to_string().contains("methods cannot be registered"));
+        let err = descriptor(quote! { (a, b): (u8, u8) }).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("expected a `name: Type` parameter")
+        );
+    }
+
+    #[test]
+    fn test_attribute_emits_sig_macro() {
+        let out = expand_command_sig(quote! {}, quote! { pub fn set_pwm(freq: u32) {} })
+            .unwrap()
+            .to_string();
+        assert!(out.contains("pub fn set_pwm (freq : u32) { }"));
+        assert!(out.contains("macro_rules ! __ushell_sig_set_pwm"));
+        assert!(out.contains(
+            ":: ushell_dispatcher :: register_commands ! { @ sig \"D:freq\" $ ($ state) * }"
+        ));
+        assert!(out.contains("pub (crate) use __ushell_sig_set_pwm ;"));
+        let err = expand_command_sig(quote! { x }, quote! { fn f() {} }).unwrap_err();
+        assert!(err.to_string().contains("takes no arguments"));
+        let err = expand_command_sig(quote! {}, quote! { struct S; }).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("`#[command_sig]` expects a function")
+        );
+    }
+
+    #[test]
+    fn test_first_step_asks_first_handler() {
+        let out = expand_register_commands(quote! {
+            mod commands; hexstr_size = 8; [crate::pwm::set_pwm, crate::stop]
+        })
+        .unwrap()
+        .to_string();
+        assert_eq!(
+            out,
+            quote! {
+                crate::pwm::__ushell_sig_set_pwm! {
+                    { mod commands; hexstr_size = 8; } [crate::stop] { } crate::pwm::set_pwm
+                }
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn test_step_collects_descriptor() {
+        let out = expand_register_commands(quote! {
+            @sig "D:freq" { mod commands; } [crate::stop] { "v": [crate::reset]; } crate::pwm::set_pwm
+        })
+        .unwrap()
+        .to_string();
+        assert_eq!(
+            out,
+            quote! {
+                crate::__ushell_sig_stop! {
+                    { mod commands; } [] { "v": [crate::reset]; "D:freq": [crate::pwm::set_pwm]; }
+                    crate::stop
+                }
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn test_last_step_expands_module() {
+        let out = expand_register_commands(quote! {
+            @sig "D:freq" { mod commands; } [] { } crate::pwm::set_pwm
+        })
+        .unwrap()
+        .to_string();
+        assert!(out.contains("pub mod commands"));
+        assert!(out.contains("pub const MAX_HEXSTR_LEN : usize = 0"));
+        assert!(out.contains("crate :: pwm :: set_pwm"));
+
+        let err = expand_register_commands(quote! {
+            @sig "h:data" { mod commands; } [] { } crate::flash::write
+        })
+        .unwrap_err();
+        assert!(err.to_string().contains("hexstr_size"));
+    }
+
+    #[test]
+    fn test_malformed_input() {
+        let err = expand_register_commands(quote! { mod commands; [] }).unwrap_err();
+        assert!(err.to_string().contains("at least one handler path"));
+        let err = expand_register_commands(quote! { mod commands; }).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("followed by a `[handler, ...]` list")
+        );
+        let err = expand_register_commands(quote! { mod commands; [crate::a] x }).unwrap_err();
+        assert!(err.to_string().contains("unexpected tokens"));
+    }
+}
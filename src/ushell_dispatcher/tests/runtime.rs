@@ -0,0 +1,501 @@
+//! Runs the generated dispatchers: the arguments each handler receives, and the errors of
+//! malformed ones.
+
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use ushell_dispatcher::{
+    generate_commands_dispatcher, generate_shortcuts_dispatcher, merge_commands,
+};
+
+thread_local! {
+    /// What the last handler received, formatted with `Debug`.
+    static LAST: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+fn record(args: impl core::fmt::Debug) {
+    LAST.with(|last| *last.borrow_mut() = format!("{:?}", args));
+}
+
+/// Dispatches `line` and returns what the handler received.
+fn run(line: &str) -> Result<String, commands::DispatchError> {
+    LAST.with(|last| last.borrow_mut().clear());
+    commands::dispatch(line)?;
+    Ok(LAST.with(|last| last.borrow().clone()))
+}
+
+pub fn take_u32(v: u32) {
+    record(v);
+}
+
+pub fn toggled() {
+    record(());
+}
+
+pub fn take_list(bytes: &[u8]) {
+    record(bytes);
+}
+
+pub fn take_time(time: (u64, u32)) {
+    record(time);
+}
+
+pub fn take_hex(bytes: &[u8]) {
+    record(bytes);
+}
+
+pub fn take_u8(v: u8) {
+    record(v);
+}
+
+pub fn take_i8(v: i8) {
+    record(v);
+}
+
+pub fn take_i64(v: i64) {
+    record(v);
+}
+
+pub fn take_char(c: char) {
+    record(c);
+}
+
+pub fn take_str(s: &str) {
+    record(s);
+}
+
+/// Records whether cancellation was requested, then requests it.
+pub fn poll_cancel(cancel: &AtomicBool) {
+    record(cancel.load(Ordering::Relaxed));
+    cancel.store(true, Ordering::Relaxed);
+}
+
+generate_commands_dispatcher! {
+    mod commands;
+    hexstr_size = 16;
+    "D: crate::take_u32, v: crate::toggled, a: crate::take_list, T: crate::take_time, h: crate::take_hex, B: crate::take_u8, b: crate::take_i8, q: crate::take_i64, c: crate::take_char, s: crate::take_str, v[cancel]: crate::poll_cancel"
+}
+
+#[test]
+fn test_unit_suffix() {
+    assert_eq!(run("take_u32 4k").unwrap(), "4096");
+    assert_eq!(run("take_u32 2M").unwrap(), "2097152");
+    assert_eq!(run("take_u32 0x10K").unwrap(), "16384");
+    assert_eq!(
+        run("take_u32 5G"),
+        Err(commands::DispatchError::BadUnsigned)
+    );
+}
+
+#[test]
+fn test_unit_suffix_after_multibyte_char() {
+    assert_eq!(
+        run("take_u32 5é"),
+        Err(commands::DispatchError::BadUnsigned)
+    );
+    assert_eq!(run("take_u32 é"), Err(commands::DispatchError::BadUnsigned));
+    assert_eq!(
+        run("take_u32 5€k"),
+        Err(commands::DispatchError::BadUnsigned)
+    );
+}
+
+#[test]
+fn test_command_toggled_at_runtime() {
+    commands::set_command_enabled("toggled", false).unwrap();
+    assert_eq!(run("toggled"), Err(commands::DispatchError::Disabled));
+    assert_eq!(run("take_u32 1").unwrap(), "1");
+    commands::set_command_enabled("toggled", true).unwrap();
+    assert_eq!(run("toggled").unwrap(), "()");
+}
+
+#[test]
+fn test_cancellation_token() {
+    // The token is cleared first and handed to the handler
+    let cancel = AtomicBool::new(true);
+    commands::dispatch_with_cancel("poll_cancel", &cancel).unwrap();
+    assert_eq!(LAST.with(|last| last.borrow().clone()), "false");
+    assert!(cancel.load(Ordering::Relaxed));
+    // Plain dispatch hands a token of its own
+    cancel.store(false, Ordering::Relaxed);
+    assert_eq!(run("poll_cancel").unwrap(), "false");
+    assert!(!cancel.load(Ordering::Relaxed));
+    merged::dispatch_with_cancel("poll_cancel", &cancel).unwrap();
+    assert!(cancel.load(Ordering::Relaxed));
+}
+
+#[test]
+fn test_byte_list() {
+    assert_eq!(run("take_list [0x01, 0x02, 0xFF]").unwrap(), "[1, 2, 255]");
+    assert_eq!(run("take_list [ 1, 0b10, 0o3, ]").unwrap(), "[1, 2, 3]");
+    assert_eq!(run("take_list []").unwrap(), "[]");
+    assert_eq!(
+        run("take_list [256]"),
+        Err(commands::DispatchError::BadByteList)
+    );
+    assert_eq!(
+        run("take_list [1,,2]"),
+        Err(commands::DispatchError::BadByteList)
+    );
+    assert_eq!(
+        run("take_list [é]"),
+        Err(commands::DispatchError::BadByteList)
+    );
+    assert_eq!(
+        run("take_list [1, 2"),
+        Err(commands::DispatchError::BadByteList)
+    );
+    assert_eq!(
+        run("take_list 1, 2"),
+        Err(commands::DispatchError::WrongArity {
+            expected: 1,
+            usage: commands::usage("take_list")
+        })
+    );
+    // One byte more than `hexstr_size`.
+    let long = format!("take_list [{}]", ["1"; 17].join(","));
+    assert_eq!(run(&long), Err(commands::DispatchError::BadByteList));
+}
+
+#[test]
+fn test_timestamp() {
+    assert_eq!(
+        run("take_time 2024-05-01T12:30:00Z").unwrap(),
+        "(1714566600, 0)"
+    );
+    assert_eq!(
+        run("take_time 2024-05-01T14:30:00.250+02:00").unwrap(),
+        "(1714566600, 250000000)"
+    );
+    assert_eq!(run("take_time 2024-05-01").unwrap(), "(1714521600, 0)");
+    assert_eq!(
+        run("take_time 2024-02-29T23:59:59").unwrap(),
+        "(1709251199, 0)"
+    );
+    assert_eq!(
+        commands::parse_timestamp("1970-01-01T00:00:00.000000001Z"),
+        Some((0, 1))
+    );
+    for bad in [
+        "2023-02-29",
+        "2024-13-01",
+        "2024-05-01T24:00",
+        "2024-05-01T12:30:00.1234567890Z",
+        "1969-12-31T23:59:59Z",
+        "1970-01-01T00:30+01:00",
+        "2024-05-01T12:30:00Zé",
+        "2024-05-01é",
+    ] {
+        assert_eq!(
+            run(&format!("take_time {}", bad)),
+            Err(commands::DispatchError::BadTimestamp),
+            "{}",
+            bad
+        );
+    }
+}
+
+#[test]
+fn test_hex_string() {
+    assert_eq!(run("take_hex DEAD").unwrap(), "[222, 173]");
+    assert_eq!(run("take_hex 0xDE:AD").unwrap(), "[222, 173]");
+    assert_eq!(run("take_hex 0Xde_ad").unwrap(), "[222, 173]");
+    assert_eq!(
+        run("take_hex \"DE AD BE EF\"").unwrap(),
+        "[222, 173, 190, 239]"
+    );
+    for bad in [
+        "", "0x", "DEA", "D:EAD", ":DEAD", "DEAD:", "DE::AD", "DEAG", "DEé", "0xé",
+    ] {
+        assert_eq!(
+            run(&format!("take_hex \"{}\"", bad)),
+            Err(commands::DispatchError::BadHexStr),
+            "{}",
+            bad
+        );
+    }
+    // One byte more than `hexstr_size`.
+    assert_eq!(
+        run(&format!("take_hex {}", "AB".repeat(17))),
+        Err(commands::DispatchError::BadHexStr)
+    );
+    assert_eq!(
+        run(&format!("take_hex {}", "AB".repeat(16)))
+            .unwrap()
+            .matches("171")
+            .count(),
+        16
+    );
+}
+
+#[test]
+fn test_integer_formats() {
+    use commands::DispatchError::{BadSigned, BadUnsigned};
+    assert_eq!(run("take_u8 +5").unwrap(), "5");
+    assert_eq!(run("take_u8 0xFF").unwrap(), "255");
+    assert_eq!(run("take_u8 0b1010_1010").unwrap(), "170");
+    assert_eq!(run("take_u8 0o17").unwrap(), "15");
+    assert_eq!(run("take_u32 1_000").unwrap(), "1000");
+    assert_eq!(run("take_u32 1_000_000").unwrap(), "1000000");
+    assert_eq!(run("take_u32 1__").unwrap(), "1");
+    assert_eq!(run("take_u32 0xFF_FF").unwrap(), "65535");
+    assert_eq!(run("take_i8 -128").unwrap(), "-128");
+    assert_eq!(run("take_i8 +127").unwrap(), "127");
+    assert_eq!(run("take_i8 -0x80").unwrap(), "-128");
+    assert_eq!(
+        run("take_i64 -9_223_372_036_854_775_808").unwrap(),
+        "-9223372036854775808"
+    );
+    assert_eq!(run("take_i64 -2k").unwrap(), "-2048");
+
+    // Overflow, in the digits or through the unit suffix
+    assert_eq!(run("take_u8 256"), Err(BadUnsigned));
+    assert_eq!(run("take_u8 1k"), Err(BadUnsigned));
+    assert_eq!(run("take_i8 128"), Err(BadSigned));
+    assert_eq!(run("take_i8 -129"), Err(BadSigned));
+    assert_eq!(run("take_i64 9223372036854775808"), Err(BadSigned));
+    assert_eq!(run("take_u32 4294967296"), Err(BadUnsigned));
+    assert_eq!(run("take_u32 4G"), Err(BadUnsigned));
+
+    // Malformed
+    for bad in [
+        "-1", "+", "-", "_1", "0x", "0x_", "+-1", "--1", "1.5", "1e3", "٣", "1é",
+    ] {
+        assert_eq!(
+            run(&format!("take_u8 {}", bad)),
+            Err(BadUnsigned),
+            "{}",
+            bad
+        );
+    }
+    for bad in ["_1", "-_1", "+-1", "- 1", "0b2", "0o8", "١"] {
+        assert_eq!(
+            run(&format!("take_i8 \"{}\"", bad)),
+            Err(BadSigned),
+            "{}",
+            bad
+        );
+    }
+}
+
+#[test]
+fn test_char_escapes() {
+    assert_eq!(run("take_char a").unwrap(), "'a'");
+    assert_eq!(run("take_char é").unwrap(), "'é'");
+    assert_eq!(run("take_char 'é'").unwrap(), "'é'");
+    assert_eq!(run("take_char '#'").unwrap(), "'#'");
+    assert_eq!(run("take_char \" \"").unwrap(), "' '");
+    assert_eq!(run("take_char '").unwrap(), "'\\''");
+    assert_eq!(run(r"take_char '\n'").unwrap(), "'\\n'");
+    assert_eq!(run(r"take_char \n").unwrap(), "'\\n'");
+    assert_eq!(run(r"take_char \r").unwrap(), "'\\r'");
+    assert_eq!(run(r"take_char \t").unwrap(), "'\\t'");
+    assert_eq!(run(r"take_char \0").unwrap(), "'\\0'");
+    assert_eq!(run(r"take_char \\").unwrap(), "'\\\\'");
+    assert_eq!(run(r"take_char \").unwrap(), "'\\\\'");
+    assert_eq!(run(r"take_char '\''").unwrap(), "'\\''");
+    assert_eq!(run(r"take_char \x41").unwrap(), "'A'");
+    assert_eq!(run(r"take_char '\x7f'").unwrap(), "'\\u{7f}'");
+
+    // Malformed escapes, several characters and non-ASCII `\x`
+    for bad in [
+        r"\q", r"\x", r"\x4", r"\x4G", r"\x+1", r"\x80", r"\xFF", r"\x411", r"\né", r"\xé", "''",
+        "ab", "'ab'", "'a", "é'",
+    ] {
+        assert_eq!(
+            run(&format!("take_char {}", bad)),
+            Err(commands::DispatchError::BadChar),
+            "{}",
+            bad
+        );
+    }
+}
+
+#[test]
+fn test_tokenizer() {
+    use commands::DispatchError::{Empty, TooManyTokens, WrongArity};
+    let mut toks = [""; 3];
+    assert_eq!(
+        commands::tokenize("  a\t\"b c\"  [1, 2] ", &mut toks),
+        Ok(3)
+    );
+    assert_eq!(toks, ["a", "b c", "[1, 2]"]);
+    assert_eq!(commands::tokenize("a \"b c", &mut toks), Ok(2));
+    assert_eq!(toks[1], "b c");
+    assert_eq!(commands::tokenize("é ü ö", &mut toks), Ok(3));
+    assert_eq!(toks, ["é", "ü", "ö"]);
+    assert_eq!(commands::tokenize(" \t ", &mut toks), Err(Empty));
+    assert_eq!(commands::tokenize("a b c d", &mut toks), Err(TooManyTokens));
+    assert_eq!(commands::tokenize("a", &mut []), Err(TooManyTokens));
+
+    assert_eq!(
+        run("take_str \"Hello World!\"").unwrap(),
+        "\"Hello World!\""
+    );
+    assert_eq!(run(""), Err(Empty));
+    // One token more than the command takes, then more than the buffer holds.
+    let usage = commands::usage("take_u32");
+    assert_eq!(run("take_u32 1 2"), Err(WrongArity { expected: 1, usage }));
+    assert_eq!(run("take_u32 1 2 3 4 5 6 7 8"), Err(TooManyTokens));
+}
+
+pub fn boot_jump(addr: u32) {
+    record(addr);
+}
+
+generate_commands_dispatcher! {
+    mod boot;
+    hexstr_size = 4;
+    minimal = true;
+    "D:addr: crate::boot_jump"
+}
+
+merge_commands! {
+    mod merged;
+    [commands, boot, pwm]
+}
+
+#[test]
+fn test_minimal_module_lists_names() {
+    assert_eq!(boot::get_commands(), &[("boot_jump", "")]);
+    assert_eq!(boot::get_datatypes(), "");
+    assert_eq!(boot::usage("boot_jump"), "boot_jump");
+    assert_eq!(
+        boot::dispatch("boot_jump"),
+        Err(boot::DispatchError::WrongArity {
+            expected: 1,
+            usage: "boot_jump"
+        })
+    );
+    boot::dispatch("boot_jump 0x800").unwrap();
+    assert_eq!(LAST.with(|last| last.borrow().clone()), "2048");
+
+    assert!(merged::get_commands().contains(&("boot_jump", "")));
+    assert!(merged::get_commands().contains(&("take_u32", "D")));
+    merged::dispatch("boot_jump 4k").unwrap();
+    assert_eq!(LAST.with(|last| last.borrow().clone()), "4096");
+}
+
+pub struct Pwm {
+    freq: u32,
+}
+
+impl Pwm {
+    pub fn set_freq(&mut self, freq: u32) {
+        self.freq = freq;
+    }
+
+    pub fn ramp(&mut self, cancel: &AtomicBool, freq: u32) {
+        if !cancel.load(Ordering::Relaxed) {
+            self.freq = freq;
+        }
+    }
+}
+
+generate_commands_dispatcher! {
+    mod pwm;
+    hexstr_size = 4;
+    service = crate::Pwm;
+    "D: crate::Pwm::set_freq, D[cancel]: crate::Pwm::ramp"
+}
+
+#[test]
+fn test_service_methods() {
+    let mut driver = Pwm { freq: 0 };
+    pwm::dispatch_with_service("set_freq 2k", &mut driver).unwrap();
+    assert_eq!(driver.freq, 2048);
+    assert_eq!(
+        pwm::dispatch("set_freq 1"),
+        Err(pwm::DispatchError::NoService)
+    );
+    assert_eq!(driver.freq, 2048);
+    let cancel = AtomicBool::new(true);
+    pwm::dispatch_with_service_cancel("ramp 3", &mut driver, &cancel).unwrap();
+    assert_eq!(driver.freq, 3);
+
+    // Merged, a service module runs its methods through its own module only
+    assert_eq!(
+        merged::dispatch("set_freq 1"),
+        Err(merged::DispatchError::pwm(pwm::DispatchError::NoService))
+    );
+}
+
+pub fn level(param: &str) {
+    record(("level", param));
+}
+
+pub fn bound(param: &str) {
+    record(("bound", param));
+}
+
+generate_shortcuts_dispatcher! {
+    mod shortcuts;
+    shortcut_size = 64;
+    runtime_shortcuts = 16;
+    "+ : { l : crate::level, m : crate::level },"
+}
+
+/// Dispatches the shortcut `line` and returns what the handler received.
+fn run_shortcut(line: &str) -> Result<String, String> {
+    LAST.with(|last| last.borrow_mut().clear());
+    shortcuts::dispatch(line).map_err(|msg| msg.as_str().to_string())?;
+    Ok(LAST.with(|last| last.borrow().clone()))
+}
+
+#[test]
+fn test_shortcuts_registered_concurrently() {
+    const KEYS: [&str; 8] = ["#a", "#b", "#c", "#d", "#e", "#f", "#g", "#h"];
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                for key in KEYS {
+                    // Exactly one thread binds each key, the others see it defined.
+                    let _ = shortcuts::register_shortcut(key, bound);
+                }
+            });
+        }
+    });
+    for key in KEYS {
+        assert_eq!(
+            shortcuts::register_shortcut(key, bound)
+                .unwrap_err()
+                .as_str(),
+            format!("Shortcut already defined: {}", key)
+        );
+        assert_eq!(
+            run_shortcut(&format!("{} x", key)).unwrap(),
+            "(\"bound\", \"x\")"
+        );
+    }
+    assert_eq!(
+        shortcuts::register_shortcut("+l", bound)
+            .unwrap_err()
+            .as_str(),
+        "Shortcut already defined: +l"
+    );
+}
+
+#[test]
+fn test_shortcut_remapped_while_dispatching() {
+    let done = AtomicBool::new(false);
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            for _ in 0..2000 {
+                shortcuts::remap_shortcut("+m", "_m").unwrap();
+                shortcuts::remap_shortcut("+m", "+m").unwrap();
+            }
+            done.store(true, Ordering::Relaxed);
+        });
+        // Either key works at any time, with its own parameter; never a torn key.
+        while !done.load(Ordering::Relaxed) {
+            for line in ["_m 1", "+m 1"] {
+                match run_shortcut(line) {
+                    Ok(args) => assert_eq!(args, "(\"level\", \"1\")"),
+                    Err(msg) => assert!(msg.starts_with("Unknown shortcut"), "{}", msg),
+                }
+            }
+        }
+    });
+    assert_eq!(run_shortcut("+m 2").unwrap(), "(\"level\", \"2\")");
+    assert!(run_shortcut("_m 2").is_err());
+}
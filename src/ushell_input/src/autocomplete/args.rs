@@ -0,0 +1,691 @@
+use heapless::{String, Vec};
+
+/// Maximum number of argument value candidates offered at once.
+pub const MAX_ARG_CANDIDATES: usize = 8;
+
+/// Descriptor characters that stand for one positional parameter.
+const TYPE_CHARS: &str = "BWDQXbwdqxZzfFtcshaT";
+
+/// One positional parameter of a command descriptor: its type character and optional name.
+/// - `path` is `true` for a file name, declared as `s{path}`.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArgParam<'a> {
+    pub ty: char,
+    pub name: Option<&'a str>,
+    pub path: bool,
+}
+
+/// Lists the entries of a directory for path completion.
+///
+/// Called with the directory part of the argument being typed (`""` for the current
+/// directory, otherwise ending with `/`) and a sink taking each entry name, with a trailing
+/// `/` for subdirectories. Targets without a filesystem can provide their own, e.g. listing
+/// the files of a flash filesystem.
+///
+pub type DirLister = fn(dir: &str, entry: &mut dyn FnMut(&str));
+
+/// One `--name` flag or `--name=T` option of a command descriptor.
+/// - `ty` is `None` for flags and the value type for options.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Switch<'a> {
+    pub name: &'a str,
+    pub ty: Option<char>,
+}
+
+/// The argument being typed at the end of a command line.
+/// - `index`: positional index of the argument (flags and options are not counted).
+/// - `option`: name of the `--option` whose value is being typed, if any.
+/// - `partial`: the text typed so far for the argument (a suffix of the line, may be empty).
+/// - `switches_done`: no flag or option can follow (the command has none, or a lone `--`
+///   ended flag parsing).
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArgContext<'a> {
+    pub command: &'a str,
+    pub spec: &'a str,
+    pub index: usize,
+    pub option: Option<&'a str>,
+    pub partial: &'a str,
+    pub switches_done: bool,
+}
+
+/// Iterator over the positional parameters of a descriptor.
+///
+pub struct Params<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Iterator for Params<'a> {
+    type Item = ArgParam<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chars = self.rest.trim_start().chars();
+        let ty = chars.next().filter(|c| TYPE_CHARS.contains(*c));
+        let Some(ty) = ty else {
+            self.rest = "";
+            return None;
+        };
+        let mut rest = chars.as_str();
+        let mut name = None;
+        let mut path = false;
+        loop {
+            if let Some(r) = rest.strip_prefix('{') {
+                let end = r.find('}').unwrap_or(r.len());
+                path |= &r[..end] == "path";
+                rest = r.get(end + 1..).unwrap_or("");
+            } else if let Some(r) = rest.strip_prefix(':') {
+                let end = ident_end(r);
+                name = Some(&r[..end]).filter(|n| !n.is_empty());
+                rest = &r[end..];
+            } else {
+                break;
+            }
+        }
+        self.rest = rest;
+        Some(ArgParam { ty, name, path })
+    }
+}
+
+/// Iterator over the flags and options of a descriptor.
+///
+pub struct Switches<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Iterator for Switches<'a> {
+    type Item = Switch<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let i = self.rest.find("--")?;
+            let at_token_start = self.rest[..i]
+                .chars()
+                .next_back()
+                .is_none_or(char::is_whitespace);
+            let r = &self.rest[i + 2..];
+            if !at_token_start {
+                self.rest = r;
+                continue;
+            }
+            let end = r
+                .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+                .unwrap_or(r.len());
+            let name = &r[..end];
+            let mut rest = &r[end..];
+            let mut ty = None;
+            if let Some(t) = rest.strip_prefix('=') {
+                let mut chars = t.chars();
+                ty = chars.next();
+                rest = chars.as_str();
+                if rest.starts_with('(') {
+                    rest = skip_parens(rest);
+                }
+            }
+            self.rest = rest;
+            if !name.is_empty() {
+                return Some(Switch { name, ty });
+            }
+        }
+    }
+}
+
+/// Returns the positional parameters of a descriptor such as `"D:freq F{0.0..=1.0}:duty"`.
+///
+pub fn params(spec: &str) -> Params<'_> {
+    Params { rest: spec }
+}
+
+/// Returns the flags and options of a descriptor such as `"s --count=D(1) --quiet"`.
+///
+pub fn switches(spec: &str) -> Switches<'_> {
+    Switches { rest: spec }
+}
+
+/// Returns the readable type name of a descriptor character, as shown by `usage()`.
+///
+pub fn type_name(ty: char) -> &'static str {
+    match ty {
+        'B' => "u8",
+        'W' => "u16",
+        'D' => "u32",
+        'Q' => "u64",
+        'X' => "u128",
+        'b' => "i8",
+        'w' => "i16",
+        'd' => "i32",
+        'q' => "i64",
+        'x' => "i128",
+        'Z' => "usize",
+        'z' => "isize",
+        'f' => "f32",
+        'F' => "f64",
+        't' => "bool",
+        'c' => "char",
+        's' => "str",
+        'h' => "hexstr",
+        'a' => "bytes",
+        'T' => "timestamp",
+        _ => "?",
+    }
+}
+
+/// Determines the argument being typed at the end of `line`.
+///
+/// Returns `None` until the command name is followed by whitespace, or if the name is not
+/// one of `commands` (`(name, descriptor)` pairs, e.g. the generated `get_commands()`).
+///
+pub fn context<'a>(line: &'a str, commands: &'a [(&'a str, &'a str)]) -> Option<ArgContext<'a>> {
+    let (command, _) = line.split_once(char::is_whitespace)?;
+    let &(_, spec) = commands.iter().find(|(name, _)| *name == command)?;
+    let head = line.trim_end_matches(|c: char| !c.is_whitespace());
+    let partial = &line[head.len()..];
+    let walk = walk(spec, head[command.len()..].split_whitespace(), |_, _| {});
+
+    let mut ctx = ArgContext {
+        command,
+        spec,
+        index: walk.index,
+        option: walk.pending_option,
+        partial,
+        switches_done: walk.switches_done,
+    };
+    if ctx.option.is_none()
+        && !ctx.switches_done
+        && let Some((name, value)) = partial.strip_prefix("--").and_then(|s| s.split_once('='))
+        && option_type(spec, name).is_some()
+    {
+        ctx.option = Some(name);
+        ctx.partial = value;
+    }
+    Some(ctx)
+}
+
+/// Returns the positional argument `index` of a complete `command` line, e.g. a history entry.
+///
+pub fn positional_value<'a>(
+    line: &'a str,
+    command: &str,
+    spec: &str,
+    index: usize,
+) -> Option<&'a str> {
+    let mut tokens = line.split_whitespace();
+    if tokens.next() != Some(command) {
+        return None;
+    }
+    let mut value = None;
+    walk(spec, tokens, |i, tok| {
+        if i == index {
+            value = Some(tok);
+        }
+    });
+    value
+}
+
+/// Returns the placeholder for the argument being typed, e.g. `<u32 freq>`, or an empty
+/// string if the command takes no further positional argument.
+///
+pub fn placeholder<const N: usize>(ctx: &ArgContext) -> String<N> {
+    let mut out = String::new();
+    let param = match ctx.option {
+        Some(name) => option_type(ctx.spec, name).map(|ty| ArgParam {
+            ty,
+            name: Some(name),
+            path: false,
+        }),
+        None => params(ctx.spec).nth(ctx.index),
+    };
+    if let Some(p) = param {
+        let _ = out.push('<');
+        let _ = out.push_str(if p.path { "path" } else { type_name(p.ty) });
+        if let Some(name) = p.name {
+            let _ = out.push(' ');
+            let _ = out.push_str(name);
+        }
+        let _ = out.push('>');
+    }
+    out
+}
+
+/// Collects the values that complete the argument being typed.
+///
+/// - A partial starting with `-`, or an argument past the last positional, offers the
+///   command's `--flag`s and `--option=`s.
+/// - A `bool` argument offers `true` and `false`.
+/// - Any other positional offers the values used for it in `history` (complete command lines).
+///
+/// Only values starting with the partial are returned, without duplicates.
+///
+pub fn candidates<const N: usize, const M: usize, S: AsRef<str>>(
+    ctx: &ArgContext,
+    history: impl IntoIterator<Item = S>,
+) -> Vec<String<N>, M> {
+    let mut out = Vec::new();
+    let param = match ctx.option {
+        Some(name) => option_type(ctx.spec, name),
+        None => params(ctx.spec).nth(ctx.index).map(|p| p.ty),
+    };
+
+    let wants_switch = ctx.option.is_none()
+        && !ctx.switches_done
+        && (ctx.partial.starts_with('-') || param.is_none());
+    if wants_switch {
+        for sw in switches(ctx.spec) {
+            let mut text = String::<N>::new();
+            let _ = text.push_str("--");
+            let _ = text.push_str(sw.name);
+            if sw.ty.is_some() {
+                let _ = text.push('=');
+            }
+            push_candidate(&mut out, ctx.partial, &text);
+        }
+        return out;
+    }
+
+    match param {
+        Some('t') => {
+            push_candidate(&mut out, ctx.partial, "true");
+            push_candidate(&mut out, ctx.partial, "false");
+        }
+        Some(_) if ctx.option.is_none() => {
+            for entry in history {
+                if let Some(value) =
+                    positional_value(entry.as_ref(), ctx.command, ctx.spec, ctx.index)
+                {
+                    push_candidate(&mut out, ctx.partial, value);
+                }
+            }
+        }
+        _ => {}
+    }
+    out
+}
+
+/// Returns `true` if the argument being typed is a file name (`s{path}` in the descriptor).
+///
+pub fn is_path(ctx: &ArgContext) -> bool {
+    ctx.option.is_none() && params(ctx.spec).nth(ctx.index).is_some_and(|p| p.path)
+}
+
+/// Collects the file names that complete the path being typed, listed by `list`.
+///
+/// Returns the matches (sorted, at most `M`) and the longest prefix common to all of them,
+/// which may be shorter than the one of the returned matches when some did not fit. Hidden
+/// entries are only offered once the name being typed starts with `.`, and names containing
+/// whitespace are skipped as they would be split into several arguments.
+///
+pub fn path_candidates<const N: usize, const M: usize>(
+    partial: &str,
+    list: DirLister,
+) -> (Vec<String<N>, M>, String<N>) {
+    let mut out = Vec::new();
+    let mut common: Option<String<N>> = None;
+    let (dir, prefix) = partial.split_at(partial.rfind('/').map_or(0, |i| i + 1));
+    list(dir, &mut |name| {
+        if !name.starts_with(prefix)
+            || (name.starts_with('.') && !prefix.starts_with('.'))
+            || name.contains(char::is_whitespace)
+        {
+            return;
+        }
+        let mut text = String::<N>::new();
+        if text.push_str(dir).is_err() || text.push_str(name).is_err() {
+            return;
+        }
+        match &mut common {
+            Some(c) => {
+                let len = c
+                    .char_indices()
+                    .zip(text.chars())
+                    .find(|((_, a), b)| a != b)
+                    .map_or(c.len().min(text.len()), |((i, _), _)| i);
+                c.truncate(len);
+            }
+            None => common = Some(text.clone()),
+        }
+        push_candidate(&mut out, partial, &text);
+    });
+    out.sort_unstable();
+    (out, common.unwrap_or_default())
+}
+
+/// Lists the entries of a directory of the filesystem, the [`DirLister`] used by default.
+///
+#[cfg(feature = "std")]
+pub fn list_dir(dir: &str, entry: &mut dyn FnMut(&str)) {
+    let Ok(entries) = std::fs::read_dir(if dir.is_empty() { "." } else { dir }) else {
+        return;
+    };
+    for e in entries.flatten() {
+        let name = e.file_name();
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        if e.file_type().is_ok_and(|t| t.is_dir()) {
+            entry(&std::format!("{}/", name));
+        } else {
+            entry(name);
+        }
+    }
+}
+
+/// Returns the value type of option `name` in a descriptor, or `None` if it is not an option.
+///
+fn option_type(spec: &str, name: &str) -> Option<char> {
+    switches(spec)
+        .find(|sw| sw.name == name)
+        .and_then(|sw| sw.ty)
+}
+
+/// Result of walking the complete tokens of an argument list.
+struct Walk<'a> {
+    index: usize,
+    pending_option: Option<&'a str>,
+    switches_done: bool,
+}
+
+/// Walks argument tokens like the dispatcher does: `--flag`, `--opt=value` and `--opt value`
+/// are skipped, a lone `--` ends flag parsing, and every other token is a positional passed
+/// to `on_positional` with its index. Commands without switches treat all tokens as positional.
+///
+fn walk<'a>(
+    spec: &str,
+    tokens: impl Iterator<Item = &'a str>,
+    mut on_positional: impl FnMut(usize, &'a str),
+) -> Walk<'a> {
+    let has_switches = switches(spec).next().is_some();
+    let mut walk = Walk {
+        index: 0,
+        pending_option: None,
+        switches_done: !has_switches,
+    };
+    for tok in tokens {
+        if walk.pending_option.take().is_some() {
+            continue;
+        }
+        if !walk.switches_done {
+            if tok == "--" {
+                walk.switches_done = true;
+                continue;
+            }
+            if let Some(name) = tok.strip_prefix("--") {
+                if !name.contains('=') && option_type(spec, name).is_some() {
+                    walk.pending_option = Some(name);
+                }
+                continue;
+            }
+        }
+        on_positional(walk.index, tok);
+        walk.index += 1;
+    }
+    walk
+}
+
+/// Pushes `value` if it starts with `partial` and is not already present.
+fn push_candidate<const N: usize, const M: usize>(
+    out: &mut Vec<String<N>, M>,
+    partial: &str,
+    value: &str,
+) {
+    if value.starts_with(partial)
+        && !out.iter().any(|c| c.as_str() == value)
+        && let Ok(text) = String::try_from(value)
+    {
+        let _ = out.push(text);
+    }
+}
+
+/// Returns the byte length of the identifier at the start of `s`.
+fn ident_end(s: &str) -> usize {
+    s.find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(s.len())
+}
+
+/// Skips a parenthesized default value (quote-aware) and returns what follows it.
+fn skip_parens(s: &str) -> &str {
+    let mut depth = 0usize;
+    let mut in_quote = false;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        if in_quote {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_quote = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_quote = true,
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return &s[i + 1..];
+                }
+            }
+            _ => {}
+        }
+    }
+    ""
+}
+
+// ==================== TESTS =======================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMMANDS: &[(&str, &str)] = &[
+        ("set_pwm", "D:freq F{0.0..=1.0}:duty s:label"),
+        ("enable", "t:on"),
+        ("ping", "s --count=D(1) --label=s(\"a b\") --quiet"),
+        ("reset", "v"),
+        ("pair", "d{-10..=10}D{..4}"),
+        ("cat", "s{path}:file B"),
+    ];
+
+    fn ctx(line: &str) -> ArgContext<'_> {
+        context(line, COMMANDS).unwrap()
+    }
+
+    //----------------------------
+    // Descriptor parsing
+    //----------------------------
+
+    #[test]
+    fn test_params_with_names_and_ranges() {
+        let p: std::vec::Vec<_> = params("D:freq F{0.0..=1.0}:duty s:label").collect();
+        assert_eq!(p.len(), 3);
+        assert_eq!(
+            p[0],
+            ArgParam {
+                ty: 'D',
+                name: Some("freq"),
+                path: false
+            }
+        );
+        assert_eq!(
+            p[1],
+            ArgParam {
+                ty: 'F',
+                name: Some("duty"),
+                path: false
+            }
+        );
+        assert_eq!(p[2].name, Some("label"));
+    }
+
+    #[test]
+    fn test_params_compact_and_void() {
+        let p: std::vec::Vec<_> = params("d{-10..=10}D{..4}").map(|p| p.ty).collect();
+        assert_eq!(p, ['d', 'D']);
+        assert_eq!(params("v --verbose").count(), 0);
+        assert_eq!(params("Display help").count(), 1);
+    }
+
+    #[test]
+    fn test_switches_skip_defaults() {
+        let s: std::vec::Vec<_> =
+            switches("s --count=D(1) --label=s(\"a -- b)\") --quiet").collect();
+        assert_eq!(
+            s,
+            [
+                Switch {
+                    name: "count",
+                    ty: Some('D')
+                },
+                Switch {
+                    name: "label",
+                    ty: Some('s')
+                },
+                Switch {
+                    name: "quiet",
+                    ty: None
+                },
+            ]
+        );
+    }
+
+    //----------------------------
+    // Context
+    //----------------------------
+
+    #[test]
+    fn test_context_requires_complete_command() {
+        assert!(context("set_pwm", COMMANDS).is_none());
+        assert!(context("set_pw 1", COMMANDS).is_none());
+        let c = ctx("set_pwm ");
+        assert_eq!((c.index, c.partial), (0, ""));
+    }
+
+    #[test]
+    fn test_context_counts_positionals() {
+        let c = ctx("set_pwm 100 0.");
+        assert_eq!((c.index, c.partial), (1, "0."));
+        let c = ctx("ping --quiet --count 3 host ");
+        assert_eq!((c.index, c.option), (1, None));
+    }
+
+    #[test]
+    fn test_context_option_values() {
+        let c = ctx("ping --count ");
+        assert_eq!((c.index, c.option, c.partial), (0, Some("count"), ""));
+        let c = ctx("ping host --label=l");
+        assert_eq!((c.index, c.option, c.partial), (1, Some("label"), "l"));
+    }
+
+    #[test]
+    fn test_context_end_of_switches() {
+        let c = ctx("ping -- --");
+        assert!(c.switches_done);
+        let empty: Vec<String<16>, MAX_ARG_CANDIDATES> = candidates(&c, [""; 0]);
+        assert!(empty.is_empty());
+    }
+
+    //----------------------------
+    // Placeholders and candidates
+    //----------------------------
+
+    #[test]
+    fn test_placeholder() {
+        assert_eq!(placeholder::<32>(&ctx("set_pwm 1 ")).as_str(), "<f64 duty>");
+        assert_eq!(placeholder::<32>(&ctx("pair ")).as_str(), "<i32>");
+        assert_eq!(
+            placeholder::<32>(&ctx("ping --count ")).as_str(),
+            "<u32 count>"
+        );
+        assert_eq!(placeholder::<32>(&ctx("reset ")).as_str(), "");
+    }
+
+    #[test]
+    fn test_bool_candidates() {
+        let c: Vec<String<16>, MAX_ARG_CANDIDATES> = candidates(&ctx("enable "), [""; 0]);
+        assert_eq!(c, ["true", "false"]);
+        let c: Vec<String<16>, MAX_ARG_CANDIDATES> = candidates(&ctx("enable f"), [""; 0]);
+        assert_eq!(c, ["false"]);
+    }
+
+    #[test]
+    fn test_switch_candidates() {
+        let c: Vec<String<16>, MAX_ARG_CANDIDATES> = candidates(&ctx("ping host "), [""; 0]);
+        assert_eq!(c, ["--count=", "--label=", "--quiet"]);
+        let c: Vec<String<16>, MAX_ARG_CANDIDATES> = candidates(&ctx("ping --q"), [""; 0]);
+        assert_eq!(c, ["--quiet"]);
+    }
+
+    #[test]
+    fn test_history_candidates() {
+        let history = [
+            "set_pwm 100 0.5 a",
+            "ping 10.0.0.1",
+            "set_pwm 200 0.5 b",
+            "set_pwm 100 0.2 c",
+        ];
+        let c: Vec<String<16>, MAX_ARG_CANDIDATES> = candidates(&ctx("set_pwm "), history);
+        assert_eq!(c, ["100", "200"]);
+        let c: Vec<String<16>, MAX_ARG_CANDIDATES> = candidates(&ctx("set_pwm 1 0."), history);
+        assert_eq!(c, ["0.5", "0.2"]);
+        let c: Vec<String<16>, MAX_ARG_CANDIDATES> =
+            candidates(&ctx("ping --count=5 "), ["ping --count=2 host"]);
+        assert_eq!(c, ["host"]);
+    }
+
+    #[test]
+    fn test_positional_value() {
+        assert_eq!(
+            positional_value("ping --count 3 host", "ping", COMMANDS[2].1, 0),
+            Some("host")
+        );
+        assert_eq!(positional_value("pair 1 2", "ping", COMMANDS[4].1, 0), None);
+        assert_eq!(
+            positional_value("pair 1 2", "pair", COMMANDS[4].1, 1),
+            Some("2")
+        );
+    }
+
+    //----------------------------
+    // Paths
+    //----------------------------
+
+    fn vfs(dir: &str, entry: &mut dyn FnMut(&str)) {
+        let names: &[&str] = match dir {
+            "" => &["boot.cfg", "bin/", "boot/", ".hidden", "my file"],
+            "boot/" => &["kernel", "initrd"],
+            _ => &[],
+        };
+        names.iter().for_each(|n| entry(n));
+    }
+
+    #[test]
+    fn test_path_params() {
+        assert!(is_path(&ctx("cat ")));
+        assert!(!is_path(&ctx("cat x ")));
+        assert!(!is_path(&ctx("set_pwm 1 2 ")));
+        assert_eq!(placeholder::<32>(&ctx("cat ")).as_str(), "<path file>");
+    }
+
+    #[test]
+    fn test_path_candidates() {
+        let (c, common): (Vec<String<16>, MAX_ARG_CANDIDATES>, String<16>) =
+            path_candidates("bo", vfs);
+        assert_eq!(c, ["boot.cfg", "boot/"]);
+        assert_eq!(common, "boot");
+        let (c, _): (Vec<String<16>, MAX_ARG_CANDIDATES>, String<16>) =
+            path_candidates("boot/i", vfs);
+        assert_eq!(c, ["boot/initrd"]);
+        let (c, _): (Vec<String<16>, MAX_ARG_CANDIDATES>, String<16>) = path_candidates("", vfs);
+        assert_eq!(c, ["bin/", "boot.cfg", "boot/"]);
+        let (c, _): (Vec<String<16>, MAX_ARG_CANDIDATES>, String<16>) = path_candidates(".", vfs);
+        assert_eq!(c, [".hidden"]);
+    }
+
+    #[test]
+    fn test_path_common_prefix_covers_dropped_matches() {
+        let (c, common): (Vec<String<16>, 1>, String<16>) = path_candidates("b", vfs);
+        assert_eq!(c.len(), 1);
+        assert_eq!(common, "b");
+    }
+}
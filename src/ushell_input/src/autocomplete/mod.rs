@@ -1,5 +1,27 @@
+pub mod args;
+
 use heapless::{String, Vec};
 
+/// How `Autocomplete` matches the input against its candidates.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MatchMode {
+    /// Candidates must start with the input (the default).
+    #[default]
+    Prefix,
+    /// Candidates must contain the input characters in order, e.g. `gst` matches `get_status`.
+    /// Matches are ranked: prefix matches first, then by earliest start and fewest gaps.
+    Subsequence,
+}
+
+/// A secondary source of completions made of full command lines (with arguments),
+/// e.g. the `History`.
+///
+pub trait LineSource {
+    /// Calls `f` with each line, most recent first, until `f` returns `false`.
+    fn visit_lines(&self, f: &mut dyn FnMut(&str) -> bool);
+}
+
 /// Autocomplete struct for managing and filtering command candidates.
 /// - `'a`: Lifetime for string slices.
 /// - `NC`: Maximum number of candidates, NUM_COMMANDS.
@@ -14,6 +36,10 @@ pub struct Autocomplete<'a, const NC: usize, const FNL: usize> {
     input: String<FNL>,
     /// Index for cycling through filtered candidates with Tab.
     tab_index: usize,
+    /// Matching strategy.
+    mode: MatchMode,
+    /// Match candidates regardless of letter case.
+    ignore_case: bool,
 }
 
 impl<'a, const NC: usize, const FNL: usize> Autocomplete<'a, NC, FNL> {
@@ -25,19 +51,73 @@ impl<'a, const NC: usize, const FNL: usize> Autocomplete<'a, NC, FNL> {
             filtered: Vec::new(),
             input: String::new(),
             tab_index: 0,
+            mode: MatchMode::Prefix,
+            ignore_case: false,
+        }
+    }
+
+    /// Selects the matching strategy (builder style), e.g.
+    /// `Autocomplete::new(candidates).with_mode(MatchMode::Subsequence)`.
+    ///
+    pub fn with_mode(mut self, mode: MatchMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Enables case-insensitive matching (builder style).
+    ///
+    /// Completions always use the candidate's own casing, so `SET` completes `set_voltage`.
+    ///
+    pub fn with_ignore_case(mut self, ignore_case: bool) -> Self {
+        self.ignore_case = ignore_case;
+        self
+    }
+
+    /// Adds candidates after the existing ones, e.g. the commands of another module.
+    ///
+    /// Returns `false` and adds none if they do not all fit in `NC`.
+    ///
+    pub fn add_candidates(&mut self, candidates: impl ExactSizeIterator<Item = &'a str>) -> bool {
+        if candidates.len() > NC - self.candidates.len() {
+            return false;
+        }
+        for c in candidates {
+            let _ = self.candidates.push(c);
         }
+        true
     }
 
     /// Updates the input string and filters candidates accordingly.
     /// - If only one match, auto-completes input.
-    /// - If multiple matches, fills input with the longest common prefix.
+    /// - If multiple matches, fills input with the longest common prefix
+    ///   (only if it extends the input, which matters in subsequence mode).
+    /// - Completed text keeps the candidates' casing, also when matching ignores case.
     ///
     pub fn update_input(&mut self, new_input: String<FNL>) {
         self.input = new_input;
         self.filtered.clear();
-        for c in self.candidates.iter().copied() {
-            if c.starts_with(self.input.as_str()) {
-                let _ = self.filtered.push(c); // Ignore overflow
+        match self.mode {
+            MatchMode::Prefix => {
+                for c in self.candidates.iter().copied() {
+                    if Self::has_prefix(c, &self.input, self.ignore_case) {
+                        let _ = self.filtered.push(c); // Ignore overflow
+                    }
+                }
+            }
+            MatchMode::Subsequence => {
+                let mut ranked = Vec::<((bool, usize, usize, usize), &'a str), NC>::new();
+                for (i, c) in self.candidates.iter().copied().enumerate() {
+                    if let Some((start, gaps)) =
+                        Self::subsequence_match(c, &self.input, self.ignore_case)
+                    {
+                        let is_prefix = Self::has_prefix(c, &self.input, self.ignore_case);
+                        let _ = ranked.push(((!is_prefix, start, gaps, i), c));
+                    }
+                }
+                ranked.sort_unstable_by_key(|(rank, _)| *rank);
+                for (_, c) in ranked {
+                    let _ = self.filtered.push(c);
+                }
             }
         }
         self.tab_index = 0;
@@ -46,10 +126,55 @@ impl<'a, const NC: usize, const FNL: usize> Autocomplete<'a, NC, FNL> {
             let _ = self.input.push_str(self.filtered[0]);
             let _ = self.input.push(' ');
         } else if self.filtered.len() > 1 {
-            self.input = Self::longest_common_prefix(&self.filtered);
+            let prefix = Self::longest_common_prefix(&self.filtered);
+            if Self::has_prefix(&prefix, &self.input, self.ignore_case) {
+                self.input = prefix;
+            }
         }
     }
 
+    /// Matches `input` as a subsequence of `candidate`, taking the leftmost character each time.
+    ///
+    /// Returns the position of the first matched character and the number of skipped
+    /// characters between the first and the last matched one, or `None` if it does not match.
+    ///
+    fn subsequence_match(
+        candidate: &str,
+        input: &str,
+        ignore_case: bool,
+    ) -> Option<(usize, usize)> {
+        let mut chars = candidate.chars().enumerate();
+        let mut start = None;
+        let mut last = 0;
+        let mut gaps = 0;
+        for wanted in input.chars() {
+            let (pos, _) = chars.find(|&(_, c)| Self::chars_eq(c, wanted, ignore_case))?;
+            match start {
+                None => start = Some(pos),
+                Some(_) => gaps += pos - last - 1,
+            }
+            last = pos;
+        }
+        Some((start.unwrap_or(0), gaps))
+    }
+
+    /// Checks whether `candidate` starts with `input`, optionally ignoring case.
+    ///
+    fn has_prefix(candidate: &str, input: &str, ignore_case: bool) -> bool {
+        let mut chars = candidate.chars();
+        input.chars().all(|wanted| {
+            chars
+                .next()
+                .is_some_and(|c| Self::chars_eq(c, wanted, ignore_case))
+        })
+    }
+
+    /// Compares two characters, optionally ignoring case.
+    ///
+    fn chars_eq(a: char, b: char, ignore_case: bool) -> bool {
+        a == b || (ignore_case && a.to_lowercase().eq(b.to_lowercase()))
+    }
+
     /// cycles forward through filtered candidates.
     ///
     pub fn cycle_forward(&mut self) {
@@ -78,6 +203,36 @@ impl<'a, const NC: usize, const FNL: usize> Autocomplete<'a, NC, FNL> {
         let _ = self.input.push(' ');
     }
 
+    /// Completes a whole command line from a secondary `source`.
+    ///
+    /// Returns the most recent line of `source` that starts with `input` and is longer than it
+    /// (e.g. `set_voltage 3.3` for `set_v`), or `None` for an empty input. Lines are compared
+    /// exactly, so the result always extends what was typed.
+    ///
+    pub fn complete_line<const IML: usize>(
+        &self,
+        input: &str,
+        source: &impl LineSource,
+    ) -> Option<String<IML>> {
+        if input.is_empty() {
+            return None;
+        }
+        let mut found = None;
+        source.visit_lines(&mut |line| {
+            if line.len() > input.len() && line.starts_with(input) {
+                found = String::try_from(line).ok();
+            }
+            found.is_none()
+        });
+        found
+    }
+
+    /// Returns the candidates matching the current input.
+    ///
+    pub fn matches(&self) -> &[&'a str] {
+        &self.filtered
+    }
+
     /// Returns the current input string.
     ///
     pub fn current_input(&self) -> &str {
@@ -147,6 +302,19 @@ mod tests {
         assert_eq!(ac.tab_index, 0);
     }
 
+    #[test]
+    fn test_add_candidates() {
+        let mut ac = Autocomplete::<NC, FNL>::new(make_candidates());
+        assert!(!ac.add_candidates(["delta", "epsilon"].into_iter()));
+        assert!(ac.add_candidates(["delta"].into_iter()));
+        assert_eq!(ac.candidates.len(), NC);
+
+        let mut s: String<FNL> = String::new();
+        s.push_str("d").unwrap();
+        ac.update_input(s);
+        assert_eq!(ac.current_input(), "delta ");
+    }
+
     //----------------------------
     // Filtering behavior
     //----------------------------
@@ -341,11 +509,163 @@ mod tests {
         assert!(overflow_attempt.is_err());
     }
 
+    //----------------------------
+    // Subsequence matching
+    //----------------------------
+
+    fn fuzzy(input: &str) -> Autocomplete<'static, NC, FNL> {
+        let mut v: Vec<&'static str, NC> = Vec::new();
+        for c in ["git_stash", "set_timer", "get_status", "gst", "list_tasks"] {
+            v.push(c).unwrap();
+        }
+        let mut ac = Autocomplete::<NC, FNL>::new(v).with_mode(MatchMode::Subsequence);
+        ac.update_input(String::try_from(input).unwrap());
+        ac
+    }
+
+    #[test]
+    fn test_default_mode_is_prefix() {
+        let ac = Autocomplete::<NC, FNL>::new(make_candidates());
+        assert_eq!(ac.mode, MatchMode::Prefix);
+    }
+
+    #[test]
+    fn test_subsequence_ranking() {
+        let ac = fuzzy("gst");
+        assert_eq!(ac.matches(), ["gst", "git_stash", "get_status"]);
+        assert_eq!(ac.current_input(), "gst"); // LCP "g" would shrink the input
+        let ac = fuzzy("st");
+        assert_eq!(
+            ac.matches(),
+            ["set_timer", "gst", "list_tasks", "git_stash", "get_status"]
+        );
+        assert_eq!(ac.current_input(), "st");
+    }
+
+    #[test]
+    fn test_subsequence_single_match_completes() {
+        let ac = fuzzy("lks");
+        assert_eq!(ac.matches(), ["list_tasks"]);
+        assert_eq!(ac.current_input(), "list_tasks ");
+    }
+
+    #[test]
+    fn test_subsequence_no_match() {
+        let ac = fuzzy("xq");
+        assert!(ac.matches().is_empty());
+        assert_eq!(ac.current_input(), "xq");
+    }
+
+    #[test]
+    fn test_subsequence_match_positions() {
+        assert_eq!(
+            Autocomplete::<NC, FNL>::subsequence_match("get_status", "gst", false),
+            Some((0, 3))
+        );
+        assert_eq!(
+            Autocomplete::<NC, FNL>::subsequence_match("set_timer", "tm", false),
+            Some((2, 3))
+        );
+        assert_eq!(
+            Autocomplete::<NC, FNL>::subsequence_match("abc", "ca", false),
+            None
+        );
+        assert_eq!(
+            Autocomplete::<NC, FNL>::subsequence_match("abc", "", false),
+            Some((0, 0))
+        );
+        assert_eq!(
+            Autocomplete::<NC, FNL>::subsequence_match("get_status", "GST", true),
+            Some((0, 3))
+        );
+    }
+
+    //----------------------------
+    // Line source
+    //----------------------------
+
+    struct Lines(&'static [&'static str]);
+
+    impl LineSource for Lines {
+        fn visit_lines(&self, f: &mut dyn FnMut(&str) -> bool) {
+            for line in self.0.iter().rev() {
+                if !f(line) {
+                    break;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_complete_line_prefers_most_recent() {
+        let ac = Autocomplete::<NC, FNL>::new(make_candidates());
+        let lines = Lines(&["beta 1", "gamma 7", "beta 2 --fast"]);
+        let line: Option<String<32>> = ac.complete_line("be", &lines);
+        assert_eq!(line.as_deref(), Some("beta 2 --fast"));
+        let line: Option<String<32>> = ac.complete_line("beta 1", &lines);
+        assert_eq!(line, None); // not longer than the input
+        let line: Option<String<32>> = ac.complete_line("", &lines);
+        assert_eq!(line, None);
+    }
+
+    //----------------------------
+    // Case-insensitive matching
+    //----------------------------
+
+    fn caseless(input: &str) -> Autocomplete<'static, NC, FNL> {
+        let mut v: Vec<&'static str, NC> = Vec::new();
+        for c in ["set_voltage", "set_timer", "Reset", "status"] {
+            v.push(c).unwrap();
+        }
+        let mut ac = Autocomplete::<NC, FNL>::new(v).with_ignore_case(true);
+        ac.update_input(String::try_from(input).unwrap());
+        ac
+    }
+
+    #[test]
+    fn test_ignore_case_keeps_canonical_casing() {
+        let ac = caseless("SET_V");
+        assert_eq!(ac.matches(), ["set_voltage"]);
+        assert_eq!(ac.current_input(), "set_voltage ");
+
+        let ac = caseless("reS");
+        assert_eq!(ac.current_input(), "Reset ");
+    }
+
+    #[test]
+    fn test_ignore_case_common_prefix() {
+        let ac = caseless("SE");
+        assert_eq!(ac.matches(), ["set_voltage", "set_timer"]);
+        assert_eq!(ac.current_input(), "set_");
+    }
+
+    #[test]
+    fn test_case_sensitive_by_default() {
+        let mut v: Vec<&'static str, NC> = Vec::new();
+        v.push("set_voltage").unwrap();
+        let mut ac = Autocomplete::<NC, FNL>::new(v);
+        ac.update_input(String::try_from("SET").unwrap());
+        assert!(ac.matches().is_empty());
+        assert_eq!(ac.current_input(), "SET");
+    }
+
+    #[test]
+    fn test_ignore_case_with_subsequence() {
+        let mut v: Vec<&'static str, NC> = Vec::new();
+        v.push("get_status").unwrap();
+        let mut ac = Autocomplete::<NC, FNL>::new(v)
+            .with_mode(MatchMode::Subsequence)
+            .with_ignore_case(true);
+        ac.update_input(String::try_from("GST").unwrap());
+        assert_eq!(ac.current_input(), "get_status ");
+    }
+
     //----------------------------
     // Fuzz-like deterministic randomized test
     //----------------------------
 
     #[test]
+    #[allow(clippy::len_zero)]
     fn test_fuzz_random_sequences() {
         let mut ac = Autocomplete::<NC, FNL>::new(make_candidates());
 
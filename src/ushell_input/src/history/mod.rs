@@ -1,14 +1,29 @@
 #[cfg(feature = "history-persistence")]
 extern crate std;
 
-#[cfg(feature = "history-persistence")]
-const HISTORY_FILENAME: &str = ".hist";
-
-#[cfg(feature = "history-persistence")]
-use std::fmt::Write;
+/// File the history is persisted to by default (in the current directory), with the
+/// `history-persistence` feature.
+pub const HISTORY_FILENAME: &str = ".hist";
 
 use heapless::String;
 
+use crate::autocomplete::LineSource;
+use crate::output::Output;
+
+/// How [`History::push`] handles an entry equal to one already stored.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DedupPolicy {
+    /// Every entry is stored, duplicates included.
+    AllowDuplicates,
+    /// An entry equal to the most recent one is not stored again.
+    IgnoreConsecutive,
+    /// An earlier copy of the entry is removed, so re-run commands move to the most recent
+    /// position (default).
+    #[default]
+    MoveToFront,
+}
+
 /// Metadata for a single entry in the history buffer.
 /// Stores the offset and length of the entry in the circular buffer.
 ///
@@ -28,7 +43,10 @@ pub struct History<const HTC: usize, const HME: usize> {
     data_head: usize,
     entry_head: usize,
     entry_size: usize,
+    used_bytes: usize,
     current_index: usize,
+    dedup: DedupPolicy,
+    persistence: Option<&'static str>,
 }
 
 /// Iterator over history entries, yielding only the string values.
@@ -38,6 +56,13 @@ pub struct HistoryIter<'a, const HTC: usize, const HME: usize, const IML: usize>
     index: usize,
 }
 
+/// Iterator over history entries from the most recent to the oldest, yielding only the string values.
+///
+pub struct HistoryRevIter<'a, const HTC: usize, const HME: usize, const IML: usize> {
+    history: &'a History<HTC, HME>,
+    remaining: usize,
+}
+
 /// Iterator over history entries, yielding (index, string) pairs.
 ///
 pub struct HistoryWithIndexesIter<'a, const HTC: usize, const HME: usize, const IML: usize> {
@@ -57,29 +82,76 @@ impl<const HTC: usize, const HME: usize> Default for History<HTC, HME> {
 /// Implement History
 ///
 impl<const HTC: usize, const HME: usize> History<HTC, HME> {
-    /// Creates a new, empty history buffer.
+    /// Creates a new history buffer.
+    ///
+    /// With the `history-persistence` feature it is persisted to [`HISTORY_FILENAME`] (see
+    /// [`with_persistence`](Self::with_persistence)); otherwise it starts empty.
     pub fn new() -> Self {
+        if cfg!(feature = "history-persistence") {
+            Self::with_persistence(Some(HISTORY_FILENAME))
+        } else {
+            Self::with_persistence(None)
+        }
+    }
+
+    /// Creates a history persisted to the file at `path`, or never stored when `None`.
+    ///
+    /// The entries are loaded from the file, and each pushed entry is appended to it.
+    /// Without the `history-persistence` feature no file is read or written.
+    pub fn with_persistence(path: Option<&'static str>) -> Self {
         const NONE: Option<EntryMeta> = None;
-        let instance = Self {
+        let mut instance = Self {
             data: [0; HTC],
             entries: [NONE; HME],
             data_head: 0,
             entry_head: 0,
             entry_size: 0,
+            used_bytes: 0,
             current_index: 0,
+            dedup: DedupPolicy::MoveToFront,
+            persistence: None,
         };
-        #[cfg(feature = "history-persistence")]
-        let instance = {
-            let mut inst = instance;
-            inst.load_from_file(HISTORY_FILENAME);
-            inst
-        };
+        instance.set_persistence(path);
         instance
     }
 
+    /// Changes the file the history is persisted to, or disables persistence with `None`.
+    ///
+    /// The current entries are replaced by the ones loaded from the new file (none when
+    /// disabled), and each later push is appended to it. Without the `history-persistence`
+    /// feature no file is read or written.
+    pub fn set_persistence(&mut self, path: Option<&'static str>) {
+        self.clear();
+        #[cfg(feature = "history-persistence")]
+        if let Some(path) = path {
+            self.load_from_file(path);
+        }
+        self.persistence = path;
+    }
+
+    /// Returns the file the history is persisted to, if any.
+    pub fn persistence(&self) -> Option<&'static str> {
+        self.persistence
+    }
+
+    /// Sets how duplicate entries are handled by [`push`](Self::push).
+    ///
+    pub fn set_dedup_policy(&mut self, policy: DedupPolicy) {
+        self.dedup = policy;
+    }
+
+    /// Returns the duplicate handling policy.
+    ///
+    pub fn dedup_policy(&self) -> DedupPolicy {
+        self.dedup
+    }
+
     /// Pushes a new string into the history.
     /// - Trims whitespace.
-    /// - Rejects if entry is too large or a duplicate.
+    /// - Rejects if entry is too large.
+    /// - Handles a duplicate according to the [`DedupPolicy`]: with `IgnoreConsecutive` an
+    ///   entry equal to the most recent one is rejected, with `MoveToFront` the earlier copy
+    ///   is removed.
     /// - Removes oldest entries if needed to make space.
     /// - Returns `true` if the entry was added, `false` otherwise.
     ///
@@ -90,31 +162,22 @@ impl<const HTC: usize, const HME: usize> History<HTC, HME> {
         if len > HTC {
             return false;
         }
-        // Check for duplicates
-        for i in 0..self.entry_size {
-            if let Some(existing) = self.get::<HTC>(i)
-                && existing.trim() == trimmed
-            {
-                return false;
+        match self.dedup {
+            DedupPolicy::AllowDuplicates => {}
+            DedupPolicy::IgnoreConsecutive => {
+                if self.entry_size > 0 && self.entry_equals(self.entry_size - 1, trimmed) {
+                    return false;
+                }
             }
-        }
-        // Calculate used space
-        let mut used = 0;
-        for i in 0..self.entry_size {
-            let idx = (self.entry_head + HME - self.entry_size + i) % HME;
-            if let Some(meta) = self.entries[idx] {
-                used += meta.length;
+            DedupPolicy::MoveToFront => {
+                if let Some(index) = (0..self.entry_size).find(|&i| self.entry_equals(i, trimmed)) {
+                    self.remove(index);
+                }
             }
         }
-        let mut free = HTC - used;
-        // Remove oldest entries until enough space is available
-        while free < len && self.entry_size > 0 {
-            let oldest_idx = (self.entry_head + HME - self.entry_size) % HME;
-            if let Some(meta) = self.entries[oldest_idx] {
-                free += meta.length;
-            }
-            self.entries[oldest_idx] = None;
-            self.entry_size -= 1;
+        // Remove oldest entries until a slot and the bytes after `data_head` are free
+        while self.entry_size > 0 && (self.entry_size == HME || self.free_after_head() < len) {
+            self.evict_oldest();
         }
         // Write new entry
         let offset = self.data_head;
@@ -127,15 +190,93 @@ impl<const HTC: usize, const HME: usize> History<HTC, HME> {
             length: len,
         });
         self.entry_head = (self.entry_head + 1) % HME;
-        if self.entry_size < HME {
-            self.entry_size += 1;
-        }
+        self.entry_size += 1;
+        self.used_bytes += len;
         self.current_index = self.entry_size - 1;
         #[cfg(feature = "history-persistence")]
-        self.append_to_file(HISTORY_FILENAME, trimmed);
+        if let Some(path) = self.persistence {
+            self.append_to_file(path, trimmed);
+        }
         true
     }
 
+    /// Returns the slot in `entries` of the entry at `index` (0 is the oldest).
+    ///
+    fn slot(&self, index: usize) -> usize {
+        (self.entry_head + HME - self.entry_size + index) % HME
+    }
+
+    /// Returns `true` if the entry at `index` is `text`.
+    ///
+    fn entry_equals(&self, index: usize, text: &str) -> bool {
+        match self.entries[self.slot(index)] {
+            Some(meta) => {
+                meta.length == text.len()
+                    && text
+                        .bytes()
+                        .enumerate()
+                        .all(|(i, b)| self.data[(meta.offset + i) % HTC] == b)
+            }
+            None => false,
+        }
+    }
+
+    /// Returns `true` if the entry at `index` contains `needle`, comparing in place in the
+    /// circular buffer.
+    ///
+    fn entry_contains(&self, index: usize, needle: &str) -> bool {
+        let Some(meta) = self.entries[self.slot(index)] else {
+            return false;
+        };
+        let needle = needle.as_bytes();
+        if needle.len() > meta.length {
+            return false;
+        }
+        (0..=meta.length - needle.len()).any(|start| {
+            needle
+                .iter()
+                .enumerate()
+                .all(|(i, &b)| self.data[(meta.offset + start + i) % HTC] == b)
+        })
+    }
+
+    /// Removes the entry at `index`; newer entries move down by one.
+    ///
+    /// Its bytes stay in the buffer until the entries stored before them are evicted.
+    ///
+    fn remove(&mut self, index: usize) {
+        if let Some(meta) = self.entries[self.slot(index)] {
+            self.used_bytes -= meta.length;
+        }
+        for i in index..self.entry_size - 1 {
+            self.entries[self.slot(i)] = self.entries[self.slot(i + 1)];
+        }
+        let newest_idx = self.slot(self.entry_size - 1);
+        self.entries[newest_idx] = None;
+        self.entry_head = newest_idx;
+        self.entry_size -= 1;
+    }
+
+    /// Drops the oldest entry.
+    ///
+    fn evict_oldest(&mut self) {
+        let oldest_idx = self.slot(0);
+        if let Some(meta) = self.entries[oldest_idx].take() {
+            self.used_bytes -= meta.length;
+        }
+        self.entry_size -= 1;
+    }
+
+    /// Returns the number of bytes that can be written at `data_head` without overwriting the
+    /// oldest entry.
+    ///
+    fn free_after_head(&self) -> usize {
+        match self.entries[self.slot(0)] {
+            Some(meta) if self.entry_size > 0 => (meta.offset + HTC - self.data_head) % HTC,
+            _ => HTC,
+        }
+    }
+
     /// Moves to the previous entry and returns it, if any.
     ///
     pub fn get_prev_entry<const IML: usize>(&mut self) -> Option<String<IML>> {
@@ -170,12 +311,7 @@ impl<const HTC: usize, const HME: usize> History<HTC, HME> {
         let oldest_idx = (self.entry_head + HME - self.entry_size) % HME;
         let meta = self.entries[oldest_idx]?;
 
-        let mut s = String::<IML>::new();
-        for i in 0..meta.length.min(IML) {
-            let b = self.data[(meta.offset + i) % HTC];
-            s.push(b as char).ok()?;
-        }
-        Some(s)
+        self.read_entry(meta)
     }
 
     /// Returns the **last (most recent)** entry in history, if any.
@@ -188,12 +324,7 @@ impl<const HTC: usize, const HME: usize> History<HTC, HME> {
         let newest_idx = (self.entry_head + HME - 1) % HME;
         let meta = self.entries[newest_idx]?;
 
-        let mut s = String::<IML>::new();
-        for i in 0..meta.length.min(IML) {
-            let b = self.data[(meta.offset + i) % HTC];
-            s.push(b as char).ok()?;
-        }
-        Some(s)
+        self.read_entry(meta)
     }
 
     /// Sets the current index to the given value, if valid.
@@ -218,12 +349,24 @@ impl<const HTC: usize, const HME: usize> History<HTC, HME> {
         }
         let idx = (self.entry_head + HME - self.entry_size + index) % HME;
         let meta = self.entries[idx]?;
-        let mut s = String::<IML>::new();
+        self.read_entry(meta)
+    }
+
+    /// Copies the entry described by `meta` out of the circular buffer.
+    ///
+    /// An entry longer than `IML` bytes is cut at the last whole UTF-8 character that fits.
+    ///
+    fn read_entry<const IML: usize>(&self, meta: EntryMeta) -> Option<String<IML>> {
+        let mut bytes = heapless::Vec::<u8, IML>::new();
         for i in 0..meta.length.min(IML) {
-            let b = self.data[(meta.offset + i) % HTC];
-            s.push(b as char).ok()?;
+            bytes.push(self.data[(meta.offset + i) % HTC]).ok()?;
         }
-        Some(s)
+        let valid = match core::str::from_utf8(&bytes) {
+            Ok(text) => text.len(),
+            Err(e) => e.valid_up_to(),
+        };
+        bytes.truncate(valid);
+        String::from_utf8(bytes).ok()
     }
 
     /// Gets the entry and its index as a tuple, if any.
@@ -241,6 +384,30 @@ impl<const HTC: usize, const HME: usize> History<HTC, HME> {
         }
     }
 
+    /// Returns an iterator over all entries, the most recent first.
+    ///
+    pub fn iter_rev<const IML: usize>(&self) -> HistoryRevIter<'_, HTC, HME, IML> {
+        HistoryRevIter {
+            history: self,
+            remaining: self.entry_size,
+        }
+    }
+
+    /// Returns an iterator over the entries containing `needle` and their indexes, the most
+    /// recent first.
+    ///
+    /// Entries are matched in place; only the matching ones are copied out.
+    ///
+    pub fn find<'s, const IML: usize>(
+        &'s self,
+        needle: &'s str,
+    ) -> impl Iterator<Item = (usize, String<IML>)> + 's {
+        (0..self.entry_size)
+            .rev()
+            .filter(move |&index| self.entry_contains(index, needle))
+            .filter_map(move |index| self.get_at_index::<IML>(index))
+    }
+
     /// Returns an iterator over all entries with their indexes.
     ///
     pub fn iter_with_indexes<const IML: usize>(&self) -> HistoryWithIndexesIter<'_, HTC, HME, IML> {
@@ -250,17 +417,36 @@ impl<const HTC: usize, const HME: usize> History<HTC, HME> {
         }
     }
 
-    /// Prints all entries and free space info to stdout.
+    /// Prints all entries and free space info to `out`.
     ///
-    pub fn show<const IML: usize>(&self) {
+    pub fn show<const IML: usize>(&self, out: &mut dyn Output) {
         if self.is_empty() {
-            println!("History is empty");
+            let _ = writeln!(out, "History is empty");
         } else {
             self.iter_with_indexes::<IML>().for_each(|(index, entry)| {
-                println!("{:>3} : {}", index, entry);
+                let _ = writeln!(out, "{:>3} : {}", index, entry);
             });
             let (free_bytes, free_entries) = self.get_free_space();
-            println!("Left entries/bytes: {}/{}", free_entries, free_bytes);
+            let _ = writeln!(out, "Left entries/bytes: {}/{}", free_entries, free_bytes);
+        }
+    }
+
+    /// Prints the `count` most recent entries to `out`, the most recent first, followed by the
+    /// number of older entries left out and free space info.
+    ///
+    pub fn show_last<const IML: usize>(&self, out: &mut dyn Output, count: usize) {
+        if self.is_empty() {
+            let _ = writeln!(out, "History is empty");
+        } else {
+            let shown = count.min(self.entry_size);
+            for (offset, entry) in self.iter_rev::<IML>().take(shown).enumerate() {
+                let _ = writeln!(out, "{:>3} : {}", self.entry_size - 1 - offset, entry);
+            }
+            if shown < self.entry_size {
+                let _ = writeln!(out, "... {} older", self.entry_size - shown);
+            }
+            let (free_bytes, free_entries) = self.get_free_space();
+            let _ = writeln!(out, "Left entries/bytes: {}/{}", free_entries, free_bytes);
         }
     }
 
@@ -270,6 +456,7 @@ impl<const HTC: usize, const HME: usize> History<HTC, HME> {
         self.data_head = 0;
         self.entry_head = 0;
         self.entry_size = 0;
+        self.used_bytes = 0;
         for e in self.entries.iter_mut() {
             *e = None;
         }
@@ -277,64 +464,130 @@ impl<const HTC: usize, const HME: usize> History<HTC, HME> {
 
     /// Returns the number of free bytes and free entry slots.
     ///
+    /// Free bytes are the [`capacity`](Self::capacity) minus the [`used_bytes`](Self::used_bytes).
+    ///
     pub fn get_free_space(&self) -> (usize, usize) {
-        // Calculate used bytes in the circular buffer
-        let used_bytes = if self.data_head
-            >= self.entries[(self.entry_head + HME - self.entry_size) % HME]
-                .map(|meta| meta.offset)
-                .unwrap_or(self.data_head)
-        {
-            self.data_head
-                - self.entries[(self.entry_head + HME - self.entry_size) % HME]
-                    .map(|meta| meta.offset)
-                    .unwrap_or(self.data_head)
-        } else {
-            HTC - (self.entries[(self.entry_head + HME - self.entry_size) % HME]
-                .map(|meta| meta.offset)
-                .unwrap_or(self.data_head)
-                - self.data_head)
-        };
-        let free_bytes = HTC - used_bytes;
-        let free_entries = HME - self.entry_size;
-        (free_bytes, free_entries)
+        (HTC - self.used_bytes, HME - self.entry_size)
+    }
+
+    /// Returns the number of bytes taken by the stored entries.
+    ///
+    pub fn used_bytes(&self) -> usize {
+        self.used_bytes
+    }
+
+    /// Returns the size of the entry buffer in bytes (`HTC`).
+    ///
+    pub fn capacity(&self) -> usize {
+        HTC
     }
 
     /// Loads history entries from a file (if `history-persistence` feature is enabled).
     ///
+    /// The file holds one entry per line, escaped by [`escape_entry`]. Entries are pushed in
+    /// file order, so only the most recent ones are kept when the file holds more than fits,
+    /// and duplicates follow the [`DedupPolicy`]. The file is then rewritten with the kept
+    /// entries only (see [`save_to_file`](Self::save_to_file)), so it does not grow forever.
+    ///
+    /// The loaded entries are not appended to the persistence file again.
+    ///
     #[cfg(feature = "history-persistence")]
     pub fn load_from_file(&mut self, path: &str) {
-        use heapless::String as HString;
-        use heapless::Vec;
         use std::fs::File;
         use std::io::{BufRead, BufReader};
         if let Ok(file) = File::open(path) {
             let reader = BufReader::new(file);
-            let mut lines: Vec<HString<256>, HME> = Vec::new();
+            self.clear();
+            let persistence = self.persistence.take();
             for line in reader.lines().map_while(Result::ok) {
-                if lines.len() == HME {
-                    lines.remove(0);
-                }
-                let mut hl_line = HString::new();
-                let _ = write!(hl_line, "{}", line);
-                let _ = lines.push(hl_line);
+                let _ = self.push(&unescape_entry(&line));
             }
+            self.persistence = persistence;
+            self.save_to_file(path);
+        }
+    }
 
-            self.clear();
-            for line in lines {
-                let _ = self.push(&line);
+    /// Replaces the content of a file with all entries (if `history-persistence` feature is
+    /// enabled).
+    ///
+    /// The entries are written to `<path>.tmp` first, which is then renamed to `path`, so an
+    /// interrupted write leaves the previous file intact.
+    ///
+    #[cfg(feature = "history-persistence")]
+    pub fn save_to_file(&self, path: &str) {
+        use std::io::Write;
+        let tmp = std::format!("{}.tmp", path);
+        let written = std::fs::File::create(&tmp).and_then(|mut file| {
+            for entry in self.iter::<HTC>() {
+                writeln!(file, "{}", escape_entry(&entry))?;
             }
+            file.sync_all()
+        });
+        if written.is_ok() {
+            let _ = std::fs::rename(&tmp, path);
+        } else {
+            let _ = std::fs::remove_file(&tmp);
         }
     }
+
     /// Append to file (if `history-persistence` feature is enabled).
     ///
+    /// The entry is escaped by [`escape_entry`], so it always takes a single line.
+    ///
     #[cfg(feature = "history-persistence")]
     pub fn append_to_file(&self, path: &str, entry: &str) {
         use std::fs::OpenOptions;
         use std::io::Write;
         if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
-            let _ = writeln!(file, "{}", entry);
+            let _ = writeln!(file, "{}", escape_entry(entry));
+        }
+    }
+}
+
+/// Escapes a history entry as a single line of the history file.
+///
+/// `\` becomes `\\`, a newline `\n` and a carriage return `\r`; everything else is kept.
+///
+#[cfg(feature = "history-persistence")]
+pub fn escape_entry(entry: &str) -> std::string::String {
+    let mut line = std::string::String::with_capacity(entry.len());
+    for c in entry.chars() {
+        match c {
+            '\\' => line.push_str("\\\\"),
+            '\n' => line.push_str("\\n"),
+            '\r' => line.push_str("\\r"),
+            c => line.push(c),
+        }
+    }
+    line
+}
+
+/// Restores a history entry escaped by [`escape_entry`].
+///
+/// Unknown escapes are kept as they are, so files written before entries were escaped
+/// still load unchanged.
+///
+#[cfg(feature = "history-persistence")]
+pub fn unescape_entry(line: &str) -> std::string::String {
+    let mut entry = std::string::String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            entry.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => entry.push('\\'),
+            Some('n') => entry.push('\n'),
+            Some('r') => entry.push('\r'),
+            Some(other) => {
+                entry.push('\\');
+                entry.push(other);
+            }
+            None => entry.push('\\'),
         }
     }
+    entry
 }
 
 /// Implements the `Iterator` trait for `HistoryIter`.
@@ -369,6 +622,33 @@ impl<'a, const HTC: usize, const HME: usize, const IML: usize> Iterator
     }
 }
 
+/// Implements the `Iterator` trait for `HistoryRevIter`.
+///
+/// # Type Parameters
+/// - `'a`: Lifetime of the iterator.
+/// - `HTC`: History table capacity.
+/// - `HME`: History max entries.
+/// - `IML`: Item max length.
+///
+impl<'a, const HTC: usize, const HME: usize, const IML: usize> Iterator
+    for HistoryRevIter<'a, HTC, HME, IML>
+{
+    /// The type of item returned by the iterator.
+    type Item = String<IML>;
+
+    /// Advances the iterator and returns the next older value.
+    ///
+    /// Returns `None` after the oldest entry.
+    ///
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        self.history.get::<IML>(self.remaining)
+    }
+}
+
 /// Implements the `Iterator` trait for `HistoryWithIndexesIter`.
 ///
 /// This iterator yields both the index and the entry value.
@@ -402,6 +682,20 @@ impl<'a, const HTC: usize, const HME: usize, const IML: usize> Iterator
     }
 }
 
+/// Offers the history entries as whole-line completions, most recent first.
+///
+impl<const HTC: usize, const HME: usize> LineSource for History<HTC, HME> {
+    fn visit_lines(&self, f: &mut dyn FnMut(&str) -> bool) {
+        for index in (0..self.entry_size).rev() {
+            if let Some(entry) = self.get::<HTC>(index)
+                && !f(&entry)
+            {
+                break;
+            }
+        }
+    }
+}
+
 // ==================== TEST =======================
 
 #[cfg(test)]
@@ -410,9 +704,7 @@ mod tests {
 
     // Helper function to create a clean history for testing
     fn new_test_history<const HTC: usize, const HME: usize>() -> History<HTC, HME> {
-        let mut history = History::new();
-        history.clear(); // Clear any loaded data from file
-        history
+        History::with_persistence(None) // No data loaded from or stored to a file
     }
 
     // ==================== BASIC FUNCTIONALITY TESTS ====================
@@ -472,22 +764,61 @@ mod tests {
     // ==================== DUPLICATE HANDLING TESTS ====================
 
     #[test]
-    fn test_push_duplicate_rejected() {
+    fn test_push_duplicate_moves_to_front() {
         let mut history = new_test_history::<1024, 10>();
+        assert_eq!(history.dedup_policy(), DedupPolicy::MoveToFront);
         assert!(history.push("duplicate"));
-        assert!(!history.push("duplicate"));
-        assert_eq!(history.entry_size, 1);
+        assert!(history.push("other"));
+        assert!(history.push("duplicate"));
+        assert_eq!(history.entry_size, 2);
+        assert_eq!(history.get::<64>(0).as_deref(), Some("other"));
+        assert_eq!(history.get_last_entry::<64>().as_deref(), Some("duplicate"));
     }
 
     #[test]
-    fn test_push_duplicate_with_whitespace_rejected() {
+    fn test_push_duplicate_with_whitespace_merged() {
         let mut history = new_test_history::<1024, 10>();
         history.push("test");
         let result = history.push("  test  ");
-        assert!(!result);
+        assert!(result);
         assert_eq!(history.entry_size, 1);
     }
 
+    #[test]
+    fn test_dedup_ignore_consecutive() {
+        let mut history = new_test_history::<1024, 10>();
+        history.set_dedup_policy(DedupPolicy::IgnoreConsecutive);
+        assert!(history.push("a"));
+        assert!(!history.push("a"));
+        assert!(history.push("b"));
+        assert!(history.push("a"));
+        assert_eq!(history.entry_size, 3);
+    }
+
+    #[test]
+    fn test_dedup_allow_duplicates() {
+        let mut history = new_test_history::<1024, 10>();
+        history.set_dedup_policy(DedupPolicy::AllowDuplicates);
+        assert!(history.push("a"));
+        assert!(history.push("a"));
+        assert_eq!(history.entry_size, 2);
+    }
+
+    #[test]
+    fn test_move_to_front_keeps_buffer_consistent() {
+        let mut history = new_test_history::<10, 5>();
+        history.push("abc");
+        history.push("def");
+        history.push("abc"); // leaves the first copy's bytes unused
+        history.push("ghij"); // wraps around
+        let entries: Vec<String<16>> = history.iter::<16>().collect();
+        assert_eq!(entries, ["def", "abc", "ghij"]);
+
+        history.push("xy"); // evicts "def"
+        let entries: Vec<String<16>> = history.iter::<16>().collect();
+        assert_eq!(entries, ["abc", "ghij", "xy"]);
+    }
+
     #[test]
     fn test_similar_but_not_duplicate_accepted() {
         let mut history = new_test_history::<1024, 10>();
@@ -755,8 +1086,217 @@ mod tests {
         assert_eq!(entries.len(), 0);
     }
 
-    // ==================== CLEAR TESTS ====================
+    #[test]
+    fn test_visit_lines_most_recent_first() {
+        let mut history = new_test_history::<1024, 10>();
+        history.push("first");
+        history.push("second");
+        history.push("third");
+
+        let mut seen: Vec<String<16>> = Vec::new();
+        history.visit_lines(&mut |line| {
+            seen.push(String::try_from(line).unwrap());
+            seen.len() < 2
+        });
+        assert_eq!(seen, ["third", "second"]);
+    }
+
+    #[test]
+    fn test_utf8_entries() {
+        let mut history = new_test_history::<1024, 10>();
+        history.push("echo héllo 日本");
+        assert_eq!(history.get::<64>(0).unwrap().as_str(), "echo héllo 日本");
+        assert_eq!(
+            history.get_last_entry::<64>().unwrap().as_str(),
+            "echo héllo 日本"
+        );
+        // Cut at a character boundary when longer than IML
+        assert_eq!(history.get::<7>(0).unwrap().as_str(), "echo h");
+    }
+
+    // ==================== PERSISTENCE TESTS ====================
+
+    #[test]
+    fn test_persistence_disabled() {
+        let mut history = History::<64, 4>::with_persistence(None);
+        assert_eq!(history.persistence(), None);
+        history.push("entry");
+        history.set_persistence(None);
+        assert!(history.is_empty());
+    }
+
+    #[cfg(feature = "history-persistence")]
+    #[test]
+    fn test_persistence_to_chosen_file() {
+        let path = temp_history_file("ushell_hist");
+
+        let mut history = History::<64, 4>::with_persistence(Some(path));
+        assert!(history.is_empty());
+        history.push("first");
+        history.push("second");
+
+        let mut reloaded = History::<64, 4>::with_persistence(Some(path));
+        assert_eq!(reloaded.persistence(), Some(path));
+        let entries: Vec<String<16>> = reloaded.iter::<16>().collect();
+        assert_eq!(entries, ["first", "second"]);
+
+        // Loading does not store the entries again
+        reloaded.set_persistence(Some(path));
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "first\nsecond\n");
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[cfg(feature = "history-persistence")]
+    fn temp_history_file(name: &str) -> &'static str {
+        let path = std::env::temp_dir().join(std::format!("{}_{}", name, std::process::id()));
+        let path: &'static str = std::boxed::Box::leak(path.to_str().unwrap().into());
+        let _ = std::fs::remove_file(path);
+        path
+    }
+
+    #[cfg(feature = "history-persistence")]
+    #[test]
+    fn test_escape_entry_roundtrip() {
+        for entry in ["plain", "two\nlines", "back\\slash\\n", "cr\r", "\\"] {
+            let line = escape_entry(entry);
+            assert!(!line.contains('\n') && !line.contains('\r'));
+            assert_eq!(unescape_entry(&line), entry);
+        }
+        assert_eq!(escape_entry("a\nb\\"), "a\\nb\\\\");
+        // Unknown escapes and a trailing backslash are kept
+        assert_eq!(unescape_entry("C:\\temp\\"), "C:\\temp\\");
+    }
+
+    #[cfg(feature = "history-persistence")]
+    #[test]
+    fn test_persistence_multiline_entry() {
+        let path = temp_history_file("ushell_hist_multiline");
+        let long = "x".repeat(300);
+        let mut history = History::<1024, 4>::with_persistence(Some(path));
+        history.push("echo first\nsecond");
+        history.push(&long);
+
+        let reloaded = History::<1024, 4>::with_persistence(Some(path));
+        assert_eq!(reloaded.entry_size, 2);
+        assert_eq!(
+            reloaded.get::<512>(0).as_deref(),
+            Some("echo first\nsecond")
+        );
+        assert_eq!(reloaded.get::<512>(1).as_deref(), Some(long.as_str()));
+        let _ = std::fs::remove_file(path);
+    }
 
+    #[cfg(feature = "history-persistence")]
+    #[test]
+    fn test_load_compacts_file() {
+        let path = temp_history_file("ushell_hist_compact");
+        std::fs::write(path, "a\nb\na\nc\nd\ne\n").unwrap();
+
+        let history = History::<64, 3>::with_persistence(Some(path));
+        let entries: Vec<String<8>> = history.iter::<8>().collect();
+        assert_eq!(entries, ["c", "d", "e"]);
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "c\nd\ne\n");
+        assert!(!std::path::Path::new(&std::format!("{}.tmp", path)).exists());
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_show_to_output() {
+        use core::fmt::Write;
+        let mut history = new_test_history::<1024, 10>();
+        let mut out = String::<128>::new();
+        history.show::<64>(&mut out);
+        assert_eq!(out.as_str(), "History is empty\n");
+
+        history.push("first");
+        history.push("second");
+        out.clear();
+        history.show::<64>(&mut out);
+        let (free_bytes, free_entries) = history.get_free_space();
+        let mut expected = String::<128>::new();
+        let _ = write!(
+            expected,
+            "  0 : first\n  1 : second\nLeft entries/bytes: {}/{}\n",
+            free_entries, free_bytes
+        );
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_iter_rev() {
+        let mut history = new_test_history::<1024, 10>();
+        assert_eq!(history.iter_rev::<16>().next(), None);
+
+        history.push("first");
+        history.push("second");
+        history.push("third");
+        let entries: Vec<String<16>> = history.iter_rev::<16>().collect();
+        assert_eq!(entries, ["third", "second", "first"]);
+    }
+
+    #[test]
+    fn test_find() {
+        let mut history = new_test_history::<1024, 10>();
+        assert_eq!(history.find::<16>("led").next(), None);
+
+        history.push("led 1");
+        history.push("read 0");
+        history.push("led 0");
+        let found: Vec<(usize, String<16>)> = history.find::<16>("led").collect();
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0], (2, String::try_from("led 0").unwrap()));
+        assert_eq!(found[1], (0, String::try_from("led 1").unwrap()));
+
+        assert_eq!(history.find::<16>(" 0").count(), 2);
+        assert_eq!(history.find::<16>("").count(), 3);
+        assert_eq!(history.find::<16>("led 10").count(), 0);
+    }
+
+    #[test]
+    fn test_find_across_wraparound() {
+        let mut history = new_test_history::<10, 4>();
+        history.push("abcdef");
+        history.push("ghijkl"); // stored across the end of the buffer
+        let found: Vec<(usize, String<16>)> = history.find::<16>("jk").collect();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].1.as_str(), "ghijkl");
+    }
+
+    #[test]
+    fn test_show_last() {
+        use core::fmt::Write;
+        let mut history = new_test_history::<1024, 10>();
+        let mut out = String::<128>::new();
+        history.show_last::<64>(&mut out, 2);
+        assert_eq!(out.as_str(), "History is empty\n");
+
+        history.push("first");
+        history.push("second");
+        history.push("third");
+        let (free_bytes, free_entries) = history.get_free_space();
+
+        out.clear();
+        history.show_last::<64>(&mut out, 2);
+        let mut expected = String::<128>::new();
+        let _ = write!(
+            expected,
+            "  2 : third\n  1 : second\n... 1 older\nLeft entries/bytes: {}/{}\n",
+            free_entries, free_bytes
+        );
+        assert_eq!(out, expected);
+
+        out.clear();
+        history.show_last::<64>(&mut out, 10);
+        expected.clear();
+        let _ = write!(
+            expected,
+            "  2 : third\n  1 : second\n  0 : first\nLeft entries/bytes: {}/{}\n",
+            free_entries, free_bytes
+        );
+        assert_eq!(out, expected);
+    }
+
+    // ==================== CLEAR TESTS ====================
     #[test]
     fn test_clear_history() {
         let mut history = new_test_history::<1024, 10>();
@@ -813,6 +1353,54 @@ mod tests {
         assert_eq!(free_entries, 0);
     }
 
+    #[test]
+    fn test_free_space_full_buffer() {
+        let mut history = new_test_history::<10, 5>();
+        history.push("abcde");
+        history.push("fghij");
+        assert_eq!(history.used_bytes(), 10);
+        assert_eq!(history.get_free_space(), (0, 3));
+    }
+
+    #[test]
+    fn test_free_space_after_wraparound() {
+        let mut history = new_test_history::<10, 5>();
+        history.push("abcdef");
+        history.push("ghij"); // fills the buffer
+        history.push("klm"); // wraps, evicting "abcdef"
+        assert_eq!(history.entry_size, 2);
+        assert_eq!(history.used_bytes(), 7);
+        assert_eq!(history.get_free_space(), (3, 3));
+
+        history.push("nopqrs"); // stored across the end, evicting "ghij"
+        assert_eq!(history.used_bytes(), 9);
+        assert_eq!(history.get_free_space(), (1, 3));
+    }
+
+    #[test]
+    fn test_free_space_after_entry_eviction() {
+        let mut history = new_test_history::<100, 2>();
+        history.push("aaaa");
+        history.push("bb");
+        history.push("c"); // evicts "aaaa" for lack of a slot
+        assert_eq!(history.used_bytes(), 3);
+        assert_eq!(history.get_free_space(), (97, 0));
+    }
+
+    #[test]
+    fn test_free_space_after_dedup_and_clear() {
+        let mut history = new_test_history::<100, 5>();
+        history.push("one");
+        history.push("two");
+        history.push("one"); // moved to the front, not stored twice
+        assert_eq!(history.used_bytes(), 6);
+
+        history.clear();
+        assert_eq!(history.used_bytes(), 0);
+        assert_eq!(history.get_free_space(), (100, 5));
+        assert_eq!(history.capacity(), 100);
+    }
+
     // ==================== EDGE CASE TESTS ====================
 
     #[test]
@@ -935,6 +1523,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::useless_vec)]
     fn test_realistic_shell_history() {
         let mut history = new_test_history::<1024, 100>();
 
@@ -959,9 +1548,13 @@ mod tests {
             Some("git push origin main")
         );
 
-        // Duplicate command rejected
-        assert!(!history.push("git status"));
+        // Duplicate command moved to the most recent position
+        assert!(history.push("git status"));
         assert_eq!(history.entry_size, 7);
+        assert_eq!(
+            history.get_last_entry::<1024>().as_deref(),
+            Some("git status")
+        );
     }
 
     // ==================== BOUNDARY TESTS ====================
@@ -1,16 +1,33 @@
-use heapless::String;
+use heapless::{Deque, String, Vec};
+
+/// Number of edit snapshots kept for undo (and redo).
+pub const UNDO_DEPTH: usize = 8;
+
+/// Buffer contents and cursor position saved before an edit.
+struct Snapshot<const IML: usize> {
+    text: String<IML>,
+    cursor_pos: usize,
+}
 
 /// A fixed-size, heapless character buffer for managing user input and cursor movement.
 ///
 /// `InputBuffer` is ideal for embedded or resource-constrained environments where dynamic memory allocation is not desired.
 /// It supports insertion, deletion, cursor movement, and conversion to a `heapless::String`.
+/// The last [`UNDO_DEPTH`] edits can be reverted with [`undo`](Self::undo) and re-applied
+/// with [`redo`](Self::redo); consecutive typed characters are grouped into a single edit.
+///
+/// Characters are stored as `char`s, so cursor positions count characters rather than bytes.
 ///
 /// # Type Parameters
-/// - `IML`: The maximum input length (buffer size).
+/// - `IML`: The maximum input length, in UTF-8 bytes (the size of the `String<IML>` returned by
+///   [`to_string`](Self::to_string)).
 pub struct InputBuffer<const IML: usize> {
     buffer: [char; IML],
     length: usize,
     cursor_pos: usize,
+    undo_stack: Deque<Snapshot<IML>, UNDO_DEPTH>,
+    redo_stack: Vec<Snapshot<IML>, UNDO_DEPTH>,
+    typing_at: Option<usize>,
 }
 
 impl<const IML: usize> InputBuffer<IML> {
@@ -25,13 +42,17 @@ impl<const IML: usize> InputBuffer<IML> {
             buffer: ['\0'; IML],
             length: 0,
             cursor_pos: 0,
+            undo_stack: Deque::new(),
+            redo_stack: Vec::new(),
+            typing_at: None,
         }
     }
 
     /// Inserts a character at the current cursor position.
     ///
     /// Shifts subsequent characters to the right.
-    /// Returns `true` if the character was inserted, or `false` if the buffer is full.
+    /// Returns `true` if the character was inserted, or `false` if its UTF-8 encoding does not fit
+    /// in the remaining `IML` bytes.
     ///
     /// # Example
     /// ```
@@ -39,15 +60,19 @@ impl<const IML: usize> InputBuffer<IML> {
     /// assert!(buf.insert('a'));
     /// ```
     pub fn insert(&mut self, ch: char) -> bool {
-        if self.length >= IML {
+        if self.length >= IML || self.byte_len() + ch.len_utf8() > IML {
             return false;
         }
+        if self.typing_at != Some(self.cursor_pos) {
+            self.checkpoint();
+        }
         for i in (self.cursor_pos..self.length).rev() {
             self.buffer[i + 1] = self.buffer[i];
         }
         self.buffer[self.cursor_pos] = ch;
         self.length += 1;
         self.cursor_pos += 1;
+        self.typing_at = Some(self.cursor_pos);
         true
     }
 
@@ -65,6 +90,7 @@ impl<const IML: usize> InputBuffer<IML> {
         if self.cursor_pos == 0 {
             return false;
         }
+        self.checkpoint();
         for i in self.cursor_pos..self.length {
             self.buffer[i - 1] = self.buffer[i];
         }
@@ -102,6 +128,47 @@ impl<const IML: usize> InputBuffer<IML> {
         }
     }
 
+    /// Moves the cursor to the start of the previous word (Alt+B).
+    ///
+    /// Words are whitespace-delimited; whitespace before the cursor is skipped first.
+    ///
+    /// # Example
+    /// ```
+    /// let mut buf: InputBuffer<16> = InputBuffer::new();
+    /// buf.overwrite("set speed");
+    /// buf.move_word_left();
+    /// assert_eq!(buf.cursor(), 4);
+    /// ```
+    pub fn move_word_left(&mut self) {
+        while self.cursor_pos > 0 && self.buffer[self.cursor_pos - 1].is_whitespace() {
+            self.cursor_pos -= 1;
+        }
+        while self.cursor_pos > 0 && !self.buffer[self.cursor_pos - 1].is_whitespace() {
+            self.cursor_pos -= 1;
+        }
+    }
+
+    /// Moves the cursor to the end of the next word (Alt+F).
+    ///
+    /// Words are whitespace-delimited; whitespace after the cursor is skipped first.
+    ///
+    /// # Example
+    /// ```
+    /// let mut buf: InputBuffer<16> = InputBuffer::new();
+    /// buf.overwrite("set speed");
+    /// buf.move_home();
+    /// buf.move_word_right();
+    /// assert_eq!(buf.cursor(), 3);
+    /// ```
+    pub fn move_word_right(&mut self) {
+        while self.cursor_pos < self.length && self.buffer[self.cursor_pos].is_whitespace() {
+            self.cursor_pos += 1;
+        }
+        while self.cursor_pos < self.length && !self.buffer[self.cursor_pos].is_whitespace() {
+            self.cursor_pos += 1;
+        }
+    }
+
     /// Moves the cursor to the start (home) of the buffer.
     ///
     /// # Example
@@ -135,6 +202,7 @@ impl<const IML: usize> InputBuffer<IML> {
     /// ```
     pub fn delete_at_cursor(&mut self) {
         if self.cursor_pos < self.length {
+            self.checkpoint();
             for i in self.cursor_pos..self.length - 1 {
                 self.buffer[i] = self.buffer[i + 1];
             }
@@ -152,9 +220,10 @@ impl<const IML: usize> InputBuffer<IML> {
     /// buf.clear();
     /// ```
     pub fn clear(&mut self) {
-        self.buffer = ['\0'; IML];
-        self.length = 0;
-        self.cursor_pos = 0;
+        if self.length > 0 {
+            self.checkpoint();
+        }
+        self.reset();
     }
 
     /// Returns the buffer contents as a `heapless::String`.
@@ -169,7 +238,8 @@ impl<const IML: usize> InputBuffer<IML> {
         self.buffer.iter().take(self.length).collect()
     }
 
-    /// Overwrites the buffer with the given string, truncating if necessary.
+    /// Overwrites the buffer with the given string, truncating it to the characters fitting in
+    /// `IML` bytes if necessary.
     ///
     /// The cursor is moved to the end of the new content.
     ///
@@ -179,11 +249,19 @@ impl<const IML: usize> InputBuffer<IML> {
     /// buf.overwrite("hello");
     /// ```
     pub fn overwrite(&mut self, input: &str) {
-        self.clear();
-        for (i, c) in input.chars().take(IML).enumerate() {
+        let count = Self::fitting_chars(input);
+        if !self.buffer[..self.length]
+            .iter()
+            .copied()
+            .eq(input.chars().take(count))
+        {
+            self.checkpoint();
+        }
+        self.reset();
+        for (i, c) in input.chars().take(count).enumerate() {
             self.buffer[i] = c;
         }
-        self.length = input.len().min(IML);
+        self.length = count;
         self.cursor_pos = self.length;
     }
 
@@ -210,6 +288,9 @@ impl<const IML: usize> InputBuffer<IML> {
     /// buf.delete_to_start();
     /// ```
     pub fn delete_to_start(&mut self) {
+        if self.cursor_pos > 0 {
+            self.checkpoint();
+        }
         let shift = self.length - self.cursor_pos;
         for i in 0..shift {
             self.buffer[i] = self.buffer[self.cursor_pos + i];
@@ -231,12 +312,52 @@ impl<const IML: usize> InputBuffer<IML> {
     /// buf.delete_to_end();
     /// ```
     pub fn delete_to_end(&mut self) {
+        if self.cursor_pos < self.length {
+            self.checkpoint();
+        }
         for i in self.cursor_pos..self.length {
             self.buffer[i] = '\0';
         }
         self.length = self.cursor_pos;
     }
 
+    /// Deletes the word before the cursor (Ctrl+W).
+    ///
+    /// Words are whitespace-delimited: whitespace directly before the cursor is deleted
+    /// first, then the non-whitespace characters before it.
+    /// Returns `true` if anything was deleted, or `false` if the cursor is at the start.
+    ///
+    /// # Example
+    /// ```
+    /// let mut buf: InputBuffer<16> = InputBuffer::new();
+    /// buf.overwrite("set speed ");
+    /// buf.delete_word_back();
+    /// assert_eq!(buf.to_string().as_str(), "set ");
+    /// ```
+    pub fn delete_word_back(&mut self) -> bool {
+        let mut start = self.cursor_pos;
+        while start > 0 && self.buffer[start - 1].is_whitespace() {
+            start -= 1;
+        }
+        while start > 0 && !self.buffer[start - 1].is_whitespace() {
+            start -= 1;
+        }
+        if start == self.cursor_pos {
+            return false;
+        }
+        self.checkpoint();
+        let removed = self.cursor_pos - start;
+        for i in self.cursor_pos..self.length {
+            self.buffer[i - removed] = self.buffer[i];
+        }
+        for i in self.length - removed..self.length {
+            self.buffer[i] = '\0';
+        }
+        self.length -= removed;
+        self.cursor_pos = start;
+        true
+    }
+
     /// Returns the current length of the buffer.
     ///
     /// # Example
@@ -258,6 +379,118 @@ impl<const IML: usize> InputBuffer<IML> {
     pub fn is_empty(&self) -> bool {
         self.length == 0
     }
+
+    /// Reverts the most recent edit (Ctrl+_).
+    ///
+    /// Returns `true` if an edit was reverted, or `false` if there is nothing to undo.
+    ///
+    /// # Example
+    /// ```
+    /// let mut buf: InputBuffer<16> = InputBuffer::new();
+    /// buf.overwrite("set speed");
+    /// buf.delete_to_start();
+    /// assert!(buf.undo());
+    /// assert_eq!(buf.to_string().as_str(), "set speed");
+    /// ```
+    pub fn undo(&mut self) -> bool {
+        let Some(snapshot) = self.undo_stack.pop_back() else {
+            return false;
+        };
+        let _ = self.redo_stack.push(self.snapshot());
+        self.restore(&snapshot);
+        true
+    }
+
+    /// Re-applies the most recently undone edit (Alt+_).
+    ///
+    /// Returns `true` if an edit was re-applied, or `false` if there is nothing to redo.
+    /// Any new edit after an undo discards the redo history.
+    ///
+    /// # Example
+    /// ```
+    /// let mut buf: InputBuffer<16> = InputBuffer::new();
+    /// buf.overwrite("set speed");
+    /// buf.undo();
+    /// assert!(buf.redo());
+    /// assert_eq!(buf.to_string().as_str(), "set speed");
+    /// ```
+    pub fn redo(&mut self) -> bool {
+        let Some(snapshot) = self.redo_stack.pop() else {
+            return false;
+        };
+        let _ = self.undo_stack.push_back(self.snapshot());
+        self.restore(&snapshot);
+        true
+    }
+
+    /// Forgets all undo and redo history, e.g. once a line has been submitted.
+    ///
+    /// # Example
+    /// ```
+    /// let mut buf: InputBuffer<16> = InputBuffer::new();
+    /// buf.overwrite("set speed");
+    /// buf.clear_undo_history();
+    /// assert!(!buf.undo());
+    /// ```
+    pub fn clear_undo_history(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.typing_at = None;
+    }
+
+    /// Saves the current state before an edit, dropping the oldest snapshot when full.
+    fn checkpoint(&mut self) {
+        if self.undo_stack.is_full() {
+            self.undo_stack.pop_front();
+        }
+        let _ = self.undo_stack.push_back(self.snapshot());
+        self.redo_stack.clear();
+        self.typing_at = None;
+    }
+
+    fn snapshot(&self) -> Snapshot<IML> {
+        Snapshot {
+            text: self.to_string(),
+            cursor_pos: self.cursor_pos,
+        }
+    }
+
+    /// Returns the UTF-8 length of the content, in bytes.
+    fn byte_len(&self) -> usize {
+        self.buffer[..self.length]
+            .iter()
+            .map(|c| c.len_utf8())
+            .sum()
+    }
+
+    /// Returns how many leading characters of `input` fit in `IML` bytes.
+    fn fitting_chars(input: &str) -> usize {
+        let mut bytes = 0;
+        input
+            .chars()
+            .take_while(|c| {
+                bytes += c.len_utf8();
+                bytes <= IML
+            })
+            .count()
+    }
+
+    fn restore(&mut self, snapshot: &Snapshot<IML>) {
+        self.reset();
+        for (i, c) in snapshot.text.chars().take(IML).enumerate() {
+            self.buffer[i] = c;
+            self.length = i + 1;
+        }
+        self.cursor_pos = snapshot.cursor_pos.min(self.length);
+        self.typing_at = None;
+    }
+
+    /// Empties the buffer without recording an undo step.
+    fn reset(&mut self) {
+        self.buffer = ['\0'; IML];
+        self.length = 0;
+        self.cursor_pos = 0;
+    }
 }
 
 impl<const IML: usize> Default for InputBuffer<IML> {
@@ -494,6 +727,43 @@ mod tests {
         assert_eq!(buf.cursor(), 0);
     }
 
+    #[test]
+    fn test_move_word_left() {
+        let mut buf: InputBuffer<16> = InputBuffer::new();
+        buf.overwrite("set  speed 10");
+        buf.move_word_left();
+        assert_eq!(buf.cursor(), 11);
+        buf.move_word_left();
+        assert_eq!(buf.cursor(), 5);
+        buf.move_word_left();
+        assert_eq!(buf.cursor(), 0);
+        buf.move_word_left();
+        assert_eq!(buf.cursor(), 0);
+    }
+
+    #[test]
+    fn test_move_word_right() {
+        let mut buf: InputBuffer<16> = InputBuffer::new();
+        buf.overwrite("set  speed 10");
+        buf.move_home();
+        buf.move_word_right();
+        assert_eq!(buf.cursor(), 3);
+        buf.move_word_right();
+        assert_eq!(buf.cursor(), 10);
+        buf.move_word_right();
+        assert_eq!(buf.cursor(), 13);
+        buf.move_word_right();
+        assert_eq!(buf.cursor(), 13);
+    }
+
+    #[test]
+    fn test_move_word_on_empty_buffer() {
+        let mut buf: InputBuffer<8> = InputBuffer::new();
+        buf.move_word_left();
+        buf.move_word_right();
+        assert_eq!(buf.cursor(), 0);
+    }
+
     // ============================================================================
     // Delete at Cursor
     // ============================================================================
@@ -677,6 +947,148 @@ mod tests {
         assert_eq!(buf.len(), 0);
     }
 
+    // ============================================================================
+    // Delete Word Back
+    // ============================================================================
+
+    #[test]
+    fn test_delete_word_back_at_end() {
+        let mut buf: InputBuffer<16> = InputBuffer::new();
+        buf.overwrite("set speed");
+        assert!(buf.delete_word_back());
+        assert_eq!(buf.to_string().as_str(), "set ");
+        assert_eq!(buf.cursor(), 4);
+    }
+
+    #[test]
+    fn test_delete_word_back_skips_trailing_spaces() {
+        let mut buf: InputBuffer<16> = InputBuffer::new();
+        buf.overwrite("set speed  ");
+        assert!(buf.delete_word_back());
+        assert_eq!(buf.to_string().as_str(), "set ");
+        assert!(buf.delete_word_back());
+        assert_eq!(buf.to_string().as_str(), "");
+        assert!(!buf.delete_word_back());
+    }
+
+    #[test]
+    fn test_delete_word_back_mid_line() {
+        let mut buf: InputBuffer<16> = InputBuffer::new();
+        buf.overwrite("set speed 10");
+        for _ in 0..3 {
+            buf.move_left();
+        }
+        assert!(buf.delete_word_back());
+        assert_eq!(buf.to_string().as_str(), "set  10");
+        assert_eq!(buf.cursor(), 4);
+        assert_eq!(buf.len(), 7);
+    }
+
+    #[test]
+    fn test_delete_word_back_at_start() {
+        let mut buf: InputBuffer<16> = InputBuffer::new();
+        buf.overwrite("set");
+        buf.move_home();
+        assert!(!buf.delete_word_back());
+        assert_eq!(buf.to_string().as_str(), "set");
+    }
+
+    // ============================================================================
+    // Undo / Redo
+    // ============================================================================
+
+    #[test]
+    fn test_undo_groups_typed_characters() {
+        let mut buf: InputBuffer<16> = InputBuffer::new();
+        for c in "set".chars() {
+            buf.insert(c);
+        }
+        buf.insert(' ');
+        assert!(buf.undo());
+        assert!(buf.is_empty());
+        assert_eq!(buf.cursor(), 0);
+        assert!(!buf.undo());
+    }
+
+    #[test]
+    fn test_undo_restores_deleted_text() {
+        let mut buf: InputBuffer<16> = InputBuffer::new();
+        buf.overwrite("set speed 10");
+        buf.move_left();
+        buf.delete_to_start();
+        buf.delete_at_cursor();
+        assert!(buf.is_empty());
+        assert!(buf.undo());
+        assert_eq!(buf.to_string().as_str(), "0");
+        assert!(buf.undo());
+        assert_eq!(buf.to_string().as_str(), "set speed 10");
+        assert_eq!(buf.cursor(), 11);
+    }
+
+    #[test]
+    fn test_undo_restores_overwritten_text() {
+        let mut buf: InputBuffer<16> = InputBuffer::new();
+        buf.insert('s');
+        buf.overwrite("set_speed");
+        assert!(buf.undo());
+        assert_eq!(buf.to_string().as_str(), "s");
+        assert_eq!(buf.cursor(), 1);
+    }
+
+    #[test]
+    fn test_redo_reapplies_undone_edit() {
+        let mut buf: InputBuffer<16> = InputBuffer::new();
+        buf.overwrite("hello world");
+        buf.delete_word_back();
+        buf.undo();
+        assert!(buf.redo());
+        assert_eq!(buf.to_string().as_str(), "hello ");
+        assert!(!buf.redo());
+    }
+
+    #[test]
+    fn test_new_edit_discards_redo() {
+        let mut buf: InputBuffer<16> = InputBuffer::new();
+        buf.overwrite("abc");
+        buf.undo();
+        buf.insert('x');
+        assert!(!buf.redo());
+    }
+
+    #[test]
+    fn test_undo_history_is_bounded() {
+        let mut buf: InputBuffer<32> = InputBuffer::new();
+        for i in 0..UNDO_DEPTH + 3 {
+            buf.overwrite(&"x".repeat(i + 1));
+        }
+        let mut undone = 0;
+        while buf.undo() {
+            undone += 1;
+        }
+        assert_eq!(undone, UNDO_DEPTH);
+        assert_eq!(buf.len(), 3);
+    }
+
+    #[test]
+    fn test_unchanged_operations_record_nothing() {
+        let mut buf: InputBuffer<8> = InputBuffer::new();
+        buf.backspace();
+        buf.clear();
+        buf.delete_to_end();
+        buf.overwrite("");
+        assert!(!buf.undo());
+    }
+
+    #[test]
+    fn test_clear_undo_history() {
+        let mut buf: InputBuffer<8> = InputBuffer::new();
+        buf.overwrite("abc");
+        buf.undo();
+        buf.clear_undo_history();
+        assert!(!buf.undo());
+        assert!(!buf.redo());
+    }
+
     // ============================================================================
     // Complex Scenarios
     // ============================================================================
@@ -795,6 +1207,42 @@ mod tests {
         assert_eq!(buf.cursor(), 0);
     }
 
+    // ============================================================================
+    // UTF-8 Tests
+    // ============================================================================
+
+    #[test]
+    fn test_insert_multibyte_chars() {
+        let mut buf: InputBuffer<8> = InputBuffer::new();
+        assert!(buf.insert('h'));
+        assert!(buf.insert('é'));
+        assert!(buf.insert('€'));
+        assert_eq!(buf.len(), 3);
+        assert_eq!(buf.cursor(), 3);
+        assert_eq!(buf.to_string().as_str(), "hé€");
+        buf.move_left();
+        assert!(buf.backspace());
+        assert_eq!(buf.to_string().as_str(), "h€");
+    }
+
+    #[test]
+    fn test_insert_rejects_char_exceeding_byte_capacity() {
+        let mut buf: InputBuffer<4> = InputBuffer::new();
+        assert!(buf.insert('a'));
+        assert!(buf.insert('€')); // 3 bytes, buffer now full
+        assert!(!buf.insert('b'));
+        assert_eq!(buf.to_string().as_str(), "a€");
+    }
+
+    #[test]
+    fn test_overwrite_truncates_at_char_boundary() {
+        let mut buf: InputBuffer<4> = InputBuffer::new();
+        buf.overwrite("aé€");
+        assert_eq!(buf.to_string().as_str(), "aé");
+        assert_eq!(buf.len(), 2);
+        assert_eq!(buf.cursor(), 2);
+    }
+
     // ============================================================================
     // State Consistency Tests
     // ============================================================================
@@ -0,0 +1,287 @@
+use heapless::String;
+
+use crate::input::buffer::InputBuffer;
+use crate::input::key_reader::{Key, KeyDecoder};
+use crate::input::renderer::{CLEAR_SCREEN, DisplayRenderer, Theme};
+use crate::output::Output;
+
+/// Outcome of feeding one byte to a [`LineEditor`].
+///
+#[derive(Debug, PartialEq, Eq)]
+pub enum EditorEvent<const IML: usize> {
+    /// The line is still being edited (or the byte was part of a longer sequence).
+    Pending,
+    /// `Enter` finished a line.
+    Line(String<IML>),
+    /// `Ctrl+D` on an empty line: end of input.
+    Eof,
+}
+
+#[cfg(feature = "defmt")]
+impl<const IML: usize> defmt::Format for EditorEvent<IML> {
+    fn format(&self, f: defmt::Formatter) {
+        match self {
+            EditorEvent::Pending => defmt::write!(f, "Pending"),
+            EditorEvent::Line(line) => defmt::write!(f, "Line({=str})", line.as_str()),
+            EditorEvent::Eof => defmt::write!(f, "Eof"),
+        }
+    }
+}
+
+/// Applies a cursor movement or deletion key to `buffer`.
+///
+/// Handles the arrows left/right, word moves (`Alt+B` / `Alt+F`), `Home` / `End` (and
+/// `Ctrl+A` / `Ctrl+E`), `Delete`, `Backspace`, `Ctrl+U`, `Ctrl+K`, `Ctrl+W` and undo / redo.
+///
+/// Returns `None` if `key` is not one of them, `Some(false)` if it had nothing to act on
+/// (the caller typically rings the bell), `Some(true)` otherwise.
+///
+pub fn apply_edit<const IML: usize>(buffer: &mut InputBuffer<IML>, key: &Key) -> Option<bool> {
+    match key {
+        Key::ArrowLeft => buffer.move_left(),
+        Key::ArrowRight => buffer.move_right(),
+        Key::AltB => buffer.move_word_left(),
+        Key::AltF => buffer.move_word_right(),
+        Key::Home | Key::CtrlA => buffer.move_home(),
+        Key::End | Key::CtrlE => buffer.move_end(),
+        Key::Delete => buffer.delete_at_cursor(),
+        Key::CtrlU => buffer.delete_to_start(),
+        Key::CtrlK => buffer.delete_to_end(),
+        Key::CtrlW => return Some(buffer.delete_word_back()),
+        Key::Backspace => return Some(buffer.backspace()),
+        Key::CtrlUnderscore => return Some(buffer.undo()),
+        Key::AltUnderscore => return Some(buffer.redo()),
+        _ => return None,
+    }
+    Some(true)
+}
+
+/// LineEditor: a transport-agnostic line editing state machine
+///
+/// Input arrives one byte at a time through [`feed`](Self::feed) (from a UART, a USB CDC
+/// endpoint, a socket or a test script) and all terminal output goes to the [`Output`] passed
+/// with it, so no `stdin` / `stdout` is needed.
+///
+/// Supports the editing keys of [`apply_edit`], `Ctrl+L`, `Ctrl+D` and `Enter`. Autocomplete,
+/// history and the built-in `#` commands are provided by `InputParser` on top of a terminal.
+///
+/// # Type Parameters
+/// - `IML`: Maximum input length, in UTF-8 bytes.
+///
+pub struct LineEditor<const IML: usize> {
+    decoder: KeyDecoder,
+    buffer: InputBuffer<IML>,
+    prompt: &'static str,
+    theme: Theme,
+    ctrl_d_eof: bool,
+}
+
+impl<const IML: usize> LineEditor<IML> {
+    /// Creates an editor with an empty line, showing `prompt` and no colors.
+    ///
+    pub fn new(prompt: &'static str) -> Self {
+        Self {
+            decoder: KeyDecoder::new(),
+            buffer: InputBuffer::new(),
+            prompt,
+            theme: Theme::PLAIN,
+            ctrl_d_eof: true,
+        }
+    }
+
+    /// Sets the colors used for the prompt.
+    ///
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    /// Selects whether `Ctrl+D` on an empty line reports [`EditorEvent::Eof`] (enabled by
+    /// default); when disabled it only clears the line.
+    ///
+    pub fn with_ctrl_d_eof(mut self, enabled: bool) -> Self {
+        self.ctrl_d_eof = enabled;
+        self
+    }
+
+    /// Returns the line being edited.
+    ///
+    pub fn buffer(&self) -> &InputBuffer<IML> {
+        &self.buffer
+    }
+
+    /// Draws the prompt and the current line to `out`.
+    ///
+    /// Call it to show the first prompt, and again after handling each finished line.
+    ///
+    pub fn start(&mut self, out: &mut impl Output) {
+        self.render(out);
+    }
+
+    /// Feeds one input byte, writing the resulting terminal output to `out`.
+    ///
+    /// Escape and UTF-8 sequences are assembled across calls; the key they form is handled
+    /// by [`handle_key`](Self::handle_key) once complete.
+    ///
+    pub fn feed(&mut self, byte: u8, out: &mut impl Output) -> EditorEvent<IML> {
+        match self.decoder.feed(byte) {
+            Some(key) => self.handle_key(key, out),
+            None => EditorEvent::Pending,
+        }
+    }
+
+    /// Handles one decoded key, writing the resulting terminal output to `out`.
+    ///
+    /// - `Enter` moves to a new line and returns the finished line; the buffer is emptied for
+    ///   the next one.
+    /// - `Ctrl+D` returns `Eof` on an empty line (see [`with_ctrl_d_eof`](Self::with_ctrl_d_eof)),
+    ///   and clears the line otherwise.
+    /// - `Ctrl+L` clears the screen and redraws the line.
+    /// - Printable characters are inserted, ringing the bell when the line is full.
+    /// - Other keys are applied with [`apply_edit`], or ignored.
+    ///
+    pub fn handle_key(&mut self, key: Key, out: &mut impl Output) -> EditorEvent<IML> {
+        match key {
+            Key::Enter => {
+                let _ = out.write_str("\r\n");
+                let line = self.buffer.to_string();
+                self.buffer.clear();
+                self.buffer.clear_undo_history();
+                return EditorEvent::Line(line);
+            }
+            Key::CtrlD => {
+                if self.ctrl_d_eof && self.buffer.is_empty() {
+                    let _ = out.write_str("\r\n");
+                    return EditorEvent::Eof;
+                }
+                self.buffer.clear();
+            }
+            Key::CtrlL => {
+                let _ = out.write_str(CLEAR_SCREEN);
+            }
+            Key::Char(c) if !c.is_control() => {
+                if !self.buffer.insert(c) {
+                    out.bell();
+                }
+            }
+            key => match apply_edit(&mut self.buffer, &key) {
+                Some(true) => {}
+                Some(false) => out.bell(),
+                None => return EditorEvent::Pending,
+            },
+        }
+        self.render(out);
+        EditorEvent::Pending
+    }
+
+    fn render(&self, out: &mut impl Output) {
+        let _ = DisplayRenderer::draw_line(
+            out,
+            self.prompt,
+            &self.buffer.to_string(),
+            self.buffer.cursor(),
+            "",
+            &self.theme,
+        );
+    }
+}
+
+// ==================== TESTS =======================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::renderer::BELL;
+
+    type Sink = String<512>;
+
+    fn feed_all<const IML: usize>(
+        editor: &mut LineEditor<IML>,
+        bytes: &[u8],
+        out: &mut Sink,
+    ) -> EditorEvent<IML> {
+        let mut event = EditorEvent::Pending;
+        for &b in bytes {
+            event = editor.feed(b, out);
+        }
+        event
+    }
+
+    #[test]
+    fn test_feed_line() {
+        let mut editor = LineEditor::<32>::new("> ");
+        let mut out = Sink::new();
+        let event = feed_all(&mut editor, b"ab\x1B[D c\r", &mut out);
+        assert_eq!(event, EditorEvent::Line(String::try_from("a cb").unwrap()));
+        assert!(editor.buffer().is_empty());
+        assert!(out.ends_with("\r\n"));
+    }
+
+    #[test]
+    fn test_feed_utf8_and_backspace() {
+        let mut editor = LineEditor::<32>::new("> ");
+        let mut out = Sink::new();
+        let event = feed_all(&mut editor, "héx\x7F\n".as_bytes(), &mut out);
+        assert_eq!(event, EditorEvent::Line(String::try_from("hé").unwrap()));
+    }
+
+    #[test]
+    fn test_feed_renders_to_sink() {
+        let mut editor = LineEditor::<32>::new("> ");
+        let mut out = Sink::new();
+        editor.start(&mut out);
+        assert_eq!(out.as_str(), "\r\x1B[K> \x1B[3G");
+
+        out.clear();
+        editor.feed(b'x', &mut out);
+        assert_eq!(out.as_str(), "\r\x1B[K> x\x1B[4G");
+    }
+
+    #[test]
+    fn test_feed_bell_when_nothing_to_delete() {
+        let mut editor = LineEditor::<32>::new("> ");
+        let mut out = Sink::new();
+        editor.feed(0x7F, &mut out);
+        assert!(out.starts_with(BELL));
+    }
+
+    #[test]
+    fn test_feed_eof_only_on_empty_line() {
+        let mut editor = LineEditor::<32>::new("> ");
+        let mut out = Sink::new();
+        assert_eq!(
+            feed_all(&mut editor, b"ab\x04", &mut out),
+            EditorEvent::Pending
+        );
+        assert!(editor.buffer().is_empty());
+        assert_eq!(editor.feed(0x04, &mut out), EditorEvent::Eof);
+
+        let mut editor = LineEditor::<32>::new("> ").with_ctrl_d_eof(false);
+        assert_eq!(editor.feed(0x04, &mut out), EditorEvent::Pending);
+    }
+
+    #[test]
+    fn test_feed_full_buffer_rings_bell() {
+        let mut editor = LineEditor::<2>::new("> ");
+        let mut out = Sink::new();
+        feed_all(&mut editor, b"ab", &mut out);
+        out.clear();
+        editor.feed(b'c', &mut out);
+        assert!(out.starts_with(BELL));
+        assert_eq!(editor.buffer().to_string().as_str(), "ab");
+    }
+
+    #[test]
+    fn test_apply_edit() {
+        let mut buffer = InputBuffer::<32>::new();
+        buffer.overwrite("one two");
+        assert_eq!(apply_edit(&mut buffer, &Key::CtrlW), Some(true));
+        assert_eq!(buffer.to_string().as_str(), "one ");
+        assert_eq!(apply_edit(&mut buffer, &Key::Home), Some(true));
+        assert_eq!(apply_edit(&mut buffer, &Key::Backspace), Some(false));
+        assert_eq!(apply_edit(&mut buffer, &Key::CtrlUnderscore), Some(true));
+        assert_eq!(buffer.to_string().as_str(), "one two");
+        assert_eq!(apply_edit(&mut buffer, &Key::Tab), None);
+        assert_eq!(apply_edit(&mut buffer, &Key::Char('x')), None);
+    }
+}
@@ -7,6 +7,8 @@ pub enum Key {
     ArrowDown,  // Move to next history entry or move cursor down
     ArrowLeft,  // Move cursor left
     ArrowRight, // Move cursor right
+    AltB,       // Move cursor to the previous word
+    AltF,       // Move cursor to the next word end
 
     // Navigation keys
     Home,     // Move cursor to the start of the line
@@ -23,20 +25,331 @@ pub enum Key {
     ShiftTab,  // Navigate autocomplete backward
 
     // Control sequences for line editing
-    CtrlU, // Delete from cursor to beginning of line
-    CtrlK, // Delete from cursor to end of line
-    CtrlD, // Delete the entire line
+    CtrlU,          // Delete from cursor to beginning of line
+    CtrlK,          // Delete from cursor to end of line
+    CtrlD,          // Delete the entire line
+    CtrlW,          // Delete the word before the cursor
+    CtrlA,          // Move cursor to the beginning of line
+    CtrlE,          // Move cursor to the end of line
+    CtrlL,          // Clear the screen and redraw the prompt
+    CtrlUnderscore, // Undo the last edit (Ctrl+_ / Ctrl+-)
+    AltUnderscore,  // Redo the last undone edit
 
     // Printable character
     Char(char), // Any regular character input
 }
 
-#[cfg(windows)]
+/// Decodes the UTF-8 sequence starting with `first`, pulling its continuation bytes from `next`.
+///
+/// Returns `None` for an invalid lead byte, a truncated sequence or malformed continuation bytes.
+///
+pub fn decode_utf8(first: u8, mut next: impl FnMut() -> Option<u8>) -> Option<char> {
+    let len = match first {
+        0x00..=0x7F => 1,
+        0xC2..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF4 => 4,
+        _ => return None,
+    };
+    let mut bytes = [first, 0, 0, 0];
+    for byte in bytes.iter_mut().take(len).skip(1) {
+        *byte = next()?;
+    }
+    core::str::from_utf8(&bytes[..len]).ok()?.chars().next()
+}
+
+/// KeyReader: a source of keys for `InputParser`
+///
+/// The default [`StdinReader`] reads the local terminal; [`StreamReader`] decodes keys from any
+/// byte stream (serial port, socket, test script), and applications can provide their own, e.g.
+/// over a UART without the `std` feature (or feed the bytes to a [`KeyDecoder`] instead).
+///
+pub trait KeyReader {
+    /// Error of a read, telling the end of the input from other failures.
+    type Error: ReadError;
+
+    /// Blocks until a key is available and returns it.
+    ///
+    /// An error for which [`ReadError::is_eof`] holds means no more input will arrive.
+    ///
+    fn read_key(&mut self) -> Result<Key, Self::Error>;
+
+    /// Returns a key if one is available, without blocking.
+    ///
+    /// The default implementation blocks on [`read_key`](Self::read_key); readers able to
+    /// check for pending input should override it.
+    ///
+    fn poll_key(&mut self) -> Result<Option<Key>, Self::Error> {
+        self.read_key().map(Some)
+    }
+}
+
+/// Error of a [`KeyReader`].
+///
+pub trait ReadError {
+    /// Returns `true` if no more input will arrive.
+    fn is_eof(&self) -> bool;
+}
+
+/// An `UnexpectedEof` error ends the input.
+#[cfg(feature = "std")]
+impl ReadError for std::io::Error {
+    fn is_eof(&self) -> bool {
+        self.kind() == std::io::ErrorKind::UnexpectedEof
+    }
+}
+
+/// The end of the input, for readers that fail in no other way.
+///
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Eof;
+
+impl ReadError for Eof {
+    fn is_eof(&self) -> bool {
+        true
+    }
+}
+
+/// Readers that cannot fail never end either.
+impl ReadError for core::convert::Infallible {
+    fn is_eof(&self) -> bool {
+        match *self {}
+    }
+}
+
+/// Key source of a parser without the `std` feature until one is installed with `with_reader`:
+/// it has no keys, so a shell driven by `feed_key` or `run_async` needs none.
+///
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoKeys;
+
+impl KeyReader for NoKeys {
+    type Error = Eof;
+
+    fn read_key(&mut self) -> Result<Key, Eof> {
+        Err(Eof)
+    }
+
+    fn poll_key(&mut self) -> Result<Option<Key>, Eof> {
+        Ok(None)
+    }
+}
+
+/// Reads keys from the process terminal through the platform-specific `read_key` / `poll_key`.
+///
+#[cfg(feature = "std")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StdinReader;
+
+#[cfg(feature = "std")]
+impl KeyReader for StdinReader {
+    type Error = std::io::Error;
+
+    fn read_key(&mut self) -> std::io::Result<Key> {
+        platform::read_key()
+    }
+
+    fn poll_key(&mut self) -> std::io::Result<Option<Key>> {
+        platform::poll_key()
+    }
+}
+
+/// Decodes keys from a byte stream (any `io::Read`) with a [`KeyDecoder`].
+///
+/// A read returning no bytes is reported as an `UnexpectedEof` error.
+///
+#[cfg(feature = "std")]
+pub struct StreamReader<R> {
+    inner: R,
+    decoder: KeyDecoder,
+}
+
+#[cfg(feature = "std")]
+impl<R: std::io::Read> StreamReader<R> {
+    /// Creates a reader decoding the bytes of `inner`.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            decoder: KeyDecoder::new(),
+        }
+    }
+
+    /// Returns the underlying byte stream.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: std::io::Read> KeyReader for StreamReader<R> {
+    type Error = std::io::Error;
+
+    fn read_key(&mut self) -> std::io::Result<Key> {
+        let mut byte = [0u8];
+        loop {
+            if self.inner.read(&mut byte)? == 0 {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "No input",
+                ));
+            }
+            if let Some(key) = self.decoder.feed(byte[0]) {
+                return Ok(key);
+            }
+        }
+    }
+}
+
+/// Progress of a [`KeyDecoder`] through a multi-byte sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DecodeState {
+    Ground,
+    Escape,
+    Csi,
+    CsiParam(u8),
+    Utf8 { len: u8, have: u8 },
+}
+
+/// KeyDecoder: assembles keys from raw terminal input bytes
+///
+/// Bytes are fed one at a time; escape sequences (`ESC [ A`, `ESC [ 3 ~`, `ESC b`, ...) and
+/// UTF-8 sequences spanning several bytes produce a key once complete. Used by the unix
+/// `read_key` and by `LineEditor`, so any byte transport decodes keys the same way.
+///
+#[derive(Debug, Clone)]
+pub struct KeyDecoder {
+    state: DecodeState,
+    utf8: [u8; 4],
+}
+
+impl KeyDecoder {
+    /// Creates a decoder waiting for the first byte of a key.
+    pub const fn new() -> Self {
+        Self {
+            state: DecodeState::Ground,
+            utf8: [0; 4],
+        }
+    }
+
+    /// Feeds one byte; returns the key it completes, if any.
+    ///
+    /// Unknown escape sequences and malformed UTF-8 are dropped.
+    ///
+    pub fn feed(&mut self, b: u8) -> Option<Key> {
+        match self.state {
+            DecodeState::Ground => self.ground(b),
+            DecodeState::Escape => {
+                // Meta/Alt+<key> arrives as ESC <key>
+                self.state = DecodeState::Ground;
+                match b {
+                    b'b' => Some(Key::AltB),
+                    b'f' => Some(Key::AltF),
+                    b'_' => Some(Key::AltUnderscore),
+                    b'[' => {
+                        self.state = DecodeState::Csi;
+                        None
+                    }
+                    _ => None,
+                }
+            }
+            DecodeState::Csi => {
+                self.state = DecodeState::Ground;
+                Some(match b {
+                    b'A' => Key::ArrowUp,
+                    b'B' => Key::ArrowDown,
+                    b'C' => Key::ArrowRight,
+                    b'D' => Key::ArrowLeft,
+                    b'H' => Key::Home,
+                    b'F' => Key::End,
+                    b'Z' => Key::ShiftTab,
+                    b'1' | b'2' | b'3' | b'5' | b'6' => {
+                        // Wait for the closing '~'
+                        self.state = DecodeState::CsiParam(b);
+                        return None;
+                    }
+                    _ => Key::Char(b as char),
+                })
+            }
+            DecodeState::CsiParam(param) => {
+                self.state = DecodeState::Ground;
+                Some(match param {
+                    b'1' => Key::Home,
+                    b'2' => Key::Insert,
+                    b'3' => Key::Delete,
+                    b'5' => Key::PageUp,
+                    b'6' => Key::PageDown,
+                    _ => Key::Char('~'),
+                })
+            }
+            DecodeState::Utf8 { len, have } => {
+                self.utf8[have as usize] = b;
+                if have + 1 < len {
+                    self.state = DecodeState::Utf8 {
+                        len,
+                        have: have + 1,
+                    };
+                    return None;
+                }
+                self.state = DecodeState::Ground;
+                let mut rest = self.utf8[1..len as usize].iter().copied();
+                decode_utf8(self.utf8[0], || rest.next()).map(Key::Char)
+            }
+        }
+    }
+
+    fn ground(&mut self, b: u8) -> Option<Key> {
+        Some(match b {
+            b'\x1B' => {
+                self.state = DecodeState::Escape;
+                return None;
+            }
+
+            // Control keys
+            b'\x15' => Key::CtrlU,          // Ctrl+U
+            b'\x0B' => Key::CtrlK,          // Ctrl+K
+            b'\x04' => Key::CtrlD,          // Ctrl+D
+            b'\x17' => Key::CtrlW,          // Ctrl+W
+            b'\x01' => Key::CtrlA,          // Ctrl+A
+            b'\x05' => Key::CtrlE,          // Ctrl+E
+            b'\x0C' => Key::CtrlL,          // Ctrl+L
+            b'\x1F' => Key::CtrlUnderscore, // Ctrl+_ / Ctrl+-
+
+            // Normal keys
+            b'\r' | b'\n' => Key::Enter,
+            b'\t' => Key::Tab,
+            b'\x7F' | b'\x08' => Key::Backspace,
+
+            // Multi-byte UTF-8 sequence, an invalid lead byte is dropped
+            c if !c.is_ascii() => {
+                let len = match c {
+                    0xC2..=0xDF => 2,
+                    0xE0..=0xEF => 3,
+                    0xF0..=0xF4 => 4,
+                    _ => return None,
+                };
+                self.utf8[0] = c;
+                self.state = DecodeState::Utf8 { len, have: 1 };
+                return None;
+            }
+            c => Key::Char(c as char),
+        })
+    }
+}
+
+impl Default for KeyDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(all(feature = "std", windows))]
 pub mod platform {
-    use super::Key;
+    use super::{Key, KeyDecoder};
     use std::io;
     use winapi::shared::minwindef::DWORD;
-    use winapi::um::consoleapi::ReadConsoleInputW;
+    use winapi::um::consoleapi::{
+        GetNumberOfConsoleInputEvents, PeekConsoleInputW, ReadConsoleInputW,
+    };
     use winapi::um::processenv::GetStdHandle;
     use winapi::um::winbase::STD_INPUT_HANDLE;
     use winapi::um::wincon::{INPUT_RECORD, KEY_EVENT};
@@ -45,7 +358,49 @@ pub mod platform {
     const LEFT_CTRL_PRESSED: u32 = 0x0008;
     const RIGHT_CTRL_PRESSED: u32 = 0x0004;
     const SHIFT_PRESSED: u32 = 0x0010;
+    const LEFT_ALT_PRESSED: u32 = 0x0002;
+    const RIGHT_ALT_PRESSED: u32 = 0x0001;
+
+    /// Reads a key if one is pending, without blocking.
+    ///
+    /// Pending events other than key presses (key releases, mouse, focus) are discarded.
+    ///
+    pub fn poll_key() -> io::Result<Option<Key>> {
+        unsafe {
+            let handle = GetStdHandle(STD_INPUT_HANDLE);
+            if handle.is_null() {
+                return Err(io::Error::new(io::ErrorKind::Other, "Invalid handle"));
+            }
 
+            let mut record: INPUT_RECORD = std::mem::zeroed();
+            let mut count: DWORD = 0;
+            loop {
+                if GetNumberOfConsoleInputEvents(handle, &mut count) == 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                if count == 0 {
+                    return Ok(None);
+                }
+                if PeekConsoleInputW(handle, &mut record, 1, &mut count) == 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                if record.EventType == KEY_EVENT && record.Event.KeyEvent().bKeyDown != 0 {
+                    return read_key().map(Some);
+                }
+                if ReadConsoleInputW(handle, &mut record, 1, &mut count) == 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+        }
+    }
+
+    /// Reads one key press from the console.
+    ///
+    /// Keys are mapped from their virtual key codes. When the console delivers virtual-terminal
+    /// input instead (`ENABLE_VIRTUAL_TERMINAL_INPUT`, set by Windows Terminal, SSH or the
+    /// application), the characters of an escape sequence (`ESC [ A`, `ESC [ 3 ~`, ...) arrive
+    /// as separate events and are assembled by `KeyDecoder`, as on unix.
+    ///
     pub fn read_key() -> io::Result<Key> {
         unsafe {
             let handle = GetStdHandle(STD_INPUT_HANDLE);
@@ -55,6 +410,9 @@ pub mod platform {
 
             let mut record: INPUT_RECORD = std::mem::zeroed();
             let mut read: DWORD = 0;
+            let mut high_surrogate: Option<u16> = None;
+            let mut decoder = KeyDecoder::new();
+            let mut in_sequence = false;
 
             loop {
                 if ReadConsoleInputW(handle, &mut record, 1, &mut read) == 0 {
@@ -73,13 +431,47 @@ pub mod platform {
                         & (LEFT_CTRL_PRESSED | RIGHT_CTRL_PRESSED))
                         != 0;
                     let shift = (key_event.dwControlKeyState & SHIFT_PRESSED) != 0;
+                    let alt =
+                        (key_event.dwControlKeyState & (LEFT_ALT_PRESSED | RIGHT_ALT_PRESSED)) != 0;
+
+                    // Virtual-terminal input: feed the sequence to the decoder until it forms a key
+                    if c == 0x1B {
+                        in_sequence = true;
+                    }
+                    if in_sequence {
+                        if c == 0 {
+                            continue;
+                        }
+                        if c < 0x80 {
+                            if let Some(key) = decoder.feed(c as u8) {
+                                return Ok(key);
+                            }
+                            continue;
+                        }
+                        in_sequence = false;
+                    }
 
                     // Handle Ctrl+ combos explicitly
                     if ctrl {
                         match vkey {
-                            0x55 => return Ok(Key::CtrlU), // 'U'
-                            0x4B => return Ok(Key::CtrlK), // 'K'
-                            0x44 => return Ok(Key::CtrlD), // 'D'
+                            0x55 => return Ok(Key::CtrlU),          // 'U'
+                            0x4B => return Ok(Key::CtrlK),          // 'K'
+                            0x44 => return Ok(Key::CtrlD),          // 'D'
+                            0x57 => return Ok(Key::CtrlW),          // 'W'
+                            0x41 => return Ok(Key::CtrlA),          // 'A'
+                            0x45 => return Ok(Key::CtrlE),          // 'E'
+                            0x4C => return Ok(Key::CtrlL),          // 'L'
+                            0xBD => return Ok(Key::CtrlUnderscore), // '-' / '_'
+                            _ => {}
+                        }
+                    }
+
+                    // Handle Alt+ combos (word navigation)
+                    if alt {
+                        match vkey {
+                            0x42 => return Ok(Key::AltB),          // 'B'
+                            0x46 => return Ok(Key::AltF),          // 'F'
+                            0xBD => return Ok(Key::AltUnderscore), // '-' / '_'
                             _ => {}
                         }
                     }
@@ -101,6 +493,20 @@ pub mod platform {
                         _ => {}
                     }
 
+                    // Characters outside the BMP arrive as two UTF-16 surrogate events
+                    if (0xD800..0xDC00).contains(&c) {
+                        high_surrogate = Some(c as u16);
+                        continue;
+                    }
+                    if let Some(high) = high_surrogate.take()
+                        && (0xDC00..0xE000).contains(&c)
+                    {
+                        if let Some(Ok(ch)) = char::decode_utf16([high, c as u16]).next() {
+                            return Ok(Key::Char(ch));
+                        }
+                        continue;
+                    }
+
                     // Printable char — ignore NULs
                     if c != 0 {
                         return Ok(Key::Char(std::char::from_u32(c).unwrap_or('\0')));
@@ -111,58 +517,37 @@ pub mod platform {
     }
 }
 
-#[cfg(not(windows))]
+#[cfg(all(feature = "std", not(windows)))]
 pub mod platform {
-    use super::Key;
+    use super::{Key, KeyDecoder};
     use std::io::{self, Read};
 
+    /// Reads a key if input is pending on stdin, without blocking.
+    ///
+    /// Only waiting for the first byte is avoided; the rest of an escape or UTF-8 sequence is
+    /// read as usual.
+    ///
+    pub fn poll_key() -> io::Result<Option<Key>> {
+        let mut fds = libc::pollfd {
+            fd: libc::STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: `fds` is a valid array of one pollfd; a zero timeout makes the call non-blocking.
+        match unsafe { libc::poll(&mut fds, 1, 0) } {
+            -1 => Err(io::Error::last_os_error()),
+            0 => Ok(None),
+            _ => read_key().map(Some),
+        }
+    }
+
     pub fn read_key() -> io::Result<Key> {
         let stdin = io::stdin();
-        let mut bytes = stdin.lock().bytes();
-
-        while let Some(Ok(b)) = bytes.next() {
-            match b {
-                b'\x1B' => {
-                    // Escape sequence
-                    if let Some(Ok(b2)) = bytes.next()
-                        && b2 == b'['
-                        && let Some(Ok(b3)) = bytes.next()
-                    {
-                        return Ok(match b3 {
-                            b'A' => Key::ArrowUp,
-                            b'B' => Key::ArrowDown,
-                            b'C' => Key::ArrowRight,
-                            b'D' => Key::ArrowLeft,
-                            b'H' => Key::Home,
-                            b'F' => Key::End,
-                            b'Z' => Key::ShiftTab,
-                            b'1' | b'2' | b'3' | b'5' | b'6' => {
-                                // Read next '~' to confirm
-                                let _ = bytes.next();
-                                match b3 {
-                                    b'1' => Key::Home,
-                                    b'2' => Key::Insert,
-                                    b'3' => Key::Delete,
-                                    b'5' => Key::PageUp,
-                                    b'6' => Key::PageDown,
-                                    _ => Key::Char('~'),
-                                }
-                            }
-                            _ => Key::Char(b3 as char),
-                        });
-                    }
-                }
+        let mut decoder = KeyDecoder::new();
 
-                // Control keys
-                b'\x15' => return Ok(Key::CtrlU), // Ctrl+U
-                b'\x0B' => return Ok(Key::CtrlK), // Ctrl+K
-                b'\x04' => return Ok(Key::CtrlD), // Ctrl+D
-
-                // Normal keys
-                b'\r' | b'\n' => return Ok(Key::Enter),
-                b'\t' => return Ok(Key::Tab),
-                b'\x7F' | b'\x08' => return Ok(Key::Backspace),
-                c => return Ok(Key::Char(c as char)),
+        for b in stdin.lock().bytes() {
+            if let Some(key) = decoder.feed(b?) {
+                return Ok(key);
             }
         }
 
@@ -181,6 +566,8 @@ pub fn key_test() -> io::Result<()> {
             Key::ArrowDown => println!("Arrow Down"),
             Key::ArrowLeft => println!("Arrow Left"),
             Key::ArrowRight => println!("Arrow Right"),
+            Key::AltB => println!("Alt+B"),
+            Key::AltF => println!("Alt+F"),
             Key::Home => println!("Home"),
             Key::End => println!("End"),
             Key::Insert => println!("Insert"),
@@ -194,6 +581,12 @@ pub fn key_test() -> io::Result<()> {
             Key::CtrlU => println!("Ctrl+U"),
             Key::CtrlK => println!("Ctrl+K"),
             Key::CtrlD => println!("Ctrl+D"),
+            Key::CtrlW => println!("Ctrl+W"),
+            Key::CtrlA => println!("Ctrl+A"),
+            Key::CtrlE => println!("Ctrl+E"),
+            Key::CtrlL => println!("Ctrl+L"),
+            Key::CtrlUnderscore => println!("Ctrl+_"),
+            Key::AltUnderscore => println!("Alt+_"),
             Key::Char(c) => println!("Char: {:?}", c),
         }
     }
@@ -224,6 +617,8 @@ mod tests {
             Key::ArrowDown,
             Key::ArrowLeft,
             Key::ArrowRight,
+            Key::AltB,
+            Key::AltF,
             Key::Home,
             Key::End,
             Key::Insert,
@@ -237,6 +632,12 @@ mod tests {
             Key::CtrlU,
             Key::CtrlK,
             Key::CtrlD,
+            Key::CtrlW,
+            Key::CtrlA,
+            Key::CtrlE,
+            Key::CtrlL,
+            Key::CtrlUnderscore,
+            Key::AltUnderscore,
             Key::Char('x'),
         ];
     }
@@ -303,6 +704,8 @@ mod tests {
                     | Key::ArrowRight
                     | Key::Home
                     | Key::End
+                    | Key::CtrlA
+                    | Key::CtrlE
                     | Key::PageUp
                     | Key::PageDown
             )
@@ -311,20 +714,23 @@ mod tests {
         fn is_editing_key(key: &Key) -> bool {
             matches!(
                 key,
-                Key::Backspace | Key::Delete | Key::CtrlU | Key::CtrlK | Key::CtrlD
+                Key::Backspace | Key::Delete | Key::CtrlU | Key::CtrlK | Key::CtrlD | Key::CtrlW
             )
         }
 
         assert!(is_navigation_key(&Key::Home));
         assert!(is_navigation_key(&Key::PageDown));
+        assert!(is_navigation_key(&Key::CtrlE));
         assert!(!is_navigation_key(&Key::Tab));
 
         assert!(is_editing_key(&Key::Delete));
         assert!(is_editing_key(&Key::CtrlU));
+        assert!(is_editing_key(&Key::CtrlW));
         assert!(!is_editing_key(&Key::Enter));
     }
 
     #[test]
+    #[allow(clippy::needless_return)]
     fn test_key_to_string_representation() {
         fn key_name(key: &Key) -> &str {
             match key {
@@ -332,6 +738,8 @@ mod tests {
                 Key::ArrowDown => "↓",
                 Key::ArrowLeft => "←",
                 Key::ArrowRight => "→",
+                Key::AltB => "Alt+B",
+                Key::AltF => "Alt+F",
                 Key::Home => "Home",
                 Key::End => "End",
                 Key::Enter => "Enter",
@@ -341,6 +749,12 @@ mod tests {
                 Key::CtrlU => "Ctrl+U",
                 Key::CtrlK => "Ctrl+K",
                 Key::CtrlD => "Ctrl+D",
+                Key::CtrlW => "Ctrl+W",
+                Key::CtrlA => "Ctrl+A",
+                Key::CtrlE => "Ctrl+E",
+                Key::CtrlL => "Ctrl+L",
+                Key::CtrlUnderscore => "Ctrl+_",
+                Key::AltUnderscore => "Alt+_",
                 Key::Delete => "Delete",
                 Key::PageUp => "PgUp",
                 Key::PageDown => "PgDn",
@@ -353,4 +767,83 @@ mod tests {
         assert_eq!(key_name(&Key::CtrlK), "Ctrl+K");
         assert_eq!(key_name(&Key::Char(' ')), "Space");
     }
+
+    fn decode_keys(bytes: &[u8]) -> std::vec::Vec<std::string::String> {
+        let mut decoder = KeyDecoder::new();
+        bytes
+            .iter()
+            .filter_map(|&b| decoder.feed(b))
+            .map(|key| format!("{:?}", key))
+            .collect()
+    }
+
+    #[test]
+    fn test_key_decoder_sequences() {
+        assert_eq!(
+            decode_keys(b"a\x1B[A\x1B[3~\x1Bb\x1B[Z\r\x7F\x15"),
+            [
+                "Char('a')",
+                "ArrowUp",
+                "Delete",
+                "AltB",
+                "ShiftTab",
+                "Enter",
+                "Backspace",
+                "CtrlU"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_key_decoder_utf8() {
+        assert_eq!(
+            decode_keys("é€😀".as_bytes()),
+            ["Char('é')", "Char('€')", "Char('😀')"]
+        );
+        assert_eq!(decode_keys(&[0xC3, 0x41, b'x']), ["Char('x')"]); // malformed dropped
+    }
+
+    #[test]
+    fn test_key_decoder_drops_unknown_escape() {
+        assert_eq!(decode_keys(b"\x1Bqz"), ["Char('z')"]);
+    }
+
+    #[test]
+    fn test_no_keys() {
+        assert_eq!(NoKeys.poll_key().ok().map(|key| key.is_none()), Some(true));
+        assert!(NoKeys.read_key().unwrap_err().is_eof());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_stream_reader() {
+        let mut reader = StreamReader::new(&b"x\x1B[B\xC3\xA9"[..]);
+        assert!(matches!(reader.read_key(), Ok(Key::Char('x'))));
+        assert!(matches!(reader.poll_key(), Ok(Some(Key::ArrowDown))));
+        assert!(matches!(reader.read_key(), Ok(Key::Char('é'))));
+        let err = reader.read_key().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+        assert!(err.is_eof());
+    }
+
+    fn decode(bytes: &[u8]) -> Option<char> {
+        let mut rest = bytes[1..].iter().copied();
+        decode_utf8(bytes[0], || rest.next())
+    }
+
+    #[test]
+    fn test_decode_utf8_sequences() {
+        assert_eq!(decode(b"a"), Some('a'));
+        assert_eq!(decode("é".as_bytes()), Some('é'));
+        assert_eq!(decode("€".as_bytes()), Some('€'));
+        assert_eq!(decode("😀".as_bytes()), Some('😀'));
+    }
+
+    #[test]
+    fn test_decode_utf8_rejects_malformed() {
+        assert_eq!(decode(&[0x80]), None); // continuation byte as lead
+        assert_eq!(decode(&[0xC0, 0x80]), None); // overlong lead
+        assert_eq!(decode(&[0xE2, 0x82]), None); // truncated
+        assert_eq!(decode(&[0xC3, 0x41]), None); // bad continuation
+    }
 }
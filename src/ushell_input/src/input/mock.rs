@@ -0,0 +1,306 @@
+//! Scripted terminal for integration tests: keys are queued up front, output is captured.
+//!
+//! A [`MockTerminal`] hands out a key source ([`MockKeys`]) and an output ([`MockOutput`]) that
+//! share its state, so a test installs them in an `InputParser` (or a `uShell`) and keeps the
+//! terminal to queue more keys and inspect what was rendered, without a TTY:
+//!
+//! ```rust,ignore
+//! let term = MockTerminal::new();
+//! let mut shell = uShell::new(...)
+//!     .with_reader(term.keys())
+//!     .with_output(term.output());
+//!
+//! term.type_line("set_freq 1200").type_str("set_f").key(Key::Tab);
+//! shell.run(); // returns once the script is used up
+//! assert!(term.take_text().contains("set_freq 1200"));
+//! ```
+
+use core::fmt;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::string::String;
+use std::vec;
+use std::vec::Vec;
+
+use crate::input::key_reader::{Key, KeyDecoder, KeyReader};
+use crate::output::Output;
+
+/// State shared by a [`MockTerminal`] and the readers and outputs it handed out.
+#[derive(Default)]
+struct State {
+    keys: VecDeque<Key>,
+    output: String,
+    width: Option<usize>,
+    flushes: usize,
+}
+
+/// A scripted terminal: queues keys for a [`MockKeys`] reader and collects the text written to
+/// a [`MockOutput`].
+///
+/// Cloning it gives another handle to the same terminal.
+///
+#[derive(Clone, Default)]
+pub struct MockTerminal {
+    state: Rc<RefCell<State>>,
+}
+
+impl MockTerminal {
+    /// Creates a terminal with no queued keys and no known width.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reports `columns` as the width of the output, so long lines scroll as on a real screen.
+    pub fn with_width(self, columns: usize) -> Self {
+        self.state.borrow_mut().width = Some(columns);
+        self
+    }
+
+    /// Returns a key source reading the queued keys.
+    ///
+    /// Once they are used up, `read_key` reports `UnexpectedEof` (the parser then ends input,
+    /// as at the end of a script) and `poll_key` reports no pending key.
+    ///
+    pub fn keys(&self) -> MockKeys {
+        MockKeys {
+            state: Rc::clone(&self.state),
+        }
+    }
+
+    /// Returns an output collecting everything written to it in this terminal.
+    pub fn output(&self) -> MockOutput {
+        MockOutput {
+            state: Rc::clone(&self.state),
+        }
+    }
+
+    /// Queues one key.
+    pub fn key(&self, key: Key) -> &Self {
+        self.state.borrow_mut().keys.push_back(key);
+        self
+    }
+
+    /// Queues a key per character of `text`, with `\n` as `Enter`.
+    pub fn type_str(&self, text: &str) -> &Self {
+        let mut state = self.state.borrow_mut();
+        state.keys.extend(text.chars().map(|c| match c {
+            '\n' => Key::Enter,
+            c => Key::Char(c),
+        }));
+        drop(state);
+        self
+    }
+
+    /// Queues the characters of `line`, then `Enter`.
+    pub fn type_line(&self, line: &str) -> &Self {
+        self.type_str(line).key(Key::Enter)
+    }
+
+    /// Queues the keys encoded by raw terminal bytes, e.g. `b"\x1b[A"` for the up arrow, decoded
+    /// as the `StreamReader` of a serial port would.
+    pub fn send_bytes(&self, bytes: &[u8]) -> &Self {
+        let mut decoder = KeyDecoder::new();
+        let mut state = self.state.borrow_mut();
+        state
+            .keys
+            .extend(bytes.iter().filter_map(|&b| decoder.feed(b)));
+        drop(state);
+        self
+    }
+
+    /// Returns the number of queued keys not read yet.
+    pub fn pending_keys(&self) -> usize {
+        self.state.borrow().keys.len()
+    }
+
+    /// Returns how many times the output was flushed, i.e. how many updates were rendered.
+    pub fn flushes(&self) -> usize {
+        self.state.borrow().flushes
+    }
+
+    /// Returns the output collected so far, escape sequences included, and clears it.
+    pub fn take_output(&self) -> String {
+        core::mem::take(&mut self.state.borrow_mut().output)
+    }
+
+    /// Returns the output collected so far as a terminal would show it, and clears it.
+    ///
+    /// Redraws of the input line overwrite each other and escape sequences are applied or
+    /// dropped (see `render`), so a test sees `"> status\n"` rather than every keystroke.
+    ///
+    pub fn take_text(&self) -> String {
+        render(&self.take_output())
+    }
+}
+
+/// The key source of a [`MockTerminal`].
+///
+pub struct MockKeys {
+    state: Rc<RefCell<State>>,
+}
+
+impl KeyReader for MockKeys {
+    type Error = std::io::Error;
+
+    fn read_key(&mut self) -> std::io::Result<Key> {
+        self.state
+            .borrow_mut()
+            .keys
+            .pop_front()
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "End of script"))
+    }
+
+    fn poll_key(&mut self) -> std::io::Result<Option<Key>> {
+        Ok(self.state.borrow_mut().keys.pop_front())
+    }
+}
+
+/// The output of a [`MockTerminal`].
+///
+pub struct MockOutput {
+    state: Rc<RefCell<State>>,
+}
+
+impl fmt::Write for MockOutput {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.state.borrow_mut().output.push_str(s);
+        Ok(())
+    }
+}
+
+impl Output for MockOutput {
+    fn flush(&mut self) {
+        self.state.borrow_mut().flushes += 1;
+    }
+
+    fn width(&self) -> Option<usize> {
+        self.state.borrow().width
+    }
+}
+
+/// Replays `text` on an unbounded screen and returns the lines left on it.
+///
+/// Handles what the renderer emits: `\r`, `\n`, backspace, erase to end of line (`ESC [ K`),
+/// cursor columns and moves (`ESC [ n G/C/D`) and clear screen (`ESC [ 2 J`). Colors and bells
+/// are dropped; every character takes one column, and trailing spaces are not kept.
+fn render(text: &str) -> String {
+    let mut lines: Vec<Vec<char>> = vec![Vec::new()];
+    let mut col = 0usize;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        let line = lines.last_mut().expect("at least one line");
+        match c {
+            '\r' => col = 0,
+            '\n' => {
+                lines.push(Vec::new());
+                col = 0;
+            }
+            '\x08' => col = col.saturating_sub(1),
+            '\x07' => {}
+            '\x1B' => {
+                if chars.next() != Some('[') {
+                    continue;
+                }
+                let mut param = String::new();
+                for c in chars.by_ref() {
+                    if !('@'..='~').contains(&c) {
+                        param.push(c);
+                        continue;
+                    }
+                    let n: usize = param.parse().unwrap_or(1);
+                    match c {
+                        'K' => line.truncate(col),
+                        'G' => col = n.saturating_sub(1),
+                        'C' => col += n,
+                        'D' => col = col.saturating_sub(n),
+                        'J' if n == 2 => {
+                            lines = vec![Vec::new()];
+                            col = 0;
+                        }
+                        _ => {}
+                    }
+                    break;
+                }
+            }
+            c => {
+                if line.len() <= col {
+                    line.resize(col, ' ');
+                    line.push(c);
+                } else {
+                    line[col] = c;
+                }
+                col += 1;
+            }
+        }
+    }
+    let lines: Vec<String> = lines
+        .iter()
+        .map(|l| l.iter().collect::<String>().trim_end().into())
+        .collect();
+    lines.join("\n")
+}
+
+// ==================== TESTS =======================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::parser::{InputParser, ShellEvent};
+    use crate::input::renderer::Theme;
+
+    const COMMANDS: &[(&str, &str)] = &[("set_freq", "D:freq"), ("status", "v")];
+
+    fn parser(term: &MockTerminal) -> InputParser<'static, 4, 16, 64, 64, 8, MockKeys, MockOutput> {
+        InputParser::new(COMMANDS, "", "", "> ")
+            .with_history_file(None)
+            .with_theme(Theme::PLAIN)
+            .with_reader(term.keys())
+            .with_output(term.output())
+    }
+
+    #[test]
+    fn test_scripted_lines() {
+        let term = MockTerminal::new();
+        let mut parser = parser(&term);
+        term.type_line("status")
+            .type_str("set_f")
+            .key(Key::Tab)
+            .type_str("5\n");
+        assert_eq!(
+            parser.read_line(),
+            ShellEvent::Line("status".try_into().unwrap())
+        );
+        assert_eq!(
+            parser.read_line(),
+            ShellEvent::Line("set_freq 5".try_into().unwrap())
+        );
+        assert_eq!(term.pending_keys(), 0);
+        assert_eq!(parser.read_line(), ShellEvent::Exit);
+        let text = term.take_text();
+        assert_eq!(text, "> status\n> set_freq 5\n>");
+        assert!(term.flushes() > 0);
+        assert!(term.take_output().is_empty());
+    }
+
+    #[test]
+    fn test_raw_bytes_and_poll() {
+        let term = MockTerminal::new();
+        let mut parser = parser(&term);
+        term.type_line("status").send_bytes(b"\x1b[A\r");
+        let mut lines = Vec::new();
+        while let Some(event) = parser.poll() {
+            if let ShellEvent::Line(line) = event {
+                lines.push(line);
+            }
+        }
+        assert_eq!(lines, ["status", "status"]);
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(render("\r\x1B[K\x1B[1;32m> \x1B[0mok \x07\n"), "> ok\n");
+        assert_eq!(render("> abc\x1B[4G\x1B[Kx\x1B[2Cy"), "> ax  y");
+        assert_eq!(render("old\n\x1B[2J\x1B[Hnew\x08\x08E"), "nEw");
+    }
+}
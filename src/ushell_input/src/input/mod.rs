@@ -1,4 +1,7 @@
 pub mod buffer;
+pub mod editor;
 pub mod key_reader;
+#[cfg(feature = "std")]
+pub mod mock;
 pub mod parser;
 pub mod renderer;
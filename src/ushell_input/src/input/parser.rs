@@ -1,6 +1,5 @@
 #![allow(clippy::unbuffered_bytes)]
 
-use heapless::{String, Vec};
 /// InputParser is a generic, configurable command-line input handler designed for embedded or constrained environments. It supports:
 /// - Autocompletion
 /// - Input history
@@ -13,14 +12,84 @@ use heapless::{String, Vec};
 /// - History
 /// - InputBuffer
 /// - DisplayRenderer
-use std::io::{self, Write};
-
-use crate::autocomplete::Autocomplete;
-use crate::history::History;
+use crate::autocomplete::args::{self, ArgContext, DirLister, MAX_ARG_CANDIDATES};
+use crate::autocomplete::{Autocomplete, MatchMode};
+use crate::history::{DedupPolicy, History};
 use crate::input::buffer::InputBuffer;
+use crate::input::editor::apply_edit;
 use crate::input::key_reader::Key;
-use crate::input::key_reader::platform::read_key;
-use crate::input::renderer::DisplayRenderer;
+use crate::input::key_reader::{KeyReader, ReadError};
+use crate::input::renderer::{DisplayRenderer, Theme};
+use crate::output::{DEFAULT_WIDTH, Output};
+use core::fmt;
+use heapless::{String, Vec};
+
+/// Key source of a parser created with [`InputParser::new`]: the local terminal with the `std`
+/// feature, none without it (install one with [`InputParser::with_reader`], or feed the keys
+/// with [`InputParser::feed_key`]).
+#[cfg(feature = "std")]
+pub type DefaultReader = crate::input::key_reader::StdinReader;
+#[cfg(not(feature = "std"))]
+pub type DefaultReader = crate::input::key_reader::NoKeys;
+
+/// Output of a parser created with [`InputParser::new`]: the standard output with the `std`
+/// feature, none without it (install one with [`InputParser::with_output`]).
+#[cfg(feature = "std")]
+pub type DefaultOutput = crate::output::Stdout;
+#[cfg(not(feature = "std"))]
+pub type DefaultOutput = crate::output::Discard;
+
+/// Maximum length of a prompt produced by a prompt provider (longer output is truncated).
+pub const PROMPT_MAX_LEN: usize = 64;
+
+/// Maximum number of command tables added with [`InputParser::add_commands`].
+pub const MAX_COMMAND_TABLES: usize = 4;
+
+/// Number of most recent history entries listed by `#h` (`#h N` lists `N`).
+pub const HISTORY_SHOW_COUNT: usize = 20;
+
+/// Maximum length of the error code shown by a [`StatusIndicator`] (longer codes are truncated).
+pub const STATUS_CODE_LEN: usize = 24;
+
+/// What the prompt shows of the result of the previous line (see
+/// [`InputParser::with_status_indicator`]).
+///
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum StatusIndicator {
+    /// Nothing.
+    #[default]
+    Off,
+    /// `✓` after a success, `✗` after a failure.
+    Glyph,
+    /// The error code after a failure, nothing after a success.
+    Code,
+    /// `✓` after a success, `✗` and the error code after a failure.
+    GlyphAndCode,
+}
+
+/// Outcome of [`InputParser::poll`] for a consumed key, or of [`InputParser::read_line`].
+///
+#[derive(Debug, PartialEq, Eq)]
+pub enum ShellEvent<const IML: usize> {
+    /// The line is still being edited, or was handled without anything to execute.
+    Pending,
+    /// A finished line to execute, or a hashtag command registered with
+    /// [`InputParser::with_hashtags`] (with its leading `#`).
+    Line(String<IML>),
+    /// The user requested to quit.
+    Exit,
+}
+
+#[cfg(feature = "defmt")]
+impl<const IML: usize> defmt::Format for ShellEvent<IML> {
+    fn format(&self, f: defmt::Formatter) {
+        match self {
+            ShellEvent::Pending => defmt::write!(f, "Pending"),
+            ShellEvent::Line(line) => defmt::write!(f, "Line({=str})", line.as_str()),
+            ShellEvent::Exit => defmt::write!(f, "Exit"),
+        }
+    }
+}
 
 /// # Type Parameters
 /// - `NC`: Maximum number of autocomplete candidates.
@@ -28,15 +97,33 @@ use crate::input::renderer::DisplayRenderer;
 /// - `IML`: Maximum input buffer length.
 /// - `HTC`: History capacity (number of entries).
 /// - `HME`: Maximum entry length in history.
+/// - `KR`: Source of the keys ([`DefaultReader`] by default, see [`with_reader`](Self::with_reader)).
+/// - `OUT`: Destination of the output ([`DefaultOutput`] by default, see [`with_output`](Self::with_output)).
 ///
 /// # Fields
 /// - `shell_commands`: Static list of available shell commands and their descriptions.
+/// - `extra_commands`: Further command lists added with `add_commands` (e.g. other modules).
 /// - `shell_datatypes`: Description of supported argument types.
 /// - `shell_shortcuts`: Description of available keyboard shortcuts.
+/// - `shell_usage`: Optional per-command usage lookup (e.g. the generated `usage(name)`).
+/// - `shell_shortcut_specs`: `(shortcut, description)` pairs for the shortcut table (empty if unset).
+/// - `host_hashtags`: `(name, description)` pairs of hashtag commands handled by the caller.
 /// - `autocomplete`: Autocomplete engine for input suggestions.
 /// - `history`: Command history manager (heap-allocated or stack-based depending on feature flags).
 /// - `buffer`: Input buffer for editing and cursor movement (heap-allocated or stack-based depending on feature flags).
 /// - `prompt`: Static prompt string displayed to the user.
+/// - `prompt_fn`: Optional provider writing the prompt, evaluated before each render (overrides `prompt`).
+/// - `status_indicator`: What the prompt shows of `last_status` (nothing by default).
+/// - `last_status`: Result of the previous line, with its error code (`None` before the first one).
+/// - `dir_lister`: Lists directory entries to complete `s{path}` arguments (the filesystem by
+///   default with the `std` feature, nothing without it).
+/// - `ctrl_d_eof`: `Ctrl+D` on an empty line ends input like an end-of-file (enabled by default).
+/// - `theme`: Colors used for the prompt, hints and markers (`Theme::detect()` by default with the
+///   `std` feature, `Theme::PLAIN` without it).
+/// - `ghost`: Predicted completion shown as dimmed text after the cursor (empty if none):
+///   the rest of a matching history line, or of the autocompleted command name.
+/// - `prompt_shown`: `poll` or `feed_key` already rendered the prompt for the current line.
+/// - `candidates_shown`: The matching command names were listed by the current run of `Tab` presses.
 ///
 pub struct InputParser<
     'a,
@@ -45,10 +132,18 @@ pub struct InputParser<
     const IML: usize,
     const HTC: usize,
     const HME: usize,
+    KR: KeyReader = DefaultReader,
+    OUT: Output = DefaultOutput,
 > {
+    reader: KR,
+    out: OUT,
     shell_commands: &'static [(&'static str, &'static str)],
+    extra_commands: Vec<&'static [(&'static str, &'static str)], MAX_COMMAND_TABLES>,
     shell_datatypes: &'static str,
     shell_shortcuts: &'static str,
+    shell_usage: Option<fn(&str) -> &'static str>,
+    shell_shortcut_specs: &'static [(&'static str, &'static str)],
+    host_hashtags: &'static [(&'static str, &'static str)],
     autocomplete: Autocomplete<'a, NC, FNL>,
 
     #[cfg(feature = "heap-history")]
@@ -62,6 +157,15 @@ pub struct InputParser<
     buffer: InputBuffer<IML>,
 
     prompt: &'static str,
+    prompt_fn: Option<fn(&mut dyn fmt::Write)>,
+    status_indicator: StatusIndicator,
+    last_status: Option<Result<(), String<STATUS_CODE_LEN>>>,
+    dir_lister: DirLister,
+    ctrl_d_eof: bool,
+    theme: Theme,
+    ghost: String<IML>,
+    prompt_shown: bool,
+    candidates_shown: bool,
 }
 
 impl<'a, const NC: usize, const FNL: usize, const IML: usize, const HTC: usize, const HME: usize>
@@ -101,13 +205,385 @@ impl<'a, const NC: usize, const FNL: usize, const IML: usize, const HTC: usize,
         let buffer = InputBuffer::<IML>::new();
 
         Self {
+            reader: DefaultReader::default(),
+            out: DefaultOutput::default(),
             shell_commands,
+            extra_commands: Vec::new(),
             shell_datatypes,
             shell_shortcuts,
+            shell_usage: None,
+            shell_shortcut_specs: &[],
+            host_hashtags: &[],
             autocomplete: Autocomplete::<'a, NC, FNL>::new(candidates),
             history,
             buffer,
             prompt,
+            prompt_fn: None,
+            status_indicator: StatusIndicator::Off,
+            last_status: None,
+            #[cfg(feature = "std")]
+            dir_lister: args::list_dir,
+            #[cfg(not(feature = "std"))]
+            dir_lister: |_, _| {},
+            ctrl_d_eof: true,
+            #[cfg(feature = "std")]
+            theme: Theme::detect(),
+            #[cfg(not(feature = "std"))]
+            theme: Theme::PLAIN,
+            ghost: String::new(),
+            prompt_shown: false,
+            candidates_shown: false,
+        }
+    }
+}
+
+impl<
+    'a,
+    const NC: usize,
+    const FNL: usize,
+    const IML: usize,
+    const HTC: usize,
+    const HME: usize,
+    KR: KeyReader,
+    OUT: Output,
+> InputParser<'a, NC, FNL, IML, HTC, HME, KR, OUT>
+{
+    /// Replaces the source of the keys, e.g. with a `StreamReader` over a serial port or a
+    /// test script.
+    ///
+    pub fn with_reader<R: KeyReader>(
+        self,
+        reader: R,
+    ) -> InputParser<'a, NC, FNL, IML, HTC, HME, R, OUT> {
+        InputParser {
+            reader,
+            out: self.out,
+            shell_commands: self.shell_commands,
+            extra_commands: self.extra_commands,
+            shell_datatypes: self.shell_datatypes,
+            shell_shortcuts: self.shell_shortcuts,
+            shell_usage: self.shell_usage,
+            shell_shortcut_specs: self.shell_shortcut_specs,
+            host_hashtags: self.host_hashtags,
+            autocomplete: self.autocomplete,
+            history: self.history,
+            buffer: self.buffer,
+            prompt: self.prompt,
+            prompt_fn: self.prompt_fn,
+            status_indicator: self.status_indicator,
+            last_status: self.last_status,
+            dir_lister: self.dir_lister,
+            ctrl_d_eof: self.ctrl_d_eof,
+            theme: self.theme,
+            ghost: self.ghost,
+            prompt_shown: self.prompt_shown,
+            candidates_shown: self.candidates_shown,
+        }
+    }
+
+    /// Replaces the destination of all output (prompt, edits, listings, messages), e.g. with
+    /// a serial port, or a `String` to capture it.
+    ///
+    pub fn with_output<O: Output>(self, out: O) -> InputParser<'a, NC, FNL, IML, HTC, HME, KR, O> {
+        InputParser {
+            reader: self.reader,
+            out,
+            shell_commands: self.shell_commands,
+            extra_commands: self.extra_commands,
+            shell_datatypes: self.shell_datatypes,
+            shell_shortcuts: self.shell_shortcuts,
+            shell_usage: self.shell_usage,
+            shell_shortcut_specs: self.shell_shortcut_specs,
+            host_hashtags: self.host_hashtags,
+            autocomplete: self.autocomplete,
+            history: self.history,
+            buffer: self.buffer,
+            prompt: self.prompt,
+            prompt_fn: self.prompt_fn,
+            status_indicator: self.status_indicator,
+            last_status: self.last_status,
+            dir_lister: self.dir_lister,
+            ctrl_d_eof: self.ctrl_d_eof,
+            theme: self.theme,
+            ghost: self.ghost,
+            prompt_shown: self.prompt_shown,
+            candidates_shown: self.candidates_shown,
+        }
+    }
+
+    /// Returns the output, e.g. to print the result of a command where the shell prints.
+    ///
+    pub fn output(&mut self) -> &mut OUT {
+        &mut self.out
+    }
+
+    /// Sets the per-command usage lookup used by the command list.
+    ///
+    /// With a lookup set (typically the generated `usage(name)`), `##` shows each command's
+    /// readable signature instead of its raw descriptor.
+    ///
+    pub fn with_usage(mut self, usage: fn(&str) -> &'static str) -> Self {
+        self.shell_usage = Some(usage);
+        self
+    }
+
+    /// Selects how typed command names are matched against the commands.
+    ///
+    /// `MatchMode::Subsequence` also offers commands containing the typed characters in order
+    /// (e.g. `gst` for `get_status`); they are completed with `Tab` since they are not ghost
+    /// suggestions of the typed text.
+    ///
+    pub fn with_match_mode(mut self, mode: MatchMode) -> Self {
+        self.autocomplete = self.autocomplete.with_mode(mode);
+        self
+    }
+
+    /// Makes command name completion case-insensitive.
+    ///
+    /// Completed names keep their canonical casing, so `SET<Tab>` completes `set_voltage`.
+    ///
+    pub fn with_ignore_case(mut self, ignore_case: bool) -> Self {
+        self.autocomplete = self.autocomplete.with_ignore_case(ignore_case);
+        self
+    }
+
+    /// Selects how re-entered lines are stored in the history (`DedupPolicy::MoveToFront` by
+    /// default).
+    ///
+    pub fn with_dedup_policy(mut self, policy: DedupPolicy) -> Self {
+        self.history.set_dedup_policy(policy);
+        self
+    }
+
+    /// Persists the history to the file at `path` instead of `.hist`, or disables persistence
+    /// with `None` (see `History::set_persistence`).
+    ///
+    pub fn with_history_file(mut self, path: Option<&'static str>) -> Self {
+        self.history.set_persistence(path);
+        self
+    }
+
+    /// Replaces the filesystem as the source of the file names completed for `s{path}`
+    /// arguments, e.g. with a virtual filesystem or the files of a flash partition.
+    ///
+    pub fn with_dir_lister(mut self, lister: DirLister) -> Self {
+        self.dir_lister = lister;
+        self
+    }
+
+    /// Selects whether `Ctrl+D` on an empty line quits, as an end-of-file does in POSIX shells.
+    ///
+    /// Enabled by default. With text on the line `Ctrl+D` always clears it; when disabled it
+    /// only clears.
+    ///
+    pub fn with_ctrl_d_eof(mut self, enabled: bool) -> Self {
+        self.ctrl_d_eof = enabled;
+        self
+    }
+
+    /// Returns `true` if `Ctrl+D` should end input now: enabled and the line is empty.
+    ///
+    fn is_eof(&self) -> bool {
+        self.ctrl_d_eof && self.buffer.is_empty()
+    }
+
+    /// Sets the `(shortcut, description)` pairs used by the full listing.
+    ///
+    /// With specs set (typically the generated `get_shortcut_specs()`), `###` shows the user
+    /// shortcuts as an aligned table instead of the `" | "`-joined list.
+    ///
+    pub fn with_shortcut_specs(mut self, specs: &'static [(&'static str, &'static str)]) -> Self {
+        self.shell_shortcut_specs = specs;
+        self
+    }
+
+    /// Registers hashtag commands handled by the caller, as `(name, description)` pairs.
+    ///
+    /// A line `#name [args]` whose name is not a built-in command is yielded as is (with its
+    /// `#`, not stored in the history) instead of being reported as not implemented. The
+    /// commands are listed with the built-in ones by `##`.
+    ///
+    pub fn with_hashtags(mut self, hashtags: &'static [(&'static str, &'static str)]) -> Self {
+        self.host_hashtags = hashtags;
+        self
+    }
+
+    /// Adds the commands of another module, listed and completed like the ones given to `new`.
+    ///
+    /// Returns `false` and adds nothing if `MAX_COMMAND_TABLES` tables were already added, or
+    /// if the command names do not all fit in the `NC` autocomplete candidates.
+    ///
+    pub fn add_commands(&mut self, commands: &'static [(&'static str, &'static str)]) -> bool {
+        if self.extra_commands.is_full()
+            || !self
+                .autocomplete
+                .add_candidates(commands.iter().map(|&(name, _)| name))
+        {
+            return false;
+        }
+        let _ = self.extra_commands.push(commands);
+        true
+    }
+
+    /// Returns all command tables: the one given to `new`, then the added ones.
+    ///
+    fn command_tables(&self) -> impl Iterator<Item = &'static [(&'static str, &'static str)]> + '_ {
+        core::iter::once(self.shell_commands).chain(self.extra_commands.iter().copied())
+    }
+
+    /// Returns the argument context of `line` in the first command table that knows its command.
+    ///
+    fn arg_context<'l>(&self, line: &'l str) -> Option<ArgContext<'l>> {
+        self.command_tables()
+            .find_map(|commands| args::context(line, commands))
+    }
+
+    /// Sets a prompt provider used instead of the static prompt.
+    ///
+    /// The provider is called before each render, so the prompt can reflect changing state
+    /// (device status, current directory, last error). Output beyond `PROMPT_MAX_LEN` bytes
+    /// is dropped.
+    ///
+    pub fn with_prompt_fn(mut self, prompt_fn: fn(&mut dyn fmt::Write)) -> Self {
+        self.prompt_fn = Some(prompt_fn);
+        self
+    }
+
+    /// Prefixes the prompt with the result of the previous line, as set by `set_last_status`,
+    /// so failures stand out in long sessions (e.g. `✗ UnknownFunction > `). The glyph and
+    /// code take the success or error color of the theme.
+    ///
+    pub fn with_status_indicator(mut self, indicator: StatusIndicator) -> Self {
+        self.status_indicator = indicator;
+        self
+    }
+
+    /// Records the result of the line just run for the status indicator: `Ok(())`, or
+    /// `Err(code)` with a short error code (truncated to `STATUS_CODE_LEN` bytes).
+    ///
+    pub fn set_last_status(&mut self, result: Result<(), &str>) {
+        self.last_status = Some(result.map_err(|code| {
+            let mut text = String::new();
+            for c in code.chars() {
+                if text.push(c).is_err() {
+                    break;
+                }
+            }
+            text
+        }));
+    }
+
+    /// Sets the colors used for the prompt, the ghost text and hints, and the boundary marker.
+    ///
+    /// Defaults to `Theme::detect()`; pass `Theme::PLAIN` to disable colors.
+    ///
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    /// Returns the theme in use.
+    ///
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    /// Renders the prompt and the current buffer, with the cursor at its position.
+    ///
+    fn render(&mut self) {
+        self.render_hint("");
+    }
+
+    /// Renders the prompt and the current buffer followed by a dimmed `hint`.
+    ///
+    fn render_hint(&mut self, hint: &str) {
+        let prompt = self.prompt();
+        DisplayRenderer::render_hint(
+            &mut self.out,
+            &prompt,
+            &self.buffer.to_string(),
+            self.buffer.cursor(),
+            hint,
+            &self.theme,
+        );
+    }
+
+    /// Returns the number of columns of the output, or `DEFAULT_WIDTH` if unknown.
+    ///
+    fn out_width(&self) -> usize {
+        self.out.width().unwrap_or(DEFAULT_WIDTH)
+    }
+
+    /// Returns the prompt to render: the provider's output if one is set, the static prompt
+    /// otherwise, after the status of the previous line (see `with_status_indicator`).
+    ///
+    fn prompt(&self) -> String<PROMPT_MAX_LEN> {
+        let mut prompt = String::new();
+        self.write_status(&mut prompt);
+        match self.prompt_fn {
+            Some(prompt_fn) => prompt_fn(&mut prompt),
+            None => {
+                let _ = prompt.push_str(self.prompt);
+            }
+        }
+        prompt
+    }
+
+    /// Writes the status indicator of the previous line, if any, followed by a space.
+    ///
+    fn write_status(&self, out: &mut dyn fmt::Write) {
+        let Some(status) = &self.last_status else {
+            return;
+        };
+        let (color, glyph) = match status {
+            Ok(()) => (self.theme.success, "✓"),
+            Err(_) => (self.theme.error, "✗"),
+        };
+        let (glyph, code) = match (self.status_indicator, status) {
+            (StatusIndicator::Glyph, _) | (StatusIndicator::GlyphAndCode, Ok(())) => (glyph, ""),
+            (StatusIndicator::Code, Err(code)) => ("", code.as_str()),
+            (StatusIndicator::GlyphAndCode, Err(code)) => (glyph, code.as_str()),
+            _ => return,
+        };
+        let separator = if glyph.is_empty() || code.is_empty() {
+            ""
+        } else {
+            " "
+        };
+        let reset = if color.is_empty() {
+            ""
+        } else {
+            self.theme.reset
+        };
+        // The prompt color is restarted after the reset of the indicator's own color.
+        let _ = write!(
+            out,
+            "{}{}{}{}{} {}",
+            color, glyph, separator, code, reset, self.theme.prompt
+        );
+    }
+
+    /// Prints the user shortcuts: one aligned `key : description` row per shortcut when specs
+    /// are set, the joined shortcut list otherwise.
+    ///
+    fn list_shortcuts(&mut self) {
+        let _ = write!(self.out, "\nUser shortcuts:\n");
+        if self.shell_shortcut_specs.is_empty() {
+            let _ = writeln!(self.out, "{}", self.shell_shortcuts);
+            return;
+        }
+        let max_key_len = self
+            .shell_shortcut_specs
+            .iter()
+            .map(|(key, _)| key.chars().count())
+            .max()
+            .unwrap_or(0);
+        for (key, help) in self.shell_shortcut_specs {
+            let _ = if help.is_empty() {
+                writeln!(self.out, "{:>width$}", key, width = max_key_len)
+            } else {
+                writeln!(self.out, "{:>width$} : {}", key, help, width = max_key_len)
+            };
         }
     }
 
@@ -116,34 +592,49 @@ impl<'a, const NC: usize, const FNL: usize, const IML: usize, const HTC: usize,
     /// If the character is successfully inserted into the input buffer:
     /// - Updates the autocomplete engine with the first FNL characters.
     /// - Retrieves the current autocomplete suggestion.
-    /// - If the suggestion differs from the input prefix, overwrites the buffer with the suggestion.
+    /// - With the cursor at the end, keeps the rest of the most recent history line starting with
+    ///   the input as ghost text, or else the rest of the suggestion if it extends the input
+    ///   (input within the FNL window); the buffer itself is never overwritten.
+    ///   The ghost text is accepted with `Tab` or `Right` and dropped by any other key.
     ///
     /// If the character cannot be inserted (e.g., buffer full):
-    /// - Displays a boundary marker and flushes stdout.
+    /// - Displays a boundary marker.
     ///
-    /// Finally, renders the updated buffer and prompt to the display.
+    /// Finally, renders the updated buffer and prompt to the display. Typing a space after a
+    /// known command shows the placeholder of the next argument (e.g. `<u32 freq>`) as a hint.
     ///
     pub fn handle_char(&mut self, ch: char) {
+        self.ghost.clear();
         if self.buffer.insert(ch) {
             let input_full = self.buffer.to_string();
             let autocomplete_input: String<FNL> = input_full.chars().take(FNL).collect();
             self.autocomplete.update_input(autocomplete_input);
             let suggestion = self.autocomplete.current_input();
-            let input_prefix: String<FNL> = input_full.chars().take(FNL).collect();
-            if suggestion != input_prefix {
-                let mut new_buf = String::<IML>::new();
-                new_buf.push_str(suggestion).ok();
-                for c in input_full.chars().skip(FNL) {
-                    let _ = new_buf.push(c);
+            if self.buffer.cursor() == self.buffer.len() {
+                let history: &History<HTC, HME> = &self.history;
+                if let Some(line) = self.autocomplete.complete_line::<IML>(&input_full, history) {
+                    let _ = self.ghost.push_str(&line[input_full.len()..]);
+                } else if input_full.len() <= FNL
+                    && let Some(rest) = suggestion.strip_prefix(input_full.as_str())
+                {
+                    let _ = self.ghost.push_str(rest);
                 }
-                self.buffer.overwrite(&new_buf);
             }
         } else {
-            DisplayRenderer::boundary_marker();
-            let _ = io::stdout().flush();
+            DisplayRenderer::boundary_marker(&mut self.out, &self.theme);
         }
         let cursor_pos = self.buffer.cursor().min(self.buffer.len());
-        DisplayRenderer::render(self.prompt, &self.buffer.to_string(), cursor_pos);
+        let line = self.buffer.to_string();
+        let mut hint = String::<IML>::new();
+        let _ = hint.push_str(&self.ghost);
+        if hint.is_empty()
+            && ch == ' '
+            && cursor_pos == self.buffer.len()
+            && let Some(ctx) = self.arg_context(&line)
+        {
+            hint = args::placeholder(&ctx);
+        }
+        self.render_hint(&hint);
     }
 
     /// Handles the backspace key event within the input buffer.
@@ -166,9 +657,9 @@ impl<'a, const NC: usize, const FNL: usize, const IML: usize, const HTC: usize,
             }
             self.autocomplete.update_input(input_fn);
         } else {
-            DisplayRenderer::bell();
+            self.out.bell();
         }
-        DisplayRenderer::render(self.prompt, &self.buffer.to_string(), self.buffer.cursor());
+        self.render();
     }
 
     /// Handles the tab key event to cycle through autocomplete suggestions.
@@ -181,7 +672,32 @@ impl<'a, const NC: usize, const FNL: usize, const IML: usize, const HTC: usize,
     ///
     /// Overwrites the buffer with the new input and re-renders the prompt and buffer display.
     ///
+    /// With several matching commands, the first press lists them below the prompt (see
+    /// `DisplayRenderer::render_candidates`) instead of cycling; further presses cycle.
+    ///
+    /// Pending ghost text is accepted first (see `accept_ghost`). Once the command name is
+    /// complete (followed by a space) and the cursor is at the end of the line, completes the
+    /// argument under the cursor instead (see `complete_argument`).
+    ///
     pub fn handle_tab(&mut self, reverse: bool) {
+        if self.accept_ghost() {
+            self.render();
+            return;
+        }
+        if self.buffer.cursor() == self.buffer.len() {
+            let line = self.buffer.to_string();
+            if let Some(ctx) = self.arg_context(&line) {
+                self.complete_argument(&line, &ctx);
+                return;
+            }
+        }
+        if self.autocomplete.matches().len() > 1 && !self.candidates_shown {
+            let width = self.out_width();
+            DisplayRenderer::render_candidates(&mut self.out, self.autocomplete.matches(), width);
+            self.candidates_shown = true;
+            self.render();
+            return;
+        }
         if reverse {
             self.autocomplete.cycle_backward();
         } else {
@@ -197,7 +713,155 @@ impl<'a, const NC: usize, const FNL: usize, const IML: usize, const HTC: usize,
             let _ = new_buf.push(c);
         }
         self.buffer.overwrite(&new_buf);
-        DisplayRenderer::render(self.prompt, &self.buffer.to_string(), self.buffer.cursor());
+        self.render();
+    }
+
+    /// Appends the pending ghost text to the buffer and clears it.
+    ///
+    /// Returns `true` if there was ghost text to accept. The acceptance is a single undo step.
+    ///
+    fn accept_ghost(&mut self) -> bool {
+        if self.ghost.is_empty() {
+            return false;
+        }
+        let mut new_buf = self.buffer.to_string();
+        let _ = new_buf.push_str(&self.ghost);
+        self.buffer.overwrite(&new_buf);
+        self.ghost.clear();
+        true
+    }
+
+    /// Completes the argument being typed, using the command's descriptor.
+    ///
+    /// Candidates are the command's `--flag`s / `--option=`s, `true` / `false` for `bool`
+    /// arguments, the entries of the directory being typed for `s{path}` arguments (see
+    /// `with_dir_lister`), or values used for the same argument in the history:
+    /// - A single candidate replaces the partial argument (followed by a space, unless it is
+    ///   an option or a directory).
+    /// - Several candidates extend it to their longest common prefix and are listed below the prompt.
+    /// - Without candidates, the argument's placeholder (e.g. `<u32 freq>`) is shown as a hint,
+    ///   or the bell rings if the command takes no further argument.
+    ///
+    fn complete_argument(&mut self, line: &str, ctx: &ArgContext) {
+        let (values, common): (Vec<String<IML>, MAX_ARG_CANDIDATES>, Option<String<IML>>) =
+            if args::is_path(ctx) {
+                let (values, common) = args::path_candidates(ctx.partial, self.dir_lister);
+                (values, Some(common))
+            } else {
+                (args::candidates(ctx, self.history.iter::<IML>()), None)
+            };
+        let head = &line[..line.len() - ctx.partial.len()];
+        let mut completion = ctx.partial;
+        let mut separator = "";
+        let mut hint = String::<IML>::new();
+
+        match values.as_slice() {
+            [] => {
+                hint = args::placeholder(ctx);
+                if hint.is_empty() {
+                    self.out.bell();
+                }
+            }
+            [value] => {
+                completion = value;
+                if !value.ends_with('=') && !value.ends_with('/') {
+                    separator = " ";
+                }
+            }
+            [first, rest @ ..] => {
+                let mut prefix = first.as_str();
+                for value in rest {
+                    while !value.starts_with(prefix) {
+                        prefix = &prefix[..prefix.char_indices().last().map_or(0, |(i, _)| i)];
+                    }
+                }
+                if let Some(common) = &common {
+                    prefix = common.as_str();
+                }
+                if prefix.len() > completion.len() {
+                    completion = prefix;
+                }
+                let width = self.out_width();
+                DisplayRenderer::render_candidates(&mut self.out, &values, width);
+            }
+        }
+
+        let mut new_line = String::<IML>::new();
+        let _ = new_line.push_str(head);
+        let _ = new_line.push_str(completion);
+        let _ = new_line.push_str(separator);
+        if !hint.is_empty() && !new_line.ends_with(' ') {
+            let _ = hint.insert_str(0, "  ");
+        }
+        if new_line.as_str() != line {
+            self.buffer.overwrite(&new_line);
+        }
+        self.render_hint(&hint);
+    }
+
+    /// Handles the undo and redo keys.
+    ///
+    /// If `redo` is `true`, re-applies the last undone edit (Alt+_); otherwise reverts the
+    /// last edit (Ctrl+_). Rings the bell if there is nothing to undo or redo.
+    ///
+    /// Updates the autocomplete engine with the restored input and re-renders the display.
+    ///
+    pub fn handle_undo(&mut self, redo: bool) {
+        let changed = if redo {
+            self.buffer.redo()
+        } else {
+            self.buffer.undo()
+        };
+        if changed {
+            let input_full = self.buffer.to_string();
+            let input_fn: String<FNL> = input_full.chars().take(FNL).collect();
+            self.autocomplete.update_input(input_fn);
+        } else {
+            self.out.bell();
+        }
+        self.render();
+    }
+
+    /// Starts over: clears the history (in memory, the persistence file is kept), the line being
+    /// edited and the screen.
+    ///
+    pub fn reset(&mut self) {
+        self.history.clear();
+        self.buffer.clear();
+        self.buffer.clear_undo_history();
+        self.ghost.clear();
+        self.prompt_shown = false;
+        self.candidates_shown = false;
+        DisplayRenderer::clear_screen(&mut self.out);
+    }
+
+    /// Returns `true` if a key was pressed, without blocking; the key is consumed.
+    ///
+    /// Used to stop work repeated between lines, e.g. a command re-run until a key is pressed.
+    ///
+    pub fn key_pressed(&mut self) -> bool {
+        matches!(self.reader.poll_key(), Ok(Some(_)))
+    }
+
+    /// Waits for the next key and returns it, or `None` once the reader reached the end of its
+    /// input.
+    ///
+    /// Used to page long output between lines, e.g. the `help` listing of a shell.
+    ///
+    pub fn wait_key(&mut self) -> Option<Key> {
+        loop {
+            match self.reader.read_key() {
+                Ok(key) => return Some(key),
+                Err(e) if e.is_eof() => return None,
+                Err(_) => continue,
+            }
+        }
+    }
+
+    /// Clears the screen and moves the cursor to its top left corner.
+    ///
+    pub fn clear_screen(&mut self) {
+        DisplayRenderer::clear_screen(&mut self.out);
     }
 
     /// Finalizes the input process by returning the current buffer content as a string.
@@ -212,17 +876,29 @@ impl<'a, const NC: usize, const FNL: usize, const IML: usize, const HTC: usize,
     ///
     /// Prints each command name and its specification, aligned for readability.
     /// Calculates the maximum command name length to ensure consistent formatting.
+    /// When a usage lookup is set, the signature after the name replaces the specification.
     ///
-    pub fn list_commands(&self) {
-        println!("\r\nCommands:");
+    pub fn list_commands(&mut self) {
+        let _ = writeln!(self.out, "\r\nCommands:");
         let max_name_len = self
-            .shell_commands
-            .iter()
+            .command_tables()
+            .flatten()
             .map(|(name, _)| name.len())
             .max()
             .unwrap_or(0);
-        for (name, spec) in self.shell_commands {
-            println!("{:>width$} : {}", name, spec, width = max_name_len);
+        let tables: Vec<_, { MAX_COMMAND_TABLES + 1 }> = self.command_tables().collect();
+        for (name, spec) in tables.iter().copied().flatten() {
+            let text = match self.shell_usage {
+                Some(usage) => usage(name).strip_prefix(name).unwrap_or(spec).trim_start(),
+                None => spec,
+            };
+            let _ = writeln!(
+                self.out,
+                "{:>width$} : {}",
+                name,
+                text,
+                width = max_name_len
+            );
         }
     }
 
@@ -231,13 +907,17 @@ impl<'a, const NC: usize, const FNL: usize, const IML: usize, const HTC: usize,
     /// - Calls `list_commands()` to print the command list.
     /// - Prints predefined shell shortcuts.
     ///
-    fn list_all(&self) {
+    fn list_all(&mut self) {
         self.list_commands();
-        print!(
-            "\nShortcuts:\n### : list all\n##  : list cmds\n#q  : exit\n#h  : list history\n#c  : clear history\n#N  : exec from history at index N\n"
+        let _ = write!(
+            self.out,
+            "\nShortcuts:\n### : list all\n##  : list cmds\n#q  : exit\n#h  : list recent history (#h N: last N)\n#f  : find in history (#f text)\n#c  : clear history\n#N  : exec from history at index N\n!!  : exec last entry again\n!N  : exec history entry N (echoed, stored as last)\n"
         );
-        print!("\nUser shortcuts:\n{}\n", self.shell_shortcuts);
-        print!("\nArg types:\n{}\n", self.shell_datatypes);
+        for (name, help) in self.host_hashtags {
+            let _ = writeln!(self.out, "#{:<2} : {}", name, help);
+        }
+        self.list_shortcuts();
+        let _ = write!(self.out, "\nArg types:\n{}\n", self.shell_datatypes);
     }
 
     /// Handles special hashtag-prefixed input commands.
@@ -245,9 +925,12 @@ impl<'a, const NC: usize, const FNL: usize, const IML: usize, const HTC: usize,
     /// Supports the following commands:
     /// - `"q"`: Quits or exits the current context (returns `false`, no output).
     /// - `"#"`: Displays available commands via `list_all()`.
-    /// - `"h"`: Shows command history.
+    /// - `"h"`: Shows the [`HISTORY_SHOW_COUNT`] most recent history entries, newest first.
+    /// - `"h N"`: Shows the `N` most recent history entries, newest first.
+    /// - `"f text"`: Lists the history entries containing `text`, newest first.
     /// - `"c"`: Clears command history.
     /// - Numeric input: Attempts to retrieve a history entry by index.
+    /// - A command registered with `with_hashtags`: Yields the line to the caller.
     ///
     /// Returns a tuple:
     /// - `bool`: Indicates whether the command was handled.
@@ -264,23 +947,33 @@ impl<'a, const NC: usize, const FNL: usize, const IML: usize, const HTC: usize,
                 (true, None)
             }
             "h" => {
-                self.history.show::<IML>();
+                let history: &History<HTC, HME> = &self.history;
+                history.show_last::<IML>(&mut self.out, HISTORY_SHOW_COUNT);
                 (true, None)
             }
             "c" => {
                 self.history.clear();
-                println!("History cleared");
+                let _ = writeln!(self.out, "History cleared");
                 (true, None)
             }
             _ => {
-                if let Ok(index) = input.parse::<usize>() {
-                    if let Some(entry) = self.history.get(index) {
-                        return (true, Some(entry));
+                if let Some(count) = input.strip_prefix("h ") {
+                    if let Ok(count) = count.trim().parse::<usize>() {
+                        let history: &History<HTC, HME> = &self.history;
+                        history.show_last::<IML>(&mut self.out, count);
                     } else {
-                        println!("No history entry at index {}", index);
+                        let _ = writeln!(self.out, "Usage: #h [count]");
                     }
+                } else if let Some(text) = input.strip_prefix("f ") {
+                    self.find_in_history(text.trim());
+                } else if input == "f" {
+                    let _ = writeln!(self.out, "Usage: #f text");
+                } else if let Ok(index) = input.parse::<usize>() {
+                    return (true, self.recall(Some(index)));
+                } else if let Some(line) = self.host_hashtag(input) {
+                    return (true, Some(line));
                 } else {
-                    println!("Not implemented");
+                    let _ = writeln!(self.out, "Not implemented");
                 }
                 (true, None)
             }
@@ -292,192 +985,410 @@ impl<'a, const NC: usize, const FNL: usize, const IML: usize, const HTC: usize,
     /// Supports various key bindings for editing and navigation:
     /// - `Enter`: Finalizes input.
     /// - `Backspace`: Deletes character before cursor.
-    /// - `Tab` / `Shift+Tab`: Cycles autocomplete suggestions.
+    /// - `Tab` / `Shift+Tab`: Accepts the ghost suggestion, or cycles autocomplete suggestions.
     /// - `Ctrl+U`: Deletes from cursor to start of line.
     /// - `Ctrl+K`: Deletes from cursor to end of line.
-    /// - `Ctrl+D`: Clears the entire buffer.
+    /// - `Ctrl+D`: Clears the entire buffer, or quits if it is already empty (see `with_ctrl_d_eof`).
+    /// - `Ctrl+W`: Deletes the word before the cursor.
+    /// - `Ctrl+_` / `Alt+_`: Undoes / redoes the last edit.
+    /// - `Ctrl+L`: Clears the screen and redraws the prompt with the current input.
     /// - Arrow keys: Navigates through buffer or command history.
-    /// - `Home` / `End`: Moves cursor to start/end of line.
+    /// - `Home` / `End` (or `Ctrl+A` / `Ctrl+E`): Moves cursor to start/end of line.
+    /// - `Alt+B` / `Alt+F`: Moves cursor one word left/right.
     /// - `Delete`: Deletes character at cursor.
     ///
     /// After input is finalized:
     /// - If input starts with `#`, it is treated as a special command (e.g., history or help).
     /// - Otherwise, the input is executed via the provided `exec` callback and stored in history.
     ///
-    /// Returns `true` if input was successfully handled or executed, `false` if the user requested to quit.
+    /// Returns `true` if input was successfully handled or executed, `false` if the user requested to
+    /// quit or the reader reached the end of its input.
     ///
     pub fn parse_input<F>(&mut self, exec: F) -> bool
     where
         F: Fn(&String<IML>),
     {
-        DisplayRenderer::render(self.prompt, "", 0);
+        match self.read_line() {
+            ShellEvent::Line(line) => {
+                exec(&line);
+                true
+            }
+            ShellEvent::Pending => true,
+            ShellEvent::Exit => false,
+        }
+    }
+
+    /// Reads one line like [`parse_input`](Self::parse_input), returning it instead of passing
+    /// it to a callback.
+    ///
+    /// Returns `ShellEvent::Line` with a line to execute (already stored in the history),
+    /// `ShellEvent::Exit` if the user requested to quit or the reader reached the end of its
+    /// input, or `ShellEvent::Pending` for a line handled internally (an empty line or a `#`
+    /// command).
+    ///
+    pub fn read_line(&mut self) -> ShellEvent<IML> {
+        self.render();
 
         loop {
-            let key = match read_key() {
+            let key = match self.reader.read_key() {
                 Ok(k) => k,
+                Err(e) if e.is_eof() => return ShellEvent::Exit,
                 Err(_) => continue,
             };
+            match self.process_key(key) {
+                None => {}
+                Some((false, _)) => return ShellEvent::Exit,
+                Some((true, Some(line))) => return ShellEvent::Line(line),
+                Some((true, None)) => return ShellEvent::Pending,
+            }
+        }
+    }
 
-            match key {
-                Key::Enter => {
-                    println!();
-                    break;
-                }
+    /// Processes one key without blocking, for shells driven from a superloop or an RTOS tick.
+    ///
+    /// - Renders the prompt on the first call for a new line.
+    /// - Consumes at most one pending key, handled like in [`parse_input`](Self::parse_input).
+    ///
+    /// Returns `None` if no key was available, otherwise:
+    /// - `ShellEvent::Line` with a line to execute (already stored in the history),
+    /// - `ShellEvent::Exit` if the user requested to quit,
+    /// - `ShellEvent::Pending` while the line is being edited, or after a line handled
+    ///   internally (an empty line or a `#` command).
+    ///
+    pub fn poll(&mut self) -> Option<ShellEvent<IML>> {
+        self.show_prompt();
+        let key = self.reader.poll_key().ok()??;
+        Some(self.poll_event(key))
+    }
 
-                Key::Backspace => {
-                    self.handle_backspace();
-                }
+    /// Renders the prompt, unless [`poll`](Self::poll) or [`feed_key`](Self::feed_key) already
+    /// did for the current line.
+    ///
+    pub fn show_prompt(&mut self) {
+        if !self.prompt_shown {
+            self.render();
+            self.prompt_shown = true;
+        }
+    }
 
-                Key::Tab => {
-                    self.handle_tab(false);
-                }
+    /// Processes a key read elsewhere (e.g. awaited from an async source) like [`poll`](Self::poll)
+    /// does with a key of the reader, and returns the same events.
+    ///
+    pub fn feed_key(&mut self, key: Key) -> ShellEvent<IML> {
+        self.show_prompt();
+        self.poll_event(key)
+    }
 
-                Key::ShiftTab => {
-                    self.handle_tab(true);
+    /// Turns the outcome of `key` into the event returned by [`poll`](Self::poll).
+    ///
+    fn poll_event(&mut self, key: Key) -> ShellEvent<IML> {
+        match self.process_key(key) {
+            None => ShellEvent::Pending,
+            Some(outcome) => {
+                self.prompt_shown = false;
+                match outcome {
+                    (false, _) => ShellEvent::Exit,
+                    (true, Some(line)) => ShellEvent::Line(line),
+                    (true, None) => ShellEvent::Pending,
                 }
+            }
+        }
+    }
 
-                Key::CtrlU => {
-                    self.buffer.delete_to_start();
-                    DisplayRenderer::render(
-                        self.prompt,
-                        &self.buffer.to_string(),
-                        self.buffer.cursor(),
-                    );
-                }
+    /// Applies one key to the line being edited.
+    ///
+    /// Returns `None` while editing continues, or, once the line is finished (`Enter`, or
+    /// `Ctrl+D` on an empty line), whether to keep running and the line to execute (see
+    /// [`take_line`](Self::take_line)).
+    ///
+    fn process_key(&mut self, key: Key) -> Option<(bool, Option<String<IML>>)> {
+        if !matches!(key, Key::Char(_) | Key::Tab | Key::ArrowRight) {
+            self.ghost.clear();
+        }
+        if !matches!(key, Key::Tab | Key::ShiftTab) {
+            self.candidates_shown = false;
+        }
 
-                Key::CtrlK => {
-                    self.buffer.delete_to_end();
-                    DisplayRenderer::render(
-                        self.prompt,
-                        &self.buffer.to_string(),
-                        self.buffer.cursor(),
-                    );
-                }
+        match key {
+            Key::Enter => {
+                let _ = writeln!(self.out);
+                return Some(self.take_line());
+            }
 
-                Key::CtrlD => {
-                    self.buffer.clear();
-                    DisplayRenderer::render(self.prompt, "", 0);
-                }
+            Key::Backspace => {
+                self.handle_backspace();
+            }
+
+            Key::Tab => {
+                self.handle_tab(false);
+            }
+
+            Key::ShiftTab => {
+                self.handle_tab(true);
+            }
 
-                Key::ArrowLeft => {
-                    self.buffer.move_left();
-                    DisplayRenderer::render(
-                        self.prompt,
-                        &self.buffer.to_string(),
-                        self.buffer.cursor(),
-                    );
+            Key::CtrlD => {
+                if self.is_eof() {
+                    let _ = writeln!(self.out);
+                    return Some((false, None));
                 }
+                self.buffer.clear();
+                self.render();
+            }
+
+            Key::CtrlUnderscore => {
+                self.handle_undo(false);
+            }
+
+            Key::AltUnderscore => {
+                self.handle_undo(true);
+            }
+
+            Key::CtrlL => {
+                DisplayRenderer::clear_screen(&mut self.out);
+                self.render();
+            }
 
-                Key::ArrowRight => {
+            Key::ArrowRight => {
+                if !self.accept_ghost() {
                     self.buffer.move_right();
-                    DisplayRenderer::render(
-                        self.prompt,
-                        &self.buffer.to_string(),
-                        self.buffer.cursor(),
-                    );
                 }
+                self.render();
+            }
 
-                Key::ArrowUp => {
-                    if let Some(cmd) = self.history.get_next_entry::<IML>() {
-                        self.buffer.overwrite(&cmd);
-                        DisplayRenderer::render(
-                            self.prompt,
-                            &self.buffer.to_string(),
-                            self.buffer.cursor(),
-                        );
-                    }
+            Key::ArrowUp => {
+                if let Some(cmd) = self.history.get_next_entry::<IML>() {
+                    self.buffer.overwrite(&cmd);
+                    self.render();
                 }
+            }
 
-                Key::ArrowDown => {
-                    if let Some(cmd) = self.history.get_prev_entry::<IML>() {
-                        self.buffer.overwrite(&cmd);
-                        DisplayRenderer::render(
-                            self.prompt,
-                            &self.buffer.to_string(),
-                            self.buffer.cursor(),
-                        );
-                    }
+            Key::ArrowDown => {
+                if let Some(cmd) = self.history.get_prev_entry::<IML>() {
+                    self.buffer.overwrite(&cmd);
+                    self.render();
                 }
+            }
 
-                Key::Home => {
-                    self.buffer.move_home();
-                    DisplayRenderer::render(
-                        self.prompt,
-                        &self.buffer.to_string(),
-                        self.buffer.cursor(),
-                    );
+            Key::PageUp => {
+                if let Some(cmd) = self.history.get_first_entry::<IML>() {
+                    self.buffer.overwrite(&cmd);
+                    self.render();
                 }
+            }
 
-                Key::End => {
-                    self.buffer.move_end();
-                    DisplayRenderer::render(
-                        self.prompt,
-                        &self.buffer.to_string(),
-                        self.buffer.cursor(),
-                    );
+            Key::PageDown => {
+                if let Some(cmd) = self.history.get_last_entry::<IML>() {
+                    self.buffer.overwrite(&cmd);
+                    self.render();
                 }
+            }
 
-                Key::Delete => {
-                    self.buffer.delete_at_cursor();
-                    DisplayRenderer::render(
-                        self.prompt,
-                        &self.buffer.to_string(),
-                        self.buffer.cursor(),
-                    );
-                }
+            Key::Char(c) if Self::valid_char(c) => {
+                self.handle_char(c);
+            }
 
-                Key::PageUp => {
-                    if let Some(cmd) = self.history.get_first_entry::<IML>() {
-                        self.buffer.overwrite(&cmd);
-                        DisplayRenderer::render(
-                            self.prompt,
-                            &self.buffer.to_string(),
-                            self.buffer.cursor(),
-                        );
+            // Cursor movements and deletions: arrows left, words, Home/End, Delete, Ctrl+U/K/W
+            key => {
+                let buffer: &mut InputBuffer<IML> = &mut self.buffer;
+                if let Some(applied) = apply_edit(buffer, &key) {
+                    if !applied {
+                        self.out.bell();
                     }
+                    self.render();
                 }
+            }
+        }
 
-                Key::PageDown => {
-                    if let Some(cmd) = self.history.get_last_entry::<IML>() {
-                        self.buffer.overwrite(&cmd);
-                        DisplayRenderer::render(
-                            self.prompt,
-                            &self.buffer.to_string(),
-                            self.buffer.cursor(),
-                        );
-                    }
-                }
+        None
+    }
 
-                Key::Char(c) => {
-                    if Self::valid_byte(c as u8) {
-                        self.handle_char(c);
-                    }
+    /// Returns the line `#input` if its name was registered with `with_hashtags`.
+    ///
+    fn host_hashtag(&self, input: &str) -> Option<String<IML>> {
+        let name = input.split_whitespace().next()?;
+        if !self.host_hashtags.iter().any(|(host, _)| *host == name) {
+            return None;
+        }
+        let mut line = String::new();
+        line.push('#').ok()?;
+        line.push_str(input).ok()?;
+        Some(line)
+    }
+
+    /// Lists the history entries containing `text`, newest first, with their indexes.
+    ///
+    fn find_in_history(&mut self, text: &str) {
+        let history: &History<HTC, HME> = &self.history;
+        let mut found = false;
+        for (index, entry) in history.find::<IML>(text) {
+            let _ = writeln!(self.out, "{:>3} : {}", index, entry);
+            found = true;
+        }
+        if !found {
+            let _ = writeln!(self.out, "No history entry contains '{}'", text);
+        }
+    }
+
+    /// Returns the history entry at `index`, or the most recent one for `None`.
+    ///
+    /// Shared by the `#N` recall and the `!N` / `!!` expansions; reports a missing entry to the
+    /// output.
+    ///
+    fn recall(&mut self, index: Option<usize>) -> Option<String<IML>> {
+        let history: &History<HTC, HME> = &self.history;
+        let entry = match index {
+            Some(index) => history.get(index),
+            None => history.get_last_entry(),
+        };
+        if entry.is_none() {
+            match index {
+                Some(index) => {
+                    let _ = writeln!(self.out, "No history entry at index {}", index);
                 }
+                None => {
+                    let _ = writeln!(self.out, "History is empty");
+                }
+            }
+        }
+        entry
+    }
 
-                _ => {}
+    /// Resolves a history expansion: `!!` recalls the most recent entry, `!N` the entry at
+    /// index `N`.
+    ///
+    /// Returns `None` if `line` is not an expansion (only whole lines are expanded, so `!` inside
+    /// arguments is kept), otherwise the recalled entry, if any.
+    ///
+    fn expand_history(&mut self, line: &str) -> Option<Option<String<IML>>> {
+        let expansion = line.strip_prefix('!')?;
+        if expansion == "!" {
+            Some(self.recall(None))
+        } else if !expansion.is_empty() && expansion.bytes().all(|b| b.is_ascii_digit()) {
+            match expansion.parse::<usize>() {
+                Ok(index) => Some(self.recall(Some(index))),
+                Err(_) => {
+                    let _ = writeln!(self.out, "No history entry at index {}", expansion);
+                    Some(None)
+                }
             }
+        } else {
+            None
         }
+    }
 
-        // Finalize input
-        let mut retval = true;
+    /// Finishes the current line and clears the buffer for the next one.
+    ///
+    /// - A line starting with `#` is handled as a special command (e.g., history or help); a
+    ///   history recall (`#N`) yields the recalled entry to execute.
+    /// - A history expansion (`!!` or `!N`) is echoed, stored in history as the most recent
+    ///   line and yielded to execute.
+    /// - Any other non-empty line is stored in history and yielded to execute.
+    ///
+    /// Returns whether to keep running (`false` for `#q`) and the line to execute, if any.
+    ///
+    fn take_line(&mut self) -> (bool, Option<String<IML>>) {
         let final_input = self.finalize();
+        if final_input.is_empty() {
+            return (true, None);
+        }
 
-        if !final_input.is_empty() {
-            if let Some(stripped) = final_input.strip_prefix('#') {
-                let (new_retval, maybe_history_command) = self.handle_hashtag(stripped);
-                retval = new_retval;
-                if let Some(history_command) = maybe_history_command {
-                    exec(&history_command);
-                }
-            } else {
-                exec(&final_input);
-                self.history.push(&final_input);
+        let outcome = if let Some(stripped) = final_input.strip_prefix('#') {
+            self.handle_hashtag(stripped)
+        } else if let Some(expanded) = self.expand_history(&final_input) {
+            if let Some(line) = &expanded {
+                let _ = writeln!(self.out, "{}", line);
+                self.history.push(line);
             }
+            (true, expanded)
+        } else {
+            self.history.push(&final_input);
+            (true, Some(final_input))
+        };
+        self.buffer.clear();
+        self.buffer.clear_undo_history();
+        outcome
+    }
+
+    /// Handles a whole line as if it was typed and confirmed with `Enter`, without reading keys
+    /// or rendering, e.g. for lines read from a pipe or a script.
+    ///
+    /// Hashtag commands and history expansions apply as for typed lines, and a line to execute
+    /// is stored in the history. Surrounding whitespace is removed, and text beyond `IML`
+    /// characters is dropped.
+    ///
+    pub fn submit(&mut self, line: &str) -> ShellEvent<IML> {
+        self.buffer.overwrite(line.trim());
+        match self.take_line() {
+            (false, _) => ShellEvent::Exit,
+            (true, Some(line)) => ShellEvent::Line(line),
+            (true, None) => ShellEvent::Pending,
+        }
+    }
 
-            self.buffer.clear();
+    /// Reads a secret (e.g. a password) from `stdin` and passes it to `use_secret`.
+    ///
+    /// - Renders `prompt`, then one `*` per typed character instead of the text itself.
+    /// - Only `Backspace`, `Ctrl+U` and `Enter` are handled; there is no autocomplete, and
+    ///   the secret is never recorded in the history.
+    /// - Once `use_secret` returns, every copy of the secret is overwritten with zeros.
+    ///
+    /// Returns the result of `use_secret`.
+    ///
+    pub fn read_secret<R>(&mut self, prompt: &str, use_secret: impl FnOnce(&str) -> R) -> R {
+        let mut secret = Vec::<char, IML>::new();
+        DisplayRenderer::render(&mut self.out, prompt, "", 0, &self.theme);
+        loop {
+            let key = match self.reader.read_key() {
+                Ok(k) => k,
+                Err(e) if e.is_eof() => break,
+                Err(_) => continue,
+            };
+            if Self::edit_secret(&mut self.out, &mut secret, &key) {
+                break;
+            }
+            let mask: String<IML> = secret.iter().map(|_| '*').collect();
+            DisplayRenderer::render(&mut self.out, prompt, &mask, mask.len(), &self.theme);
         }
+        let _ = writeln!(self.out);
+
+        let mut text: String<IML> = secret.iter().collect();
+        let result = use_secret(&text);
+        // SAFETY: zero bytes keep the contents valid UTF-8.
+        zeroize(unsafe { text.as_mut_vec() }, 0);
+        zeroize(&mut secret, '\0');
+        result
+    }
 
-        retval
+    /// Applies one key to the secret being typed by `read_secret`.
+    ///
+    /// Returns `true` when the secret is complete (`Enter`). Rings the bell for a character that
+    /// does not fit in `IML` bytes or a backspace on an empty secret.
+    ///
+    fn edit_secret(out: &mut dyn Output, secret: &mut Vec<char, IML>, key: &Key) -> bool {
+        match *key {
+            Key::Enter => return true,
+            Key::Backspace => {
+                if let Some(last) = secret.last_mut() {
+                    zeroize(core::slice::from_mut(last), '\0');
+                    secret.pop();
+                } else {
+                    out.bell();
+                }
+            }
+            Key::CtrlU => {
+                zeroize(secret, '\0');
+                secret.clear();
+            }
+            Key::Char(c) if Self::valid_char(c) => {
+                let used: usize = secret.iter().map(|c| c.len_utf8()).sum();
+                if used + c.len_utf8() > IML || secret.push(c).is_err() {
+                    out.bell();
+                }
+            }
+            _ => {}
+        }
+        false
     }
 
     /// Checks whether a given byte represents a valid ASCII character for input.
@@ -492,13 +1403,39 @@ impl<'a, const NC: usize, const FNL: usize, const IML: usize, const HTC: usize,
         let c = b as char;
         c.is_ascii() && (c.is_ascii_alphanumeric() || c == ' ' || matches!(c, '!'..='~'))
     }
+
+    /// Checks whether a typed character is valid for input.
+    ///
+    /// ASCII characters follow [`valid_byte`](Self::valid_byte); any other character is valid
+    /// unless it is a control character, so UTF-8 text can be typed into string arguments.
+    ///
+    fn valid_char(c: char) -> bool {
+        if c.is_ascii() {
+            Self::valid_byte(c as u8)
+        } else {
+            !c.is_control()
+        }
+    }
+}
+
+/// Overwrites every element of `data` with `zero`, through volatile writes the compiler
+/// cannot elide.
+///
+fn zeroize<T: Copy>(data: &mut [T], zero: T) {
+    for item in data.iter_mut() {
+        // SAFETY: `item` is a valid, aligned, exclusive reference.
+        unsafe { core::ptr::write_volatile(item, zero) };
+    }
+    core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
 }
 
 // ==================== TESTS =======================
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
+#[allow(clippy::len_zero, clippy::absurd_extreme_comparisons)]
 mod input_parser_tests {
     use super::*;
+    use crate::input::renderer::display_width;
     use heapless::String;
 
     // Test constants
@@ -561,6 +1498,94 @@ mod input_parser_tests {
         assert_eq!(parser.prompt, TEST_PROMPT);
     }
 
+    #[test]
+    fn test_prompt_defaults_to_static() {
+        let parser = TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT);
+        assert_eq!(parser.prompt().as_str(), TEST_PROMPT);
+    }
+
+    #[test]
+    fn test_prompt_fn_evaluated_on_each_call() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        fn counting_prompt(w: &mut dyn fmt::Write) {
+            let n = CALLS.fetch_add(1, Ordering::SeqCst);
+            let _ = write!(w, "[{}]> ", n);
+        }
+
+        let parser = TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT)
+            .with_prompt_fn(counting_prompt);
+        assert_eq!(parser.prompt().as_str(), "[0]> ");
+        assert_eq!(parser.prompt().as_str(), "[1]> ");
+    }
+
+    #[test]
+    fn test_prompt_fn_truncates_long_output() {
+        fn long_prompt(w: &mut dyn fmt::Write) {
+            for _ in 0..PROMPT_MAX_LEN {
+                let _ = w.write_str("ab");
+            }
+        }
+
+        let parser = TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT)
+            .with_prompt_fn(long_prompt);
+        assert_eq!(parser.prompt().len(), PROMPT_MAX_LEN);
+    }
+
+    #[test]
+    fn test_status_indicator() {
+        let mut parser =
+            TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT)
+                .with_theme(Theme::PLAIN)
+                .with_status_indicator(StatusIndicator::GlyphAndCode);
+        assert_eq!(parser.prompt().as_str(), TEST_PROMPT);
+        parser.set_last_status(Ok(()));
+        assert_eq!(parser.prompt().as_str(), "✓ > ");
+        parser.set_last_status(Err("UnknownFunction"));
+        assert_eq!(parser.prompt().as_str(), "✗ UnknownFunction > ");
+        parser.status_indicator = StatusIndicator::Code;
+        assert_eq!(parser.prompt().as_str(), "UnknownFunction > ");
+        parser.set_last_status(Ok(()));
+        assert_eq!(parser.prompt().as_str(), TEST_PROMPT);
+        parser.status_indicator = StatusIndicator::Off;
+        parser.set_last_status(Err("x"));
+        assert_eq!(parser.prompt().as_str(), TEST_PROMPT);
+    }
+
+    #[test]
+    fn test_status_indicator_colors() {
+        let mut parser =
+            TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT)
+                .with_theme(Theme::ANSI)
+                .with_status_indicator(StatusIndicator::Glyph);
+        parser.set_last_status(Err("UnknownFunction"));
+        assert_eq!(parser.prompt().as_str(), "\x1B[31m✗\x1B[0m \x1B[1m> ");
+        assert_eq!(display_width(&parser.prompt()), 4);
+    }
+
+    #[test]
+    fn test_with_theme() {
+        let parser = TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT)
+            .with_theme(Theme::PLAIN);
+        assert_eq!(parser.theme(), &Theme::PLAIN);
+    }
+
+    #[test]
+    fn test_ctrl_d_eof_only_on_empty_line() {
+        let mut parser =
+            TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT);
+        assert!(parser.is_eof());
+        parser.buffer.insert('x');
+        assert!(!parser.is_eof());
+    }
+
+    #[test]
+    fn test_ctrl_d_eof_disabled() {
+        let parser = TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT)
+            .with_ctrl_d_eof(false);
+        assert!(!parser.is_eof());
+    }
+
     // ==================== HANDLE_CHAR TESTS ====================
 
     #[test]
@@ -647,14 +1672,58 @@ mod input_parser_tests {
         let mut parser =
             SmallParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT);
 
-        // Try to fill buffer beyond capacity
-        for _ in 0..50 {
-            parser.handle_char('a');
-        }
+        // Try to fill buffer beyond capacity
+        for _ in 0..50 {
+            parser.handle_char('a');
+        }
+
+        let result = parser.finalize();
+        // Buffer should be limited to its capacity
+        assert!(result.len() <= 32);
+    }
+
+    #[test]
+    fn test_handle_char_shows_ghost_without_overwriting() {
+        let mut parser =
+            TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT)
+                .with_history_file(None);
+
+        parser.handle_char('h');
+        assert_eq!(parser.buffer.to_string().as_str(), "h");
+        assert_eq!(parser.ghost.as_str(), "el");
+
+        parser.handle_char('x');
+        assert!(parser.ghost.is_empty());
+    }
+
+    #[test]
+    fn test_handle_char_ghost_from_history() {
+        let mut parser =
+            TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT)
+                .with_history_file(None);
+        parser.history.push("list -a");
+
+        parser.handle_char('l');
+        assert_eq!(parser.ghost.as_str(), "ist -a");
+        parser.handle_tab(false);
+        assert_eq!(parser.buffer.to_string().as_str(), "list -a");
+
+        parser.buffer.clear();
+        parser.handle_char('h');
+        assert_eq!(parser.ghost.as_str(), "el"); // no history line, command names only
+    }
+
+    #[test]
+    fn test_ghost_only_at_end_of_line() {
+        let mut parser =
+            TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT)
+                .with_history_file(None);
 
-        let result = parser.finalize();
-        // Buffer should be limited to its capacity
-        assert!(result.len() <= 32);
+        parser.buffer.overwrite("xit");
+        parser.buffer.move_home();
+        parser.handle_char('e');
+        assert_eq!(parser.buffer.to_string().as_str(), "exit");
+        assert!(parser.ghost.is_empty());
     }
 
     // ==================== HANDLE_BACKSPACE TESTS ====================
@@ -742,6 +1811,54 @@ mod input_parser_tests {
         assert!(first.len() > 0 && second.len() > 0);
     }
 
+    #[test]
+    fn test_handle_tab_accepts_ghost() {
+        let mut parser =
+            TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT)
+                .with_history_file(None);
+
+        parser.handle_char('e');
+        assert_eq!(parser.ghost.as_str(), "xit ");
+        parser.handle_tab(false);
+        assert_eq!(parser.buffer.to_string().as_str(), "exit ");
+        assert!(parser.ghost.is_empty());
+
+        assert!(parser.buffer.undo());
+        assert_eq!(parser.buffer.to_string().as_str(), "e");
+    }
+
+    #[test]
+    fn test_handle_tab_lists_candidates_before_cycling() {
+        let mut parser =
+            TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT)
+                .with_history_file(None);
+
+        parser.handle_char('h');
+        parser.handle_tab(false); // accepts the ghost "el"
+        parser.handle_tab(false); // lists help / hello
+        assert_eq!(parser.buffer.to_string().as_str(), "hel");
+        assert!(parser.candidates_shown);
+
+        parser.handle_tab(false);
+        assert!(parser.buffer.to_string().starts_with("hel"));
+        assert_ne!(parser.buffer.to_string().as_str(), "hel");
+    }
+
+    #[test]
+    fn test_handle_tab_ignore_case() {
+        let mut parser =
+            TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT)
+                .with_ignore_case(true)
+                .with_history_file(None);
+
+        for c in "LIS".chars() {
+            parser.handle_char(c);
+        }
+        assert!(parser.ghost.is_empty());
+        parser.handle_tab(false);
+        assert_eq!(parser.buffer.to_string().as_str(), "list ");
+    }
+
     #[test]
     fn test_handle_tab_reverse_cycling() {
         let mut parser =
@@ -783,6 +1900,88 @@ mod input_parser_tests {
         assert!(result.contains("arg"));
     }
 
+    const ARG_COMMANDS: &[(&str, &str)] = &[("enable", "t:on"), ("set_freq", "D:freq --verbose")];
+
+    fn tab_on(parser: &mut TestParser, line: &str) -> String<128> {
+        parser.buffer.overwrite(line);
+        parser.handle_tab(false);
+        parser.buffer.to_string()
+    }
+
+    #[test]
+    fn test_handle_tab_completes_bool_argument() {
+        let mut parser = TestParser::new(ARG_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT);
+        assert_eq!(tab_on(&mut parser, "enable f").as_str(), "enable false ");
+        assert_eq!(tab_on(&mut parser, "enable ").as_str(), "enable ");
+    }
+
+    #[test]
+    fn test_handle_tab_completes_switch() {
+        let mut parser = TestParser::new(ARG_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT);
+        assert_eq!(
+            tab_on(&mut parser, "set_freq 5 --v").as_str(),
+            "set_freq 5 --verbose "
+        );
+        assert_eq!(
+            tab_on(&mut parser, "set_freq 5 ").as_str(),
+            "set_freq 5 --verbose "
+        );
+    }
+
+    #[test]
+    fn test_handle_tab_completes_from_history() {
+        let mut parser = InputParser::<'static, 10, 32, 128, 128, 16>::new(
+            ARG_COMMANDS,
+            TEST_DATATYPES,
+            TEST_SHORTCUTS,
+            TEST_PROMPT,
+        )
+        .with_history_file(None);
+        parser.history.push("set_freq 1200");
+        parser.history.push("set_freq 1250 --verbose");
+        for (line, completed) in [
+            ("set_freq 1", "set_freq 12"),
+            ("set_freq 12", "set_freq 12"),
+            ("set_freq 120", "set_freq 1200 "),
+        ] {
+            parser.buffer.overwrite(line);
+            parser.handle_tab(false);
+            assert_eq!(parser.buffer.to_string().as_str(), completed);
+        }
+    }
+
+    #[test]
+    fn test_handle_tab_argument_without_candidates_keeps_input() {
+        let mut parser = TestParser::new(ARG_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT);
+        assert_eq!(tab_on(&mut parser, "set_freq ").as_str(), "set_freq ");
+        assert_eq!(tab_on(&mut parser, "set_freq 7").as_str(), "set_freq 7");
+    }
+
+    fn test_fs(dir: &str, entry: &mut dyn FnMut(&str)) {
+        let names: &[&str] = match dir {
+            "" => &["log.txt", "logs/", "data.bin"],
+            "logs/" => &["boot.log"],
+            _ => &[],
+        };
+        names.iter().for_each(|n| entry(n));
+    }
+
+    #[test]
+    fn test_handle_tab_completes_path() {
+        const PATH_COMMANDS: &[(&str, &str)] = &[("cat", "s{path}:file")];
+        let mut parser =
+            TestParser::new(PATH_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT)
+                .with_dir_lister(test_fs);
+        assert_eq!(tab_on(&mut parser, "cat d").as_str(), "cat data.bin ");
+        assert_eq!(tab_on(&mut parser, "cat l").as_str(), "cat log");
+        assert_eq!(tab_on(&mut parser, "cat logs").as_str(), "cat logs/");
+        assert_eq!(
+            tab_on(&mut parser, "cat logs/").as_str(),
+            "cat logs/boot.log "
+        );
+        assert_eq!(tab_on(&mut parser, "cat x").as_str(), "cat x");
+    }
+
     // ==================== FINALIZE TESTS ====================
 
     #[test]
@@ -869,6 +2068,88 @@ mod input_parser_tests {
         assert!(cmd.is_none());
     }
 
+    #[test]
+    fn test_handle_hashtag_history_newest_first() {
+        let mut parser =
+            TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT)
+                .with_history_file(None)
+                .with_output(std::string::String::new());
+        parser.history.push("first");
+        parser.history.push("second");
+        parser.history.push("third");
+
+        let (retval, cmd) = parser.handle_hashtag("h");
+        assert!(retval);
+        assert!(cmd.is_none());
+        assert!(
+            parser
+                .output()
+                .starts_with("  2 : third\n  1 : second\n  0 : first\n")
+        );
+
+        parser.output().clear();
+        parser.handle_hashtag("h 1");
+        assert!(parser.output().starts_with("  2 : third\n... 2 older\n"));
+
+        parser.output().clear();
+        parser.handle_hashtag("h x");
+        assert_eq!(parser.output().as_str(), "Usage: #h [count]\n");
+    }
+
+    #[test]
+    fn test_handle_hashtag_find() {
+        let mut parser =
+            TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT)
+                .with_history_file(None)
+                .with_output(std::string::String::new());
+        parser.history.push("led 1");
+        parser.history.push("init");
+        parser.history.push("led 0");
+
+        let (retval, cmd) = parser.handle_hashtag("f led");
+        assert!(retval);
+        assert!(cmd.is_none());
+        assert_eq!(parser.output().as_str(), "  2 : led 0\n  0 : led 1\n");
+
+        parser.output().clear();
+        parser.handle_hashtag("f send");
+        assert_eq!(
+            parser.output().as_str(),
+            "No history entry contains 'send'\n"
+        );
+
+        parser.output().clear();
+        parser.handle_hashtag("f");
+        assert_eq!(parser.output().as_str(), "Usage: #f text\n");
+    }
+
+    const TEST_HASHTAGS: &[(&str, &str)] = &[("v", "set verbosity")];
+
+    #[test]
+    fn test_handle_hashtag_host_command() {
+        let mut parser =
+            TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT)
+                .with_hashtags(TEST_HASHTAGS)
+                .with_history_file(None)
+                .with_output(std::string::String::new());
+
+        let (retval, cmd) = parser.handle_hashtag("v quiet");
+        assert!(retval);
+        assert_eq!(cmd.as_deref(), Some("#v quiet"));
+        let (_, cmd) = parser.handle_hashtag("v");
+        assert_eq!(cmd.as_deref(), Some("#v"));
+        assert!(parser.history.is_empty());
+
+        // Unregistered names are still rejected
+        let (_, cmd) = parser.handle_hashtag("vv");
+        assert!(cmd.is_none());
+        assert_eq!(parser.output().as_str(), "Not implemented\n");
+
+        parser.output().clear();
+        parser.list_all();
+        assert!(parser.output().contains("#v  : set verbosity\n"));
+    }
+
     #[test]
     fn test_handle_hashtag_clear_history() {
         let mut parser =
@@ -891,7 +2172,8 @@ mod input_parser_tests {
     #[test]
     fn test_handle_hashtag_numeric_index() {
         let mut parser =
-            TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT);
+            TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT)
+                .with_history_file(None);
 
         // Add history entry
         let test_cmd = String::<64>::try_from("test command").unwrap();
@@ -925,6 +2207,307 @@ mod input_parser_tests {
         assert!(cmd.is_none());
     }
 
+    #[test]
+    fn test_reset() {
+        let mut parser =
+            TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT)
+                .with_history_file(None)
+                .with_output(std::string::String::new());
+        parser.history.push("hello");
+        parser.handle_char('t');
+
+        parser.reset();
+        assert!(parser.history.is_empty());
+        assert!(parser.buffer.is_empty());
+        assert!(parser.ghost.is_empty());
+        assert!(parser.output().ends_with("\x1B[2J\x1B[H"));
+    }
+
+    #[test]
+    fn test_submit() {
+        let mut parser =
+            TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT)
+                .with_history_file(None)
+                .with_output(std::string::String::new());
+        assert_eq!(
+            parser.submit("  status  "),
+            ShellEvent::Line(String::try_from("status").unwrap())
+        );
+        assert_eq!(parser.history.get::<64>(0).as_deref(), Some("status"));
+        assert!(parser.buffer.is_empty());
+        assert_eq!(parser.submit(""), ShellEvent::Pending);
+        assert_eq!(parser.submit("#h"), ShellEvent::Pending);
+        assert_eq!(
+            parser.submit("!!"),
+            ShellEvent::Line(String::try_from("status").unwrap())
+        );
+        assert_eq!(parser.submit("#q"), ShellEvent::Exit);
+        // Nothing was drawn
+        assert!(!parser.output().contains(TEST_PROMPT));
+    }
+
+    // ==================== HISTORY EXPANSION TESTS ====================
+
+    type CaptureParser = InputParser<
+        'static,
+        10,
+        32,
+        128,
+        64,
+        64,
+        crate::input::key_reader::StdinReader,
+        std::string::String,
+    >;
+
+    fn expansion_parser() -> CaptureParser {
+        let mut parser = InputParser::<'static, 10, 32, 128, 64, 64>::new(
+            TEST_COMMANDS,
+            TEST_DATATYPES,
+            TEST_SHORTCUTS,
+            TEST_PROMPT,
+        )
+        .with_history_file(None)
+        .with_output(std::string::String::new());
+        parser.history.push("first");
+        parser.history.push("second");
+        parser
+    }
+
+    fn submit(parser: &mut CaptureParser, line: &str) -> (bool, Option<String<128>>) {
+        parser.output().clear();
+        parser.buffer.clear();
+        for c in line.chars() {
+            let _ = parser.buffer.insert(c);
+        }
+        parser.take_line()
+    }
+
+    #[test]
+    fn test_expand_last_entry() {
+        let mut parser = expansion_parser();
+        let (retval, cmd) = submit(&mut parser, "!!");
+        assert!(retval);
+        assert_eq!(cmd.as_deref(), Some("second"));
+        assert!(parser.output().ends_with("second\n"));
+    }
+
+    #[test]
+    fn test_expand_entry_at_index() {
+        let mut parser = expansion_parser();
+        let (_, cmd) = submit(&mut parser, "!0");
+        assert_eq!(cmd.as_deref(), Some("first"));
+        // The expanded line becomes the most recent entry
+        let (_, cmd) = submit(&mut parser, "!!");
+        assert_eq!(cmd.as_deref(), Some("first"));
+        assert!(parser.buffer.is_empty());
+    }
+
+    #[test]
+    fn test_expand_missing_entry() {
+        let mut parser = expansion_parser();
+        let (retval, cmd) = submit(&mut parser, "!9");
+        assert!(retval);
+        assert!(cmd.is_none());
+        assert!(parser.output().ends_with("No history entry at index 9\n"));
+
+        let (_, cmd) = submit(&mut parser, "!99999999999999999999999");
+        assert!(cmd.is_none());
+
+        parser.history.clear();
+        let (_, cmd) = submit(&mut parser, "!!");
+        assert!(cmd.is_none());
+        assert!(parser.output().ends_with("History is empty\n"));
+    }
+
+    #[test]
+    fn test_no_expansion_for_other_lines() {
+        let mut parser = expansion_parser();
+        for line in ["!", "!x", "!1a", "echo !!"] {
+            let (_, cmd) = submit(&mut parser, line);
+            assert_eq!(cmd.as_deref(), Some(line));
+        }
+    }
+
+    // ==================== READ_SECRET TESTS ====================
+
+    #[test]
+    fn test_edit_secret_keys() {
+        let mut out = String::<16>::new();
+        let mut secret = Vec::<char, 128>::new();
+        for key in [
+            Key::Char('p'),
+            Key::Char('w'),
+            Key::Char('é'),
+            Key::Backspace,
+        ] {
+            assert!(!TestParser::edit_secret(&mut out, &mut secret, &key));
+        }
+        assert_eq!(secret.as_slice(), ['p', 'w']);
+        assert!(!TestParser::edit_secret(&mut out, &mut secret, &Key::Tab)); // ignored
+        assert!(!TestParser::edit_secret(&mut out, &mut secret, &Key::CtrlU));
+        assert!(secret.is_empty());
+        assert!(out.is_empty());
+        assert!(!TestParser::edit_secret(
+            &mut out,
+            &mut secret,
+            &Key::Backspace
+        )); // bell only
+        assert_eq!(out.as_str(), "\x07");
+        assert!(TestParser::edit_secret(&mut out, &mut secret, &Key::Enter));
+    }
+
+    #[test]
+    fn test_edit_secret_respects_byte_capacity() {
+        let mut out = String::<16>::new();
+        let mut secret = Vec::<char, 32>::new();
+        for _ in 0..20 {
+            SmallParser::edit_secret(&mut out, &mut secret, &Key::Char('€'));
+        }
+        assert_eq!(secret.len(), 10); // 10 * 3 bytes <= 32
+    }
+
+    #[test]
+    fn test_zeroize() {
+        let mut data = ['s', 'e', 'c', 'r', 'e', 't'];
+        zeroize(&mut data, '\0');
+        assert_eq!(data, ['\0'; 6]);
+
+        let mut text: String<8> = String::try_from("secret").unwrap();
+        zeroize(unsafe { text.as_mut_vec() }, 0);
+        assert!(text.bytes().all(|b| b == 0));
+    }
+
+    // ==================== KEY READER TESTS ====================
+
+    #[test]
+    fn test_parse_input_from_stream_reader() {
+        use crate::input::key_reader::StreamReader;
+        use std::cell::RefCell;
+
+        let mut parser =
+            TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT)
+                .with_reader(StreamReader::new(&b"exit now\rtest\x1B[D\x7F\r"[..]));
+        let lines = RefCell::new(std::vec::Vec::new());
+        let exec = |line: &String<128>| lines.borrow_mut().push(line.clone());
+
+        assert!(parser.parse_input(exec));
+        assert!(parser.parse_input(exec));
+        assert!(!parser.parse_input(exec)); // end of the script
+        assert_eq!(*lines.borrow(), ["exit now", "tet"]);
+    }
+
+    #[test]
+    fn test_read_secret_from_stream_reader() {
+        use crate::input::key_reader::StreamReader;
+
+        let mut parser =
+            TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT)
+                .with_reader(StreamReader::new(&b"s3cret\r"[..]));
+        let length = parser.read_secret("Password: ", |secret| {
+            assert_eq!(secret, "s3cret");
+            secret.len()
+        });
+        assert_eq!(length, 6);
+        assert!(parser.history.iter::<128>().all(|entry| entry != "s3cret"));
+    }
+
+    #[test]
+    fn test_key_pressed_consumes_key() {
+        use crate::input::key_reader::StreamReader;
+
+        let mut parser =
+            TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT)
+                .with_reader(StreamReader::new(&b"q"[..]));
+        assert!(parser.key_pressed());
+        assert!(!parser.key_pressed()); // end of the script
+        assert!(parser.buffer.is_empty());
+    }
+
+    #[test]
+    fn test_wait_key_returns_keys_until_eof() {
+        use crate::input::key_reader::StreamReader;
+
+        let mut parser =
+            TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT)
+                .with_reader(StreamReader::new(&b"q\r"[..]));
+        assert!(matches!(parser.wait_key(), Some(Key::Char('q'))));
+        assert!(matches!(parser.wait_key(), Some(Key::Enter)));
+        assert!(parser.wait_key().is_none());
+    }
+
+    // ==================== POLL TESTS ====================
+
+    #[test]
+    fn test_poll_event_line() {
+        let mut parser =
+            TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT);
+        parser.prompt_shown = true;
+        assert_eq!(parser.poll_event(Key::Char('l')), ShellEvent::Pending);
+        assert_eq!(parser.poll_event(Key::Char('s')), ShellEvent::Pending);
+        assert!(parser.prompt_shown);
+
+        let event = parser.poll_event(Key::Enter);
+        assert_eq!(event, ShellEvent::Line(String::try_from("ls").unwrap()));
+        assert!(!parser.prompt_shown); // next poll renders a new prompt
+        assert!(parser.buffer.is_empty());
+        assert!(parser.history.iter::<128>().any(|entry| entry == "ls"));
+    }
+
+    #[test]
+    fn test_poll_event_empty_line_and_hashtag() {
+        let mut parser =
+            TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT);
+        assert_eq!(parser.poll_event(Key::Enter), ShellEvent::Pending);
+        parser.poll_event(Key::Char('#'));
+        parser.poll_event(Key::Char('c'));
+        assert_eq!(parser.poll_event(Key::Enter), ShellEvent::Pending);
+    }
+
+    #[test]
+    fn test_poll_event_exit() {
+        let mut parser =
+            TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT);
+        assert_eq!(parser.poll_event(Key::CtrlD), ShellEvent::Exit);
+
+        parser.poll_event(Key::Char('#'));
+        parser.poll_event(Key::Char('q'));
+        assert_eq!(parser.poll_event(Key::Enter), ShellEvent::Exit);
+    }
+
+    #[test]
+    fn test_feed_key_shows_prompt_first() {
+        let mut parser =
+            TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT);
+        parser.show_prompt();
+        assert!(parser.prompt_shown);
+        parser.prompt_shown = false;
+        assert_eq!(parser.feed_key(Key::Char('l')), ShellEvent::Pending);
+        assert!(parser.prompt_shown);
+        parser.feed_key(Key::Char('s'));
+        let event = parser.feed_key(Key::Enter);
+        assert_eq!(event, ShellEvent::Line(String::try_from("ls").unwrap()));
+        assert!(!parser.prompt_shown);
+    }
+
+    #[test]
+    fn test_poll_event_history_recall() {
+        let mut parser =
+            TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT)
+                .with_history_file(None);
+        parser.history.push("help");
+        let (index, _) = parser
+            .history
+            .iter_with_indexes::<128>()
+            .find(|(_, entry)| entry == "help")
+            .unwrap();
+        parser.poll_event(Key::Char('#'));
+        for c in index.to_string().chars() {
+            parser.poll_event(Key::Char(c));
+        }
+        let event = parser.poll_event(Key::Enter);
+        assert_eq!(event, ShellEvent::Line(String::try_from("help").unwrap()));
+    }
+
     // ==================== VALID_BYTE TESTS ====================
 
     #[test]
@@ -963,6 +2546,26 @@ mod input_parser_tests {
         assert!(!TestParser::valid_byte(127)); // DEL
     }
 
+    #[test]
+    fn test_valid_char_unicode() {
+        assert!(TestParser::valid_char('a'));
+        assert!(TestParser::valid_char('é'));
+        assert!(TestParser::valid_char('€'));
+        assert!(TestParser::valid_char('😀'));
+        assert!(!TestParser::valid_char('\u{1B}'));
+        assert!(!TestParser::valid_char('\u{85}')); // C1 control
+    }
+
+    #[test]
+    fn test_handle_char_multibyte() {
+        let mut parser =
+            TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT);
+        for c in "echo héllo".chars() {
+            parser.handle_char(c);
+        }
+        assert_eq!(parser.buffer.to_string().as_str(), "echo héllo");
+    }
+
     #[test]
     fn test_valid_byte_printable_range() {
         // Test full printable range
@@ -975,7 +2578,8 @@ mod input_parser_tests {
 
     #[test]
     fn test_list_commands_does_not_panic() {
-        let parser = TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT);
+        let mut parser =
+            TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT);
 
         // Should not panic
         parser.list_commands();
@@ -984,7 +2588,7 @@ mod input_parser_tests {
     #[test]
     fn test_list_commands_with_empty_list() {
         let empty_commands: &[(&str, &str)] = &[];
-        let parser = InputParser::<10, 32, 128, 20, 64>::new(
+        let mut parser = InputParser::<10, 32, 128, 20, 64>::new(
             empty_commands,
             TEST_DATATYPES,
             TEST_SHORTCUTS,
@@ -995,6 +2599,106 @@ mod input_parser_tests {
         parser.list_commands();
     }
 
+    fn test_usage(name: &str) -> &'static str {
+        match name {
+            "hello" => "hello <str>",
+            "test" => "other <u8>", // does not start with the name: spec is shown
+            _ => "",
+        }
+    }
+
+    #[test]
+    fn test_with_usage_sets_lookup() {
+        let parser = TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT);
+        assert!(parser.shell_usage.is_none());
+
+        let mut parser = parser.with_usage(test_usage);
+        assert!(parser.shell_usage.is_some());
+
+        // Should not panic, including for names without usage text
+        parser.list_commands();
+    }
+
+    #[test]
+    fn test_with_dedup_policy() {
+        let parser = TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT)
+            .with_dedup_policy(DedupPolicy::AllowDuplicates);
+        assert_eq!(parser.history.dedup_policy(), DedupPolicy::AllowDuplicates);
+    }
+
+    #[test]
+    fn test_with_history_file_disabled() {
+        let parser = TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT)
+            .with_history_file(None);
+        assert_eq!(parser.history.persistence(), None);
+        assert!(parser.history.is_empty());
+    }
+
+    const NET_COMMANDS: &[(&str, &str)] = &[("net_up", "t"), ("net_send", "sh")];
+
+    #[test]
+    fn test_add_commands() {
+        let mut parser =
+            TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT)
+                .with_history_file(None)
+                .with_output(std::string::String::new());
+        assert!(parser.add_commands(NET_COMMANDS));
+
+        parser.list_commands();
+        assert!(parser.output().contains("  net_up : t\n"));
+        assert!(parser.output().contains("   hello : Say hello\n"));
+
+        // Arguments of the added commands are recognized
+        assert!(parser.arg_context("net_send ").is_some());
+        assert!(parser.arg_context("unknown ").is_none());
+
+        // The names take part in autocompletion
+        parser.handle_char('n');
+        assert_eq!(parser.ghost.as_str(), "et_");
+    }
+
+    #[test]
+    fn test_add_commands_rejects_overflow() {
+        const MANY: &[(&str, &str)] = &[("a1", "v"), ("a2", "v"), ("a3", "v"), ("a4", "v")];
+        let mut parser =
+            TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT);
+        assert!(parser.add_commands(MANY));
+        // 5 + 4 names of the 10 candidates are taken
+        assert!(!parser.add_commands(NET_COMMANDS));
+        assert_eq!(parser.extra_commands.len(), 1);
+    }
+
+    #[test]
+    fn test_list_commands_to_output() {
+        let mut parser =
+            TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT)
+                .with_usage(test_usage)
+                .with_output(std::string::String::new());
+        parser.list_commands();
+        let out = parser.output();
+        assert!(out.starts_with("\r\nCommands:\n"));
+        assert!(out.contains("hello : <str>\n"));
+        assert!(out.contains(" test : Run tests\n"));
+    }
+
+    const TEST_SHORTCUT_SPECS: &[(&str, &str)] = &[("+l", "set level"), ("+m", "")];
+
+    #[test]
+    fn test_with_shortcut_specs_sets_table() {
+        let mut parser =
+            TestParser::new(TEST_COMMANDS, TEST_DATATYPES, TEST_SHORTCUTS, TEST_PROMPT);
+        assert!(parser.shell_shortcut_specs.is_empty());
+        // Falls back to the joined list
+        parser.list_shortcuts();
+
+        let mut parser = parser.with_shortcut_specs(TEST_SHORTCUT_SPECS);
+        assert_eq!(parser.shell_shortcut_specs.len(), 2);
+        assert_eq!(parser.shell_shortcut_specs[0], ("+l", "set level"));
+
+        // Should not panic, including for shortcuts without a description
+        parser.list_all();
+    }
+
     // ==================== INTEGRATION TESTS ====================
 
     #[test]
@@ -1224,7 +2928,8 @@ mod input_parser_tests {
             TEST_DATATYPES,
             TEST_SHORTCUTS,
             TEST_PROMPT,
-        );
+        )
+        .with_history_file(None);
 
         // Fill history to capacity
         for i in 0..5 {
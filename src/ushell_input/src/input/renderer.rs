@@ -1,45 +1,442 @@
-use std::io::{self, Write};
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io;
+
+use crate::output::Output;
+
+/// Terminal bell.
+pub const BELL: &str = "\x07";
+
+/// Erases the screen and moves the cursor to the top-left corner.
+pub const CLEAR_SCREEN: &str = "\x1B[2J\x1B[H";
+
+/// Theme: ANSI sequences used to color the shell output
+///
+/// Each field is the escape sequence starting a color, and `reset` ends it.
+/// `Theme::PLAIN` has only empty sequences, for terminals without ANSI color support.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    pub prompt: &'static str,
+    pub error: &'static str,
+    pub success: &'static str,
+    pub suggestion: &'static str,
+    pub reset: &'static str,
+}
+
+impl Theme {
+    /// Bold prompt, red errors, green successes and dimmed suggestions.
+    pub const ANSI: Theme = Theme {
+        prompt: "\x1B[1m",
+        error: "\x1B[31m",
+        success: "\x1B[32m",
+        suggestion: "\x1B[2m",
+        reset: "\x1B[0m",
+    };
+
+    /// No colors at all.
+    pub const PLAIN: Theme = Theme {
+        prompt: "",
+        error: "",
+        success: "",
+        suggestion: "",
+        reset: "",
+    };
+
+    /// Picks `ANSI`, or `PLAIN` when colors are unwanted or unsupported.
+    ///
+    /// - `PLAIN` if the `NO_COLOR` environment variable is set (to any value).
+    /// - `PLAIN` if `TERM` is `dumb`.
+    /// - `PLAIN` if stdout is not a terminal.
+    ///
+    #[cfg(feature = "std")]
+    pub fn detect() -> Theme {
+        use std::io::IsTerminal;
+        Self::select(
+            std::env::var_os("NO_COLOR").is_some(),
+            std::env::var("TERM").ok().as_deref(),
+            io::stdout().is_terminal(),
+        )
+    }
+
+    #[cfg(any(feature = "std", test))]
+    fn select(no_color: bool, term: Option<&str>, is_terminal: bool) -> Theme {
+        if no_color || term == Some("dumb") || !is_terminal {
+            Theme::PLAIN
+        } else {
+            Theme::ANSI
+        }
+    }
+
+    /// Wraps `text` in `color` and `reset`, e.g. `theme.paint(theme.error, "Error")`.
+    ///
+    pub fn paint<'t>(&self, color: &'t str, text: &'t str) -> Painted<'t> {
+        Painted {
+            color,
+            text,
+            reset: if color.is_empty() { "" } else { self.reset },
+        }
+    }
+}
+
+/// Text wrapped in a color by `Theme::paint`, displayed with its escape sequences.
+///
+pub struct Painted<'t> {
+    color: &'t str,
+    text: &'t str,
+    reset: &'t str,
+}
+
+impl core::fmt::Display for Painted<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}{}{}", self.color, self.text, self.reset)
+    }
+}
+
+/// Ranges of characters taking no terminal column (combining marks, zero-width spaces and joiners,
+/// variation selectors).
+const ZERO_WIDTH: &[(u32, u32)] = &[
+    (0x0300, 0x036F),
+    (0x0483, 0x0489),
+    (0x0591, 0x05BD),
+    (0x0610, 0x061A),
+    (0x064B, 0x065F),
+    (0x1AB0, 0x1AFF),
+    (0x1DC0, 0x1DFF),
+    (0x200B, 0x200F),
+    (0x20D0, 0x20FF),
+    (0xFE00, 0xFE0F),
+    (0xFE20, 0xFE2F),
+    (0xE0100, 0xE01EF),
+];
+
+/// Ranges of characters taking two terminal columns (East Asian wide and fullwidth, emoji).
+const DOUBLE_WIDTH: &[(u32, u32)] = &[
+    (0x1100, 0x115F),
+    (0x2E80, 0x303E),
+    (0x3041, 0x33FF),
+    (0x3400, 0x4DBF),
+    (0x4E00, 0x9FFF),
+    (0xA000, 0xA4CF),
+    (0xAC00, 0xD7A3),
+    (0xF900, 0xFAFF),
+    (0xFE30, 0xFE4F),
+    (0xFF00, 0xFF60),
+    (0xFFE0, 0xFFE6),
+    (0x1F300, 0x1F64F),
+    (0x1F680, 0x1F6FF),
+    (0x1F900, 0x1F9FF),
+    (0x20000, 0x2FFFD),
+    (0x30000, 0x3FFFD),
+];
+
+/// Returns the number of terminal columns taken by `c` (0, 1 or 2), wcwidth-style.
+///
+/// Control characters are counted as 0.
+///
+pub fn char_width(c: char) -> usize {
+    let in_table = |table: &[(u32, u32)]| {
+        let code = c as u32;
+        table
+            .iter()
+            .any(|&(first, last)| (first..=last).contains(&code))
+    };
+    if c.is_control() || in_table(ZERO_WIDTH) {
+        0
+    } else if in_table(DOUBLE_WIDTH) {
+        2
+    } else {
+        1
+    }
+}
+
+/// Returns the number of terminal columns taken by `text`.
+///
+/// ANSI escape sequences (`ESC [ ... m` colors in a prompt) take no column.
+///
+pub fn display_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1B' && chars.clone().next() == Some('[') {
+            chars.next();
+            chars.find(|c| ('@'..='~').contains(c));
+            continue;
+        }
+        width += char_width(c);
+    }
+    width
+}
+
+/// Returns the longest start of `text` fitting in `columns` terminal columns.
+///
+fn fit_width(text: &str, columns: usize) -> &str {
+    let mut used = 0;
+    for (i, c) in text.char_indices() {
+        used += char_width(c);
+        if used > columns {
+            return &text[..i];
+        }
+    }
+    text
+}
+
+/// Narrowest space for the input below which the line is drawn unscrolled.
+const MIN_VIEWPORT_WIDTH: usize = 4;
+
+/// Part of the input shown by `DisplayRenderer::draw_line` when the line is wider than the
+/// output.
+///
+#[derive(Debug, PartialEq, Eq)]
+struct Viewport {
+    /// Byte range of the visible content.
+    start: usize,
+    end: usize,
+    /// Cursor position, in characters from `start`.
+    cursor: usize,
+    /// Content is hidden before `start` (shown as `<`) / after `end` (shown as `>`).
+    left: bool,
+    right: bool,
+}
 
 /// DisplayRenderer: handles terminal output
 ///
+/// Everything is written to the [`Output`] passed to each function (e.g. `Stdout`).
+///
 pub struct DisplayRenderer;
 
 impl DisplayRenderer {
     /// Renders the prompt and input content to the terminal.
     ///
     /// - Clears the current line.
-    /// - Prints the prompt followed by the content.
-    /// - Moves the cursor to the correct position based on `cursor_pos`.
+    /// - Prints the prompt (in the theme's prompt color) followed by the content.
+    /// - Moves the cursor to the correct position based on `cursor_pos` (in characters), counting
+    ///   the display width of each character so wide (CJK, emoji) input is handled.
     /// - Ensures cursor position does not exceed content length.
-    /// - Flushes stdout to apply changes immediately.
+    /// - Flushes `out` to apply changes immediately.
+    ///
+    pub fn render(
+        out: &mut dyn Output,
+        prompt: &str,
+        content: &str,
+        cursor_pos: usize,
+        theme: &Theme,
+    ) {
+        Self::render_hint(out, prompt, content, cursor_pos, "", theme);
+    }
+
+    /// Renders the prompt and input content followed by a dimmed hint.
+    ///
+    /// - Behaves like [`render`](Self::render), then prints `hint` in the theme's suggestion
+    ///   color after the content.
+    /// - The hint is not part of the input; the next render erases it.
+    /// - An empty hint renders exactly like `render`.
+    ///
+    pub fn render_hint(
+        out: &mut dyn Output,
+        prompt: &str,
+        content: &str,
+        cursor_pos: usize,
+        hint: &str,
+        theme: &Theme,
+    ) {
+        let _ = Self::draw_line(out, prompt, content, cursor_pos, hint, theme);
+        out.flush();
+    }
+
+    /// Writes the escape sequences redrawing the input line to `out`.
+    ///
+    /// This is [`render_hint`](Self::render_hint) without the flush: the line is cleared, then
+    /// the prompt, the content and the hint (if not empty) are written, and the cursor is moved
+    /// to `cursor_pos`.
     ///
-    pub fn render(prompt: &str, content: &str, cursor_pos: usize) {
-        let safe_cursor_pos = cursor_pos.min(content.len());
-        print!("\r\x1B[K{}{}", prompt, content);
-        print!("\x1B[{}G", prompt.len() + safe_cursor_pos + 1);
-        let _ = io::stdout().flush();
+    /// When `out` reports its width and the input does not fit after the prompt, only a window
+    /// of it around the cursor is drawn, with a dimmed `<` / `>` where text is hidden and no
+    /// hint, so the line never wraps. A hint that does not fit is truncated.
+    ///
+    pub fn draw_line(
+        out: &mut dyn Output,
+        prompt: &str,
+        content: &str,
+        cursor_pos: usize,
+        hint: &str,
+        theme: &Theme,
+    ) -> fmt::Result {
+        // The last column stays free so the terminal does not wrap the cursor
+        let columns = out.width().map_or(usize::MAX, |width| {
+            width.saturating_sub(display_width(prompt) + 1)
+        });
+        let view = Self::viewport(content, cursor_pos, columns);
+        let visible = &content[view.start..view.end];
+
+        out.clear_line();
+        write!(out, "{}", theme.paint(theme.prompt, prompt))?;
+        if view.left {
+            write!(out, "{}", theme.paint(theme.suggestion, "<"))?;
+        }
+        write!(out, "{}", visible)?;
+        if view.right {
+            write!(out, "{}", theme.paint(theme.suggestion, ">"))?;
+        } else if !view.left {
+            let hint = fit_width(hint, columns.saturating_sub(display_width(visible)));
+            if !hint.is_empty() {
+                write!(out, "{}", theme.paint(theme.suggestion, hint))?;
+            }
+        }
+        write!(
+            out,
+            "\x1B[{}G",
+            Self::cursor_column(prompt, visible, view.cursor) + view.left as usize
+        )
+    }
+
+    /// Chooses the part of `content` to draw in `columns` terminal columns, keeping the cursor
+    /// (before character `cursor_pos`) visible.
+    ///
+    /// - All of it when it fits, or when `columns` is too narrow to scroll.
+    /// - Otherwise the start stays visible while the cursor is close to it; further right the
+    ///   window ends just after the cursor. A marker column is kept on each side with hidden text.
+    ///
+    fn viewport(content: &str, cursor_pos: usize, columns: usize) -> Viewport {
+        let cursor_pos = cursor_pos.min(content.chars().count());
+        if display_width(content) <= columns || columns < MIN_VIEWPORT_WIDTH {
+            return Viewport {
+                start: 0,
+                end: content.len(),
+                cursor: cursor_pos,
+                left: false,
+                right: false,
+            };
+        }
+
+        let cursor_byte = content
+            .char_indices()
+            .nth(cursor_pos)
+            .map_or(content.len(), |(i, _)| i);
+        let before = &content[..cursor_byte];
+
+        // Room before the cursor: minus its own cell and the right marker (and the left one)
+        let (start, left) = if display_width(before) < columns - 1 {
+            (0, false)
+        } else {
+            let room = columns - 3;
+            let mut start = cursor_byte;
+            let mut used = 0;
+            for (i, c) in before.char_indices().rev() {
+                used += char_width(c);
+                if used > room {
+                    break;
+                }
+                start = i;
+            }
+            (start, true)
+        };
+
+        let mut room = columns - left as usize;
+        let rest = &content[start..];
+        let right = display_width(rest) > room;
+        if right {
+            room -= 1;
+        }
+        let end = start + fit_width(rest, room).len();
+        Viewport {
+            start,
+            end,
+            cursor: content[start..cursor_byte].chars().count(),
+            left,
+            right,
+        }
+    }
+
+    /// Returns the 1-based terminal column of the cursor placed before character `cursor_pos` of
+    /// `content`, after the prompt.
+    ///
+    fn cursor_column(prompt: &str, content: &str, cursor_pos: usize) -> usize {
+        let before_cursor: usize = content.chars().take(cursor_pos).map(char_width).sum();
+        display_width(prompt) + before_cursor + 1
+    }
+
+    /// Prints completion candidates in columns below the current line.
+    ///
+    /// - Moves to a new line, then prints the candidates row by row in equally wide columns.
+    /// - Candidates longer than `width` are truncated to it.
+    /// - The caller is expected to re-render the prompt afterwards.
+    ///
+    pub fn render_candidates<S: AsRef<str>>(out: &mut dyn Output, candidates: &[S], width: usize) {
+        let (column_width, columns) = Self::columns(candidates, width);
+        let _ = writeln!(out);
+        for row in candidates.chunks(columns) {
+            // Spaces are held back until more text follows, so rows carry no trailing padding
+            let mut spaces = 0;
+            for candidate in row {
+                let mut used = 0;
+                for c in candidate.as_ref().chars() {
+                    used += char_width(c);
+                    if used > column_width {
+                        used -= char_width(c);
+                        break;
+                    }
+                    if c == ' ' {
+                        spaces += 1;
+                    } else {
+                        for _ in 0..core::mem::take(&mut spaces) {
+                            let _ = out.write_char(' ');
+                        }
+                        let _ = out.write_char(c);
+                    }
+                }
+                spaces += column_width - used;
+            }
+            let _ = writeln!(out);
+        }
+        out.flush();
+    }
+
+    /// Computes the `(column width, column count)` used by `render_candidates`.
+    ///
+    /// Columns are two columns wider than the widest candidate (capped at `width`),
+    /// and at least one column is always used.
+    ///
+    fn columns<S: AsRef<str>>(candidates: &[S], width: usize) -> (usize, usize) {
+        let longest = candidates
+            .iter()
+            .map(|c| display_width(c.as_ref()))
+            .max()
+            .unwrap_or(0);
+        let column_width = (longest + 2).min(width.max(1));
+        (column_width, (width / column_width).max(1))
+    }
+
+    /// Clears the whole terminal screen.
+    ///
+    /// - Erases the visible screen and moves the cursor to the top-left corner.
+    /// - The caller is expected to re-render the prompt afterwards.
+    /// - Flushes `out` to apply changes immediately.
+    ///
+    pub fn clear_screen(out: &mut dyn Output) {
+        let _ = out.write_str(CLEAR_SCREEN);
+        out.flush();
     }
 
     /// Emits an audible bell sound in the terminal.
     ///
     /// - Useful for signaling invalid actions (e.g., backspace at start of buffer).
-    /// - Flushes stdout to ensure the bell is triggered immediately.
+    /// - Same as [`Output::bell`], which flushes to ensure the bell is triggered immediately.
     ///
-    pub fn bell() {
-        print!("\x07");
-        let _ = io::stdout().flush();
+    pub fn bell(out: &mut dyn Output) {
+        out.bell();
     }
 
-    /// Prints a red boundary marker in the terminal.
+    /// Prints a boundary marker in the terminal.
     ///
-    /// - Displays a red newline character.
+    /// - Displays a `|` in the theme's error color.
     /// - Moves the cursor back two positions.
-    /// - Flushes stdout to apply changes immediately.
+    /// - Flushes `out` to apply changes immediately.
     /// - Can be used to visually separate sections or indicate limits.
     ///
-    pub fn boundary_marker() {
-        print!("\x1B[31m|\x1B[0m\x1B[1D \x1B[1D");
-        let _ = io::stdout().flush();
+    pub fn boundary_marker(out: &mut dyn Output, theme: &Theme) {
+        let _ = write!(out, "{}\x1B[1D \x1B[1D", theme.paint(theme.error, "|"));
+        out.flush();
     }
 }
 
@@ -48,22 +445,242 @@ impl DisplayRenderer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(feature = "std")]
+    use crate::output::Stdout;
+
+    type Capture = heapless::String<128>;
 
-    // Test will just call methods to ensure they do not panic.
-    // Because DisplayRenderer writes directly to stdout,
-    // full capture is tricky without changing library code.
+    #[cfg(feature = "std")]
     #[test]
     fn test_render_does_not_panic() {
-        DisplayRenderer::render(">", "Hello", 3);
+        DisplayRenderer::render(&mut Stdout, ">", "Hello", 3, &Theme::ANSI);
+        DisplayRenderer::render(&mut Stdout, ">", "Hello", 3, &Theme::PLAIN);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_render_hint_does_not_panic() {
+        DisplayRenderer::render_hint(&mut Stdout, ">", "set ", 4, "<u32 freq>", &Theme::ANSI);
+        DisplayRenderer::render_hint(&mut Stdout, ">", "set ", 4, "", &Theme::PLAIN);
+    }
+
+    #[test]
+    fn test_render_hint_captured() {
+        let mut out = Capture::new();
+        DisplayRenderer::render_hint(&mut out, "> ", "set ", 4, "<u32>", &Theme::PLAIN);
+        assert_eq!(out.as_str(), "\r\x1B[K> set <u32>\x1B[7G");
+    }
+
+    #[test]
+    fn test_draw_line() {
+        let mut out = Capture::new();
+        DisplayRenderer::draw_line(&mut out, "> ", "ab", 1, "", &Theme::PLAIN).unwrap();
+        assert_eq!(out.as_str(), "\r\x1B[K> ab\x1B[4G");
+
+        out.clear();
+        DisplayRenderer::draw_line(&mut out, "> ", "ab", 2, "cd", &Theme::ANSI).unwrap();
+        assert_eq!(
+            out.as_str(),
+            "\r\x1B[K\x1B[1m> \x1B[0mab\x1B[2mcd\x1B[0m\x1B[5G"
+        );
+    }
+
+    /// Captures output like a terminal `width` columns wide.
+    struct Narrow {
+        text: Capture,
+        width: usize,
+    }
+
+    impl fmt::Write for Narrow {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.text.write_str(s)
+        }
+    }
+
+    impl Output for Narrow {
+        fn width(&self) -> Option<usize> {
+            Some(self.width)
+        }
+    }
+
+    fn draw_narrow(width: usize, content: &str, cursor_pos: usize, hint: &str) -> Capture {
+        let mut out = Narrow {
+            text: Capture::new(),
+            width,
+        };
+        DisplayRenderer::draw_line(&mut out, "> ", content, cursor_pos, hint, &Theme::PLAIN)
+            .unwrap();
+        out.text
+    }
+
+    #[test]
+    fn test_draw_line_fits_width() {
+        assert_eq!(
+            draw_narrow(10, "abcdefg", 7, ""),
+            "\r\x1B[K> abcdefg\x1B[10G"
+        );
+        // A hint that does not fit is truncated
+        assert_eq!(
+            draw_narrow(10, "ab", 2, "<u32 x>"),
+            "\r\x1B[K> ab<u32 \x1B[5G"
+        );
     }
 
+    #[test]
+    fn test_draw_line_scrolls_long_input() {
+        // Cursor near the start: the start stays visible, the rest is marked hidden
+        assert_eq!(
+            draw_narrow(10, "abcdefghij", 2, "hint"),
+            "\r\x1B[K> abcdef>\x1B[5G"
+        );
+        // Cursor at the end: the window ends at the cursor
+        assert_eq!(
+            draw_narrow(10, "abcdefghij", 10, ""),
+            "\r\x1B[K> <ghij\x1B[8G"
+        );
+        // Cursor in the middle: hidden text on both sides
+        assert_eq!(
+            draw_narrow(10, "abcdefghij", 7, ""),
+            "\r\x1B[K> <defgh>\x1B[8G"
+        );
+    }
+
+    #[test]
+    fn test_viewport() {
+        let view = DisplayRenderer::viewport("abcdefghij", 10, 7);
+        assert_eq!(
+            view,
+            Viewport {
+                start: 6,
+                end: 10,
+                cursor: 4,
+                left: true,
+                right: false
+            }
+        );
+        // Fits, or too narrow to scroll: everything is shown
+        assert!(!DisplayRenderer::viewport("abc", 3, 7).left);
+        assert_eq!(DisplayRenderer::viewport("abcdefghij", 10, 3).end, 10);
+        // Wide characters are measured by display width
+        let view = DisplayRenderer::viewport("日本語テキスト", 7, 7);
+        assert_eq!((view.start, view.cursor, view.left), (15, 2, true));
+    }
+
+    #[test]
+    fn test_fit_width() {
+        assert_eq!(fit_width("hello", 3), "hel");
+        assert_eq!(fit_width("hello", 9), "hello");
+        assert_eq!(fit_width("日本", 3), "日");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_render_candidates_does_not_panic() {
+        DisplayRenderer::render_candidates(&mut Stdout, &["help", "hello"], 80);
+        DisplayRenderer::render_candidates::<&str>(&mut Stdout, &[], 80);
+        DisplayRenderer::render_candidates(&mut Stdout, &["a_very_long_candidate"], 4);
+    }
+
+    #[test]
+    fn test_render_candidates_captured() {
+        let mut out = Capture::new();
+        DisplayRenderer::render_candidates(&mut out, &["help", "hello", "list"], 14);
+        assert_eq!(out.as_str(), "\nhelp   hello\nlist\n");
+
+        out.clear();
+        DisplayRenderer::render_candidates(&mut out, &["a_very_long_candidate"], 4);
+        assert_eq!(out.as_str(), "\na_ve\n");
+    }
+
+    #[test]
+    fn test_columns_layout() {
+        assert_eq!(DisplayRenderer::columns(&["help", "hello"], 80), (7, 11));
+        assert_eq!(DisplayRenderer::columns(&["help", "hello"], 10), (7, 1));
+        assert_eq!(
+            DisplayRenderer::columns(&["a_very_long_candidate"], 8),
+            (8, 1)
+        );
+    }
+
+    #[test]
+    fn test_columns_layout_wide_chars() {
+        assert_eq!(DisplayRenderer::columns(&["日本", "ab"], 20), (6, 3));
+    }
+
+    #[test]
+    fn test_char_width() {
+        assert_eq!(char_width('a'), 1);
+        assert_eq!(char_width('é'), 1);
+        assert_eq!(char_width('\u{0301}'), 0); // combining acute accent
+        assert_eq!(char_width('\u{200D}'), 0); // zero-width joiner
+        assert_eq!(char_width('日'), 2);
+        assert_eq!(char_width('한'), 2);
+        assert_eq!(char_width('Ａ'), 2); // fullwidth
+        assert_eq!(char_width('😀'), 2);
+        assert_eq!(char_width('\x07'), 0);
+    }
+
+    #[test]
+    fn test_display_width() {
+        assert_eq!(display_width(""), 0);
+        assert_eq!(display_width("> "), 2);
+        assert_eq!(display_width("e\u{0301}"), 1);
+        assert_eq!(display_width("日本語"), 6);
+        assert_eq!(display_width("\x1B[32m✓\x1B[0m > "), 4);
+    }
+
+    #[test]
+    fn test_cursor_column_counts_display_width() {
+        assert_eq!(DisplayRenderer::cursor_column("> ", "abc", 1), 4);
+        assert_eq!(DisplayRenderer::cursor_column("> ", "日本語", 2), 7);
+        assert_eq!(DisplayRenderer::cursor_column("λ ", "😀x", 1), 5);
+        assert_eq!(DisplayRenderer::cursor_column("> ", "ab", 10), 5); // clamped to the end
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_clear_screen_does_not_panic() {
+        DisplayRenderer::clear_screen(&mut Stdout);
+    }
+
+    #[cfg(feature = "std")]
     #[test]
     fn test_bell_does_not_panic() {
-        DisplayRenderer::bell();
+        DisplayRenderer::bell(&mut Stdout);
     }
 
+    #[cfg(feature = "std")]
     #[test]
     fn test_boundary_marker_does_not_panic() {
-        DisplayRenderer::boundary_marker();
+        DisplayRenderer::boundary_marker(&mut Stdout, &Theme::ANSI);
+    }
+
+    #[test]
+    fn test_screen_control_captured() {
+        let mut out = Capture::new();
+        DisplayRenderer::clear_screen(&mut out);
+        DisplayRenderer::bell(&mut out);
+        DisplayRenderer::boundary_marker(&mut out, &Theme::PLAIN);
+        assert_eq!(out.as_str(), "\x1B[2J\x1B[H\x07|\x1B[1D \x1B[1D");
+    }
+
+    #[test]
+    fn test_theme_select() {
+        assert_eq!(Theme::select(false, Some("xterm"), true), Theme::ANSI);
+        assert_eq!(Theme::select(false, None, true), Theme::ANSI);
+        assert_eq!(Theme::select(true, Some("xterm"), true), Theme::PLAIN);
+        assert_eq!(Theme::select(false, Some("dumb"), true), Theme::PLAIN);
+        assert_eq!(Theme::select(false, Some("xterm"), false), Theme::PLAIN);
+    }
+
+    #[test]
+    fn test_theme_paint() {
+        let ansi = Theme::ANSI;
+        assert_eq!(
+            format!("{}", ansi.paint(ansi.error, "Error")),
+            "\x1B[31mError\x1B[0m"
+        );
+        let plain = Theme::PLAIN;
+        assert_eq!(format!("{}", plain.paint(plain.error, "Error")), "Error");
     }
 }
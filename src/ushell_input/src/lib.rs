@@ -1,11 +1,9 @@
-#[cfg(any(
-    feature = "history-persistence",
-    feature = "heap-history",
-    feature = "heap-input-buffer"
-))]
-extern crate std;
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
 pub mod autocomplete;
 pub mod history;
 pub mod input;
+pub mod output;
+#[cfg(feature = "std")]
 pub mod terminal;
+pub mod transport;
@@ -0,0 +1,263 @@
+use core::fmt;
+
+use crate::input::renderer::BELL;
+
+/// Output: destination of everything the shell prints
+///
+/// Text is written through the `fmt::Write` supertrait (so `write!` / `writeln!` work on any
+/// output); the other methods have defaults emitting the usual ANSI sequences.
+///
+/// Implemented by [`Stdout`] for the local terminal, and by `heapless::String` and
+/// `std::string::String` so output can be captured (in tests, or to forward it over a
+/// transport). Implement it for a UART, a socket or a log sink to use the shell without `stdout`.
+///
+pub trait Output: fmt::Write {
+    /// Pushes buffered output to the device. Does nothing by default.
+    ///
+    fn flush(&mut self) {}
+
+    /// Emits an audible bell and flushes, e.g. for an invalid action.
+    ///
+    fn bell(&mut self) {
+        let _ = self.write_str(BELL);
+        self.flush();
+    }
+
+    /// Moves to the start of the current line and erases it.
+    ///
+    fn clear_line(&mut self) {
+        let _ = self.write_str("\r\x1B[K");
+    }
+
+    /// Returns the number of columns of the device, if known.
+    ///
+    /// Longer input lines are scrolled horizontally to fit (see `DisplayRenderer::draw_line`).
+    /// `None` by default: lines are drawn whole.
+    ///
+    fn width(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// Width assumed when the width of the output cannot be queried (e.g. it is not a terminal).
+pub const DEFAULT_WIDTH: usize = 80;
+
+/// Output dropping everything, for a parser without the `std` feature until one is installed
+/// with `with_output`.
+///
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Discard;
+
+impl fmt::Write for Discard {
+    fn write_str(&mut self, _: &str) -> fmt::Result {
+        Ok(())
+    }
+}
+
+impl Output for Discard {}
+
+/// `\n` to `\r\n` translation of the outputs writing to a byte stream, whose other end (a terminal
+/// in raw mode) does not translate it.
+///
+/// Used by [`StreamOutput`] and the transport outputs, and by an [`Output`] for a UART of one's
+/// own. Keeps whether the last byte written was a `\r`, so a `\r\n` split across two writes is
+/// not doubled.
+///
+#[derive(Debug, Clone, Copy)]
+pub struct Crlf {
+    enabled: bool,
+    after_cr: bool,
+}
+
+impl Crlf {
+    /// Creates a translation, enabled or passing the text through.
+    pub const fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            after_cr: false,
+        }
+    }
+
+    /// Selects whether `\n` is sent as `\r\n`.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Hands `s` to `write` in pieces, with a `\r` before each `\n` not following one.
+    pub fn write<E>(
+        &mut self,
+        s: &str,
+        mut write: impl FnMut(&[u8]) -> Result<(), E>,
+    ) -> Result<(), E> {
+        if !self.enabled {
+            return write(s.as_bytes());
+        }
+        for (i, part) in s.split('\n').enumerate() {
+            if i > 0 {
+                write(if self.after_cr { b"\n" } else { b"\r\n" })?;
+                self.after_cr = false;
+            }
+            if let Some(&last) = part.as_bytes().last() {
+                write(part.as_bytes())?;
+                self.after_cr = last == b'\r';
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Output to the process's standard output.
+///
+#[cfg(feature = "std")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Stdout;
+
+#[cfg(feature = "std")]
+impl fmt::Write for Stdout {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        print!("{}", s);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl Output for Stdout {
+    fn width(&self) -> Option<usize> {
+        Some(crate::terminal::width())
+    }
+
+    fn flush(&mut self) {
+        use std::io::Write;
+        let _ = std::io::stdout().flush();
+    }
+}
+
+/// Output to any byte stream (`io::Write`): a socket, a serial port, a pipe.
+///
+/// Text is collected and written on [`flush`](Output::flush), which the renderer calls after
+/// each update, so a line is sent as one write rather than byte by byte. Each `\n` is sent as
+/// `\r\n` (see [`with_crlf`](Self::with_crlf)), as a terminal in raw mode on the other end does
+/// not translate it. Write errors are dropped: a gone peer is noticed by the reader instead.
+///
+#[cfg(feature = "std")]
+pub struct StreamOutput<W: std::io::Write> {
+    inner: W,
+    pending: std::vec::Vec<u8>,
+    crlf: Crlf,
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> StreamOutput<W> {
+    /// Creates an output writing to `inner`, translating `\n` to `\r\n`.
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            pending: std::vec::Vec::new(),
+            crlf: Crlf::new(true),
+        }
+    }
+
+    /// Selects whether `\n` is sent as `\r\n` (enabled by default).
+    pub fn with_crlf(mut self, enabled: bool) -> Self {
+        self.crlf.set_enabled(enabled);
+        self
+    }
+
+    /// Returns the underlying byte stream.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> fmt::Write for StreamOutput<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let pending = &mut self.pending;
+        self.crlf.write(s, |bytes| {
+            pending.extend_from_slice(bytes);
+            Ok(())
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> Output for StreamOutput<W> {
+    fn flush(&mut self) {
+        if !self.pending.is_empty() {
+            let _ = self.inner.write_all(&self.pending);
+            self.pending.clear();
+        }
+        let _ = self.inner.flush();
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> Drop for StreamOutput<W> {
+    fn drop(&mut self) {
+        Output::flush(self);
+    }
+}
+
+impl<const N: usize> Output for heapless::String<N> {}
+
+#[cfg(feature = "std")]
+impl Output for std::string::String {}
+
+// ==================== TESTS =======================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::fmt::Write;
+
+    #[test]
+    fn test_default_sequences() {
+        let mut out = heapless::String::<32>::new();
+        out.clear_line();
+        let _ = write!(out, "x");
+        out.bell();
+        assert_eq!(out.as_str(), "\r\x1B[Kx\x07");
+    }
+
+    #[test]
+    fn test_crlf() {
+        let mut sent = heapless::Vec::<u8, 32>::new();
+        let mut crlf = Crlf::new(true);
+        for s in ["a\nb\r\nc\r", "\nd\n", "\n"] {
+            let _ = crlf.write(s, |bytes| sent.extend_from_slice(bytes));
+        }
+        assert_eq!(sent.as_slice(), b"a\r\nb\r\nc\r\nd\r\n\r\n");
+
+        sent.clear();
+        crlf.set_enabled(false);
+        let _ = crlf.write("a\nb", |bytes| sent.extend_from_slice(bytes));
+        assert_eq!(sent.as_slice(), b"a\nb");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_stream_output_translates_newlines_on_flush() {
+        let mut out = StreamOutput::new(std::vec::Vec::new());
+        let _ = write!(out, "a\nb\r\n");
+        assert!(out.inner.is_empty()); // nothing written before the flush
+        Output::flush(&mut out);
+        assert_eq!(out.inner, b"a\r\nb\r\n");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_stream_output_without_crlf() {
+        let mut out = StreamOutput::new(std::vec::Vec::new()).with_crlf(false);
+        let _ = writeln!(out, "a");
+        Output::flush(&mut out);
+        assert_eq!(out.inner, b"a\n");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_stdout_does_not_panic() {
+        let mut out = Stdout;
+        let _ = write!(out, "");
+        out.flush();
+    }
+}
@@ -9,6 +9,9 @@
 //! - On **Unix**, it uses the `termios` crate to manipulate terminal attributes.
 //! - On **Windows**, it uses the `winapi` crate to modify console modes.
 //!
+//! It also provides [`width`], the number of terminal columns used to lay out output, and
+//! [`is_interactive`], which tells a terminal from piped input.
+//!
 //! # Example
 //! ```rust
 //! // Enable raw mode
@@ -17,6 +20,49 @@
 //! // When `_raw` is dropped, the original mode is restored
 //! ```
 
+pub use crate::output::DEFAULT_WIDTH;
+
+/// Returns the width of the terminal in columns, or [`DEFAULT_WIDTH`] if it is unknown.
+///
+#[cfg(unix)]
+pub fn width() -> usize {
+    // SAFETY: `TIOCGWINSZ` only writes a `winsize` into the provided, properly sized struct.
+    let mut size: libc::winsize = unsafe { core::mem::zeroed() };
+    let ok = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0;
+    if ok && size.ws_col > 0 {
+        size.ws_col as usize
+    } else {
+        DEFAULT_WIDTH
+    }
+}
+
+/// Returns the width of the terminal in columns, or [`DEFAULT_WIDTH`] if it is unknown.
+///
+#[cfg(windows)]
+pub fn width() -> usize {
+    use winapi::um::processenv::GetStdHandle;
+    use winapi::um::winbase::STD_OUTPUT_HANDLE;
+    use winapi::um::wincon::{CONSOLE_SCREEN_BUFFER_INFO, GetConsoleScreenBufferInfo};
+    unsafe {
+        let mut info: CONSOLE_SCREEN_BUFFER_INFO = core::mem::zeroed();
+        if GetConsoleScreenBufferInfo(GetStdHandle(STD_OUTPUT_HANDLE), &mut info) != 0 {
+            let columns = info.srWindow.Right - info.srWindow.Left + 1;
+            if columns > 0 {
+                return columns as usize;
+            }
+        }
+    }
+    DEFAULT_WIDTH
+}
+
+/// Returns `true` if stdin is a terminal, `false` if input is piped or redirected from a file
+/// (where raw mode and line editing do not apply).
+///
+pub fn is_interactive() -> bool {
+    use std::io::IsTerminal;
+    std::io::stdin().is_terminal()
+}
+
 /// Represents a handle to the terminal's raw mode state.
 /// When dropped, restores the original terminal mode.
 ///
@@ -27,13 +73,17 @@ pub struct RawMode {
     #[cfg(windows)]
     /// Original console mode (Windows).
     original_mode: u32,
+    #[cfg(windows)]
+    /// Original output mode, if stdout is a console (Windows).
+    original_output_mode: Option<u32>,
 }
 
 impl RawMode {
     /// Enables raw mode for the terminal.
     ///
     /// On Unix, `fd` is the file descriptor (usually 0 for stdin).
-    /// On Windows, the argument is ignored.
+    /// On Windows, the argument is ignored; virtual-terminal processing is also enabled on
+    /// stdout so the ANSI sequences used for rendering work in `cmd.exe`.
     ///
     /// # Panics
     /// Panics if unable to get or set terminal/console mode.
@@ -54,8 +104,8 @@ impl RawMode {
             consoleapi::{GetConsoleMode, SetConsoleMode},
             handleapi::INVALID_HANDLE_VALUE,
             processenv::GetStdHandle,
-            winbase::STD_INPUT_HANDLE,
-            wincon::{ENABLE_ECHO_INPUT, ENABLE_LINE_INPUT},
+            winbase::{STD_INPUT_HANDLE, STD_OUTPUT_HANDLE},
+            wincon::{ENABLE_ECHO_INPUT, ENABLE_LINE_INPUT, ENABLE_VIRTUAL_TERMINAL_PROCESSING},
         };
         unsafe {
             let handle = GetStdHandle(STD_INPUT_HANDLE);
@@ -72,7 +122,22 @@ impl RawMode {
             let success = SetConsoleMode(handle, mode);
             assert!(success != 0, "Failed to set console mode");
 
-            RawMode { original_mode }
+            // Interpret ANSI escape sequences on output; skipped when stdout is redirected
+            let output = GetStdHandle(STD_OUTPUT_HANDLE);
+            let mut output_mode = 0;
+            let original_output_mode = if output != INVALID_HANDLE_VALUE
+                && GetConsoleMode(output, &mut output_mode) != 0
+            {
+                SetConsoleMode(output, output_mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING);
+                Some(output_mode)
+            } else {
+                None
+            };
+
+            RawMode {
+                original_mode,
+                original_output_mode,
+            }
         }
     }
 }
@@ -90,11 +155,14 @@ impl Drop for RawMode {
         use winapi::um::consoleapi::*;
         use winapi::um::handleapi::INVALID_HANDLE_VALUE;
         use winapi::um::processenv::*;
-        use winapi::um::winbase::STD_INPUT_HANDLE;
+        use winapi::um::winbase::{STD_INPUT_HANDLE, STD_OUTPUT_HANDLE};
         unsafe {
             let handle = GetStdHandle(STD_INPUT_HANDLE);
             assert!(handle != INVALID_HANDLE_VALUE);
             SetConsoleMode(handle, self.original_mode);
+            if let Some(mode) = self.original_output_mode {
+                SetConsoleMode(GetStdHandle(STD_OUTPUT_HANDLE), mode);
+            }
         }
     }
 }
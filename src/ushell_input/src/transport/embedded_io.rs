@@ -0,0 +1,223 @@
+//! embedded-io transport: the shell over the `Read` / `Write` halves of a HAL driver.
+//!
+//! Any driver implementing the [`embedded_io`] traits (a UART, a USB serial class, a TCP socket
+//! of an embedded stack) carries the shell without `std`: [`IoKeys`] decodes the keys of an
+//! `InputParser` from the reader, and [`IoOutput`] sends the output of the parser, or of a
+//! `LineEditor`, to the writer.
+//!
+//! ```rust,ignore
+//! let (rx, tx) = uart.split();
+//! let mut parser = InputParser::<16, 32, 128, 16, 128>::new(commands, datatypes, shortcuts, "> ")
+//!     .with_reader(IoKeys::new(rx))
+//!     .with_output(IoOutput::new(tx));
+//! loop {
+//!     if let Some(ShellEvent::Line(line)) = parser.poll() {
+//!         let _ = commands::dispatch(&line);
+//!     }
+//! }
+//! ```
+//!
+//! A read returning no bytes ends the input, and each `\n` of the output is sent as `\r\n`, as
+//! a terminal in raw mode on the other end does not translate it.
+
+use core::fmt;
+use embedded_io::{Read, ReadReady, Write};
+
+use crate::input::key_reader::{Key, KeyDecoder, KeyReader, ReadError};
+use crate::output::{Crlf, Output};
+
+/// Key source decoding the bytes of an `embedded_io` reader with a [`KeyDecoder`].
+///
+/// The reader must tell whether bytes are waiting ([`ReadReady`]), so that
+/// [`poll_key`](KeyReader::poll_key) (and the `poll` of the parser) never blocks.
+///
+pub struct IoKeys<R> {
+    inner: R,
+    decoder: KeyDecoder,
+}
+
+impl<R: Read + ReadReady> IoKeys<R> {
+    /// Creates a key source reading from `inner`.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            decoder: KeyDecoder::new(),
+        }
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Reads one byte, blocking until it arrives.
+    fn read_byte(&mut self) -> Result<u8, IoKeysError<R::Error>> {
+        let mut byte = [0u8];
+        match self.inner.read(&mut byte) {
+            Ok(0) => Err(IoKeysError::Eof),
+            Ok(_) => Ok(byte[0]),
+            Err(e) => Err(IoKeysError::Read(e)),
+        }
+    }
+}
+
+impl<R: Read + ReadReady> KeyReader for IoKeys<R> {
+    type Error = IoKeysError<R::Error>;
+
+    fn read_key(&mut self) -> Result<Key, Self::Error> {
+        loop {
+            let byte = self.read_byte()?;
+            if let Some(key) = self.decoder.feed(byte) {
+                return Ok(key);
+            }
+        }
+    }
+
+    fn poll_key(&mut self) -> Result<Option<Key>, Self::Error> {
+        while self.inner.read_ready().map_err(IoKeysError::Read)? {
+            let byte = self.read_byte()?;
+            if let Some(key) = self.decoder.feed(byte) {
+                return Ok(Some(key));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Error of [`IoKeys`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoKeysError<E> {
+    /// The reader returned no bytes: no more input will arrive.
+    Eof,
+    /// The reader failed.
+    Read(E),
+}
+
+impl<E> ReadError for IoKeysError<E> {
+    fn is_eof(&self) -> bool {
+        matches!(self, IoKeysError::Eof)
+    }
+}
+
+/// Output to an `embedded_io` writer.
+///
+/// Text is written as it comes, so a line may take several writes; [`flush`](Output::flush)
+/// flushes the writer, which the renderer calls after each update. Each `\n` is sent as `\r\n`
+/// (see [`with_crlf`](Self::with_crlf)). A failed write is reported as `fmt::Error`.
+///
+pub struct IoOutput<W> {
+    inner: W,
+    crlf: Crlf,
+}
+
+impl<W: Write> IoOutput<W> {
+    /// Creates an output writing to `inner`, translating `\n` to `\r\n`.
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            crlf: Crlf::new(true),
+        }
+    }
+
+    /// Selects whether `\n` is sent as `\r\n` (enabled by default).
+    pub fn with_crlf(mut self, enabled: bool) -> Self {
+        self.crlf.set_enabled(enabled);
+        self
+    }
+
+    /// Returns the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+}
+
+impl<W: Write> fmt::Write for IoOutput<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let inner = &mut self.inner;
+        self.crlf
+            .write(s, |bytes| inner.write_all(bytes).map_err(|_| fmt::Error))
+    }
+}
+
+impl<W: Write> Output for IoOutput<W> {
+    fn flush(&mut self) {
+        let _ = self.inner.flush();
+    }
+}
+
+// ==================== TESTS =======================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::convert::Infallible;
+    use core::fmt::Write as _;
+    use heapless::Vec;
+
+    /// A writer keeping what it is sent.
+    #[derive(Default)]
+    struct Sent(Vec<u8, 128>);
+
+    impl embedded_io::ErrorType for Sent {
+        type Error = Infallible;
+    }
+
+    impl Write for Sent {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> {
+            let len = buf.len().min(self.0.capacity() - self.0.len());
+            let _ = self.0.extend_from_slice(&buf[..len]);
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> Result<(), Infallible> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_io_keys() {
+        let mut keys = IoKeys::new(&b"x\x1B[B\xC3\xA9"[..]);
+        assert!(matches!(keys.read_key(), Ok(Key::Char('x'))));
+        assert!(matches!(keys.poll_key(), Ok(Some(Key::ArrowDown))));
+        assert!(matches!(keys.read_key(), Ok(Key::Char('é'))));
+        assert!(keys.read_key().unwrap_err().is_eof());
+    }
+
+    #[test]
+    fn test_io_keys_poll_at_end() {
+        let mut keys = IoKeys::new(&b""[..]);
+        // An empty slice is ready: it is at its end
+        assert!(matches!(keys.poll_key(), Err(IoKeysError::Eof)));
+    }
+
+    #[test]
+    fn test_parser_over_embedded_io() {
+        use crate::input::parser::{InputParser, ShellEvent};
+
+        let mut parser = InputParser::<4, 16, 32, 4, 32>::new(&[("ls", "")], "", "", "> ")
+            .with_reader(IoKeys::new(&b"ls\r"[..]))
+            .with_output(IoOutput::new(Sent::default()));
+        assert_eq!(
+            parser.read_line(),
+            ShellEvent::Line("ls".try_into().unwrap())
+        );
+        assert_eq!(parser.read_line(), ShellEvent::Exit);
+        let sent = parser.output().get_mut().0.as_slice();
+        assert!(sent.windows(4).any(|w| w == b"> ls"));
+        // The line ends with `\r\n`, not a bare `\n`
+        assert!(sent.windows(3).any(|w| w == b"G\r\n"));
+    }
+
+    #[test]
+    fn test_io_output_crlf() {
+        let mut out = IoOutput::new(Sent::default());
+        let _ = write!(out, "a\nb\r\nc\r");
+        let _ = write!(out, "\nd");
+        out.flush();
+        assert_eq!(out.get_mut().0.as_slice(), b"a\r\nb\r\nc\r\nd");
+
+        let mut out = IoOutput::new(Sent::default()).with_crlf(false);
+        let _ = write!(out, "a\nb");
+        assert_eq!(out.get_mut().0.as_slice(), b"a\nb");
+    }
+}
@@ -0,0 +1,240 @@
+//! Interrupt glue: a lock-free queue between a receive interrupt and the line editor.
+//!
+//! A UART interrupt cannot wait for the shell, and the shell must not run in the interrupt.
+//! [`IsrQueue`] is a single-producer, single-consumer ring split into an [`IsrProducer`], owned
+//! by the interrupt handler, and an [`IsrConsumer`], owned by the main loop. Neither side takes
+//! a lock or disables interrupts:
+//!
+//! ```rust,ignore
+//! static mut RX: IsrQueue<64> = IsrQueue::new();
+//! static PRODUCER: Mutex<RefCell<Option<IsrProducer<'static>>>> = ...;
+//!
+//! #[interrupt]
+//! fn USART1() {
+//!     let byte = uart.read_data_register();
+//!     PRODUCER.lock(|p| p.borrow_mut().as_mut().map(|p| p.push(byte)));
+//! }
+//!
+//! // main loop
+//! let (producer, mut consumer) = unsafe { RX.split() };
+//! PRODUCER.lock(|p| *p.borrow_mut() = Some(producer));
+//! loop {
+//!     while let Some(event) = consumer.poll(&mut editor, &mut uart_tx) {
+//!         if let EditorEvent::Line(line) = event {
+//!             run(&line, &mut uart_tx);
+//!         }
+//!     }
+//!     if consumer.stats().dropped > reported { ... }
+//! }
+//! ```
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use heapless::spsc::{Consumer, Producer, Queue};
+
+use crate::input::editor::{EditorEvent, LineEditor};
+use crate::output::Output;
+
+/// The received bytes, between an interrupt handler and the main loop.
+///
+/// # Type Parameters
+/// - `N`: Size of the ring; it holds up to `N - 1` bytes, and a power of two is fastest.
+///
+pub struct IsrQueue<const N: usize> {
+    queue: Queue<u8, N>,
+    counters: Counters,
+}
+
+/// Counters written by the producer only, so a plain load and store updates them even on cores
+/// without atomic read-modify-write instructions.
+struct Counters {
+    received: AtomicUsize,
+    dropped: AtomicUsize,
+}
+
+impl Counters {
+    fn bump(counter: &AtomicUsize) {
+        counter.store(
+            counter.load(Ordering::Relaxed).wrapping_add(1),
+            Ordering::Relaxed,
+        );
+    }
+}
+
+/// A snapshot of the receive statistics of an [`IsrQueue`].
+///
+/// Both counters wrap around on overflow.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IsrStats {
+    /// Bytes pushed by the interrupt, including the dropped ones.
+    pub received: usize,
+    /// Bytes lost because the queue was full.
+    pub dropped: usize,
+}
+
+impl<const N: usize> Default for IsrQueue<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> IsrQueue<N> {
+    /// Creates an empty queue, usable as the initializer of a `static`.
+    pub const fn new() -> Self {
+        Self {
+            queue: Queue::new(),
+            counters: Counters {
+                received: AtomicUsize::new(0),
+                dropped: AtomicUsize::new(0),
+            },
+        }
+    }
+
+    /// Splits the queue into the end given to the interrupt handler and the end kept by the
+    /// main loop.
+    ///
+    pub const fn split(&mut self) -> (IsrProducer<'_>, IsrConsumer<'_>) {
+        let (producer, consumer) = self.queue.split_const();
+        (
+            IsrProducer {
+                producer,
+                counters: &self.counters,
+            },
+            IsrConsumer {
+                consumer,
+                counters: &self.counters,
+                after_cr: false,
+            },
+        )
+    }
+}
+
+/// The interrupt side of an [`IsrQueue`].
+///
+pub struct IsrProducer<'a> {
+    producer: Producer<'a, u8>,
+    counters: &'a Counters,
+}
+
+impl IsrProducer<'_> {
+    /// Queues a received byte, returning `false` if the queue was full and the byte was dropped.
+    ///
+    pub fn push(&mut self, byte: u8) -> bool {
+        Counters::bump(&self.counters.received);
+        if self.producer.enqueue(byte).is_err() {
+            Counters::bump(&self.counters.dropped);
+            return false;
+        }
+        true
+    }
+
+    /// Queues the bytes of a receive FIFO or DMA chunk, returning how many of them fitted.
+    ///
+    pub fn push_slice(&mut self, bytes: &[u8]) -> usize {
+        bytes.iter().filter(|&&b| self.push(b)).count()
+    }
+}
+
+/// The main loop side of an [`IsrQueue`].
+///
+pub struct IsrConsumer<'a> {
+    consumer: Consumer<'a, u8>,
+    counters: &'a Counters,
+    after_cr: bool,
+}
+
+impl IsrConsumer<'_> {
+    /// Feeds the queued bytes to `editor` until a line is finished or the queue is empty.
+    ///
+    /// Returns `None` once every queued byte was processed, otherwise the `Line` (or `Eof`) it
+    /// ended with. The `LF` of a `CR LF` sent by the terminal is dropped, so it does not end a
+    /// second, empty line.
+    ///
+    pub fn poll<const IML: usize>(
+        &mut self,
+        editor: &mut LineEditor<IML>,
+        out: &mut impl Output,
+    ) -> Option<EditorEvent<IML>> {
+        while let Some(b) = self.consumer.dequeue() {
+            if core::mem::replace(&mut self.after_cr, b == b'\r') && b == b'\n' {
+                continue;
+            }
+            match editor.feed(b, out) {
+                EditorEvent::Pending => {}
+                event => return Some(event),
+            }
+        }
+        None
+    }
+
+    /// Returns the number of bytes waiting to be processed.
+    pub fn len(&self) -> usize {
+        self.consumer.len()
+    }
+
+    /// Returns `true` if no byte is waiting to be processed.
+    pub fn is_empty(&self) -> bool {
+        self.consumer.is_empty()
+    }
+
+    /// Returns the receive statistics, to report bytes lost to a slow main loop.
+    pub fn stats(&self) -> IsrStats {
+        IsrStats {
+            received: self.counters.received.load(Ordering::Relaxed),
+            dropped: self.counters.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+// ==================== TESTS =======================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Echo = heapless::String<64>;
+
+    #[test]
+    fn test_pushed_bytes_form_a_line() {
+        let mut queue = IsrQueue::<16>::new();
+        let (mut producer, mut consumer) = queue.split();
+        let mut editor = LineEditor::<32>::new("> ");
+        assert_eq!(producer.push_slice(b"le"), 2);
+        assert_eq!(consumer.poll(&mut editor, &mut Echo::new()), None);
+        producer.push_slice(b"d on\r\n");
+        let expected = heapless::String::try_from("led on").unwrap();
+        assert_eq!(
+            consumer.poll(&mut editor, &mut Echo::new()),
+            Some(EditorEvent::Line(expected))
+        );
+        assert_eq!(consumer.poll(&mut editor, &mut Echo::new()), None);
+        assert!(consumer.is_empty());
+    }
+
+    #[test]
+    fn test_overflow_is_counted() {
+        let mut queue = IsrQueue::<4>::new();
+        let (mut producer, consumer) = queue.split();
+        assert_eq!(producer.push_slice(b"abcde"), 3);
+        assert!(!producer.push(b'f'));
+        assert_eq!(consumer.len(), 3);
+        assert_eq!(
+            consumer.stats(),
+            IsrStats {
+                received: 6,
+                dropped: 3
+            }
+        );
+    }
+
+    #[test]
+    fn test_draining_makes_room() {
+        let mut queue = IsrQueue::<4>::new();
+        let (mut producer, mut consumer) = queue.split();
+        let mut editor = LineEditor::<32>::new("> ");
+        producer.push_slice(b"abc");
+        assert_eq!(consumer.poll(&mut editor, &mut Echo::new()), None);
+        assert!(producer.push(b'd'));
+        assert_eq!(consumer.stats().dropped, 0);
+    }
+}
@@ -0,0 +1,31 @@
+//! Transports running the shell over a byte stream instead of the local terminal.
+//!
+//! The host transports (`transport` feature) provide a key source for
+//! `InputParser::with_reader` and an [`Output`] for `with_output`, so the same command table is
+//! reachable over another link:
+//! - [`telnet`]: a TCP listener, with minimal telnet option negotiation.
+//! - [`serial`]: a host serial port, with line ending normalization.
+//! - [`websocket`]: a browser terminal, served over HTTP and a WebSocket.
+//!
+//! Device-side glue drives the byte-fed `LineEditor`, also without the `std` feature:
+//! - [`usb_cdc`]: the packets of a USB CDC-ACM virtual serial port.
+//! - [`isr`]: the bytes a UART receive interrupt pushes into a lock-free queue.
+//! - [`embedded_io`] (`embedded-io` feature): the `Read` / `Write` halves of a HAL driver,
+//!   as the key source and output of an `InputParser`.
+//! - [`rtt`] (`rtt` feature): the SEGGER RTT channels of a debug probe, as the key source and
+//!   output of an `InputParser`.
+//!
+//! [`Output`]: crate::output::Output
+
+#[cfg(feature = "embedded-io")]
+pub mod embedded_io;
+pub mod isr;
+#[cfg(feature = "rtt")]
+pub mod rtt;
+#[cfg(feature = "transport")]
+pub mod serial;
+#[cfg(feature = "transport")]
+pub mod telnet;
+pub mod usb_cdc;
+#[cfg(feature = "transport")]
+pub mod websocket;
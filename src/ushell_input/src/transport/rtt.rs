@@ -0,0 +1,183 @@
+//! RTT transport: the shell over the SEGGER RTT channels of a debug probe.
+//!
+//! Firmware running under a probe (probe-rs, J-Link) gets the full `InputParser` (history,
+//! autocomplete, hints) without a UART: [`RttKeys`] decodes the keys the host writes to a down
+//! channel, and [`RttOutput`] writes the output to an up channel of `rtt-target`.
+//!
+//! ```rust,ignore
+//! let channels = rtt_init! {
+//!     up: { 0: { size: 1024, mode: ChannelMode::BlockIfFull, name: "Terminal" } }
+//!     down: { 0: { size: 16, name: "Terminal" } }
+//! };
+//! let mut parser = InputParser::<16, 32, 128, 16, 128>::new(commands, datatypes, shortcuts, "> ")
+//!     .with_reader(RttKeys::new(channels.down.0))
+//!     .with_output(RttOutput::new(channels.up.0));
+//! loop {
+//!     if let Some(ShellEvent::Line(line)) = parser.poll() {
+//!         let _ = commands::dispatch(&line);
+//!     }
+//! }
+//! ```
+//!
+//! The input never ends: [`read_key`](KeyReader::read_key) spins until the host sends a key. What
+//! a full up channel does with the output depends on its mode: `BlockIfFull` waits for the probe,
+//! the default `NoBlockSkip` drops the text, so a session without a probe does not hang.
+
+use core::convert::Infallible;
+use core::fmt;
+use rtt_target::{DownChannel, UpChannel};
+
+use crate::input::key_reader::{Key, KeyDecoder, KeyReader};
+use crate::output::{Crlf, Output};
+
+/// Key source decoding the bytes of an RTT down channel with a [`KeyDecoder`].
+///
+pub struct RttKeys {
+    channel: DownChannel,
+    decoder: KeyDecoder,
+}
+
+impl RttKeys {
+    /// Creates a key source reading from `channel`.
+    pub fn new(channel: DownChannel) -> Self {
+        Self {
+            channel,
+            decoder: KeyDecoder::new(),
+        }
+    }
+
+    /// Returns the down channel.
+    pub fn into_inner(self) -> DownChannel {
+        self.channel
+    }
+}
+
+impl KeyReader for RttKeys {
+    type Error = Infallible;
+
+    fn read_key(&mut self) -> Result<Key, Infallible> {
+        loop {
+            if let Some(key) = self.poll_key()? {
+                return Ok(key);
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    fn poll_key(&mut self) -> Result<Option<Key>, Infallible> {
+        let mut byte = [0u8];
+        while self.channel.read(&mut byte) == 1 {
+            if let Some(key) = self.decoder.feed(byte[0]) {
+                return Ok(Some(key));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Output to an RTT up channel.
+///
+/// Text goes to the channel buffer as it comes, where the probe picks it up, so
+/// [`flush`](Output::flush) has nothing to do (it does not wait for the probe, which may never
+/// come). Each `\n` is sent as `\r\n` (see [`with_crlf`](Self::with_crlf)).
+///
+pub struct RttOutput {
+    channel: UpChannel,
+    crlf: Crlf,
+}
+
+impl RttOutput {
+    /// Creates an output writing to `channel`, translating `\n` to `\r\n`.
+    pub fn new(channel: UpChannel) -> Self {
+        Self {
+            channel,
+            crlf: Crlf::new(true),
+        }
+    }
+
+    /// Selects whether `\n` is sent as `\r\n` (enabled by default).
+    pub fn with_crlf(mut self, enabled: bool) -> Self {
+        self.crlf.set_enabled(enabled);
+        self
+    }
+
+    /// Returns the up channel, e.g. to change its mode.
+    pub fn get_mut(&mut self) -> &mut UpChannel {
+        &mut self.channel
+    }
+}
+
+impl fmt::Write for RttOutput {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let channel = &mut self.channel;
+        self.crlf.write(s, |bytes| {
+            channel.write(bytes);
+            Ok(())
+        })
+    }
+}
+
+impl Output for RttOutput {}
+
+// ==================== TESTS =======================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::fmt::Write as _;
+    use rtt_target::ChannelMode;
+    use rtt_target::rtt::RttChannel;
+
+    /// Runs `f` with both ends of one channel: what the up end writes, the down end reads.
+    fn loopback<R>(f: impl FnOnce(UpChannel, DownChannel) -> R) -> R {
+        let mut buffer = [0u8; 64];
+        // SAFETY: a zeroed channel is valid, and it is initialized with a buffer outliving both
+        // ends, which only run on this thread.
+        unsafe {
+            let mut channel: RttChannel = core::mem::zeroed();
+            channel.init(
+                c"loop".as_ptr().cast(),
+                ChannelMode::NoBlockTrim,
+                &mut buffer,
+            );
+            let channel: *mut RttChannel = &mut channel;
+            f(UpChannel::new(channel), DownChannel::new(channel))
+        }
+    }
+
+    fn read_all(channel: &mut DownChannel) -> heapless::Vec<u8, 64> {
+        let mut buf = [0u8; 64];
+        let len = channel.read(&mut buf);
+        heapless::Vec::from_slice(&buf[..len]).unwrap()
+    }
+
+    #[test]
+    fn test_rtt_keys() {
+        loopback(|mut host, down| {
+            let mut keys = RttKeys::new(down);
+            assert!(matches!(keys.poll_key(), Ok(None)));
+            host.write(b"x\x1B[B\xC3");
+            assert!(matches!(keys.read_key(), Ok(Key::Char('x'))));
+            assert!(matches!(keys.poll_key(), Ok(Some(Key::ArrowDown))));
+            // Half a character: nothing yet
+            assert!(matches!(keys.poll_key(), Ok(None)));
+            host.write(b"\xA9");
+            assert!(matches!(keys.poll_key(), Ok(Some(Key::Char('é')))));
+        });
+    }
+
+    #[test]
+    fn test_rtt_output_crlf() {
+        loopback(|up, mut host| {
+            let mut out = RttOutput::new(up);
+            let _ = write!(out, "a\nb\r\nc\r");
+            let _ = write!(out, "\nd");
+            out.flush();
+            assert_eq!(read_all(&mut host).as_slice(), b"a\r\nb\r\nc\r\nd");
+
+            let mut out = out.with_crlf(false);
+            let _ = write!(out, "a\nb");
+            assert_eq!(read_all(&mut host).as_slice(), b"a\nb");
+        });
+    }
+}
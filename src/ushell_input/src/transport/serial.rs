@@ -0,0 +1,221 @@
+//! Serial transport: the shell over a host serial port.
+//!
+//! [`SerialPort`] wraps the `Read + Write` pair of a port (from [`SerialPort::open`] on unix, or
+//! from any serial crate) and splits it into the key source and output of the shell:
+//!
+//! ```rust,ignore
+//! let (keys, out) = SerialPort::open("/dev/ttyUSB0", 115200)?
+//!     .with_local_echo(true)
+//!     .split();
+//! shell().with_output(out).with_reader(keys).run();
+//! ```
+//!
+//! The line endings are normalized both ways: `CR`, `LF` and `CR LF` each end one line on input,
+//! and each `\n` is sent as `\r\n`.
+
+use std::io::{self, Read, Write};
+
+use crate::input::key_reader::StreamReader;
+use crate::output::StreamOutput;
+
+/// Key source of a serial port.
+pub type SerialKeys<R> = StreamReader<SerialReader<R>>;
+
+/// A serial port about to carry the shell; see [`split`](Self::split).
+///
+pub struct SerialPort<R, W> {
+    reader: R,
+    writer: W,
+    local_echo: bool,
+}
+
+impl<R: Read, W: Write> SerialPort<R, W> {
+    /// Creates a port reading the keys from `reader` and writing the output to `writer`
+    /// (usually two handles of the same device).
+    pub fn new(reader: R, writer: W) -> Self {
+        Self {
+            reader,
+            writer,
+            local_echo: false,
+        }
+    }
+
+    /// Selects whether everything sent to the port is also shown on the local stdout (disabled
+    /// by default), so the session can be followed on the host console.
+    pub fn with_local_echo(mut self, enabled: bool) -> Self {
+        self.local_echo = enabled;
+        self
+    }
+
+    /// Returns the key source and the output of the port.
+    pub fn split(self) -> (SerialKeys<R>, StreamOutput<SerialWriter<W>>) {
+        (
+            StreamReader::new(SerialReader::new(self.reader)),
+            StreamOutput::new(SerialWriter {
+                inner: self.writer,
+                local_echo: self.local_echo,
+            }),
+        )
+    }
+}
+
+#[cfg(unix)]
+impl SerialPort<std::fs::File, std::fs::File> {
+    /// Opens the serial device at `path` (e.g. `/dev/ttyUSB0`) at `baud` bits per second,
+    /// 8N1, in raw mode.
+    ///
+    /// Supported rates: 1200, 2400, 4800, 9600, 19200, 38400, 57600, 115200 and 230400.
+    ///
+    pub fn open(path: &str, baud: u32) -> io::Result<Self> {
+        use std::os::unix::io::AsRawFd;
+        use termios::*;
+
+        let speed = match baud {
+            1200 => libc::B1200,
+            2400 => libc::B2400,
+            4800 => libc::B4800,
+            9600 => libc::B9600,
+            19200 => libc::B19200,
+            38400 => libc::B38400,
+            57600 => libc::B57600,
+            115200 => libc::B115200,
+            230400 => libc::B230400,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "unsupported baud rate",
+                ));
+            }
+        };
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)?;
+        let fd = file.as_raw_fd();
+        let mut settings = Termios::from_fd(fd)?;
+        cfmakeraw(&mut settings);
+        settings.c_cflag |= CREAD | CLOCAL;
+        settings.c_cflag &= !(CSTOPB | PARENB);
+        settings.c_cc[VMIN] = 1;
+        settings.c_cc[VTIME] = 0;
+        cfsetspeed(&mut settings, speed as speed_t)?;
+        tcsetattr(fd, TCSANOW, &settings)?;
+        let writer = file.try_clone()?;
+        Ok(Self::new(file, writer))
+    }
+}
+
+/// Bytes received from a serial port, with each line ending reduced to a single `CR`: a `LF`
+/// following a `CR` is dropped, a lone `LF` becomes a `CR`.
+///
+pub struct SerialReader<R> {
+    inner: R,
+    after_cr: bool,
+}
+
+impl<R: Read> SerialReader<R> {
+    /// Creates a reader normalizing the line endings of `inner`.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            after_cr: false,
+        }
+    }
+
+    /// Returns the underlying byte stream.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for SerialReader<R> {
+    /// Reads at least one byte, unless the port reached its end (`Ok(0)`).
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let got = self.inner.read(out)?;
+            if got == 0 {
+                return Ok(0);
+            }
+            let mut len = 0;
+            for i in 0..got {
+                let b = out[i];
+                let after_cr = core::mem::replace(&mut self.after_cr, b == b'\r');
+                match b {
+                    b'\n' if after_cr => continue,
+                    b'\n' => out[len] = b'\r',
+                    _ => out[len] = b,
+                }
+                len += 1;
+            }
+            if len > 0 {
+                return Ok(len);
+            }
+        }
+    }
+}
+
+/// Output side of a [`SerialPort`], optionally showing what it sends on the local stdout.
+///
+pub struct SerialWriter<W> {
+    inner: W,
+    local_echo: bool,
+}
+
+impl<W: Write> Write for SerialWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        if self.local_echo {
+            let _ = io::stdout().write_all(&buf[..len]);
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.local_echo {
+            let _ = io::stdout().flush();
+        }
+        self.inner.flush()
+    }
+}
+
+// ==================== TESTS =======================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::key_reader::{Key, KeyReader};
+    use crate::output::Output;
+    use core::fmt::Write as _;
+
+    fn normalized(input: &[u8]) -> std::vec::Vec<u8> {
+        let mut data = std::vec::Vec::new();
+        SerialReader::new(input).read_to_end(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_line_endings_become_a_single_cr() {
+        assert_eq!(normalized(b"a\r\nb\nc\rd"), b"a\rb\rc\rd");
+        assert_eq!(normalized(b"\r\r\n\n"), b"\r\r\r");
+    }
+
+    #[test]
+    fn test_split_reads_keys_and_writes_crlf() {
+        let (mut keys, mut out) = SerialPort::new(&b"ls\r\n"[..], std::vec::Vec::new()).split();
+        assert!(matches!(keys.read_key(), Ok(Key::Char('l'))));
+        assert!(matches!(keys.read_key(), Ok(Key::Char('s'))));
+        assert!(matches!(keys.read_key(), Ok(Key::Enter)));
+        assert!(keys.read_key().is_err()); // the LF of CR LF is no second Enter
+
+        let _ = writeln!(out, "ok");
+        out.flush();
+        assert_eq!(out.get_mut().inner, b"ok\r\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_open_rejects_unsupported_baud_rate() {
+        let err = SerialPort::open("/dev/null", 12345).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,231 @@
+//! Telnet transport: the shell over a TCP connection.
+//!
+//! [`accept`] waits for a client on a `TcpListener`, asks it for character mode (the server
+//! echoes, no line buffering on the client) and returns the key source and output of the
+//! connection:
+//!
+//! ```rust,ignore
+//! let listener = TcpListener::bind("0.0.0.0:2323")?;
+//! loop {
+//!     let (reader, out) = telnet::accept(&listener)?;
+//!     let mut parser = InputParser::<NC, FNL, IML, HTC, HME>::new(cmds, types, shortcuts, "> ")
+//!         .with_reader(reader)
+//!         .with_output(out);
+//!     while parser.parse_input(|line| run(line)) {}
+//! }
+//! ```
+//!
+//! The negotiation is minimal: the options sent by the client are consumed and never answered,
+//! which common clients (`telnet`, PuTTY, `nc`) accept.
+
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use crate::input::key_reader::StreamReader;
+use crate::output::StreamOutput;
+
+/// Interpret As Command: starts a telnet command; doubled for a literal `0xFF` data byte.
+pub const IAC: u8 = 255;
+/// Refuses to perform an option.
+pub const DONT: u8 = 254;
+/// Asks the peer to perform an option.
+pub const DO: u8 = 253;
+/// Refuses to perform an option.
+pub const WONT: u8 = 252;
+/// Offers to perform an option.
+pub const WILL: u8 = 251;
+/// Starts an option subnegotiation, ended by `IAC SE`.
+pub const SB: u8 = 250;
+/// Ends an option subnegotiation.
+pub const SE: u8 = 240;
+/// Option: the server echoes the input.
+pub const OPT_ECHO: u8 = 1;
+/// Option: no go-ahead signal, i.e. full-duplex character mode.
+pub const OPT_SUPPRESS_GO_AHEAD: u8 = 3;
+/// Option: the client edits lines locally.
+pub const OPT_LINEMODE: u8 = 34;
+
+/// Commands sent to a new client: the server echoes and goes ahead, the client sends each key
+/// as typed instead of whole lines.
+pub const NEGOTIATION: [u8; 9] = [
+    IAC,
+    WILL,
+    OPT_ECHO,
+    IAC,
+    WILL,
+    OPT_SUPPRESS_GO_AHEAD,
+    IAC,
+    DONT,
+    OPT_LINEMODE,
+];
+
+/// Key source of a telnet connection.
+pub type TelnetKeys = StreamReader<TelnetReader<TcpStream>>;
+
+/// Waits for a client on `listener`, negotiates character mode and returns the key source and
+/// the output of the connection.
+///
+/// A single client is served at a time: call `accept` again once the shell using the previous
+/// connection returned.
+///
+pub fn accept(listener: &TcpListener) -> io::Result<(TelnetKeys, StreamOutput<TcpStream>)> {
+    let (stream, _) = listener.accept()?;
+    stream.set_nodelay(true)?;
+    let mut writer = stream.try_clone()?;
+    writer.write_all(&NEGOTIATION)?;
+    Ok((
+        StreamReader::new(TelnetReader::new(stream)),
+        StreamOutput::new(writer),
+    ))
+}
+
+/// Progress of a [`TelnetReader`] through the received bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TelnetState {
+    Data,
+    /// After a `CR`: a following `LF` or `NUL` belongs to the same `Enter`.
+    Cr,
+    Iac,
+    /// After `IAC WILL|WONT|DO|DONT`, waiting for the option.
+    Option,
+    Sub,
+    SubIac,
+}
+
+/// Byte stream of a telnet connection with the protocol removed: options and subnegotiations
+/// are dropped, `IAC IAC` gives a `0xFF` byte and the `CR LF` / `CR NUL` of `Enter` a single
+/// `CR`.
+///
+pub struct TelnetReader<R> {
+    inner: R,
+    state: TelnetState,
+    buf: [u8; 64],
+}
+
+impl<R: Read> TelnetReader<R> {
+    /// Creates a reader filtering the bytes of `inner`.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            state: TelnetState::Data,
+            buf: [0; 64],
+        }
+    }
+
+    /// Returns the underlying byte stream.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Returns the data byte carried by `b`, if any, and advances the protocol state.
+    fn filter(&mut self, b: u8) -> Option<u8> {
+        let (next, data) = match (self.state, b) {
+            (TelnetState::Data | TelnetState::Cr, IAC) => (TelnetState::Iac, None),
+            (TelnetState::Cr, b'\n' | 0) => (TelnetState::Data, None),
+            (TelnetState::Data | TelnetState::Cr, b'\r') => (TelnetState::Cr, Some(b)),
+            (TelnetState::Data | TelnetState::Cr, _) => (TelnetState::Data, Some(b)),
+            (TelnetState::Iac, IAC) => (TelnetState::Data, Some(IAC)),
+            (TelnetState::Iac, WILL | WONT | DO | DONT) => (TelnetState::Option, None),
+            (TelnetState::Iac, SB) => (TelnetState::Sub, None),
+            (TelnetState::Iac, _) | (TelnetState::Option, _) => (TelnetState::Data, None),
+            (TelnetState::Sub, IAC) => (TelnetState::SubIac, None),
+            (TelnetState::Sub, _) => (TelnetState::Sub, None),
+            (TelnetState::SubIac, SE) => (TelnetState::Data, None),
+            (TelnetState::SubIac, _) => (TelnetState::Sub, None),
+        };
+        self.state = next;
+        data
+    }
+}
+
+impl<R: Read> Read for TelnetReader<R> {
+    /// Reads at least one data byte, unless the connection was closed (`Ok(0)`).
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if out.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let want = out.len().min(self.buf.len());
+            let got = self.inner.read(&mut self.buf[..want])?;
+            if got == 0 {
+                return Ok(0);
+            }
+            let mut len = 0;
+            for i in 0..got {
+                if let Some(b) = self.filter(self.buf[i]) {
+                    out[len] = b;
+                    len += 1;
+                }
+            }
+            if len > 0 {
+                return Ok(len);
+            }
+        }
+    }
+}
+
+// ==================== TESTS =======================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filtered(input: &[u8]) -> std::vec::Vec<u8> {
+        let mut reader = TelnetReader::new(input);
+        let mut data = std::vec::Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_plain_bytes_pass_through() {
+        assert_eq!(filtered(b"led true"), b"led true");
+    }
+
+    #[test]
+    fn test_options_are_dropped() {
+        let input = [b'a', IAC, DO, OPT_ECHO, b'b', IAC, WONT, OPT_LINEMODE, b'c'];
+        assert_eq!(filtered(&input), b"abc");
+    }
+
+    #[test]
+    fn test_subnegotiation_is_dropped() {
+        let input = [b'a', IAC, SB, 24, 0, b'x', b't', IAC, SE, b'b'];
+        assert_eq!(filtered(&input), b"ab");
+    }
+
+    #[test]
+    fn test_escaped_iac_is_data() {
+        assert_eq!(filtered(&[IAC, IAC, b'a']), [IAC, b'a']);
+    }
+
+    #[test]
+    fn test_enter_is_a_single_cr() {
+        assert_eq!(filtered(b"a\r\nb\r\0c\rd"), b"a\rb\rc\rd");
+    }
+
+    #[test]
+    fn test_accept_negotiates_character_mode() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (mut keys, mut out) = accept(&listener).unwrap();
+
+        let mut negotiation = [0; NEGOTIATION.len()];
+        client.read_exact(&mut negotiation).unwrap();
+        assert_eq!(negotiation, NEGOTIATION);
+
+        use crate::input::key_reader::{Key, KeyReader};
+        client
+            .write_all(&[IAC, DO, OPT_ECHO, b'x', b'\r', b'\n'])
+            .unwrap();
+        assert!(matches!(keys.read_key(), Ok(Key::Char('x'))));
+        assert!(matches!(keys.read_key(), Ok(Key::Enter)));
+
+        use core::fmt::Write as _;
+        let _ = writeln!(out, "ok");
+        crate::output::Output::flush(&mut out);
+        let mut reply = [0; 4];
+        client.read_exact(&mut reply).unwrap();
+        assert_eq!(&reply, b"ok\r\n");
+    }
+}
@@ -0,0 +1,270 @@
+//! USB CDC-ACM glue: the line editor over the bulk endpoints of a virtual serial port.
+//!
+//! USB moves data in packets, driven by the device stack: the OUT endpoint hands over what the
+//! host sent whenever a packet arrives, and the IN endpoint can only send a packet when the
+//! host polls for one. [`CdcShell`] decouples both from the shell with two fixed queues:
+//!
+//! ```rust,ignore
+//! static SHELL: Mutex<RefCell<CdcShell<128, 256, 1024>>> = ...;
+//!
+//! fn on_out_packet(data: &[u8]) { SHELL.lock(|s| s.borrow_mut().receive(data)); }
+//! fn on_in_ready(ep: &mut InEndpoint) {
+//!     let mut packet = [0; 64];
+//!     if let Some(len) = SHELL.lock(|s| s.borrow_mut().next_packet(&mut packet)) {
+//!         ep.write(&packet[..len]);
+//!     }
+//! }
+//!
+//! // main loop
+//! while let Some(event) = SHELL.lock(|s| s.borrow_mut().poll()) {
+//!     if let EditorEvent::Line(line) = event {
+//!         SHELL.lock(|s| run(&line, s.borrow_mut().output()));
+//!     }
+//! }
+//! ```
+
+use core::fmt;
+use heapless::Deque;
+
+use crate::input::editor::{EditorEvent, LineEditor};
+use crate::output::Output;
+
+/// The [`LineEditor`] fed from a USB CDC OUT endpoint and writing to an IN endpoint.
+///
+/// # Type Parameters
+/// - `IML`: Maximum input length, in UTF-8 bytes.
+/// - `RX`: Bytes received from the host and not processed yet.
+/// - `TX`: Bytes of output waiting for the host to fetch them.
+///
+pub struct CdcShell<const IML: usize, const RX: usize, const TX: usize> {
+    editor: LineEditor<IML>,
+    rx: Deque<u8, RX>,
+    tx: PacketQueue<TX>,
+    after_cr: bool,
+}
+
+impl<const IML: usize, const RX: usize, const TX: usize> CdcShell<IML, RX, TX> {
+    /// Creates the glue around `editor`; call [`start`](Self::start) once the host opened the
+    /// port to show the prompt.
+    ///
+    pub fn new(editor: LineEditor<IML>) -> Self {
+        Self {
+            editor,
+            rx: Deque::new(),
+            tx: PacketQueue::new(),
+            after_cr: false,
+        }
+    }
+
+    /// Queues the prompt and the current line, e.g. once the host set DTR.
+    ///
+    pub fn start(&mut self) {
+        self.editor.start(&mut self.tx);
+    }
+
+    /// Queues the bytes of a packet received on the OUT endpoint, typically from the USB
+    /// interrupt.
+    ///
+    /// Returns the number of bytes accepted: the rest did not fit in the `RX` queue, and a
+    /// stack able to NAK the endpoint should keep it until [`poll`](Self::poll) made room.
+    ///
+    pub fn receive(&mut self, packet: &[u8]) -> usize {
+        let free = self.rx.capacity() - self.rx.len();
+        let accepted = packet.len().min(free);
+        for &b in &packet[..accepted] {
+            let _ = self.rx.push_back(b);
+        }
+        accepted
+    }
+
+    /// Feeds the received bytes to the editor until a line is finished or the queue is empty.
+    ///
+    /// Returns `None` once every received byte was processed, otherwise the `Line` (or `Eof`)
+    /// it ended with. The `LF` of a `CR LF` sent by the terminal is dropped, so it does not end a
+    /// second, empty line.
+    ///
+    pub fn poll(&mut self) -> Option<EditorEvent<IML>> {
+        while let Some(b) = self.rx.pop_front() {
+            if core::mem::replace(&mut self.after_cr, b == b'\r') && b == b'\n' {
+                continue;
+            }
+            match self.editor.feed(b, &mut self.tx) {
+                EditorEvent::Pending => {}
+                event => return Some(event),
+            }
+        }
+        None
+    }
+
+    /// Returns the output queue, to print the result of a line before the next prompt.
+    ///
+    pub fn output(&mut self) -> &mut PacketQueue<TX> {
+        &mut self.tx
+    }
+
+    /// Fills `packet` (sized to the max packet size of the IN endpoint) with pending output.
+    ///
+    /// Returns the number of bytes to send, `Some(0)` for the zero-length packet ending a
+    /// transfer made of full packets, or `None` when there is nothing to send.
+    ///
+    pub fn next_packet(&mut self, packet: &mut [u8]) -> Option<usize> {
+        self.tx.fill(packet)
+    }
+}
+
+/// Output waiting to be sent on a USB IN endpoint, one packet at a time.
+///
+/// Each `\n` is queued as `\r\n`. Text not fitting in the `N` bytes is dropped and counted.
+///
+pub struct PacketQueue<const N: usize> {
+    bytes: Deque<u8, N>,
+    dropped: usize,
+    zlp_pending: bool,
+}
+
+impl<const N: usize> Default for PacketQueue<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> PacketQueue<N> {
+    /// Creates an empty queue.
+    pub const fn new() -> Self {
+        Self {
+            bytes: Deque::new(),
+            dropped: 0,
+            zlp_pending: false,
+        }
+    }
+
+    /// Returns the number of bytes waiting to be sent.
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Returns `true` if nothing is waiting to be sent.
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Returns the number of bytes dropped because the queue was full.
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Moves the next bytes into `packet`, see [`CdcShell::next_packet`].
+    pub fn fill(&mut self, packet: &mut [u8]) -> Option<usize> {
+        if self.bytes.is_empty() {
+            return core::mem::take(&mut self.zlp_pending).then_some(0);
+        }
+        let mut len = 0;
+        while len < packet.len() {
+            match self.bytes.pop_front() {
+                Some(b) => {
+                    packet[len] = b;
+                    len += 1;
+                }
+                None => break,
+            }
+        }
+        // A full last packet does not tell the host the transfer is over
+        self.zlp_pending = len == packet.len() && self.bytes.is_empty();
+        Some(len)
+    }
+
+    fn push(&mut self, b: u8) {
+        if self.bytes.push_back(b).is_err() {
+            self.dropped += 1;
+        }
+    }
+}
+
+impl<const N: usize> fmt::Write for PacketQueue<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &b in s.as_bytes() {
+            if b == b'\n' && self.bytes.back() != Some(&b'\r') {
+                self.push(b'\r');
+            }
+            self.push(b);
+        }
+        Ok(())
+    }
+}
+
+impl<const N: usize> Output for PacketQueue<N> {}
+
+// ==================== TESTS =======================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::fmt::Write;
+
+    type TestShell = CdcShell<32, 16, 64>;
+
+    fn drain(shell: &mut TestShell, max_packet: usize) -> std::vec::Vec<std::vec::Vec<u8>> {
+        let mut packets = std::vec::Vec::new();
+        let mut packet = std::vec![0; max_packet];
+        while let Some(len) = shell.next_packet(&mut packet) {
+            packets.push(packet[..len].to_vec());
+        }
+        packets
+    }
+
+    #[test]
+    fn test_received_packets_form_a_line() {
+        let mut shell = TestShell::new(LineEditor::new("> "));
+        assert_eq!(shell.receive(b"le"), 2);
+        assert_eq!(shell.poll(), None);
+        shell.receive(b"d on\r\n");
+        let expected = heapless::String::try_from("led on").unwrap();
+        assert_eq!(shell.poll(), Some(EditorEvent::Line(expected)));
+        assert_eq!(shell.poll(), None); // the LF is no second line
+    }
+
+    #[test]
+    fn test_receive_stops_when_full() {
+        let mut shell = TestShell::new(LineEditor::new("> "));
+        assert_eq!(shell.receive(&[b'a'; 20]), 16);
+        assert_eq!(shell.receive(b"b"), 0);
+        assert_eq!(shell.poll(), None);
+        assert_eq!(shell.receive(b"b"), 1);
+    }
+
+    #[test]
+    fn test_output_is_sent_in_packets() {
+        let mut shell = TestShell::new(LineEditor::new("> "));
+        let _ = write!(shell.output(), "0123456789");
+        let packets = drain(&mut shell, 4);
+        assert_eq!(packets, [&b"0123"[..], b"4567", b"89"]);
+    }
+
+    #[test]
+    fn test_full_last_packet_is_followed_by_zlp() {
+        let mut shell = TestShell::new(LineEditor::new("> "));
+        let _ = write!(shell.output(), "01234567");
+        let packets = drain(&mut shell, 4);
+        assert_eq!(packets, [&b"0123"[..], b"4567", b""]);
+        assert_eq!(shell.next_packet(&mut [0; 4]), None);
+    }
+
+    #[test]
+    fn test_output_newlines_and_overflow() {
+        let mut queue = PacketQueue::<4>::new();
+        let _ = writeln!(queue, "ab");
+        let _ = write!(queue, "c");
+        assert_eq!(queue.len(), 4);
+        assert_eq!(queue.dropped(), 1);
+        let mut packet = [0; 8];
+        assert_eq!(queue.fill(&mut packet), Some(4));
+        assert_eq!(&packet[..4], b"ab\r\n");
+    }
+
+    #[test]
+    fn test_start_queues_the_prompt() {
+        let mut shell = TestShell::new(LineEditor::new("> "));
+        shell.start();
+        assert!(!shell.output().is_empty());
+    }
+}
@@ -0,0 +1,576 @@
+//! WebSocket transport: the shell in a browser terminal.
+//!
+//! [`accept`] answers the HTTP requests arriving on a `TcpListener`: a plain `GET` receives
+//! [`TERMINAL_PAGE`], a terminal emulator page which connects back to the same address, and a
+//! WebSocket upgrade returns the key source and output of the connection:
+//!
+//! ```rust,ignore
+//! let listener = TcpListener::bind("127.0.0.1:8080")?; // then open http://127.0.0.1:8080
+//! loop {
+//!     let (keys, out) = websocket::accept(&listener)?;
+//!     shell().with_output(out).with_reader(keys).run();
+//! }
+//! ```
+//!
+//! The browser sends the keys as text frames (escape sequences as typed on a terminal) and
+//! receives the output as text frames. Output frames carry at most [`MAX_FRAME_LEN`] bytes and
+//! are written with blocking writes, so a slow client holds the shell back instead of having
+//! output pile up in memory.
+
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use crate::input::key_reader::StreamReader;
+use crate::output::StreamOutput;
+
+/// Page served to a browser: an xterm.js terminal connected to the WebSocket of the shell.
+pub const TERMINAL_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>uRustShell</title>
+<link rel="stylesheet" href="https://cdn.jsdelivr.net/npm/xterm@5.3.0/css/xterm.css">
+<script src="https://cdn.jsdelivr.net/npm/xterm@5.3.0/lib/xterm.js"></script>
+<style>body { margin: 0; background: #000; }</style>
+</head>
+<body>
+<div id="terminal"></div>
+<script>
+const term = new Terminal({ cursorBlink: true });
+term.open(document.getElementById("terminal"));
+const ws = new WebSocket("ws://" + location.host + "/");
+ws.onmessage = (event) => term.write(event.data);
+ws.onclose = () => term.write("\r\n[connection closed]\r\n");
+term.onData((keys) => ws.readyState === WebSocket.OPEN && ws.send(keys));
+term.focus();
+</script>
+</body>
+</html>
+"#;
+
+/// Largest payload of an output frame; longer output is sent as several frames.
+pub const MAX_FRAME_LEN: usize = 4096;
+
+/// Largest HTTP request head accepted before the connection is dropped.
+const MAX_REQUEST_LEN: usize = 8192;
+
+/// Largest payload accepted in a received frame (keys and control frames are small).
+const MAX_RECEIVED_FRAME_LEN: u64 = 64 * 1024;
+
+/// GUID appended to the key of the client to compute `Sec-WebSocket-Accept` (RFC 6455).
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+/// Key source of a WebSocket connection.
+pub type WebSocketKeys = StreamReader<WebSocketReader<TcpStream>>;
+
+/// Waits for a WebSocket client on `listener` and returns the key source and output of the
+/// connection.
+///
+/// Plain `GET` requests are answered with [`TERMINAL_PAGE`] (other requests with an error)
+/// and their connection closed while waiting. A single client is served at a time.
+///
+pub fn accept(
+    listener: &TcpListener,
+) -> io::Result<(WebSocketKeys, StreamOutput<WebSocketWriter<TcpStream>>)> {
+    loop {
+        let (mut stream, _) = listener.accept()?;
+        let head = match read_request_head(&mut stream) {
+            Ok(head) => head,
+            Err(_) => continue,
+        };
+        match websocket_key(&head) {
+            Some(key) => {
+                let response = format!(
+                    "HTTP/1.1 101 Switching Protocols\r\n\
+                     Upgrade: websocket\r\n\
+                     Connection: Upgrade\r\n\
+                     Sec-WebSocket-Accept: {}\r\n\r\n",
+                    accept_key(key)
+                );
+                stream.write_all(response.as_bytes())?;
+                stream.set_nodelay(true)?;
+                let writer = stream.try_clone()?;
+                return Ok((
+                    StreamReader::new(WebSocketReader::new(stream)),
+                    StreamOutput::new(WebSocketWriter::new(writer)),
+                ));
+            }
+            None if head.starts_with("GET ") => {
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    TERMINAL_PAGE.len(),
+                    TERMINAL_PAGE
+                );
+            }
+            None => {
+                let _ = stream.write_all(
+                    b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                );
+            }
+        }
+    }
+}
+
+/// Reads an HTTP request up to its empty line.
+fn read_request_head(stream: &mut impl Read) -> io::Result<std::string::String> {
+    let mut head = std::vec::Vec::new();
+    let mut byte = [0u8];
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() >= MAX_REQUEST_LEN || stream.read(&mut byte)? == 0 {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        head.push(byte[0]);
+    }
+    std::string::String::from_utf8(head).map_err(|_| io::ErrorKind::InvalidData.into())
+}
+
+/// Returns the `Sec-WebSocket-Key` of an upgrade request, `None` for other requests.
+fn websocket_key(head: &str) -> Option<&str> {
+    let mut upgrade = false;
+    let mut key = None;
+    for line in head.lines().skip(1) {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("upgrade") && value.eq_ignore_ascii_case("websocket") {
+            upgrade = true;
+        } else if name.eq_ignore_ascii_case("sec-websocket-key") {
+            key = Some(value);
+        }
+    }
+    key.filter(|_| upgrade)
+}
+
+/// Computes the `Sec-WebSocket-Accept` answering `key`.
+fn accept_key(key: &str) -> std::string::String {
+    let mut input = std::string::String::from(key);
+    input.push_str(WEBSOCKET_GUID);
+    base64(&sha1(input.as_bytes()))
+}
+
+/// SHA-1 digest, only used for the handshake.
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks_exact(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0u8; 20];
+    for (bytes, word) in digest.chunks_exact_mut(4).zip(h) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+/// Standard base64 with padding, only used for the handshake.
+fn base64(data: &[u8]) -> std::string::String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = std::string::String::new();
+    for chunk in data.chunks(3) {
+        let bits = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |acc, (i, &b)| acc | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Writes one unmasked frame (server to client).
+fn write_frame(out: &mut impl Write, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut header = std::vec![0x80 | opcode];
+    match payload.len() {
+        len @ 0..=125 => header.push(len as u8),
+        len @ 126..=0xFFFF => {
+            header.push(126);
+            header.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            header.push(127);
+            header.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    out.write_all(&header)?;
+    out.write_all(payload)
+}
+
+/// Byte stream of the data frames received on a WebSocket connection.
+///
+/// Text, binary and continuation frames give their (unmasked) payload. Pings are answered with
+/// pongs, pongs are ignored, and a close frame is answered and ends the stream (`Ok(0)`), as
+/// does an oversized frame.
+///
+pub struct WebSocketReader<S> {
+    stream: S,
+    /// Unread payload of the current frame, and its position in the mask.
+    remaining: u64,
+    mask: [u8; 4],
+    mask_pos: usize,
+    closed: bool,
+}
+
+impl<S: Read + Write> WebSocketReader<S> {
+    /// Creates a reader of the frames of `stream`, which also carries the control replies.
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream,
+            remaining: 0,
+            mask: [0; 4],
+            mask_pos: 0,
+            closed: false,
+        }
+    }
+
+    /// Reads the next data frame header; `false` once the connection is closed.
+    fn next_data_frame(&mut self) -> io::Result<bool> {
+        loop {
+            let mut header = [0u8; 2];
+            if !read_full(&mut self.stream, &mut header)? {
+                return Ok(false);
+            }
+            let opcode = header[0] & 0x0F;
+            let masked = header[1] & 0x80 != 0;
+            let len = match header[1] & 0x7F {
+                126 => {
+                    let mut len = [0u8; 2];
+                    if !read_full(&mut self.stream, &mut len)? {
+                        return Ok(false);
+                    }
+                    u16::from_be_bytes(len) as u64
+                }
+                127 => {
+                    let mut len = [0u8; 8];
+                    if !read_full(&mut self.stream, &mut len)? {
+                        return Ok(false);
+                    }
+                    u64::from_be_bytes(len)
+                }
+                len => len as u64,
+            };
+            if len > MAX_RECEIVED_FRAME_LEN {
+                return Ok(false);
+            }
+            self.mask = [0; 4];
+            if masked && !read_full(&mut self.stream, &mut self.mask)? {
+                return Ok(false);
+            }
+            self.mask_pos = 0;
+            match opcode {
+                OPCODE_TEXT | OPCODE_BINARY | OPCODE_CONTINUATION if len > 0 => {
+                    self.remaining = len;
+                    return Ok(true);
+                }
+                OPCODE_TEXT | OPCODE_BINARY | OPCODE_CONTINUATION => {}
+                _ => {
+                    let mut payload = std::vec![0u8; len as usize];
+                    if !read_full(&mut self.stream, &mut payload)? {
+                        return Ok(false);
+                    }
+                    self.unmask(&mut payload);
+                    match opcode {
+                        OPCODE_CLOSE => {
+                            let _ = write_frame(&mut self.stream, OPCODE_CLOSE, &payload);
+                            return Ok(false);
+                        }
+                        OPCODE_PING => write_frame(&mut self.stream, OPCODE_PONG, &payload)?,
+                        _ => {} // pong or unknown control frame
+                    }
+                }
+            }
+        }
+    }
+
+    fn unmask(&mut self, data: &mut [u8]) {
+        for b in data {
+            *b ^= self.mask[self.mask_pos % 4];
+            self.mask_pos += 1;
+        }
+    }
+}
+
+impl<S: Read + Write> Read for WebSocketReader<S> {
+    /// Reads payload bytes of the data frames, `Ok(0)` once the connection is closed.
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if out.is_empty() || self.closed {
+            return Ok(0);
+        }
+        if self.remaining == 0 && !self.next_data_frame()? {
+            self.closed = true;
+            return Ok(0);
+        }
+        let want = out.len().min(self.remaining as usize);
+        let got = self.stream.read(&mut out[..want])?;
+        if got == 0 {
+            self.closed = true;
+            return Ok(0);
+        }
+        self.remaining -= got as u64;
+        self.unmask(&mut out[..got]);
+        Ok(got)
+    }
+}
+
+/// Fills `buf` from `stream`; `false` if the stream ended first.
+fn read_full(stream: &mut impl Read, buf: &mut [u8]) -> io::Result<bool> {
+    match stream.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Sends what is written to it as text frames of at most [`MAX_FRAME_LEN`] bytes, each ending
+/// on a character boundary.
+///
+/// Meant to sit under a [`StreamOutput`], which writes whole strings on each flush.
+///
+pub struct WebSocketWriter<W> {
+    inner: W,
+}
+
+impl<W: Write> WebSocketWriter<W> {
+    /// Creates a writer framing the text sent to `inner`.
+    pub fn new(inner: W) -> Self {
+        Self { inner }
+    }
+}
+
+impl<W: Write> Write for WebSocketWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut len = buf.len().min(MAX_FRAME_LEN);
+        // Do not split a UTF-8 sequence: back off to the start of the last character
+        while len < buf.len() && len > 0 && buf[len] & 0xC0 == 0x80 {
+            len -= 1;
+        }
+        if len == 0 {
+            return Ok(0);
+        }
+        write_frame(&mut self.inner, OPCODE_TEXT, &buf[..len])?;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// ==================== TESTS =======================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Read side from `input`, writes (control replies) collected.
+    struct Duplex {
+        input: io::Cursor<std::vec::Vec<u8>>,
+        output: std::vec::Vec<u8>,
+    }
+
+    impl Read for Duplex {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Duplex {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A frame as sent by a browser: masked.
+    fn client_frame(opcode: u8, payload: &[u8]) -> std::vec::Vec<u8> {
+        let mask = [0x12, 0x34, 0x56, 0x78];
+        let mut frame = std::vec![0x80 | opcode];
+        if payload.len() < 126 {
+            frame.push(0x80 | payload.len() as u8);
+        } else {
+            frame.push(0x80 | 126);
+            frame.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        }
+        frame.extend_from_slice(&mask);
+        frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        frame
+    }
+
+    fn reader(frames: &[std::vec::Vec<u8>]) -> WebSocketReader<Duplex> {
+        WebSocketReader::new(Duplex {
+            input: io::Cursor::new(frames.concat()),
+            output: std::vec::Vec::new(),
+        })
+    }
+
+    // ==================== HANDSHAKE TESTS ====================
+
+    #[test]
+    fn test_accept_key_of_rfc_example() {
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn test_base64_padding() {
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+    }
+
+    #[test]
+    fn test_websocket_key_requires_upgrade() {
+        let upgrade = "GET / HTTP/1.1\r\nUpgrade: WebSocket\r\nSec-WebSocket-Key: abc\r\n\r\n";
+        assert_eq!(websocket_key(upgrade), Some("abc"));
+        let plain = "GET / HTTP/1.1\r\nSec-WebSocket-Key: abc\r\n\r\n";
+        assert_eq!(websocket_key(plain), None);
+    }
+
+    // ==================== FRAME TESTS ====================
+
+    #[test]
+    fn test_text_frames_are_unmasked() {
+        let mut reader = reader(&[
+            client_frame(OPCODE_TEXT, b"led"),
+            client_frame(OPCODE_TEXT, b" on\r"),
+        ]);
+        let mut data = std::vec::Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"led on\r");
+    }
+
+    #[test]
+    fn test_long_frame_length() {
+        let payload = [b'x'; 300];
+        let mut reader = reader(&[client_frame(OPCODE_TEXT, &payload)]);
+        let mut data = std::vec::Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, payload);
+    }
+
+    #[test]
+    fn test_ping_is_answered_and_close_ends_stream() {
+        let mut reader = reader(&[
+            client_frame(OPCODE_PING, b"hi"),
+            client_frame(OPCODE_TEXT, b"a"),
+            client_frame(OPCODE_CLOSE, b""),
+            client_frame(OPCODE_TEXT, b"ignored"),
+        ]);
+        let mut data = std::vec::Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"a");
+        let replies = &reader.stream.output;
+        assert_eq!(replies[..], [0x8A, 2, b'h', b'i', 0x88, 0]);
+    }
+
+    #[test]
+    fn test_output_is_split_into_text_frames() {
+        let mut writer = WebSocketWriter::new(std::vec::Vec::new());
+        let text = "é".repeat(MAX_FRAME_LEN); // 2 bytes each: frames must not split one
+        writer.write_all(text.as_bytes()).unwrap();
+
+        let mut frames = writer.inner.as_slice();
+        let mut payload = std::vec::Vec::new();
+        while !frames.is_empty() {
+            assert_eq!(frames[0], 0x81);
+            assert_eq!(frames[1], 126);
+            let len = u16::from_be_bytes([frames[2], frames[3]]) as usize;
+            assert!(len <= MAX_FRAME_LEN);
+            let chunk = &frames[4..4 + len];
+            assert!(core::str::from_utf8(chunk).is_ok());
+            payload.extend_from_slice(chunk);
+            frames = &frames[4 + len..];
+        }
+        assert_eq!(payload, text.as_bytes());
+    }
+
+    #[test]
+    fn test_accept_serves_page_then_upgrades() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = std::thread::spawn(move || {
+            let mut page = TcpStream::connect(addr).unwrap();
+            page.write_all(b"GET / HTTP/1.1\r\nHost: x\r\n\r\n")
+                .unwrap();
+            let mut response = std::string::String::new();
+            page.read_to_string(&mut response).unwrap();
+            assert!(response.starts_with("HTTP/1.1 200 OK"));
+            assert!(response.ends_with(TERMINAL_PAGE));
+
+            let mut ws = TcpStream::connect(addr).unwrap();
+            ws.write_all(
+                b"GET / HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+                  Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n",
+            )
+            .unwrap();
+            let head = read_request_head(&mut ws).unwrap();
+            assert!(head.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo="));
+            ws.write_all(&client_frame(OPCODE_TEXT, b"x")).unwrap();
+            let mut frame = [0u8; 6];
+            ws.read_exact(&mut frame).unwrap();
+            assert_eq!(frame, [0x81, 4, b'o', b'k', b'\r', b'\n']);
+        });
+
+        let (mut keys, mut out) = accept(&listener).unwrap();
+        use crate::input::key_reader::{Key, KeyReader};
+        assert!(matches!(keys.read_key(), Ok(Key::Char('x'))));
+        use core::fmt::Write as _;
+        let _ = writeln!(out, "ok");
+        crate::output::Output::flush(&mut out);
+        client.join().unwrap();
+    }
+}